@@ -0,0 +1,132 @@
+struct CameraUniform {
+	view_pos: vec3<f32>,
+  view_proj: mat4x4<f32>,
+}
+
+struct CelestialUniform {
+    // The direction toward the sun.
+    sun_direction: vec3<f32>,
+    // How bright the stars are, from 0 during the day to 1 at night.
+    stars: f32,
+    // How far the sky has turned since sunrise, in radians.
+    rotation: f32,
+    // How much of the sun, moon and stars shows, from 0 to 1.
+    opacity: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+		// Where on the body the fragment is, from -1 to 1 across it.
+		@location(0) uv: vec2<f32>,
+		@location(1) @interpolate(flat) body: u32,
+		// How high above the horizon the body is, from -1 to 1.
+		@location(2) @interpolate(flat) height: f32,
+};
+
+// The bodies drawn before the stars.
+const SUN: u32 = 0u;
+const MOON: u32 = 1u;
+// The number of stars, matching the renderer.
+const STAR_COUNT: u32 = 1024u;
+// How wide the bodies look, as a fraction of their distance.
+const SUN_SIZE: f32 = 0.12;
+const MOON_SIZE: f32 = 0.08;
+const STAR_SIZE: f32 = 0.004;
+// How far from the camera the bodies are placed. They are never cut off by the far plane, so
+// this only keeps them clear of the near plane.
+const DISTANCE: f32 = 100.0;
+// How much of the sun is its bright core, the rest being its glow.
+const SUN_CORE: f32 = 0.5;
+const SUN_COLOR: vec3<f32> = vec3<f32>(1.0, 0.95, 0.7);
+const MOON_COLOR: vec3<f32> = vec3<f32>(0.85, 0.87, 0.95);
+// How many cells wide the craters of the moon are drawn in.
+const MOON_CELLS: f32 = 4.0;
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var<uniform> celestial: CelestialUniform;
+
+// Returns a pseudo random value from 0 to 1 for `seed`.
+fn hash(seed: f32) -> f32 {
+	return fract(sin(seed * 127.1) * 43758.5453);
+}
+
+// Returns the direction toward star `index`, spread evenly over the sky and turning with it.
+fn star_direction(index: u32) -> vec3<f32> {
+	let z = hash(f32(index) * 2.0) * 2.0 - 1.0;
+	let angle = hash(f32(index) * 2.0 + 1.0) * 6.2831853 + celestial.rotation;
+	let radius = sqrt(1.0 - z * z);
+	return vec3<f32>(radius * cos(angle), radius * sin(angle), z);
+}
+
+// Draws the sun, the moon opposite it and the stars as squares facing the camera, six vertices
+// each.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+	var corners = array<vec2<f32>, 6>(
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, 1.0),
+	);
+	let body = index / 6u;
+	let corner = corners[index % 6u];
+
+	var direction = normalize(celestial.sun_direction);
+	var size = SUN_SIZE;
+	if body == MOON {
+		direction = -direction;
+		size = MOON_SIZE;
+	} else if body > MOON {
+		direction = star_direction(body);
+		size = STAR_SIZE * mix(0.5, 1.5, hash(f32(body) + 0.5));
+	}
+
+	// Turn the square to face the camera, keeping its edges level where possible.
+	var up_hint = vec3<f32>(0.0, 1.0, 0.0);
+	if abs(direction.y) > 0.99 {
+		up_hint = vec3<f32>(1.0, 0.0, 0.0);
+	}
+	let right = normalize(cross(up_hint, direction));
+	let up = cross(direction, right);
+	let offset = (direction + (right * corner.x + up * corner.y) * size) * DISTANCE;
+
+	var out: VertexOutput;
+	out.clip_position = camera.view_proj * vec4<f32>(camera.view_pos + offset, 1.0);
+	// Keep the depth in range, since the bodies are infinitely far away.
+	out.clip_position.z = out.clip_position.w * 0.5;
+	out.uv = corner;
+	out.body = body;
+	out.height = direction.y;
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+		// Sink the bodies into the fog toward the horizon, where the terrain may not cover them.
+		let horizon = smoothstep(-0.1, 0.05, in.height) * celestial.opacity;
+
+		if in.body == SUN {
+			// A bright square core, surrounded by a glow fading out to the edges.
+			let edge = max(abs(in.uv.x), abs(in.uv.y));
+			var alpha = 1.0;
+			if edge > SUN_CORE {
+				alpha = pow(1.0 - (edge - SUN_CORE) / (1.0 - SUN_CORE), 2.0) * 0.5;
+			}
+			return vec4<f32>(SUN_COLOR, alpha * horizon);
+		}
+
+		if in.body == MOON {
+			// A pale square, darker in a few cells like craters.
+			let cell = floor((in.uv * 0.5 + 0.5) * MOON_CELLS);
+			let crater = step(0.7, hash(cell.x * 7.0 + cell.y * 13.0 + 1.0));
+			return vec4<f32>(MOON_COLOR * (1.0 - crater * 0.25), horizon);
+		}
+
+		// Round stars of varied brightness, fading in as the sky darkens.
+		let brightness = mix(0.4, 1.0, hash(f32(in.body) + 0.25));
+		let alpha = (1.0 - smoothstep(0.5, 1.0, length(in.uv))) * brightness * celestial.stars;
+		return vec4<f32>(vec3<f32>(1.0), alpha * horizon);
+}
@@ -0,0 +1,106 @@
+struct CameraUniform {
+	view_pos: vec3<f32>,
+  view_proj: mat4x4<f32>,
+}
+
+struct SkyUniform {
+    color: vec4<f32>,
+    // How bright sky light is at the current time of day, from 0 to 1.
+    sun_intensity: f32,
+    // The distances from the camera the fog starts and ends at.
+    fog_start: f32,
+    fog_end: f32,
+    // How much dark light levels are raised, from 0 to 1.
+    brightness: f32,
+}
+
+struct TimeUniform {
+    // The seconds since the renderer started.
+    seconds: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+		@location(0) world_position: vec3<f32>,
+		// The position relative to the camera, which unlike the distance can be interpolated
+		// across the plane.
+		@location(1) offset: vec3<f32>,
+};
+
+// How high the clouds float, in world units.
+const CLOUD_HEIGHT: f32 = 256.0;
+// How far the cloud plane reaches from the camera, past the largest render distance.
+const CLOUD_RADIUS: f32 = 4096.0;
+// The width of a cloud cell, in world units.
+const CELL_SIZE: f32 = 24.0;
+// How many cells wide the patches of clouds are.
+const PATCH_SIZE: f32 = 6.0;
+// How much of the sky is covered, from 0 to 1.
+const COVERAGE: f32 = 0.45;
+// How fast the clouds drift, in world units per second.
+const WIND: vec2<f32> = vec2<f32>(3.0, 1.0);
+// How opaque the clouds are.
+const OPACITY: f32 = 0.8;
+// How bright the clouds are at night, compared to the day.
+const NIGHT_BRIGHTNESS: f32 = 0.15;
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var<uniform> sky: SkyUniform;
+
+@group(2) @binding(0) var<uniform> time: TimeUniform;
+
+// Returns a pseudo random value from 0 to 1 for a cell.
+fn hash(cell: vec2<f32>) -> f32 {
+	return fract(sin(dot(cell, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// Returns smooth noise from 0 to 1, blending the random values of the surrounding cells.
+fn value_noise(position: vec2<f32>) -> f32 {
+	let cell = floor(position);
+	let t = smoothstep(vec2<f32>(0.0), vec2<f32>(1.0), fract(position));
+	let a = hash(cell);
+	let b = hash(cell + vec2<f32>(1.0, 0.0));
+	let c = hash(cell + vec2<f32>(0.0, 1.0));
+	let d = hash(cell + vec2<f32>(1.0, 1.0));
+	return mix(mix(a, b, t.x), mix(c, d, t.x), t.y);
+}
+
+// Draws a square around the camera at the height of the clouds, from two triangles.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+	var corners = array<vec2<f32>, 6>(
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, 1.0),
+	);
+	let corner = corners[index] * CLOUD_RADIUS + camera.view_pos.xz;
+	let position = vec3<f32>(corner.x, CLOUD_HEIGHT, corner.y);
+
+	var out: VertexOutput;
+	out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+	out.world_position = position;
+	out.offset = position - camera.view_pos;
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+		// Clouds are made of whole cells, filled where the drifting noise is above the
+		// coverage.
+		let cell = floor((in.world_position.xz + WIND * time.seconds) / CELL_SIZE);
+		let patches = value_noise(cell / PATCH_SIZE) * 0.7 + value_noise(cell / (PATCH_SIZE * 0.4)) * 0.3;
+		if patches < 1.0 - COVERAGE {
+			discard;
+		}
+
+		// Light the clouds with the sun and tint them with the sky, so they darken at night
+		// and redden at dusk, and fade them out with the fog.
+		let brightness = mix(NIGHT_BRIGHTNESS, 1.0, sky.sun_intensity);
+		let color = mix(vec3<f32>(1.0), sky.color.rgb, 0.25) * brightness;
+		let fog = smoothstep(sky.fog_start, sky.fog_end, length(in.offset));
+    return vec4<f32>(color, OPACITY * (1.0 - fog));
+}
@@ -0,0 +1,129 @@
+struct CameraUniform {
+	view_pos: vec3<f32>,
+  view_proj: mat4x4<f32>,
+}
+
+struct SkyUniform {
+    color: vec4<f32>,
+    // How bright sky light is at the current time of day, from 0 to 1.
+    sun_intensity: f32,
+    // The distances from the camera the fog starts and ends at.
+    fog_start: f32,
+    fog_end: f32,
+    // How much dark light levels are raised, from 0 to 1.
+    brightness: f32,
+}
+
+struct ShadowUniform {
+    // Transforms world positions into the shadow map of each cascade.
+    cascades: array<mat4x4<f32>, SHADOW_CASCADES>,
+    // How much of the direct light shadows block, from 0 to 1.
+    strength: f32,
+    // How far from the camera shadows end.
+    distance: f32,
+    texel_size: f32,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+    @location(8) light: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+		@location(0) tex_coords: vec2<f32>,
+		@location(1) @interpolate(flat) layer: u32,
+		@location(2) normal: vec3<f32>,
+		@location(3) @interpolate(flat) light: vec2<f32>,
+		@location(4) distance: f32,
+		@location(5) world_position: vec3<f32>,
+};
+
+// The number of shadow cascades, matching the renderer.
+const SHADOW_CASCADES: u32 = 3u;
+// How much brightness is kept per light level below the maximum of 15.
+const LIGHT_FALLOFF: f32 = 0.8;
+// The brightness of entities without any light, like the terrain around them.
+const MIN_BRIGHTNESS: f32 = 0.05;
+// How bright faces pointing down are, compared to faces pointing up.
+const BOTTOM_SHADE: f32 = 0.5;
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var texture: texture_2d_array<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+@group(2) @binding(0) var<uniform> sky: SkyUniform;
+@group(2) @binding(1) var<uniform> shadow: ShadowUniform;
+@group(2) @binding(2) var shadow_map: texture_depth_2d_array;
+@group(2) @binding(3) var shadow_sampler: sampler_comparison;
+
+@vertex
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+	let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+	let position = (model * vec4<f32>(input.position, 1.0)).xyz;
+
+	var out: VertexOutput;
+	out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+	out.tex_coords = input.tex_coords;
+	out.layer = input.layer;
+	// Entities are only moved, turned and scaled evenly, so normals turn with them.
+	out.normal = normalize((model * vec4<f32>(input.normal, 0.0)).xyz);
+	out.light = instance.light;
+	out.distance = distance(position, camera.view_pos);
+	out.world_position = position;
+	return out;
+}
+
+// Returns how much of the direct light reaches `position`, `distance` away from the camera,
+// like for the terrain.
+fn sunlight(position: vec3<f32>, distance: f32) -> f32 {
+		for (var i = 0u; i < SHADOW_CASCADES; i++) {
+			let clip = shadow.cascades[i] * vec4<f32>(position, 1.0);
+			let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+			if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z > 1.0 {
+				continue;
+			}
+			let lit = textureSampleCompareLevel(shadow_map, shadow_sampler, uv, i, clip.z);
+			let fade = smoothstep(shadow.distance * 0.8, shadow.distance, distance);
+			return mix(lit, 1.0, fade);
+		}
+		return 1.0;
+}
+
+// Raises dark light levels by the brightness setting, from the plain falloff at 0 to
+// lifting the shadows at 1, keeping full light as it is.
+fn adjust_brightness(brightness: f32) -> f32 {
+	return mix(brightness, 1.0 - pow(1.0 - brightness, 3.0), sky.brightness);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+		let sample = textureSample(texture, texture_sampler, in.tex_coords, in.layer);
+		if sample.a < 0.5 {
+			discard;
+		}
+
+		// Light the entity like the terrain at its position, with faces turned away from the
+		// sky a little darker so its shape shows.
+		let sun = 1.0 - shadow.strength * (1.0 - sunlight(in.world_position, in.distance));
+		let sky_level = in.light.x * sky.sun_intensity;
+		let sky_brightness = pow(LIGHT_FALLOFF, (1.0 - sky_level) * 15.0) * sun;
+		let block_brightness = pow(LIGHT_FALLOFF, (1.0 - in.light.y) * 15.0);
+		let brightness = adjust_brightness(
+			max(max(sky_brightness, block_brightness), MIN_BRIGHTNESS),
+		);
+		let face = mix(BOTTOM_SHADE, 1.0, normalize(in.normal).y * 0.5 + 0.5);
+		let fog = smoothstep(sky.fog_start, sky.fog_end, in.distance);
+    return vec4<f32>(mix(sample.rgb * brightness * face, sky.color.rgb, fog), 1.0);
+}
@@ -0,0 +1,153 @@
+// The depth buffer is multisampled along with the frame, in which case the renderer swaps
+// this for `texture_multisampled_2d<f32>` and the first sample of each pixel is read.
+alias DepthTexture = texture_2d<f32>;
+
+// The maximum number of levels of the Hi-Z pyramid.
+const MAX_LEVELS: u32 = 16u;
+
+struct CullUniform {
+    // The camera the Hi-Z pyramid was drawn with.
+    view_proj: mat4x4<f32>,
+    // The size of the depth buffer the pyramid was built from, in pixels.
+    depth_size: vec2<f32>,
+    // The number of levels of the pyramid.
+    level_count: u32,
+    // The draws culled, starting at `first` in the draw arguments.
+    first: u32,
+    count: u32,
+    // Where each level starts in the pyramid, followed by its width and height.
+    levels: array<vec4<u32>, MAX_LEVELS>,
+}
+
+// The level of the pyramid being built, and the one before it.
+struct LevelUniform {
+    offset: u32,
+    width: u32,
+    height: u32,
+    previous_offset: u32,
+    previous_width: u32,
+    previous_height: u32,
+}
+
+struct Bounds {
+    // The corners of the box around the mesh of a draw. `min.w` is 0 if the mesh has no
+    // bounds, so it is never culled.
+    min: vec4<f32>,
+    max: vec4<f32>,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var depth_texture: DepthTexture;
+@group(0) @binding(1) var<uniform> level: LevelUniform;
+@group(0) @binding(2) var<storage, read_write> pyramid_levels: array<f32>;
+@group(0) @binding(3) var<uniform> cull: CullUniform;
+@group(0) @binding(4) var<storage, read> bounds: array<Bounds>;
+@group(0) @binding(5) var<storage, read_write> draws: array<DrawArgs>;
+@group(0) @binding(6) var<storage, read> pyramid: array<f32>;
+
+// Builds the first level of the pyramid, keeping the farthest depth of each 2x2 block of
+// pixels. Blocks at the edges are clamped, so odd sizes are still covered.
+@compute @workgroup_size(8, 8)
+fn cs_downsample_depth(@builtin(global_invocation_id) id: vec3<u32>) {
+	if id.x >= level.width || id.y >= level.height {
+		return;
+	}
+
+	let texel = vec2<i32>(id.xy) * 2;
+	let size = vec2<i32>(textureDimensions(depth_texture));
+	var depth = 0.0;
+	for (var i = 0; i < 4; i++) {
+		let offset = vec2<i32>(i & 1, i >> 1u);
+		depth = max(depth, textureLoad(depth_texture, min(texel + offset, size - 1), 0).r);
+	}
+	pyramid_levels[level.offset + id.y * level.width + id.x] = depth;
+}
+
+// Builds a level of the pyramid from the one before it, like `cs_downsample_depth`.
+@compute @workgroup_size(8, 8)
+fn cs_downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+	if id.x >= level.width || id.y >= level.height {
+		return;
+	}
+
+	var depth = 0.0;
+	for (var i = 0u; i < 4u; i++) {
+		let x = min(id.x * 2u + (i & 1u), level.previous_width - 1u);
+		let y = min(id.y * 2u + (i >> 1u), level.previous_height - 1u);
+		depth = max(depth, pyramid_levels[level.previous_offset + y * level.previous_width + x]);
+	}
+	pyramid_levels[level.offset + id.y * level.width + id.x] = depth;
+}
+
+// Returns the depth stored in the pyramid at a texel of a level.
+fn pyramid_depth(level: u32, texel: vec2<u32>) -> f32 {
+	let info = cull.levels[level];
+	return pyramid[info.x + texel.y * info.y + texel.x];
+}
+
+// Returns whether a box is behind the terrain in the pyramid. Boxes that can't be tested,
+// because they reach behind the camera the pyramid was drawn from, are never occluded.
+fn is_occluded(box: Bounds) -> bool {
+	if box.min.w == 0.0 {
+		return false;
+	}
+
+	var rect_min = vec2<f32>(1.0);
+	var rect_max = vec2<f32>(-1.0);
+	var nearest = 1.0;
+	for (var i = 0u; i < 8u; i++) {
+		let corner = select(box.min.xyz, box.max.xyz, vec3<bool>((i & 1u) != 0u, (i & 2u) != 0u, (i & 4u) != 0u));
+		let clip = cull.view_proj * vec4<f32>(corner, 1.0);
+		if clip.w <= 0.0 {
+			return false;
+		}
+		let ndc = clip.xyz / clip.w;
+		rect_min = min(rect_min, ndc.xy);
+		rect_max = max(rect_max, ndc.xy);
+		nearest = min(nearest, ndc.z);
+	}
+	if nearest <= 0.0 {
+		return false;
+	}
+	// Only the part of the box in view can be seen.
+	rect_min = max(rect_min, vec2<f32>(-1.0));
+	rect_max = min(rect_max, vec2<f32>(1.0));
+	if any(rect_min >= rect_max) {
+		return false;
+	}
+
+	// Pick the level where the box covers at most 2x2 texels, and compare with the farthest
+	// terrain in them.
+	let uv_min = vec2<f32>(rect_min.x, -rect_max.y) * 0.5 + 0.5;
+	let uv_max = vec2<f32>(rect_max.x, -rect_min.y) * 0.5 + 0.5;
+	let extent = (uv_max - uv_min) * cull.depth_size * 0.5;
+	let level = min(u32(ceil(log2(max(max(extent.x, extent.y), 1.0)))), cull.level_count - 1u);
+	let texel_size = exp2(f32(level + 1u));
+	let last_texel = cull.levels[level].yz - 1u;
+	let first = min(vec2<u32>(uv_min * cull.depth_size / texel_size), last_texel);
+	let last = min(vec2<u32>(uv_max * cull.depth_size / texel_size), last_texel);
+
+	let depth = max(
+		max(pyramid_depth(level, first), pyramid_depth(level, vec2<u32>(last.x, first.y))),
+		max(pyramid_depth(level, vec2<u32>(first.x, last.y)), pyramid_depth(level, last)),
+	);
+	return nearest > depth;
+}
+
+// Hides the draws of meshes occluded in the pyramid by drawing no instances of them.
+@compute @workgroup_size(64)
+fn cs_cull(@builtin(global_invocation_id) id: vec3<u32>) {
+	if id.x >= cull.count {
+		return;
+	}
+
+	let index = cull.first + id.x;
+	draws[index].instance_count = select(1u, 0u, is_occluded(bounds[id.x]));
+}
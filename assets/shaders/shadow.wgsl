@@ -0,0 +1,40 @@
+struct CascadeUniform {
+    view_proj: mat4x4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+		@location(0) tex_coords: vec2<f32>,
+		@location(1) @interpolate(flat) layer: u32,
+};
+
+@group(0) @binding(0) var<uniform> cascade: CascadeUniform;
+
+@group(1) @binding(0) var texture: texture_2d_array<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+	var out: VertexOutput;
+
+	out.clip_position = cascade.view_proj * vec4<f32>(input.position, 1.0);
+	out.tex_coords = input.tex_coords;
+	out.layer = input.layer;
+
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) {
+		// Light passes through the transparent parts of leaves and plants.
+		let sample = textureSample(texture, texture_sampler, in.tex_coords, in.layer);
+		if sample.a < 0.5 {
+			discard;
+		}
+}
@@ -0,0 +1,142 @@
+// The depth buffer is multisampled along with the frame, in which case the renderer swaps
+// this for `texture_multisampled_2d<f32>` and the first sample of each pixel is read.
+alias DepthTexture = texture_2d<f32>;
+
+struct SsaoUniform {
+    view_proj: mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    view_pos: vec3<f32>,
+    // How far around a position is searched for occluders, in world units.
+    radius: f32,
+    // How much fully occluded positions are darkened, from 0 to 1.
+    strength: f32,
+    // How far from the camera the occlusion fades out.
+    distance: f32,
+}
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// The number of positions sampled around each pixel.
+const SAMPLES: u32 = 16u;
+// How much closer to the camera than a sample the terrain has to be to occlude it, so flat
+// surfaces don't occlude themselves.
+const BIAS: f32 = 0.05;
+const GOLDEN_ANGLE: f32 = 2.39996;
+const TAU: f32 = 6.28318;
+
+@group(0) @binding(0) var<uniform> ssao: SsaoUniform;
+@group(0) @binding(1) var depth_texture: DepthTexture;
+@group(0) @binding(2) var occlusion_texture: texture_2d<f32>;
+@group(0) @binding(3) var blurred_texture: texture_2d<f32>;
+
+// Covers the screen with a single triangle.
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+	var out: FullscreenOutput;
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+	out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+	return out;
+}
+
+fn depth_at(pixel: vec2<i32>) -> f32 {
+	let size = vec2<i32>(textureDimensions(depth_texture));
+	return textureLoad(depth_texture, clamp(pixel, vec2<i32>(0), size - 1), 0).r;
+}
+
+// Reconstructs the world position of the terrain drawn at `pixel` from the depth buffer.
+fn position_at(pixel: vec2<i32>) -> vec3<f32> {
+	let size = vec2<f32>(textureDimensions(depth_texture));
+	let uv = (vec2<f32>(pixel) + 0.5) / size;
+	let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+	let world = ssao.inverse_view_proj * vec4<f32>(ndc, depth_at(pixel), 1.0);
+	return world.xyz / world.w;
+}
+
+// Returns the shorter of the differences toward the neighbors before and after a position, so
+// normals at the edges of blocks don't bend toward terrain behind them.
+fn closest_difference(position: vec3<f32>, before: vec3<f32>, after: vec3<f32>) -> vec3<f32> {
+	let forward = after - position;
+	let backward = position - before;
+	return select(backward, forward, length(forward) < length(backward));
+}
+
+// Returns how much light reaches each pixel past the terrain around it, from 0 to 1.
+@fragment
+fn fs_occlusion(in: FullscreenOutput) -> @location(0) vec4<f32> {
+	let pixel = vec2<i32>(in.clip_position.xy);
+	if depth_at(pixel) >= 1.0 {
+		return vec4<f32>(1.0);
+	}
+
+	let position = position_at(pixel);
+	let dx = closest_difference(position, position_at(pixel - vec2<i32>(1, 0)), position_at(pixel + vec2<i32>(1, 0)));
+	let dy = closest_difference(position, position_at(pixel - vec2<i32>(0, 1)), position_at(pixel + vec2<i32>(0, 1)));
+	var normal = normalize(cross(dx, dy));
+	if dot(normal, ssao.view_pos - position) < 0.0 {
+		normal = -normal;
+	}
+
+	var tangent = cross(normal, vec3<f32>(0.0, 1.0, 0.0));
+	if abs(normal.y) > 0.9 {
+		tangent = cross(normal, vec3<f32>(1.0, 0.0, 0.0));
+	}
+	tangent = normalize(tangent);
+	let bitangent = cross(normal, tangent);
+
+	// Rotate the samples differently in every pixel of a 4x4 tile, which the blur evens out.
+	let index = (pixel.x & 3) + (pixel.y & 3) * 4;
+	let rotation = f32((index * 7) % 16) / 16.0 * TAU;
+
+	let size = vec2<f32>(textureDimensions(depth_texture));
+	let view_distance = distance(position, ssao.view_pos);
+	var occlusion = 0.0;
+	for (var i = 0u; i < SAMPLES; i++) {
+		// Spread the samples over the hemisphere around the normal, more of them close by.
+		let t = (f32(i) + 0.5) / f32(SAMPLES);
+		let angle = f32(i) * GOLDEN_ANGLE + rotation;
+		let r = sqrt(t);
+		let direction = tangent * (r * cos(angle)) + bitangent * (r * sin(angle)) + normal * sqrt(1.0 - t);
+		let sample = position + direction * ssao.radius * mix(0.1, 1.0, t * t);
+
+		let clip = ssao.view_proj * vec4<f32>(sample, 1.0);
+		let ndc = clip.xy / clip.w;
+		let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+		if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+			continue;
+		}
+
+		let scene = position_at(vec2<i32>(uv * size));
+		if distance(scene, ssao.view_pos) < distance(sample, ssao.view_pos) - BIAS {
+			// Terrain far in front of the sample doesn't occlude it.
+			occlusion += smoothstep(0.0, 1.0, ssao.radius / max(distance(position, scene), 0.0001));
+		}
+	}
+
+	let fade = smoothstep(ssao.distance * 0.75, ssao.distance, view_distance);
+	let light = 1.0 - occlusion / f32(SAMPLES) * ssao.strength;
+	return vec4<f32>(mix(light, 1.0, fade));
+}
+
+// Averages the occlusion over the 4x4 tile the sample rotations repeat in.
+@fragment
+fn fs_blur(in: FullscreenOutput) -> @location(0) vec4<f32> {
+	let pixel = vec2<i32>(in.clip_position.xy);
+	let size = vec2<i32>(textureDimensions(occlusion_texture));
+	var light = 0.0;
+	for (var x = -2; x < 2; x++) {
+		for (var y = -2; y < 2; y++) {
+			let offset = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+			light += textureLoad(occlusion_texture, offset, 0).r;
+		}
+	}
+	return vec4<f32>(light / 16.0);
+}
+
+// Darkens the frame by the occlusion, which is blended by multiplying.
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+	let light = textureLoad(blurred_texture, vec2<i32>(in.clip_position.xy), 0).r;
+	return vec4<f32>(light, light, light, 1.0);
+}
@@ -3,21 +3,64 @@ struct CameraUniform {
   view_proj: mat4x4<f32>,
 }
 
+struct SkyUniform {
+    color: vec4<f32>,
+    // How bright sky light is at the current time of day, from 0 to 1.
+    sun_intensity: f32,
+    // The distances from the camera the fog starts and ends at.
+    fog_start: f32,
+    fog_end: f32,
+    // How much dark light levels are raised, from 0 to 1.
+    brightness: f32,
+}
+
+struct ShadowUniform {
+    // Transforms world positions into the shadow map of each cascade.
+    cascades: array<mat4x4<f32>, SHADOW_CASCADES>,
+    // How much of the direct light shadows block, from 0 to 1.
+    strength: f32,
+    // How far from the camera shadows end.
+    distance: f32,
+    texel_size: f32,
+}
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) occlusion: f32,
+    @location(4) light: vec2<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
 		@location(0) tex_coords: vec2<f32>,
+		@location(1) @interpolate(flat) layer: u32,
+		@location(2) occlusion: f32,
+		@location(3) light: vec2<f32>,
+		@location(4) distance: f32,
+		@location(5) world_position: vec3<f32>,
 };
 
+// The number of shadow cascades, matching the renderer.
+const SHADOW_CASCADES: u32 = 3u;
+// How much fully occluded corners are darkened.
+const AMBIENT_OCCLUSION: f32 = 0.6;
+// How much brightness is kept per light level below the maximum of 15.
+const LIGHT_FALLOFF: f32 = 0.8;
+// The brightness of blocks without any light, so caves are never pitch black.
+const MIN_BRIGHTNESS: f32 = 0.05;
+
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
 
-@group(1) @binding(0) var texture: texture_2d<f32>; 
+@group(1) @binding(0) var texture: texture_2d_array<f32>; 
 @group(1) @binding(1) var texture_sampler: sampler; 
 
+@group(2) @binding(0) var<uniform> sky: SkyUniform;
+@group(2) @binding(1) var<uniform> shadow: ShadowUniform;
+@group(2) @binding(2) var shadow_map: texture_depth_2d_array;
+@group(2) @binding(3) var shadow_sampler: sampler_comparison;
+
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
 	var out: VertexOutput;
@@ -26,15 +69,81 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 
 	out.clip_position = view_pos;
 	out.tex_coords = input.tex_coords;
+	out.layer = input.layer;
+	out.occlusion = input.occlusion;
+	out.light = input.light;
+	out.distance = distance(input.position, camera.view_pos);
+	out.world_position = input.position;
 
 	return out;
 
 }
 
+// Returns how much of the direct light reaches `position`, `distance` away from the camera,
+// from 0 in full shadow to 1. Uses the first cascade covering the position.
+fn sunlight(position: vec3<f32>, distance: f32) -> f32 {
+		for (var i = 0u; i < SHADOW_CASCADES; i++) {
+			let clip = shadow.cascades[i] * vec4<f32>(position, 1.0);
+			// Texture coordinates grow downward, unlike clip space.
+			let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+			if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z > 1.0 {
+				continue;
+			}
+
+			// Average the comparisons around the position, softening the edges of shadows.
+			var lit = 0.0;
+			for (var x = -1; x <= 1; x++) {
+				for (var y = -1; y <= 1; y++) {
+					let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+					lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, i, clip.z);
+				}
+			}
+
+			// Fade the shadows out before they end, instead of cutting them off.
+			let fade = smoothstep(shadow.distance * 0.8, shadow.distance, distance);
+			return mix(lit / 9.0, 1.0, fade);
+		}
+		return 1.0;
+}
+
+// Raises dark light levels by the brightness setting, from the plain falloff at 0 to
+// lifting the shadows at 1, keeping full light as it is.
+fn adjust_brightness(brightness: f32) -> f32 {
+	return mix(brightness, 1.0 - pow(1.0 - brightness, 3.0), sky.brightness);
+}
+
+// Darkens corners surrounded by blocks, blocks far from the sky and light sources, and blocks
+// in the shadow of the sun, and fogs distant blocks.
+fn shade(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+		let sun = 1.0 - shadow.strength * (1.0 - sunlight(in.world_position, in.distance));
+		let sky_level = in.light.x * sky.sun_intensity;
+		let sky_brightness = pow(LIGHT_FALLOFF, (1.0 - sky_level) * 15.0) * sun;
+		let block_brightness = pow(LIGHT_FALLOFF, (1.0 - in.light.y) * 15.0);
+		let brightness = adjust_brightness(
+			max(max(sky_brightness, block_brightness), MIN_BRIGHTNESS),
+		);
+		let light = (1.0 - in.occlusion * AMBIENT_OCCLUSION) * brightness;
+		// Fade into the sky toward the render distance, hiding chunks as they load.
+		let fog = smoothstep(sky.fog_start, sky.fog_end, in.distance);
+    return vec4<f32>(mix(color.rgb * light, sky.color.rgb, fog), color.a);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-		var sample = textureSample(texture, texture_sampler, in.tex_coords);
-    return vec4<f32>(sample);
+		var sample = textureSample(texture, texture_sampler, in.tex_coords, in.layer);
+		// Cut out the transparent parts of leaves and plants.
+		if sample.a < 0.5 {
+			discard;
+		}
+		return shade(in, sample);
 }
 
-
+@fragment
+fn fs_transparent(in: VertexOutput) -> @location(0) vec4<f32> {
+		var sample = textureSample(texture, texture_sampler, in.tex_coords, in.layer);
+		// Fully clear texels, like the inside of glass, have nothing to blend.
+		if sample.a < 0.01 {
+			discard;
+		}
+		return shade(in, sample);
+}
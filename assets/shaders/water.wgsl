@@ -0,0 +1,110 @@
+struct CameraUniform {
+	view_pos: vec3<f32>,
+  view_proj: mat4x4<f32>,
+}
+
+struct SkyUniform {
+    color: vec4<f32>,
+    // How bright sky light is at the current time of day, from 0 to 1.
+    sun_intensity: f32,
+    // The distances from the camera the fog starts and ends at.
+    fog_start: f32,
+    fog_end: f32,
+    // How much dark light levels are raised, from 0 to 1.
+    brightness: f32,
+}
+
+struct TimeUniform {
+    // The seconds since the renderer started.
+    seconds: f32,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) layer: u32,
+    @location(3) occlusion: f32,
+    @location(4) light: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+		@location(0) tex_coords: vec2<f32>,
+		@location(1) @interpolate(flat) layer: u32,
+		@location(2) occlusion: f32,
+		@location(3) light: vec2<f32>,
+		@location(4) distance: f32,
+};
+
+// How much fully occluded corners are darkened.
+const AMBIENT_OCCLUSION: f32 = 0.6;
+// How much brightness is kept per light level below the maximum of 15.
+const LIGHT_FALLOFF: f32 = 0.8;
+// The brightness of blocks without any light, so caves are never pitch black.
+const MIN_BRIGHTNESS: f32 = 0.05;
+// How far the surface dips at the bottom of a wave, in world units.
+const WAVE_HEIGHT: f32 = 0.15;
+// How fast and how close together the waves roll.
+const WAVE_SPEED: f32 = 1.5;
+const WAVE_FREQUENCY: f32 = 0.35;
+// How fast the texture drifts, in textures per second.
+const SCROLL_SPEED: vec2<f32> = vec2<f32>(0.05, 0.03);
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var texture: texture_2d_array<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+@group(2) @binding(0) var<uniform> sky: SkyUniform;
+
+@group(3) @binding(0) var<uniform> time: TimeUniform;
+
+// Returns how far the water surface is lowered at a world position, from 0 to WAVE_HEIGHT. Only
+// depends on the position so neighboring faces stay joined.
+fn wave(position: vec3<f32>) -> f32 {
+	let t = time.seconds * WAVE_SPEED;
+	let a = sin(position.x * WAVE_FREQUENCY + t);
+	let b = sin(position.z * WAVE_FREQUENCY * 1.3 + t * 0.8);
+	return (a + b + 2.0) * 0.25 * WAVE_HEIGHT;
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+	var out: VertexOutput;
+
+	var position = input.position;
+	position.y -= wave(position);
+
+	out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+	out.tex_coords = input.tex_coords;
+	out.layer = input.layer;
+	out.occlusion = input.occlusion;
+	out.light = input.light;
+	out.distance = distance(position, camera.view_pos);
+
+	return out;
+}
+
+// Raises dark light levels by the brightness setting, from the plain falloff at 0 to
+// lifting the shadows at 1, keeping full light as it is.
+fn adjust_brightness(brightness: f32) -> f32 {
+	return mix(brightness, 1.0 - pow(1.0 - brightness, 3.0), sky.brightness);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+		// Drift the texture, with a slow ripple so the tiles don't move in lockstep.
+		let ripple = sin(in.tex_coords.yx * 6.28 + time.seconds) * 0.04;
+		let tex_coords = in.tex_coords + SCROLL_SPEED * time.seconds + ripple;
+		var sample = textureSample(texture, texture_sampler, tex_coords, in.layer);
+
+		// Darken corners surrounded by blocks, and blocks far from the sky and light sources.
+		let level = max(in.light.x * sky.sun_intensity, in.light.y);
+		let brightness = adjust_brightness(
+			max(pow(LIGHT_FALLOFF, (1.0 - level) * 15.0), MIN_BRIGHTNESS),
+		);
+		let light = (1.0 - in.occlusion * AMBIENT_OCCLUSION) * brightness;
+		// Fade into the sky toward the render distance, like the rest of the terrain.
+		let fog = smoothstep(sky.fog_start, sky.fog_end, in.distance);
+    return vec4<f32>(mix(sample.rgb * light, sky.color.rgb, fog), sample.a);
+}
@@ -0,0 +1,129 @@
+use noise::{NoiseFn, Perlin};
+
+use crate::renderer::block::BlockType;
+
+/// The climate zone of a column of blocks, which decides what its surface is made of and how
+/// much grows on it.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Desert,
+    Forest,
+    Tundra,
+}
+
+impl Biome {
+    /// Picks the biome for a temperature and a humidity, both roughly between -1 and 1.
+    pub fn from_climate(temperature: f64, humidity: f64) -> Biome {
+        if temperature < -0.25 {
+            Biome::Tundra
+        } else if temperature > 0.25 && humidity < 0.0 {
+            Biome::Desert
+        } else if humidity > 0.1 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Desert, Biome::Forest, Biome::Tundra];
+
+    /// Returns the name of the biome, as used in structure files and settings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::Plains => "plains",
+            Biome::Desert => "desert",
+            Biome::Forest => "forest",
+            Biome::Tundra => "tundra",
+        }
+    }
+
+    /// Returns the biome with the given name.
+    pub fn from_name(name: &str) -> Option<Biome> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    /// Returns the blocks the ground of the biome is made of unless configured otherwise.
+    pub fn default_surface(&self) -> SurfaceConfig {
+        let (surface, filler, rock) = match self {
+            Biome::Plains | Biome::Forest => (BlockType::Grass, BlockType::Dirt, BlockType::Stone),
+            Biome::Desert => (BlockType::Sand, BlockType::Sand, BlockType::Sandstone),
+            Biome::Tundra => (BlockType::Snow, BlockType::Dirt, BlockType::Stone),
+        };
+
+        SurfaceConfig {
+            surface,
+            filler,
+            filler_depth: 3,
+            rock,
+        }
+    }
+
+    /// Returns the chance of tall grass growing on any given surface block.
+    pub fn grass_density(&self) -> f32 {
+        match self {
+            Biome::Plains => 0.15,
+            Biome::Desert => 0.0,
+            Biome::Forest => 0.08,
+            Biome::Tundra => 0.02,
+        }
+    }
+
+    /// Returns the chance of a flower growing on any given surface block.
+    pub fn flower_density(&self) -> f32 {
+        match self {
+            Biome::Plains => 0.02,
+            Biome::Desert => 0.0,
+            Biome::Forest => 0.01,
+            Biome::Tundra => 0.0,
+        }
+    }
+
+    /// Returns the chance of a tree growing on any given surface block.
+    pub fn tree_density(&self) -> f32 {
+        match self {
+            Biome::Plains => 0.002,
+            Biome::Desert => 0.0,
+            Biome::Forest => 0.03,
+            Biome::Tundra => 0.004,
+        }
+    }
+}
+
+/// The layers of blocks making up the ground of a biome, from the top down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceConfig {
+    /// The block at the top of the terrain.
+    pub surface: BlockType,
+    /// The blocks right below the surface.
+    pub filler: BlockType,
+    /// How many blocks of filler there are.
+    pub filler_depth: i32,
+    /// Everything below the filler, down to the bedrock.
+    pub rock: BlockType,
+}
+
+/// How many blocks the climate takes to change noticeably. Biomes are much larger than hills.
+const CLIMATE_SCALE: f64 = 200.0;
+
+/// Decides the biome of every column from separate temperature and humidity noise.
+pub struct BiomeMap {
+    temperature: Perlin,
+    humidity: Perlin,
+}
+
+impl BiomeMap {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            temperature: Perlin::new(seed.wrapping_add(1)),
+            humidity: Perlin::new(seed.wrapping_add(2)),
+        }
+    }
+
+    /// Returns the biome of the column at the given block coordinates.
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+        Biome::from_climate(self.temperature.get(point), self.humidity.get(point))
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::container::{Container, CHEST_SLOTS};
+use crate::renderer::block::BlockType;
+use crate::save::read_u32;
+
+/// Data a block keeps beyond its type, like what a chest holds. It's saved along with the
+/// chunk the block is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockEntity {
+    Chest(Container),
+}
+
+impl BlockEntity {
+    /// Returns the data a newly placed `block` starts with, or `None` if it keeps none.
+    pub fn new(block: BlockType) -> Option<Self> {
+        match block {
+            BlockType::Chest => Some(BlockEntity::Chest(Container::new(CHEST_SLOTS))),
+            _ => None,
+        }
+    }
+
+    pub fn container(&self) -> Option<&Container> {
+        match self {
+            BlockEntity::Chest(container) => Some(container),
+        }
+    }
+
+    pub fn container_mut(&mut self) -> Option<&mut Container> {
+        match self {
+            BlockEntity::Chest(container) => Some(container),
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            BlockEntity::Chest(container) => {
+                writer.write_all(&[0])?;
+                container.write(writer)
+            }
+        }
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut kind = [0];
+        reader.read_exact(&mut kind)?;
+        match kind[0] {
+            0 => Ok(BlockEntity::Chest(Container::read(reader)?)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown block entity",
+            )),
+        }
+    }
+}
+
+/// Writes the block entities of a chunk, keyed by the index of their block in the chunk.
+pub fn write_all(
+    entities: &HashMap<usize, BlockEntity>,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    writer.write_all(&(entities.len() as u32).to_le_bytes())?;
+    for (index, entity) in entities {
+        writer.write_all(&(*index as u32).to_le_bytes())?;
+        entity.write(writer)?;
+    }
+    Ok(())
+}
+
+/// Reads block entities previously written with [`write_all`].
+pub fn read_all(reader: &mut impl Read) -> io::Result<HashMap<usize, BlockEntity>> {
+    let count = read_u32(reader)?;
+    let mut entities = HashMap::new();
+    for _ in 0..count {
+        let index = read_u32(reader)? as usize;
+        entities.insert(index, BlockEntity::read(reader)?);
+    }
+    Ok(entities)
+}
@@ -1,9 +1,14 @@
 use cgmath::*;
 use std::f32::consts::FRAC_PI_2;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use winit::dpi::PhysicalPosition;
 use winit::event::*;
-use winit::keyboard::KeyCode;
+
+use crate::aabb::Aabb;
+use crate::collision;
+use crate::entities::Controls;
+use crate::input::Action;
+use crate::world::World;
 
 #[rustfmt::skip]
 // This matrix is used to convert from OpenGL coordinates to wgpu coordinates.
@@ -16,7 +21,21 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
-#[derive(Debug)]
+/// How soon after the first the jump key has to be pressed again to switch between flying
+/// and walking.
+const DOUBLE_TAP: Duration = Duration::from_millis(300);
+/// How much faster the camera moves while sprinting.
+const SPRINT_SPEED: f32 = 1.6;
+/// How much wider the field of view gets while sprinting.
+const SPRINT_FOV: Deg<f32> = Deg(10.0);
+/// How quickly the field of view follows sprinting, as the fraction of the way left it
+/// moves per second, compounded.
+const FOV_TRANSITION_RATE: f32 = 10.0;
+/// How far the camera keeps from blocks while it collides with them, so the near plane
+/// doesn't cut into them.
+const CAMERA_RADIUS: f32 = 0.8;
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub position: Point3<f32>,
     yaw: Rad<f32>,
@@ -39,17 +58,40 @@ impl Camera {
         }
     }
 
-    pub fn view_projection(&self) -> Matrix4<f32> {
-        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
-        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    /// Turns the camera to look with `yaw` and `pitch`.
+    pub fn set_orientation<Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(&mut self, yaw: Y, pitch: P) {
+        self.yaw = yaw.into();
+        self.pitch = pitch.into();
+    }
+
+    /// Moves the camera to where `other` is and turns it the way `other` looks, keeping its
+    /// projection.
+    pub fn look_from(&mut self, other: &Camera) {
+        self.position = other.position;
+        self.yaw = other.yaw;
+        self.pitch = other.pitch;
+    }
 
-        let view = Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        );
+    /// Returns the matrix moving world positions into view space, where the camera looks
+    /// down the negative z axis from the origin.
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
 
-        self.projection.calc_matrix() * view
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection.calc_matrix() * self.view()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_projection())
     }
 
     pub fn forward(&self) -> Vector3<f32> {
@@ -58,11 +100,186 @@ impl Camera {
 
         Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
     }
+
+    /// Returns the corners of the part of the view between `near` and `far` from the camera,
+    /// first the four at `near` and then the four at `far`.
+    pub fn frustum_corners(&self, near: f32, far: f32) -> [Point3<f32>; 8] {
+        let forward = self.forward();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let tan = ((self.projection.fovy + self.projection.fovy_offset) / 2.0).tan();
+
+        let slice = |distance: f32| {
+            let center = self.position + forward * distance;
+            let up = up * distance * tan;
+            let right = right * distance * tan * self.projection.aspect;
+            [
+                center - right - up,
+                center + right - up,
+                center + right + up,
+                center - right + up,
+            ]
+        };
+
+        let [a, b, c, d] = slice(near);
+        let [e, f, g, h] = slice(far);
+        [a, b, c, d, e, f, g, h]
+    }
+
+    /// Returns a shadow cascade for each slice of the view ending at the distances in
+    /// `splits`, lit from `light_direction`, which points toward the light. The first slice
+    /// starts at the near plane. `resolution` is the size of the shadow map in texels.
+    pub fn shadow_cascades<const N: usize>(
+        &self,
+        light_direction: Vector3<f32>,
+        splits: &[f32; N],
+        resolution: u32,
+    ) -> [ShadowCascade; N] {
+        let light_direction = light_direction.normalize();
+        // Any up vector works as long as it isn't parallel to the light.
+        let up = if light_direction.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+
+        std::array::from_fn(|i| {
+            let near = if i == 0 {
+                self.projection.znear
+            } else {
+                splits[i - 1]
+            };
+            let far = splits[i];
+            let corners = self.frustum_corners(near, far);
+
+            // Bound the slice with a sphere, so the cascade doesn't change size as the camera
+            // turns and the edges of shadows don't shimmer.
+            let center = Point3::centroid(&corners);
+            let radius = corners
+                .iter()
+                .map(|corner| corner.distance(center))
+                .fold(0.0, f32::max)
+                .ceil();
+
+            let eye = center + light_direction * (radius + SHADOW_CASTER_DISTANCE);
+            let view = Matrix4::look_at_rh(eye, center, up);
+            let projection = DEPTH_TO_WGPU_MATRIX
+                * ortho(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    0.0,
+                    2.0 * radius + SHADOW_CASTER_DISTANCE,
+                );
+            let mut view_proj = projection * view;
+
+            // Move the cascade in whole texels, for the same reason.
+            let origin = view_proj * Vector4::unit_w() * (resolution as f32 / 2.0);
+            let offset = (origin.truncate().truncate().map(f32::round)
+                - origin.truncate().truncate())
+                * (2.0 / resolution as f32);
+            view_proj.w.x += offset.x;
+            view_proj.w.y += offset.y;
+
+            ShadowCascade { view_proj, far }
+        })
+    }
 }
-#[derive(Debug)]
+
+/// How far behind the view shadow casters are still included, so terrain outside the view
+/// still casts its shadow into it.
+const SHADOW_CASTER_DISTANCE: f32 = 200.0;
+
+#[rustfmt::skip]
+// Maps depth from OpenGL's -1..1 to wgpu's 0..1 for orthographic projections.
+const DEPTH_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// The space a slice of the view's shadows are rendered in, as seen from the light.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowCascade {
+    /// Transforms world positions into the shadow map of the cascade.
+    pub view_proj: Matrix4<f32>,
+    /// How far from the camera the cascade ends.
+    pub far: f32,
+}
+
+impl ShadowCascade {
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_proj)
+    }
+}
+/// The space visible to a camera, bounded by the planes of the screen edges and the near and
+/// far clipping distances.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// The planes as `(normal, distance)`, with normals pointing inside.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum from a view projection matrix mapping depth to 0..1, as wgpu does.
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.truncate().magnitude());
+
+        Self { planes }
+    }
+
+    /// Returns whether any part of `aabb` may be inside the frustum. Boxes near the corners of
+    /// the frustum may be reported as inside when they aren't.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Test the corner furthest along the normal of the plane.
+            let corner = Vector3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    /// Returns whether `point` is inside the frustum, counting points on its planes as inside.
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(point.to_vec()) + plane.w >= 0.0)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
+    /// Added to the field of view while it changes for effects like sprinting, on top of the
+    /// one that was picked.
+    fovy_offset: Rad<f32>,
     znear: f32,
     zfar: f32,
 }
@@ -72,6 +289,7 @@ impl Projection {
         Self {
             aspect: width as f32 / height as f32,
             fovy: fovy.into(),
+            fovy_offset: Rad(0.0),
             znear,
             zfar,
         }
@@ -81,20 +299,63 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    pub fn fovy_offset(&self) -> Rad<f32> {
+        self.fovy_offset
+    }
+
+    /// Widens the field of view by `offset`, or narrows it if negative.
+    pub fn set_fovy_offset<F: Into<Rad<f32>>>(&mut self, offset: F) {
+        self.fovy_offset = offset.into();
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    pub fn set_zfar(&mut self, zfar: f32) {
+        self.zfar = zfar;
+    }
+
     /// Returns the projection matrix for the camera.
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        let fovy = self.fovy + self.fovy_offset;
+        OPENGL_TO_WGPU_MATRIX * perspective(fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
+/// How the player moves around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Moves freely in every direction, through blocks.
+    Flying,
+    /// Walks on the ground as the player entity, falling and jumping.
+    Walking,
+}
+
 #[derive(Debug)]
 pub struct CameraController {
+    mode: MovementMode,
+    /// Whether switching to flying is allowed.
+    can_fly: bool,
+    /// Whether the camera stops against solid blocks instead of moving through them.
+    collision: bool,
+    /// When the jump key was last pressed, to tell double taps switching the mode.
+    last_jump: Option<Instant>,
     amount_left: f32,
     amount_right: f32,
     amount_forward: f32,
     amount_backward: f32,
     amount_up: f32,
     amount_down: f32,
+    sprinting: bool,
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
@@ -105,12 +366,17 @@ pub struct CameraController {
 impl CameraController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
+            mode: MovementMode::Walking,
+            can_fly: true,
+            collision: false,
+            last_jump: None,
             amount_left: 0.0,
             amount_right: 0.0,
             amount_forward: 0.0,
             amount_backward: 0.0,
             amount_up: 0.0,
             amount_down: 0.0,
+            sprinting: false,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
@@ -119,39 +385,79 @@ impl CameraController {
         }
     }
 
-    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
+    pub fn mode(&self) -> MovementMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: MovementMode) {
+        self.mode = mode;
+    }
+
+    /// Switches between flying and walking, unless flying isn't allowed.
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Flying => MovementMode::Walking,
+            MovementMode::Walking if self.can_fly => MovementMode::Flying,
+            MovementMode::Walking => MovementMode::Walking,
         };
-        match key {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.amount_forward = amount;
-                true
-            }
-            KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.amount_backward = amount;
-                true
-            }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
-                self.amount_left = amount;
-                true
-            }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.amount_right = amount;
-                true
-            }
-            KeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            KeyCode::ShiftLeft => {
-                self.amount_down = amount;
-                true
+    }
+
+    /// Sets how fast the camera flies, in world units per second.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Sets how fast the camera turns with the mouse.
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    /// Sets whether switching to flying is allowed. It doesn't stop flying.
+    pub fn set_can_fly(&mut self, can_fly: bool) {
+        self.can_fly = can_fly;
+    }
+
+    pub fn collision(&self) -> bool {
+        self.collision
+    }
+
+    /// Sets whether the camera stops against solid blocks instead of moving through them.
+    pub fn set_collision(&mut self, collision: bool) {
+        self.collision = collision;
+    }
+
+    /// Starts or stops moving for a movement `action`, returning whether it moves the camera.
+    /// Pressing jump twice in quick succession switches between flying and walking.
+    pub fn process_action(&mut self, action: Action, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        // Held keys repeat presses, which aren't taps.
+        if action == Action::Jump && pressed && self.amount_up == 0.0 {
+            let now = Instant::now();
+            match self.last_jump {
+                Some(last) if now - last < DOUBLE_TAP => {
+                    self.toggle_mode();
+                    self.last_jump = None;
+                }
+                _ => self.last_jump = Some(now),
             }
-            _ => false,
         }
+
+        let amount = if pressed { 1.0 } else { 0.0 };
+        if action == Action::Sprint {
+            self.sprinting = state == ElementState::Pressed;
+            return true;
+        }
+        let direction = match action {
+            Action::MoveForward => &mut self.amount_forward,
+            Action::MoveBackward => &mut self.amount_backward,
+            Action::MoveLeft => &mut self.amount_left,
+            Action::MoveRight => &mut self.amount_right,
+            Action::Jump => &mut self.amount_up,
+            Action::Descend => &mut self.amount_down,
+            _ => return false,
+        };
+        *direction = amount;
+        true
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
@@ -159,19 +465,75 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+    /// Returns the horizontal velocity the movement keys ask for, facing the way `camera`
+    /// looks, and widens or narrows the view of `camera` for sprinting over a step `dt`
+    /// seconds long.
+    fn walk_velocity(&self, camera: &mut Camera, dt: f32) -> Vector3<f32> {
         // Move forward/backward and left/right
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
 
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let forward_amount = self.amount_forward - self.amount_backward;
+        let right_amount = self.amount_right - self.amount_left;
+        let sprinting = self.sprinting && (forward_amount != 0.0 || right_amount != 0.0);
+        let speed = if sprinting {
+            self.speed * SPRINT_SPEED
+        } else {
+            self.speed
+        };
+
+        // Widen the view while sprinting, easing in and out of it.
+        let target = if sprinting {
+            SPRINT_FOV.into()
+        } else {
+            Rad(0.0)
+        };
+        let offset = camera.projection.fovy_offset();
+        let t = 1.0 - (-FOV_TRANSITION_RATE * dt).exp();
+        camera
+            .projection
+            .set_fovy_offset(offset + (target - offset) * t);
+
+        (forward * forward_amount + right * right_amount) * speed
+    }
+
+    /// Flies `camera` for a step of the simulation `dt` seconds long, sliding along the solid
+    /// blocks of `world` in the way while collision is on.
+    pub fn move_camera(&mut self, camera: &mut Camera, world: &World, dt: f32) {
+        let mut movement = self.walk_velocity(camera, dt) * dt;
 
         // Move up/down.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        movement.y += (self.amount_up - self.amount_down) * self.speed * dt;
 
-        // Rotate
+        if self.collision {
+            // Blocks that aren't loaded yet don't stop the camera.
+            let radius = Vector3::new(1.0, 1.0, 1.0) * CAMERA_RADIUS;
+            let aabb = Aabb::new(camera.position - radius, camera.position + radius);
+            let sweep = collision::sweep(aabb, movement, |position| {
+                world
+                    .get_block(position)
+                    .is_some_and(|block| block.is_solid())
+            });
+            camera.position += sweep.movement;
+        } else {
+            camera.position += movement;
+        }
+    }
+
+    /// Returns how the player is asked to walk for a step of the simulation `dt` seconds
+    /// long, jumping while the jump key is held.
+    pub fn controls(&mut self, camera: &mut Camera, dt: f32) -> Controls {
+        Controls {
+            walk: self.walk_velocity(camera, dt),
+            jump: self.amount_up > 0.0,
+        }
+    }
+
+    /// Turns `camera` with the mouse motion since the last frame, `dt` seconds ago. The
+    /// camera turns every frame rather than every simulation step, so looking around responds
+    /// right away.
+    pub fn rotate_camera(&mut self, camera: &mut Camera, dt: f32) {
         camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
         camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
 
@@ -205,3 +567,69 @@ impl CameraUniform {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A camera at the origin looking down -z with a 90° field of view, seeing from 1 to 100
+    /// units away, so at distance `d` it sees `d` units to each side.
+    fn frustum() -> Frustum {
+        let projection = perspective(Deg(90.0), 1.0, 1.0, 100.0);
+        let view = Matrix4::look_to_rh(Point3::origin(), -Vector3::unit_z(), Vector3::unit_y());
+        Frustum::from_matrix(DEPTH_TO_WGPU_MATRIX * projection * view)
+    }
+
+    fn aabb(center: Point3<f32>, radius: f32) -> Aabb {
+        let extent = Vector3::new(radius, radius, radius);
+        Aabb::new(center - extent, center + extent)
+    }
+
+    #[test]
+    fn contains_points_inside() {
+        let frustum = frustum();
+        assert!(frustum.contains_point(Point3::new(0.0, 0.0, -10.0)));
+        assert!(frustum.contains_point(Point3::new(9.0, -9.0, -10.0)));
+        assert!(frustum.contains_point(Point3::new(0.0, 0.0, -99.0)));
+    }
+
+    #[test]
+    fn excludes_points_outside() {
+        let frustum = frustum();
+        // Behind, before the near plane, past the far plane and off to each side.
+        assert!(!frustum.contains_point(Point3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(Point3::new(0.0, 0.0, -0.5)));
+        assert!(!frustum.contains_point(Point3::new(0.0, 0.0, -101.0)));
+        assert!(!frustum.contains_point(Point3::new(11.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(Point3::new(-11.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(Point3::new(0.0, 11.0, -10.0)));
+        assert!(!frustum.contains_point(Point3::new(0.0, -11.0, -10.0)));
+    }
+
+    #[test]
+    fn intersects_boxes_inside() {
+        let frustum = frustum();
+        assert!(frustum.intersects_aabb(&aabb(Point3::new(0.0, 0.0, -10.0), 1.0)));
+        assert!(frustum.intersects_aabb(&aabb(Point3::new(-5.0, 5.0, -50.0), 2.0)));
+    }
+
+    #[test]
+    fn excludes_boxes_outside() {
+        let frustum = frustum();
+        assert!(!frustum.intersects_aabb(&aabb(Point3::new(0.0, 0.0, 10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&aabb(Point3::new(20.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&aabb(Point3::new(0.0, -20.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&aabb(Point3::new(0.0, 0.0, -110.0), 1.0)));
+    }
+
+    #[test]
+    fn intersects_boxes_straddling_a_plane() {
+        let frustum = frustum();
+        // Centered outside but reaching in across the right side, the camera and the far plane.
+        assert!(frustum.intersects_aabb(&aabb(Point3::new(11.0, 0.0, -10.0), 2.0)));
+        assert!(frustum.intersects_aabb(&aabb(Point3::new(0.0, 0.0, 0.0), 2.0)));
+        assert!(frustum.intersects_aabb(&aabb(Point3::new(0.0, 0.0, -101.0), 2.0)));
+        // Containing the whole frustum.
+        assert!(frustum.intersects_aabb(&aabb(Point3::origin(), 500.0)));
+    }
+}
@@ -1,236 +1,1173 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
 
+use cgmath::{EuclideanSpace, InnerSpace};
 use noise::utils::NoiseMapBuilder;
 use noise::NoiseFn;
 use noise::{utils::PlaneMapBuilder, Fbm, Perlin};
+use rayon::prelude::*;
 
-use crate::noise::generate_perlin_noise;
-use crate::renderer::block::{self, Block, BlockType, Face, TerrainMesh};
+use crate::aabb::Aabb;
+use crate::biome::Biome;
+use crate::block_entity::BlockEntity;
+use crate::camera::{Camera, Frustum};
+use crate::generator::{GenerationProgress, GenerationStage, TerrainGenerator};
+use crate::light::{self, LightMap};
+use crate::mesher::{ChunkBorder, ChunkSnapshot, MeshedChunk, Mesher};
+use crate::palette::PaletteStorage;
+use crate::position::{BlockPos, ChunkPos};
+use crate::renderer::block::{BlockType, BlockVertex, Face};
+use crate::renderer::buffer::MeshBuffers;
+use crate::renderer::pool::MeshPool;
+use crate::save::{RegionStorage, SaveWorker};
+use crate::visibility::{self, ChunkVisibility};
 
 pub struct Chunk {
-    pub position: cgmath::Vector3<f32>,
-    blocks: Vec<Vec<Vec<Block>>>,
-    mesh: TerrainMesh,
+    pub position: ChunkPos,
+    blocks: ChunkData,
+    /// The uploaded mesh, `None` if the chunk has no visible faces.
+    buffers: Option<MeshBuffers<BlockVertex>>,
+    /// Changes every time the blocks change.
+    revision: u64,
+    /// The revision the uploaded mesh was generated from.
+    meshed_revision: Option<u64>,
+    /// The revision last sent to the mesher.
+    queued_revision: Option<u64>,
+    /// Which faces of the chunk can see each other, as of the uploaded mesh.
+    visibility: ChunkVisibility,
+    /// The biome of every column, indexed with [`column_index`].
+    biomes: Vec<Biome>,
+    /// The light level of every block. Not saved, since it is recomputed on load.
+    light: LightMap,
+    /// Whether the chunk has changes that are not saved to disk yet.
+    unsaved: bool,
+    /// The data kept by blocks of the chunk beyond their type, indexed with [`block_index`].
+    block_entities: HashMap<usize, BlockEntity>,
 }
 
-const CHUNK_WIDTH: usize = 32;
-const CHUNK_HEIGHT: usize = 32;
-const CHUNK_DEPTH: usize = 32;
+pub const CHUNK_WIDTH: usize = 32;
+pub const CHUNK_HEIGHT: usize = 32;
+pub const CHUNK_DEPTH: usize = 32;
+pub const CHUNK_VOLUME: usize = CHUNK_WIDTH * CHUNK_HEIGHT * CHUNK_DEPTH;
+
+/// The seed new worlds are generated with. Every part of world generation is derived from the
+/// seed of the world.
+pub const DEFAULT_SEED: u32 = 1234;
+
+/// Air below this height is filled with water when generating terrain, forming lakes and
+/// oceans.
+pub const SEA_LEVEL: i32 = 6;
+
+/// The size of a block in world units.
+pub const BLOCK_SIZE: f32 = 2.0;
+
+/// Every block of a chunk laid out contiguously, indexed with [`block_index`].
+pub type BlockArray = Box<[BlockType; CHUNK_VOLUME]>;
+
+/// The blocks of a chunk. Chunks made of a single block type, like the sky or deep
+/// underground, don't store anything per block.
+#[derive(Debug, Clone)]
+pub enum ChunkData {
+    Uniform(BlockType),
+    Paletted(PaletteStorage),
+}
+
+impl ChunkData {
+    pub fn get(&self, index: usize) -> BlockType {
+        match self {
+            ChunkData::Uniform(block_type) => *block_type,
+            ChunkData::Paletted(storage) => storage.get(index),
+        }
+    }
+
+    /// Sets a block, switching to per block storage if the chunk is no longer uniform.
+    pub fn set(&mut self, index: usize, block_type: BlockType) {
+        match self {
+            ChunkData::Uniform(uniform) if *uniform == block_type => {}
+            ChunkData::Uniform(uniform) => {
+                let mut storage = PaletteStorage::new(CHUNK_VOLUME, *uniform);
+                storage.set(index, block_type);
+                *self = ChunkData::Paletted(storage);
+            }
+            ChunkData::Paletted(storage) => storage.set(index, block_type),
+        }
+    }
+
+    /// Returns the block type of every block, if they are all the same.
+    pub fn uniform(&self) -> Option<BlockType> {
+        match self {
+            ChunkData::Uniform(block_type) => Some(*block_type),
+            ChunkData::Paletted(_) => None,
+        }
+    }
+
+    /// Switches back to uniform storage if every block has the same type.
+    pub fn compact(&mut self) {
+        if let ChunkData::Paletted(storage) = self {
+            let first = storage.get(0);
+            if storage.palette().len() == 1 || (1..storage.len()).all(|i| storage.get(i) == first) {
+                *self = ChunkData::Uniform(first);
+            }
+        }
+    }
+
+    /// Decodes every block into `out`, which must hold exactly [`CHUNK_VOLUME`] blocks.
+    pub fn unpack(&self, out: &mut [BlockType]) {
+        match self {
+            ChunkData::Uniform(block_type) => out.fill(*block_type),
+            ChunkData::Paletted(storage) => storage.unpack(out),
+        }
+    }
+
+    /// Returns the blocks of the layer bordering `face`.
+    pub fn border(&self, face: Face) -> ChunkBorder {
+        let storage = match self {
+            ChunkData::Uniform(block_type) => return ChunkBorder::Uniform(*block_type),
+            ChunkData::Paletted(storage) => storage,
+        };
+
+        let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+        let (axis, u_axis, v_axis) = face.axes();
+        let layer = if face.normal()[axis] < 0 {
+            0
+        } else {
+            dimensions[axis] - 1
+        };
+
+        let mut blocks = Vec::with_capacity(dimensions[u_axis] * dimensions[v_axis]);
+        for v in 0..dimensions[v_axis] {
+            for u in 0..dimensions[u_axis] {
+                let mut position = [0; 3];
+                position[axis] = layer;
+                position[u_axis] = u;
+                position[v_axis] = v;
+
+                let [x, y, z] = position;
+                blocks.push(storage.get(block_index(x, y, z)));
+            }
+        }
+
+        ChunkBorder::Blocks(blocks)
+    }
+
+    /// Writes the blocks in the same format regardless of how they are stored.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            ChunkData::Uniform(block_type) => {
+                PaletteStorage::new(CHUNK_VOLUME, *block_type).write(writer)
+            }
+            ChunkData::Paletted(storage) => storage.write(writer),
+        }
+    }
+}
+
+/// Hands out chunk revisions. Revisions are unique across all chunks so a mesh finished after
+/// its chunk was unloaded can never be mistaken for one of a newly loaded chunk.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+/// Returns the index of the block at the given local coordinates.
+#[inline]
+pub fn block_index(x: usize, y: usize, z: usize) -> usize {
+    (x * CHUNK_HEIGHT + y) * CHUNK_DEPTH + z
+}
+
+/// Returns the index of the column at the given local coordinates.
+#[inline]
+pub fn column_index(x: usize, z: usize) -> usize {
+    x * CHUNK_DEPTH + z
+}
 
 impl Chunk {
-    pub fn new(position: cgmath::Vector3<f32>) -> Self {
+    pub fn new(position: ChunkPos) -> Self {
         let mut this = Self {
             position,
-            mesh: TerrainMesh::new(),
-            blocks: vec![
-                vec![
-                    vec![
-                        Block::new(BlockType::Air, cgmath::Vector3::new(0.0, 0.0, 0.0));
-                        CHUNK_DEPTH as usize
-                    ];
-                    CHUNK_HEIGHT as usize
-                ];
-                CHUNK_WIDTH as usize
-            ],
+            buffers: None,
+            revision: next_revision(),
+            meshed_revision: None,
+            queued_revision: None,
+            visibility: ChunkVisibility::ALL,
+            biomes: vec![Biome::Plains; CHUNK_WIDTH * CHUNK_DEPTH],
+            light: LightMap::new(),
+            unsaved: true,
+            blocks: ChunkData::Uniform(BlockType::Air),
+            block_entities: HashMap::new(),
         };
 
         this
     }
 
-    pub fn mesh(&self) -> &TerrainMesh {
-        &self.mesh
+    /// Returns the space taken up by the blocks of the chunk in world coordinates.
+    pub fn bounds(&self) -> Aabb {
+        let half_block = cgmath::Vector3::new(1.0, 1.0, 1.0) * (BLOCK_SIZE / 2.0);
+        let min = self.position.origin().center() - half_block;
+        let max = self
+            .position
+            .block(CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH)
+            .center()
+            - half_block;
+        Aabb::new(cgmath::Point3::from_vec(min), cgmath::Point3::from_vec(max))
     }
 
-    fn init(&mut self, height_map: &HashMap<(usize, usize), f32>) {
-        let block_size = 2.0;
-        for x in 0..CHUNK_WIDTH as usize {
-            for z in 0..CHUNK_DEPTH as usize {
-                let height_map_x = x + self.position.x as usize;
-                let height_map_z = z + self.position.z as usize;
+    pub fn buffers(&self) -> Option<&MeshBuffers<BlockVertex>> {
+        self.buffers.as_ref()
+    }
 
-                let terrain_height = *height_map.get(&(height_map_x, height_map_z)).unwrap();
+    /// Returns whether the uploaded mesh is out of date with the blocks.
+    pub fn is_dirty(&self) -> bool {
+        self.meshed_revision != Some(self.revision)
+    }
 
-                for y in 0..CHUNK_HEIGHT as usize {
-                    let mut block_type = BlockType::Air;
+    /// Marks the chunk for remeshing.
+    pub fn mark_dirty(&mut self) {
+        self.revision = next_revision();
+    }
 
-                    if y == terrain_height as usize {
-                        block_type = BlockType::Grass;
-                    } else if y == 0 {
-                        block_type = BlockType::Stone;
-                    } else if y < terrain_height as usize {
-                        block_type = BlockType::Dirt;
-                    }
+    pub fn visibility(&self) -> ChunkVisibility {
+        self.visibility
+    }
 
-                    let position = cgmath::Vector3::new(
-                        self.position.x + x as f32,
-                        self.position.y + y as f32,
-                        self.position.z + z as f32,
-                    ) * block_size;
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
 
-                    self.blocks[x][y][z] = Block::new(block_type, position);
-                }
-            }
+    /// Copies the blocks and light so they can be meshed without borrowing the chunk, along
+    /// with the borders of the neighboring chunks, indexed by [`Face`].
+    pub fn snapshot(
+        &self,
+        neighbors: [Option<ChunkBorder>; 6],
+        neighbor_light: [Option<Vec<u8>>; 6],
+    ) -> ChunkSnapshot {
+        ChunkSnapshot {
+            position: self.position,
+            revision: self.revision,
+            blocks: self.blocks(),
+            light: self.light.clone(),
+            neighbors,
+            neighbor_light,
         }
+    }
 
-        self.generate_mesh();
+    pub fn light(&self) -> &LightMap {
+        &self.light
     }
 
-    pub fn generate_mesh(&mut self) {
-        self.mesh = TerrainMesh::new();
+    pub fn light_mut(&mut self) -> &mut LightMap {
+        &mut self.light
+    }
+
+    /// Lights the chunk on its own, as if it had no neighbors.
+    pub fn compute_light(&mut self) {
+        self.light = light::compute(&self.blocks());
+    }
+
+    /// Returns the block type of every block, if they are all the same.
+    pub fn uniform(&self) -> Option<BlockType> {
+        self.blocks.uniform()
+    }
+
+    /// Returns the blocks of the layer bordering `face`.
+    pub fn border(&self, face: Face) -> ChunkBorder {
+        self.blocks.border(face)
+    }
 
+    /// Returns the biome of the column at the given local coordinates.
+    pub fn biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes[column_index(x, z)]
+    }
+
+    /// Decides the biome of every column. Biomes are not saved, since they can always be
+    /// derived from the world seed again.
+    pub fn generate_biomes(&mut self, generator: &dyn TerrainGenerator) {
         for x in 0..CHUNK_WIDTH {
-            for y in 0..CHUNK_HEIGHT {
-                for z in 0..CHUNK_DEPTH {
-                    let block = &self.blocks[x][y][z];
+            for z in 0..CHUNK_DEPTH {
+                let BlockPos(world_x, _, world_z) = self.position.block(x, 0, z);
+                self.biomes[column_index(x, z)] = generator.biome_at(world_x, world_z);
+            }
+        }
+    }
 
-                    if block.is_air() {
-                        continue;
-                    }
+    /// Switches back to storage without per block data if every block has the same type.
+    pub fn compact(&mut self) {
+        self.blocks.compact();
+    }
 
-                    let x = x as isize;
-                    let y = y as isize;
-                    let z = z as isize;
+    /// Returns the block type at the given local coordinates.
+    pub fn block(&self, x: usize, y: usize, z: usize) -> BlockType {
+        self.blocks.get(block_index(x, y, z))
+    }
 
-                    // TODO: check neighbors between chunks.
+    /// Decodes every block into a flat array, which is much cheaper to read from repeatedly
+    /// than the packed storage.
+    pub fn blocks(&self) -> BlockArray {
+        let mut blocks: BlockArray = vec![BlockType::Air; CHUNK_VOLUME]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap();
+        self.blocks.unpack(blocks.as_mut_slice());
+        blocks
+    }
 
-                    // check left neighbor
-                    if self.should_render_face(x - 1, y, z) {
-                        self.mesh.add_face(block.generate_face(Face::Left));
-                    }
-                    // check right neighbor
-                    if self.should_render_face(x + 1, y, z) {
-                        self.mesh.add_face(block.generate_face(Face::Right));
-                    }
-                    // check bottom neighbor
-                    if self.should_render_face(x, y - 1, z) {
-                        self.mesh.add_face(block.generate_face(Face::Bottom));
-                    }
-                    // check top neighbor
-                    if self.should_render_face(x, y + 1, z) {
-                        self.mesh.add_face(block.generate_face(Face::Top));
-                    }
-                    // check front neighbor
-                    if self.should_render_face(x, y, z - 1) {
-                        self.mesh.add_face(block.generate_face(Face::Front));
-                    }
-                    // check back neighbor
-                    if self.should_render_face(x, y, z + 1) {
-                        self.mesh.add_face(block.generate_face(Face::Back));
-                    }
-                }
+    /// Returns the world position of the center of the block at the given local coordinates.
+    pub fn block_position(&self, x: usize, y: usize, z: usize) -> cgmath::Vector3<f32> {
+        self.position.block(x, y, z).center()
+    }
+
+    /// Sets the block at the given local coordinates and marks the chunk for remeshing. The
+    /// data the replaced block kept is dropped, and the new block starts with its own.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_type: BlockType) {
+        let index = block_index(x, y, z);
+        if self.blocks.get(index) != block_type {
+            self.blocks.set(index, block_type);
+            self.block_entities.remove(&index);
+            if let Some(entity) = BlockEntity::new(block_type) {
+                self.block_entities.insert(index, entity);
             }
+            self.mark_dirty();
+            self.unsaved = true;
+        }
+    }
+
+    /// Returns the data kept by the block at the given local coordinates, if it keeps any.
+    pub fn block_entity(&self, x: usize, y: usize, z: usize) -> Option<&BlockEntity> {
+        self.block_entities.get(&block_index(x, y, z))
+    }
+
+    /// Returns the data kept by the block at the given local coordinates to be changed, if it
+    /// keeps any, marking the chunk as unsaved.
+    pub fn block_entity_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut BlockEntity> {
+        let entity = self.block_entities.get_mut(&block_index(x, y, z))?;
+        self.unsaved = true;
+        Some(entity)
+    }
+
+    /// Returns the data kept by blocks of the chunk, indexed with [`block_index`].
+    pub fn block_entities(&self) -> &HashMap<usize, BlockEntity> {
+        &self.block_entities
+    }
+
+    /// Replaces the data kept by blocks of the chunk, like when it's loaded from disk.
+    pub fn set_block_entities(&mut self, block_entities: HashMap<usize, BlockEntity>) {
+        self.block_entities = block_entities;
+    }
+
+    pub fn is_unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Marks the chunk as matching what is saved on disk.
+    pub fn mark_saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    /// Marks the chunk as differing from what is saved on disk, like when saving it failed.
+    pub fn mark_unsaved(&mut self) {
+        self.unsaved = true;
+    }
+
+    /// Writes the blocks of the chunk.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.blocks.write(writer)
+    }
+
+    /// Reads a chunk previously written with [`Chunk::write`].
+    pub fn read(position: ChunkPos, reader: &mut impl Read) -> io::Result<Self> {
+        let blocks = PaletteStorage::read(reader)?;
+        if blocks.len() != CHUNK_VOLUME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "chunk has the wrong number of blocks",
+            ));
+        }
+
+        Ok(Self::from_storage(position, blocks))
+    }
+
+    /// Creates a chunk from blocks that were loaded from disk.
+    pub fn from_storage(position: ChunkPos, blocks: PaletteStorage) -> Self {
+        let mut chunk = Self::new(position);
+        chunk.blocks = ChunkData::Paletted(blocks);
+        chunk.blocks.compact();
+        chunk.unsaved = false;
+        chunk
+    }
+
+    /// Uploads a mesh finished by the mesher, replacing the previous buffers. The CPU side mesh
+    /// is handed back to the pool afterwards since it is no longer needed.
+    pub fn upload_mesh(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pool: &mut MeshPool,
+        meshed: MeshedChunk,
+    ) {
+        if let Some(buffers) = self.buffers.take() {
+            pool.recycle_buffers(buffers);
         }
+
+        self.buffers = pool.upload(device, queue, &meshed.mesh);
+        pool.recycle_mesh(meshed.mesh);
+        self.meshed_revision = Some(meshed.revision);
+        self.visibility = meshed.visibility;
     }
 
-    fn should_render_face(&self, x: isize, y: isize, z: isize) -> bool {
-        // check out of bounds.
-        if x < 0
-            || x >= CHUNK_WIDTH as isize
-            || y < 0
-            || y >= CHUNK_HEIGHT as isize
-            || z < 0
-            || z >= CHUNK_DEPTH as isize
-        {
-            return true;
+    /// Drops the mesh of a chunk without any blocks, which never needs meshing.
+    fn clear_mesh(&mut self, pool: &mut MeshPool) {
+        self.release_buffers(pool);
+        self.meshed_revision = Some(self.revision);
+        self.visibility = ChunkVisibility::ALL;
+    }
+
+    /// Hands the GPU buffers of the chunk back to the pool.
+    pub fn release_buffers(&mut self, pool: &mut MeshPool) {
+        if let Some(buffers) = self.buffers.take() {
+            pool.recycle_buffers(buffers);
         }
+    }
+}
 
-        let block = self.blocks[x as usize][y as usize][z as usize];
+/// Limits how much chunk work is done per frame, so bursts of work like teleporting or fast
+/// flight are spread over several frames instead of stalling one. Work over the budget is
+/// carried over to the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkBudget {
+    /// The maximum number of chunks loaded or generated per update.
+    pub generations: usize,
+    /// The maximum number of chunks sent to the mesher per frame.
+    pub remeshes: usize,
+    /// The maximum number of meshes uploaded to the GPU per frame.
+    pub uploads: usize,
+}
 
-        block.is_air()
+impl Default for ChunkBudget {
+    fn default() -> Self {
+        Self {
+            generations: 32,
+            remeshes: 64,
+            uploads: 32,
+        }
     }
 }
 
-pub fn generate_chunks(chunk_count: usize) -> Vec<Chunk> {
-    let scale = 50.0;
-    let seed = 1234;
+/// How many chunks closer a chunk straight ahead of the camera is treated as, compared to one
+/// straight behind it.
+const VIEW_DIRECTION_WEIGHT: f32 = 4.0;
 
-    let height_min = 0.0;
-    let height_max = 15.0;
+/// How long a chunk whose saved copy failed to load waits before it's read again. It's never
+/// generated instead, which would overwrite the saved copy once saved.
+const LOAD_RETRY_DELAY: Duration = Duration::from_secs(5);
 
-    let block_size = 2.0;
-    let height_map = generate_perlin_noise(
-        chunk_count * CHUNK_WIDTH as usize,
-        chunk_count * CHUNK_DEPTH as usize,
-        scale,
-        seed,
-        height_min,
-        height_max,
-    );
+/// A chunk waiting to be loaded. Chunks with a lower priority value are loaded first.
+#[derive(Debug, Clone, Copy)]
+struct PendingChunk {
+    priority: f32,
+    position: ChunkPos,
+}
+
+impl PendingChunk {
+    /// Prioritizes chunks close to the camera, preferring those in the direction it faces.
+    fn new(position: ChunkPos, camera: ChunkPos, forward: cgmath::Vector3<f32>) -> Self {
+        let offset = cgmath::Vector2::new(
+            (position.0 - camera.0) as f32,
+            (position.2 - camera.2) as f32,
+        );
+        let distance = offset.magnitude();
+        let forward = cgmath::Vector2::new(forward.x, forward.z);
+
+        let alignment = if distance > 0.0 && forward.magnitude2() > 0.0 {
+            offset.normalize().dot(forward.normalize())
+        } else {
+            0.0
+        };
 
-    let mut chunks = Vec::new();
-    for chunk_x in 0..chunk_count {
-        for chunk_z in 0..chunk_count {
-            chunks.push(Chunk::new(cgmath::Vector3::new(
-                chunk_x as f32 * CHUNK_WIDTH as f32,
-                0 as f32 * CHUNK_HEIGHT as f32,
-                chunk_z as f32 * CHUNK_DEPTH as f32,
-            )));
+        Self {
+            priority: distance - alignment * VIEW_DIRECTION_WEIGHT * 0.5,
+            position,
         }
     }
+}
+
+impl PartialEq for PendingChunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PendingChunk {}
 
-    chunks.iter_mut().for_each(|ch| ch.init(&height_map));
-    chunks
+impl PartialOrd for PendingChunk {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-pub struct ChunkList {
-    /// The list of chunks.
-    chunks: Vec<Chunk>,
-    /// The calculated mesh of all the chunks.
-    calculated_mesh: Option<TerrainMesh>,
+impl Ord for PendingChunk {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the max-heap pops the lowest priority value first.
+        other.priority.total_cmp(&self.priority)
+    }
 }
 
-impl ChunkList {
-    pub fn new(chunks: Vec<Chunk>) -> Self {
+/// Blocks waiting for the chunks they belong to, keyed by those chunks.
+pub type DeferredBlocks = HashMap<ChunkPos, Vec<(BlockPos, BlockType)>>;
+
+/// Sends progress to every subscriber, forgetting those that went away.
+fn report(
+    subscribers: &mut Vec<Sender<GenerationProgress>>,
+    stage: GenerationStage,
+    done: usize,
+    total: usize,
+) {
+    let progress = GenerationProgress { stage, done, total };
+    subscribers.retain(|subscriber| subscriber.send(progress).is_ok());
+}
+
+/// Places the blocks deferred for `chunk` into it.
+fn take_deferred(deferred: &mut DeferredBlocks, chunk: &mut Chunk) {
+    for (position, block_type) in deferred.remove(&chunk.position).unwrap_or_default() {
+        let (x, y, z) = position.local();
+        chunk.set_block(x, y, z, block_type);
+    }
+}
+
+/// Places `blocks` into whichever of `chunks` they belong to, deferring the rest. Returns the
+/// positions of the blocks that were placed.
+fn defer(
+    deferred: &mut DeferredBlocks,
+    chunks: &mut HashMap<ChunkPos, Chunk>,
+    blocks: Vec<(BlockPos, BlockType)>,
+) -> Vec<BlockPos> {
+    let mut placed = Vec::new();
+    for (position, block_type) in blocks {
+        match chunks.get_mut(&position.chunk()) {
+            Some(chunk) => {
+                let (x, y, z) = position.local();
+                chunk.set_block(x, y, z, block_type);
+                placed.push(position);
+            }
+            None => deferred
+                .entry(position.chunk())
+                .or_default()
+                .push((position, block_type)),
+        }
+    }
+    placed
+}
+
+pub struct ChunkManager {
+    /// The loaded chunks, keyed by their chunk coordinates.
+    chunks: HashMap<ChunkPos, Chunk>,
+    /// Fills newly created chunks with terrain.
+    generator: Box<dyn TerrainGenerator>,
+    /// Blocks generated into chunks that don't exist yet, placed once they do.
+    deferred: DeferredBlocks,
+    /// Chunks further than this many chunks away from the camera are unloaded.
+    load_distance: usize,
+    /// Where chunks are saved when unloaded, if anywhere.
+    storage: Option<RegionStorage>,
+    /// Writes saved chunks in the background, if they're saved anywhere.
+    saver: Option<SaveWorker>,
+    /// The chunks with unsaved changes left to be saved by the autosave under way, if any.
+    autosave_queue: Vec<ChunkPos>,
+    /// The chunks in range that are not loaded yet, most important first.
+    queue: BinaryHeap<PendingChunk>,
+    /// The chunks whose saved copy failed to load, and when, so they're read again later.
+    failed_loads: HashMap<ChunkPos, Instant>,
+    /// Recycles mesh allocations of remeshed and unloaded chunks.
+    pool: MeshPool,
+    /// Meshes dirty chunks in the background.
+    mesher: Mesher,
+    /// Meshes finished by the mesher that are waiting to be uploaded.
+    finished: VecDeque<MeshedChunk>,
+    budget: ChunkBudget,
+    /// The chunk the camera was in during the last update.
+    camera: ChunkPos,
+    /// Receive the progress of loading and generating chunks.
+    subscribers: Vec<Sender<GenerationProgress>>,
+}
+
+impl ChunkManager {
+    pub fn new(
+        load_distance: usize,
+        storage: Option<RegionStorage>,
+        generator: Box<dyn TerrainGenerator>,
+    ) -> Self {
         Self {
-            chunks,
-            calculated_mesh: None,
+            chunks: HashMap::new(),
+            generator,
+            deferred: storage
+                .as_ref()
+                .map(|storage| {
+                    storage.load_deferred().unwrap_or_else(|err| {
+                        eprintln!("Failed to load deferred blocks: {err}");
+                        HashMap::new()
+                    })
+                })
+                .unwrap_or_default(),
+            load_distance,
+            saver: storage.clone().map(SaveWorker::new),
+            autosave_queue: Vec::new(),
+            storage,
+            queue: BinaryHeap::new(),
+            pool: MeshPool::new(),
+            mesher: Mesher::new(),
+            finished: VecDeque::new(),
+            failed_loads: HashMap::new(),
+            budget: ChunkBudget::default(),
+            camera: ChunkPos::default(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Returns a receiver of the progress of loading and generating chunks, which is sent
+    /// whenever chunks are loaded or generated.
+    pub fn subscribe(&mut self) -> Receiver<GenerationProgress> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Inserts a chunk, returning the chunk previously stored at the same coordinates. The
+    /// neighbors are remeshed, since faces bordering the new chunk may now be hidden.
+    pub fn insert(&mut self, chunk: Chunk) -> Option<Chunk> {
+        let position = chunk.position;
+        let previous = self.chunks.insert(position, chunk);
+        self.mark_neighbors_dirty(position);
+        previous
+    }
+
+    pub fn get(&self, position: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&position)
+    }
+
+    pub fn get_mut(&mut self, position: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&position)
+    }
+
+    /// Removes a chunk. The neighbors are remeshed, since faces bordering the chunk are no
+    /// longer hidden.
+    pub fn remove(&mut self, position: ChunkPos) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&position);
+        if chunk.is_some() {
+            self.mark_neighbors_dirty(position);
+        }
+        chunk
+    }
+
+    fn mark_neighbors_dirty(&mut self, position: ChunkPos) {
+        for face in Face::ALL {
+            if let Some(neighbor) = self.chunks.get_mut(&position.neighbor(face)) {
+                neighbor.mark_dirty();
+            }
+        }
+    }
+
+    /// Returns the borders of the loaded neighbors of a chunk facing it, indexed by [`Face`].
+    fn neighbor_borders(&self, position: ChunkPos) -> [Option<ChunkBorder>; 6] {
+        Face::ALL.map(|face| {
+            self.chunks
+                .get(&position.neighbor(face))
+                .map(|chunk| chunk.border(face.opposite()))
+        })
+    }
+
+    /// Returns the light levels of the borders of the loaded neighbors of a chunk facing it,
+    /// indexed by [`Face`].
+    fn neighbor_light(&self, position: ChunkPos) -> [Option<Vec<u8>>; 6] {
+        Face::ALL.map(|face| {
+            self.chunks
+                .get(&position.neighbor(face))
+                .map(|chunk| chunk.light().border(face.opposite()))
+        })
+    }
+
+    pub fn contains(&self, position: ChunkPos) -> bool {
+        self.chunks.contains_key(&position)
+    }
+
+    /// Returns the positions of the loaded chunks.
+    pub fn positions(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn load_distance(&self) -> usize {
+        self.load_distance
+    }
+
+    pub fn set_load_distance(&mut self, load_distance: usize) {
+        self.load_distance = load_distance;
+    }
+
+    pub fn budget(&self) -> ChunkBudget {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: ChunkBudget) {
+        self.budget = budget;
+    }
+
+    pub fn generator(&self) -> &dyn TerrainGenerator {
+        self.generator.as_ref()
+    }
+
+    /// Fills chunks generated from now on with `generator`. Chunks that were already generated
+    /// stay as they are.
+    pub fn set_generator(&mut self, generator: Box<dyn TerrainGenerator>) {
+        self.generator = generator;
+    }
+
+    /// Returns where chunks are saved, if anywhere.
+    pub fn storage(&self) -> Option<&RegionStorage> {
+        self.storage.as_ref()
+    }
+
+    /// Returns the number of chunks in range that are still waiting to be loaded.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Unloads the chunks outside the load distance of `camera_position`, releasing their GPU
+    /// buffers, and loads the most important chunks within it. Chunks closest to the camera
+    /// and in the direction of `camera_forward` are loaded first.
+    pub fn update(
+        &mut self,
+        camera_position: cgmath::Point3<f32>,
+        camera_forward: cgmath::Vector3<f32>,
+    ) {
+        self.poll_saver();
+
+        let camera = ChunkPos::from_world(camera_position);
+        let ChunkPos(camera_x, _, camera_z) = camera;
+        self.camera = camera;
+        let distance = self.load_distance as i32;
+
+        let in_range = |position: &ChunkPos| {
+            (position.0 - camera_x).abs() <= distance && (position.2 - camera_z).abs() <= distance
+        };
+
+        let unloaded: Vec<ChunkPos> = self
+            .chunks
+            .keys()
+            .filter(|position| !in_range(position))
+            .copied()
+            .collect();
+        let mut unloaded: Vec<Chunk> = unloaded
+            .into_iter()
+            .filter_map(|position| self.remove(position))
+            .collect();
+
+        for chunk in unloaded.iter_mut() {
+            chunk.release_buffers(&mut self.pool);
+        }
+
+        self.save_in_background(unloaded.iter().filter(|ch| ch.is_unsaved()));
+
+        // Rebuild the queue every update so priorities follow the camera.
+        self.queue.clear();
+        for chunk_x in camera_x - distance..=camera_x + distance {
+            for chunk_z in camera_z - distance..=camera_z + distance {
+                let position = ChunkPos(chunk_x, 0, chunk_z);
+                if !self.contains(position) {
+                    self.queue
+                        .push(PendingChunk::new(position, camera, camera_forward));
+                }
+            }
+        }
+
+        self.failed_loads
+            .retain(|_, failed| failed.elapsed() < LOAD_RETRY_DELAY);
+        let mut missing = Vec::new();
+        while missing.len() < self.budget.generations {
+            match self.queue.pop() {
+                // Chunks still being written would be loaded as they were before.
+                Some(pending) if self.is_being_saved(pending.position) => {}
+                Some(pending) if self.failed_loads.contains_key(&pending.position) => {}
+                Some(pending) => missing.push(pending.position),
+                None => break,
+            }
+        }
+
+        if missing.is_empty() {
+            return;
+        }
+
+        let total = (distance as usize * 2 + 1).pow(2);
+        let remaining = self.queue.len();
+
+        let mut new_chunks = Vec::new();
+        if let Some(storage) = &self.storage {
+            let now = Instant::now();
+            for (position, result) in storage.load_chunks(&missing) {
+                match result {
+                    Ok(Some(mut chunk)) => {
+                        missing.retain(|missing| *missing != position);
+                        chunk.generate_biomes(self.generator.as_ref());
+                        new_chunks.push(chunk);
+                    }
+                    // Never saved, so it's generated.
+                    Ok(None) => {}
+                    Err(err) => {
+                        // Generating it instead would overwrite the saved copy once saved.
+                        eprintln!("Failed to load chunk {position:?}, retrying later: {err}");
+                        missing.retain(|missing| *missing != position);
+                        self.failed_loads.insert(position, now);
+                    }
+                }
+            }
+            if !new_chunks.is_empty() {
+                let done = total - remaining - missing.len();
+                report(&mut self.subscribers, GenerationStage::Loading, done, total);
+            }
         }
+
+        let mut overflow = Vec::new();
+        if !missing.is_empty() {
+            for (chunk, blocks) in self.generate(missing) {
+                new_chunks.push(chunk);
+                overflow.extend(blocks);
+            }
+            report(
+                &mut self.subscribers,
+                GenerationStage::Generating,
+                total - remaining,
+                total,
+            );
+        }
+
+        for chunk in new_chunks.iter_mut() {
+            take_deferred(&mut self.deferred, chunk);
+        }
+        new_chunks.par_iter_mut().for_each(|ch| ch.compute_light());
+
+        let positions: Vec<ChunkPos> = new_chunks.iter().map(|ch| ch.position).collect();
+        for chunk in new_chunks {
+            self.insert(chunk);
+        }
+
+        // Let light flow in from the neighbors, and relight around blocks that spilled into
+        // chunks that were already lit.
+        let mut changed = HashSet::new();
+        for position in positions {
+            changed.extend(light::stitch(&mut self.chunks, position));
+        }
+        for position in defer(&mut self.deferred, &mut self.chunks, overflow) {
+            changed.extend(light::relight(&mut self.chunks, position));
+        }
+        for position in changed {
+            if let Some(chunk) = self.chunks.get_mut(&position) {
+                chunk.mark_dirty();
+            }
+        }
+    }
+
+    /// Updates the light around `position` after the block there changed, remeshing every
+    /// chunk whose light changed.
+    pub fn relight(&mut self, position: BlockPos) {
+        for position in light::relight(&mut self.chunks, position) {
+            if let Some(chunk) = self.chunks.get_mut(&position) {
+                chunk.mark_dirty();
+            }
+        }
+    }
+
+    /// Generates new chunks at `positions`, along with the blocks they placed outside of
+    /// themselves.
+    fn generate(&self, positions: Vec<ChunkPos>) -> Vec<(Chunk, Vec<(BlockPos, BlockType)>)> {
+        // Chunks are independent of each other, so generate them in parallel.
+        let generator = self.generator.as_ref();
+        positions
+            .into_par_iter()
+            .map(|position| {
+                let mut chunk = Chunk::new(position);
+                chunk.generate_biomes(generator);
+                let overflow = generator.generate(&mut chunk);
+                chunk.compact();
+                (chunk, overflow)
+            })
+            .collect()
     }
 
-    pub fn add_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+    /// Generates and saves every chunk within `radius` chunks of `center` that is not saved
+    /// yet, without loading them. Progress is reported after every batch.
+    pub fn pregenerate(&mut self, center: ChunkPos, radius: usize) -> anyhow::Result<()> {
+        const BATCH_SIZE: usize = 256;
+
+        let Some(storage) = &self.storage else {
+            anyhow::bail!("the world has no directory to save chunks to");
+        };
+
+        let radius = radius as i32;
+        let mut positions = Vec::new();
+        for chunk_x in center.0 - radius..=center.0 + radius {
+            for chunk_z in center.2 - radius..=center.2 + radius {
+                positions.push(ChunkPos(chunk_x, 0, chunk_z));
+            }
+        }
+
+        let total = positions.len();
+        let mut done = 0;
+        for batch in positions.chunks(BATCH_SIZE) {
+            let saved = storage.saved_chunks(batch)?;
+            let missing: Vec<ChunkPos> = batch
+                .iter()
+                .filter(|position| !saved.contains(position) && !self.contains(**position))
+                .copied()
+                .collect();
+
+            let mut generated = HashMap::new();
+            let mut overflow = Vec::new();
+            for (mut chunk, blocks) in self.generate(missing) {
+                take_deferred(&mut self.deferred, &mut chunk);
+                generated.insert(chunk.position, chunk);
+                overflow.extend(blocks);
+            }
+            defer(&mut self.deferred, &mut generated, overflow);
+            done += batch.len();
+            report(
+                &mut self.subscribers,
+                GenerationStage::Generating,
+                done,
+                total,
+            );
+
+            storage.save_chunks(generated.values())?;
+            report(&mut self.subscribers, GenerationStage::Saving, done, total);
+        }
+
+        storage.save_deferred(&self.deferred)
+    }
+
+    /// Serializes `chunks` and queues them to be written in the background.
+    fn save_in_background<'a>(&mut self, chunks: impl IntoIterator<Item = &'a Chunk>) {
+        if let (Some(storage), Some(saver)) = (&self.storage, &mut self.saver) {
+            saver.submit(storage.encode_chunks(chunks));
+        }
     }
 
-    pub fn get_chunk(&self, position: cgmath::Vector3<f32>) -> Option<&Chunk> {
-        self.chunks.iter().find(|ch| ch.position == position)
+    /// Returns whether the chunk at `position` is waiting to be written in the background.
+    fn is_being_saved(&self, position: ChunkPos) -> bool {
+        self.saver
+            .as_ref()
+            .is_some_and(|saver| saver.is_pending(position))
     }
 
-    pub fn get_chunk_mut(&mut self, position: cgmath::Vector3<f32>) -> Option<&mut Chunk> {
-        self.chunks.iter_mut().find(|ch| ch.position == position)
+    /// Marks the loaded chunks that failed to be written in the background as unsaved again,
+    /// so they're saved later.
+    fn poll_saver(&mut self) {
+        let Some(saver) = &mut self.saver else {
+            return;
+        };
+        for position in saver.poll() {
+            if let Some(chunk) = self.chunks.get_mut(&position) {
+                chunk.mark_unsaved();
+            }
+        }
     }
 
-    pub fn merge_meshes(&mut self) -> TerrainMesh {
-        // Merge all the meshes of the chunks into a single mesh.
-        let mut global_vertices: Vec<block::BlockVertex> = Vec::new();
-        let mut global_indices: Vec<u32> = Vec::new();
-        for chunk in self.chunks.iter() {
-            let mesh = chunk.mesh();
-            let vertices = mesh.vertices();
-            let indices = mesh.indices();
+    /// Starts saving the loaded chunks with unsaved changes a few at a time, unless already
+    /// doing so. Chunks changed after it starts are left for the next one.
+    pub fn start_autosave(&mut self) {
+        if !self.autosave_queue.is_empty() || self.storage.is_none() {
+            return;
+        }
+        self.autosave_queue = self
+            .chunks
+            .values()
+            .filter(|chunk| chunk.is_unsaved())
+            .map(|chunk| chunk.position)
+            .collect();
+    }
 
-            let base_index = global_vertices.len() as u32;
+    /// Serializes up to `max_chunks` of the chunks left by the autosave under way and queues
+    /// them to be written in the background, saving the blocks waiting for their chunks once
+    /// it's through them. Returns whether the autosave is done serializing.
+    pub fn autosave(&mut self, max_chunks: usize) -> bool {
+        let start = self.autosave_queue.len().saturating_sub(max_chunks);
+        let positions: Vec<ChunkPos> = self.autosave_queue.drain(start..).collect();
+        // Chunks unloaded since were saved then.
+        let chunks: Vec<&Chunk> = positions
+            .iter()
+            .filter_map(|position| self.chunks.get(position))
+            .filter(|chunk| chunk.is_unsaved())
+            .collect();
+        if let (Some(storage), Some(saver)) = (&self.storage, &mut self.saver) {
+            saver.submit(storage.encode_chunks(chunks));
+        }
+        for position in positions {
+            if let Some(chunk) = self.chunks.get_mut(&position) {
+                chunk.mark_saved();
+            }
+        }
+        if !self.autosave_queue.is_empty() {
+            return false;
+        }
 
-            for vertex in vertices.iter() {
-                global_vertices.push(*vertex);
+        if let Some(storage) = &self.storage {
+            if let Err(err) = storage.save_deferred(&self.deferred) {
+                eprintln!("Failed to save deferred blocks: {err}");
             }
+        }
+        true
+    }
 
-            for index in indices.iter() {
-                global_indices.push(base_index + *index);
+    /// Returns whether chunks are being saved in the background.
+    pub fn is_saving(&self) -> bool {
+        !self.autosave_queue.is_empty() || self.saver.as_ref().is_some_and(SaveWorker::is_busy)
+    }
+
+    /// Saves every loaded chunk with unsaved changes, after waiting for the chunks being
+    /// written in the background.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        self.autosave_queue.clear();
+        if let Some(saver) = &mut self.saver {
+            for position in saver.flush() {
+                if let Some(chunk) = self.chunks.get_mut(&position) {
+                    chunk.mark_unsaved();
+                }
             }
         }
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
 
-        let mut mesh = TerrainMesh::new();
-        mesh.set_vertices(global_vertices);
-        mesh.set_indices(global_indices);
+        storage.save_chunks(self.chunks.values().filter(|ch| ch.is_unsaved()))?;
+        self.chunks.values_mut().for_each(|ch| ch.mark_saved());
+        storage.save_deferred(&self.deferred)?;
 
-        mesh
+        Ok(())
     }
 
-    pub fn mesh(&mut self) -> &TerrainMesh {
-        if self.calculated_mesh.is_none() {
-            self.calculated_mesh = Some(self.merge_meshes());
+    /// Sends dirty chunks to the mesher, closest to the camera first, and uploads the meshes it
+    /// has finished, both within the budget. Meshes of chunks that were unloaded or changed
+    /// again in the meantime are discarded.
+    pub fn update_meshes(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut dirty: Vec<ChunkPos> = self
+            .chunks
+            .values()
+            .filter(|ch| ch.is_dirty() && ch.queued_revision != Some(ch.revision))
+            .map(|ch| ch.position)
+            .collect();
+
+        let camera = self.camera;
+        dirty.sort_by_key(|position| {
+            (position.0 - camera.0).pow(2)
+                + (position.1 - camera.1).pow(2)
+                + (position.2 - camera.2).pow(2)
+        });
+        dirty.truncate(self.budget.remeshes);
+
+        for position in dirty {
+            let neighbors = self.neighbor_borders(position);
+            let neighbor_light = self.neighbor_light(position);
+            let chunk = self.chunks.get_mut(&position).unwrap();
+
+            // Empty chunks have nothing to mesh, so skip the round trip to the mesher.
+            if chunk
+                .uniform()
+                .is_some_and(|block_type| block_type.is_air())
+            {
+                chunk.clear_mesh(&mut self.pool);
+                continue;
+            }
+
+            chunk.queued_revision = Some(chunk.revision);
+            self.mesher.submit(
+                chunk.snapshot(neighbors, neighbor_light),
+                self.pool.take_mesh(),
+            );
         }
 
-        self.calculated_mesh.as_ref().unwrap()
+        self.finished.extend(self.mesher.drain());
+
+        let mut uploads = 0;
+        while uploads < self.budget.uploads {
+            let Some(meshed) = self.finished.pop_front() else {
+                break;
+            };
+
+            match self.chunks.get_mut(&meshed.position) {
+                Some(chunk) if chunk.revision == meshed.revision => {
+                    chunk.upload_mesh(device, queue, &mut self.pool, meshed);
+                    uploads += 1;
+                }
+                _ => self.pool.recycle_mesh(meshed.mesh),
+            }
+        }
+    }
+
+    /// Returns the uploaded meshes of all the chunks.
+    pub fn meshes(&self) -> impl Iterator<Item = &MeshBuffers<BlockVertex>> {
+        self.chunks.values().filter_map(|ch| ch.buffers())
+    }
+
+    /// Returns the chunks that may be visible from `camera_position`, skipping those fully
+    /// hidden behind opaque terrain. Positions without a loaded chunk are treated as empty.
+    pub fn visible_chunks(&self, camera_position: cgmath::Point3<f32>) -> HashSet<ChunkPos> {
+        let camera = ChunkPos::from_world(camera_position);
+        let distance = self.load_distance as i32;
+
+        // Searching is only worthwhile between the lowest and highest loaded chunks.
+        let (min_y, max_y) = self
+            .chunks
+            .keys()
+            .fold((camera.1, camera.1), |(min, max), position| {
+                (min.min(position.1), max.max(position.1))
+            });
+
+        visibility::visible_chunks(
+            camera,
+            |position| {
+                self.chunks
+                    .get(&position)
+                    .map_or(ChunkVisibility::ALL, |chunk| chunk.visibility())
+            },
+            |position| {
+                (position.0 - camera.0).abs() <= distance
+                    && (position.2 - camera.2).abs() <= distance
+                    && (min_y..=max_y).contains(&position.1)
+            },
+        )
+    }
+
+    /// Returns the uploaded meshes of the chunks that may be visible from `camera`, skipping
+    /// those outside of its frustum, nearest to the camera first.
+    pub fn visible_meshes(&self, camera: &Camera) -> Vec<&MeshBuffers<BlockVertex>> {
+        let visible = self.visible_chunks(camera.position);
+        let frustum = camera.frustum();
+        let mut chunks: Vec<&Chunk> = self
+            .chunks
+            .values()
+            .filter(|ch| visible.contains(&ch.position))
+            .filter(|ch| frustum.intersects_aabb(&ch.bounds()))
+            .filter(|ch| ch.buffers().is_some())
+            .collect();
+
+        chunks.sort_by(|a, b| {
+            let distance = |ch: &Chunk| (ch.bounds().center() - camera.position).magnitude2();
+            distance(a).total_cmp(&distance(b))
+        });
+        chunks.into_iter().filter_map(|ch| ch.buffers()).collect()
+    }
+
+    /// Returns the uploaded meshes of the chunks that may be inside `frustum`, including those
+    /// hidden behind other terrain. Used for drawing shadow casters, which needn't be visible
+    /// to the camera.
+    pub fn meshes_within(&self, frustum: &Frustum) -> Vec<&MeshBuffers<BlockVertex>> {
+        self.chunks
+            .values()
+            .filter(|ch| frustum.intersects_aabb(&ch.bounds()))
+            .filter_map(|ch| ch.buffers())
+            .collect()
     }
 }
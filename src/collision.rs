@@ -0,0 +1,110 @@
+use std::ops::RangeInclusive;
+
+use cgmath::{Vector3, Zero};
+
+use crate::aabb::Aabb;
+use crate::chunk::BLOCK_SIZE;
+use crate::position::BlockPos;
+
+/// How far into a block a box has to reach to touch it, in blocks, so boxes resting against
+/// a block don't touch the blocks beside it.
+const TOUCH_EPSILON: f32 = 1e-4;
+
+/// How far a box moved through the blocks around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    /// How far the box moved, which is less than asked for along the axes it was stopped on.
+    pub movement: Vector3<f32>,
+    /// Whether a block stopped the box along each axis.
+    pub blocked: [bool; 3],
+}
+
+/// Returns the block coordinate of the block containing the world coordinate `v`.
+fn block_coordinate(v: f32) -> i32 {
+    (v / BLOCK_SIZE + 0.5).floor() as i32
+}
+
+/// Returns the block coordinates `aabb` reaches into along `axis`.
+fn span(aabb: &Aabb, axis: usize) -> RangeInclusive<i32> {
+    block_coordinate(aabb.min[axis] + TOUCH_EPSILON * BLOCK_SIZE)
+        ..=block_coordinate(aabb.max[axis] - TOUCH_EPSILON * BLOCK_SIZE)
+}
+
+/// Returns the blocks `aabb` reaches into, not counting those it only touches.
+pub fn blocks_within(aabb: &Aabb) -> impl Iterator<Item = BlockPos> {
+    let (xs, ys, zs) = (span(aabb, 0), span(aabb, 1), span(aabb, 2));
+    xs.flat_map(move |x| {
+        let zs = zs.clone();
+        ys.clone()
+            .flat_map(move |y| zs.clone().map(move |z| BlockPos(x, y, z)))
+    })
+}
+
+/// Moves `aabb` by `movement` one axis at a time, stopping it against the blocks `is_solid`
+/// returns `true` for, so it slides along walls and lands on the ground. It moves vertically
+/// first, so boxes moving down a slope land before bumping into it. Blocks the box is already
+/// inside don't stop it, so it can move out of them.
+pub fn sweep(aabb: Aabb, movement: Vector3<f32>, is_solid: impl Fn(BlockPos) -> bool) -> Sweep {
+    let mut aabb = aabb;
+    let mut sweep = Sweep {
+        movement: Vector3::zero(),
+        blocked: [false; 3],
+    };
+
+    for axis in [1, 0, 2] {
+        let distance = sweep_axis(&aabb, axis, movement[axis], &is_solid);
+        aabb.min[axis] += distance;
+        aabb.max[axis] += distance;
+        sweep.movement[axis] = distance;
+        sweep.blocked[axis] = distance != movement[axis];
+    }
+    sweep
+}
+
+/// Returns how far `aabb` gets moving `distance` along `axis`, checking every layer of blocks
+/// its leading side passes, so it doesn't skip thin walls at high speeds.
+fn sweep_axis(
+    aabb: &Aabb,
+    axis: usize,
+    distance: f32,
+    is_solid: &impl Fn(BlockPos) -> bool,
+) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+
+    // Check the blocks the box covers across the other two axes.
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let is_blocked = |layer: i32| {
+        span(aabb, a).any(|i| {
+            span(aabb, b).any(|j| {
+                let mut coordinates = [0; 3];
+                coordinates[axis] = layer;
+                coordinates[a] = i;
+                coordinates[b] = j;
+                let [x, y, z] = coordinates;
+                is_solid(BlockPos(x, y, z))
+            })
+        })
+    };
+
+    // Block `layer` spans from `layer - 0.5` to `layer + 0.5` blocks along the axis.
+    if distance > 0.0 {
+        let start = aabb.max[axis] / BLOCK_SIZE;
+        let end = start + distance / BLOCK_SIZE;
+        let first = (start + 0.5 - TOUCH_EPSILON).ceil() as i32;
+        let last = (end + 0.5).ceil() as i32 - 1;
+        if let Some(layer) = (first..=last).find(|&layer| is_blocked(layer)) {
+            return ((layer as f32 - 0.5) * BLOCK_SIZE - aabb.max[axis]).max(0.0);
+        }
+    } else {
+        let start = aabb.min[axis] / BLOCK_SIZE;
+        let end = start + distance / BLOCK_SIZE;
+        let first = (start - 0.5 + TOUCH_EPSILON).floor() as i32;
+        let last = (end - 0.5).floor() as i32 + 1;
+        if let Some(layer) = (last..=first).rev().find(|&layer| is_blocked(layer)) {
+            return ((layer as f32 + 0.5) * BLOCK_SIZE - aabb.min[axis]).min(0.0);
+        }
+    }
+    distance
+}
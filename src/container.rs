@@ -0,0 +1,61 @@
+use std::io::{self, Read, Write};
+
+use crate::item::ItemStack;
+use crate::save::read_u32;
+
+/// How many slots a chest has.
+pub const CHEST_SLOTS: usize = 27;
+
+/// Slots each holding a stack of items or nothing, like those of a chest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Container {
+    /// Creates a container of `size` empty slots.
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    /// Returns the item in slot `index`, or `None` if it's empty or past the end.
+    pub fn get(&self, index: usize) -> Option<ItemStack> {
+        self.slots.get(index).copied().flatten()
+    }
+
+    /// Puts `stack` in slot `index`, or empties it with `None`, returning what was in it.
+    /// Slots past the end don't change, and hand `stack` back.
+    pub fn replace(&mut self, index: usize, stack: Option<ItemStack>) -> Option<ItemStack> {
+        match self.slots.get_mut(index) {
+            Some(slot) => std::mem::replace(slot, stack),
+            None => stack,
+        }
+    }
+
+    /// Writes the slots of the container.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&(self.slots.len() as u32).to_le_bytes())?;
+        for slot in &self.slots {
+            ItemStack::write_slot(*slot, writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a container previously written with [`Container::write`].
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let slots = (0..read_u32(reader)?)
+            .map(|_| ItemStack::read_slot(reader))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { slots })
+    }
+}
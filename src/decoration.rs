@@ -0,0 +1,162 @@
+use crate::biome::BiomeMap;
+use crate::chunk::{Chunk, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+
+/// How far leaves reach from the trunk, which is also how far outside a chunk a tree can
+/// stand and still reach into it.
+const CANOPY_RADIUS: i32 = 2;
+
+/// Plants trees, tall grass and flowers on top of the base terrain of a chunk of the world
+/// with seed `seed`. `surface_at`
+/// returns the terrain height and surface block of a column in world block coordinates, or
+/// `None` outside the world.
+///
+/// Whether a column grows something only depends on its coordinates, so trees standing in a
+/// neighboring chunk are planted the same way when generating this chunk, and the parts that
+/// reach into it line up with the neighbor no matter which is generated first.
+pub fn decorate(
+    chunk: &mut Chunk,
+    biome_map: &BiomeMap,
+    seed: u32,
+    surface_at: impl Fn(i32, i32) -> Option<(i32, BlockType)>,
+) {
+    let origin = chunk.position.origin();
+
+    for x in -CANOPY_RADIUS..CHUNK_WIDTH as i32 + CANOPY_RADIUS {
+        for z in -CANOPY_RADIUS..CHUNK_DEPTH as i32 + CANOPY_RADIUS {
+            let (world_x, world_z) = (origin.0 + x, origin.2 + z);
+            let Some((height, surface)) = surface_at(world_x, world_z) else {
+                continue;
+            };
+            if !can_grow_on(surface) {
+                continue;
+            }
+
+            let biome = biome_map.biome_at(world_x, world_z);
+
+            if random(seed, world_x, world_z, 0) < biome.tree_density() {
+                plant_tree(chunk, seed, BlockPos(world_x, height + 1, world_z));
+            }
+        }
+    }
+
+    for x in 0..CHUNK_WIDTH {
+        for z in 0..CHUNK_DEPTH {
+            let BlockPos(world_x, _, world_z) = chunk.position.block(x, 0, z);
+            let Some((height, _)) = surface_at(world_x, world_z) else {
+                continue;
+            };
+
+            let y = height + 1 - origin.1;
+            if y < 1 || y >= CHUNK_HEIGHT as i32 {
+                continue;
+            }
+
+            let y = y as usize;
+            if chunk.block(x, y, z) != BlockType::Air || !can_grow_on(chunk.block(x, y - 1, z)) {
+                continue;
+            }
+
+            let biome = chunk.biome(x, z);
+            let roll = random(seed, world_x, world_z, 1);
+            let plant = if roll < biome.flower_density() {
+                if random(seed, world_x, world_z, 2) < 0.5 {
+                    BlockType::Rose
+                } else {
+                    BlockType::Dandelion
+                }
+            } else if roll < biome.flower_density() + biome.grass_density() {
+                BlockType::TallGrass
+            } else {
+                continue;
+            };
+
+            chunk.set_block(x, y, z, plant);
+        }
+    }
+}
+
+fn can_grow_on(block_type: BlockType) -> bool {
+    matches!(block_type, BlockType::Grass | BlockType::Snow)
+}
+
+/// Plants a tree whose trunk starts at `base`, only placing the blocks inside `chunk`.
+fn plant_tree(chunk: &mut Chunk, seed: u32, base: BlockPos) {
+    for (position, block_type) in tree(seed, base) {
+        set_if(chunk, position, block_type, |b| {
+            tree_replaces(block_type, b)
+        });
+    }
+}
+
+/// Returns the blocks of the tree whose trunk starts at `base` in the world with seed `seed`,
+/// its leaves and then its trunk. Its shape only depends on the column it stands in.
+pub fn tree(seed: u32, base: BlockPos) -> Vec<(BlockPos, BlockType)> {
+    let trunk_height = 4 + (random(seed, base.0, base.2, 3) * 3.0) as i32;
+    let top = base.1 + trunk_height - 1;
+    let mut blocks = Vec::new();
+
+    // Two wide layers around the top of the trunk, then two narrow ones above it.
+    for y in top - 1..=top + 2 {
+        let radius = if y <= top { CANOPY_RADIUS } else { 1 };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                let corner = dx.abs() == radius && dz.abs() == radius;
+                // Round off the canopy by dropping the corners of the top layer and some of the
+                // others.
+                if corner
+                    && (y == top + 2 || random(seed, base.0 + dx, base.2 + dz, y as u32) < 0.5)
+                {
+                    continue;
+                }
+
+                blocks.push((BlockPos(base.0 + dx, y, base.2 + dz), BlockType::Leaves));
+            }
+        }
+    }
+
+    for y in base.1..=top {
+        blocks.push((BlockPos(base.0, y, base.2), BlockType::Log));
+    }
+    blocks
+}
+
+/// Returns whether the block `block_type` of a tree replaces `existing`. Leaves only grow into
+/// air, and the trunk grows through anything that isn't opaque.
+pub fn tree_replaces(block_type: BlockType, existing: BlockType) -> bool {
+    match block_type {
+        BlockType::Log => !existing.is_opaque() || existing == BlockType::Log,
+        _ => existing == BlockType::Air,
+    }
+}
+
+/// Sets the block at `position` if it is inside `chunk` and `replace` accepts the block there.
+fn set_if(
+    chunk: &mut Chunk,
+    position: BlockPos,
+    block_type: BlockType,
+    replace: impl Fn(BlockType) -> bool,
+) {
+    if position.chunk() != chunk.position {
+        return;
+    }
+
+    let (x, y, z) = position.local();
+    if replace(chunk.block(x, y, z)) {
+        chunk.set_block(x, y, z, block_type);
+    }
+}
+
+/// Returns a number between 0 and 1 that only depends on the world seed `seed`, a column and
+/// `salt`.
+pub fn random(seed: u32, x: i32, z: i32, salt: u32) -> f32 {
+    // SplitMix64 finalizer over the packed inputs.
+    let mut hash = (seed as u64) << 32 ^ (salt as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    hash ^= (x as u32 as u64) << 32 | z as u32 as u64;
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
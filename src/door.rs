@@ -0,0 +1,62 @@
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many simulation steps half a door waits before breaking once its other half is gone.
+pub const DOOR_DELAY: u64 = 1;
+
+/// Returns where the other half of the door half at `position` is, above it for the bottom
+/// half and below it for the top half.
+fn other_half(position: BlockPos, upper: bool) -> BlockPos {
+    if upper {
+        position.offset(0, -1, 0)
+    } else {
+        position.offset(0, 1, 0)
+    }
+}
+
+/// Breaks the half of a door at `position` if its other half is gone, so breaking either half
+/// breaks the whole door.
+pub fn update_door(world: &mut World, position: BlockPos) {
+    let Some((facing, open, upper)) = world.get_block(position).and_then(|b| b.door_state()) else {
+        return;
+    };
+    let other = world.get_block(other_half(position, upper));
+    // The other half may just not be loaded yet.
+    if other.is_some_and(|other| other != BlockType::door(facing, open, !upper)) {
+        world.set_block(position, BlockType::Air);
+    }
+}
+
+/// Opens the door or trapdoor at `position` if it's closed, or closes it if it's open, both
+/// halves of doors at once. Returns whether anything was opened or closed.
+pub fn toggle(world: &mut World, position: BlockPos) -> bool {
+    let Some(block) = world
+        .get_block(position)
+        .filter(|block| block.is_interactive())
+    else {
+        return false;
+    };
+    if let Some((_, _, upper)) = block.door_state() {
+        let other = other_half(position, upper);
+        if let Some(other_block) = world.get_block(other).filter(|b| b.door_state().is_some()) {
+            world.set_block(other, other_block.toggled());
+        }
+    }
+    world.set_block(position, block.toggled())
+}
+
+/// Places the bottom half of the door `block` at `position` and its top half above it, if the
+/// block above can be replaced. Returns whether the door was placed.
+pub fn place_door(world: &mut World, position: BlockPos, block: BlockType) -> bool {
+    let Some((facing, open, _)) = block.door_state() else {
+        return false;
+    };
+    let above = position.offset(0, 1, 0);
+    let replaceable = world
+        .get_block(above)
+        .is_some_and(|block| block.is_replaceable());
+    replaceable
+        && world.set_block(position, BlockType::door(facing, open, false))
+        && world.set_block(above, BlockType::door(facing, open, true))
+}
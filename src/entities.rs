@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::ops::Range;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, Rad, Vector3, Zero};
+use hecs::Entity;
+use rand::Rng;
+
+use crate::aabb::Aabb;
+use crate::chunk::{BLOCK_SIZE, CHUNK_HEIGHT};
+use crate::collision;
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::renderer::entity::EntityInstance;
+use crate::world::World;
+
+/// How quickly falling speeds up, in world units per second squared.
+const GRAVITY: f32 = 64.0;
+/// The fastest entities fall, in world units per second.
+const TERMINAL_VELOCITY: f32 = 78.0;
+/// How fast entities move up when jumping, enough to get a little over a block high.
+const JUMP_SPEED: f32 = 18.0;
+/// How high the eyes of the player are above its feet, in world units.
+pub const EYE_HEIGHT: f32 = 3.2;
+/// How tall the player is, in world units.
+const PLAYER_HEIGHT: f32 = 3.6;
+/// How wide the player is along both horizontal axes, in world units.
+const PLAYER_WIDTH: f32 = 1.2;
+/// How tall and wide mobs are, in world units.
+const MOB_SIZE: f32 = 1.6;
+/// How fast mobs walk, in world units per second.
+const MOB_SPEED: f32 = 3.0;
+/// How long mobs keep walking the same way or standing still, in seconds.
+const WANDER_TIME: Range<f32> = 1.0..4.0;
+/// How many mobs wander around the player at most.
+const MAX_MOBS: usize = 12;
+/// How likely a mob is tried to be spawned every step, while there are fewer than the most.
+const SPAWN_CHANCE: f64 = 1.0 / 30.0;
+/// How far from the player mobs are spawned horizontally, in blocks.
+const SPAWN_DISTANCE: Range<i32> = 12..32;
+/// How many blocks above and below the feet of the player grass is looked for to spawn mobs on.
+const SPAWN_HEIGHT: i32 = 16;
+/// How far from the player mobs are removed, in blocks.
+const DESPAWN_DISTANCE: f32 = 64.0;
+/// How far below the bottom of the world the player falls before dying, in blocks.
+const VOID_DEPTH: f32 = 16.0;
+
+/// Where the feet of an entity are and which way it faces.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+}
+
+/// How fast an entity moves, in world units per second.
+#[derive(Debug, Clone, Copy)]
+pub struct Velocity(pub Vector3<f32>);
+
+/// The box an entity takes up, centered on its position horizontally and standing on it.
+/// Bodies are pulled down by gravity and stopped by solid blocks.
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub width: f32,
+    pub height: f32,
+    /// Whether the entity stands on a block, and so can jump.
+    pub on_ground: bool,
+}
+
+impl Body {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            on_ground: false,
+        }
+    }
+
+    /// Returns the box the body takes up with its feet at `position`.
+    pub fn aabb(&self, position: Point3<f32>) -> Aabb {
+        let half = self.width / 2.0;
+        Aabb::new(
+            position + Vector3::new(-half, 0.0, -half),
+            position + Vector3::new(half, self.height, half),
+        )
+    }
+}
+
+/// How an entity is asked to move during the next step, by the controls or its own mind.
+#[derive(Debug, Clone, Copy)]
+pub struct Controls {
+    /// The horizontal velocity to walk with, in world units per second.
+    pub walk: Vector3<f32>,
+    /// Whether to jump if standing on the ground.
+    pub jump: bool,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            walk: Vector3::zero(),
+            jump: false,
+        }
+    }
+}
+
+/// Marks the entity the player controls, which the camera looks from while walking.
+#[derive(Debug, Clone, Copy)]
+pub struct Player;
+
+/// Makes an entity walk around at random, a while one way and a while standing still.
+#[derive(Debug, Clone, Copy)]
+pub struct Wander {
+    /// How long until the entity picks where to walk next, in seconds.
+    timer: f32,
+}
+
+/// Draws an entity as a box the size of its body, textured like a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Model(pub BlockType);
+
+/// Adds the player to `entities`, standing still with its eyes at `eye_position`.
+pub fn spawn_player(entities: &mut hecs::World, eye_position: Point3<f32>) -> Entity {
+    entities.spawn((
+        Player,
+        Transform {
+            position: eye_position - Vector3::unit_y() * EYE_HEIGHT,
+            yaw: Rad(0.0),
+        },
+        Velocity(Vector3::zero()),
+        Body::new(PLAYER_WIDTH, PLAYER_HEIGHT),
+        Controls::default(),
+    ))
+}
+
+/// Adds a mob to `entities`, standing still with its feet at `position`.
+pub fn spawn_mob(entities: &mut hecs::World, position: Point3<f32>) -> Entity {
+    entities.spawn((
+        Transform {
+            position,
+            yaw: Rad(0.0),
+        },
+        Velocity(Vector3::zero()),
+        Body::new(MOB_SIZE, MOB_SIZE),
+        Controls::default(),
+        Wander { timer: 0.0 },
+        Model(BlockType::Sandstone),
+    ))
+}
+
+/// Returns where the eyes of the player `entity` are, which the camera looks from.
+pub fn eye_position(entities: &hecs::World, entity: Entity) -> Option<Point3<f32>> {
+    let transform = entities.get::<&Transform>(entity).ok()?;
+    Some(transform.position + Vector3::unit_y() * EYE_HEIGHT)
+}
+
+/// Returns the box the body of `entity` takes up, if it has one.
+pub fn aabb(entities: &hecs::World, entity: Entity) -> Option<Aabb> {
+    let mut query = entities.query_one::<(&Transform, &Body)>(entity).ok()?;
+    query
+        .get()
+        .map(|(transform, body)| body.aabb(transform.position))
+}
+
+/// Returns whether the body of `entity` fell so far below the world that nothing can bring it
+/// back up.
+pub fn fell_out_of_world(entities: &hecs::World, entity: Entity) -> bool {
+    aabb(entities, entity).is_some_and(|aabb| aabb.min.y < -VOID_DEPTH * BLOCK_SIZE)
+}
+
+/// Moves `entity` so its eyes are at `eye_position`, standing still, like when the player is
+/// carried along by a flying camera.
+pub fn teleport(entities: &mut hecs::World, entity: Entity, eye_position: Point3<f32>) {
+    let Ok((transform, velocity, body)) =
+        entities.query_one_mut::<(&mut Transform, &mut Velocity, &mut Body)>(entity)
+    else {
+        return;
+    };
+    transform.position = eye_position - Vector3::unit_y() * EYE_HEIGHT;
+    velocity.0 = Vector3::zero();
+    body.on_ground = false;
+}
+
+/// Picks where wandering entities walk for a step `dt` seconds long. They jump when a block
+/// stops them walking, to climb it.
+pub fn wander_system(entities: &mut hecs::World, rng: &mut impl Rng, dt: f32) {
+    for (_, (wander, controls, velocity, body)) in
+        entities.query_mut::<(&mut Wander, &mut Controls, &Velocity, &Body)>()
+    {
+        wander.timer -= dt;
+        if wander.timer <= 0.0 {
+            wander.timer = rng.gen_range(WANDER_TIME);
+            controls.walk = if rng.gen_bool(0.5) {
+                let (sin, cos) = rng.gen_range(0.0..TAU).sin_cos();
+                Vector3::new(cos, 0.0, sin) * MOB_SPEED
+            } else {
+                Vector3::zero()
+            };
+        }
+
+        let walking = controls.walk != Vector3::zero();
+        let stopped = velocity.0.x == 0.0 && velocity.0.z == 0.0;
+        controls.jump = walking && stopped && body.on_ground;
+    }
+}
+
+/// Spawns mobs on grass around the player at `center` now and then, up to a handful, and
+/// removes those that wandered too far from it.
+pub fn spawn_system(
+    entities: &mut hecs::World,
+    world: &World,
+    center: Point3<f32>,
+    rng: &mut impl Rng,
+) {
+    let far: Vec<Entity> = entities
+        .query::<(&Transform, &Wander)>()
+        .iter()
+        .filter(|(_, (transform, _))| {
+            transform.position.distance(center) > DESPAWN_DISTANCE * BLOCK_SIZE
+        })
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in far {
+        entities.despawn(entity).ok();
+    }
+
+    let mobs = entities.query::<&Wander>().iter().count();
+    if mobs >= MAX_MOBS || !rng.gen_bool(SPAWN_CHANCE) {
+        return;
+    }
+    let (sin, cos) = rng.gen_range(0.0..TAU).sin_cos();
+    let distance = rng.gen_range(SPAWN_DISTANCE) as f32 * BLOCK_SIZE;
+    let column = center + Vector3::new(cos, 0.0, sin) * distance;
+    let BlockPos(x, y, z) = BlockPos::from_world(column);
+
+    // Mobs spawn on the highest grass around the height of the player with room above it.
+    let ground = (y - SPAWN_HEIGHT..y + SPAWN_HEIGHT).rev().find(|&y| {
+        world.get_block(BlockPos(x, y, z)) == Some(BlockType::Grass)
+            && (1..=2).all(|above| {
+                world
+                    .get_block(BlockPos(x, y + above, z))
+                    .is_some_and(|block| !block.is_solid() && !block.is_liquid())
+            })
+    });
+    if let Some(y) = ground {
+        let top = BlockPos(x, y, z).center() + Vector3::unit_y() * (BLOCK_SIZE / 2.0);
+        spawn_mob(entities, Point3::from_vec(top));
+    }
+}
+
+/// Sets the velocity of entities to walk how their controls ask, jumping if they stand on the
+/// ground, and turns them to face the way they walk.
+pub fn control_system(entities: &mut hecs::World) {
+    for (_, (transform, velocity, body, controls)) in
+        entities.query_mut::<(&mut Transform, &mut Velocity, &Body, &Controls)>()
+    {
+        velocity.0.x = controls.walk.x;
+        velocity.0.z = controls.walk.z;
+        if controls.jump && body.on_ground {
+            velocity.0.y = JUMP_SPEED;
+        }
+        if controls.walk.magnitude2() > 0.0 {
+            transform.yaw = Rad(controls.walk.z.atan2(controls.walk.x));
+        }
+    }
+}
+
+/// Advances the bodies of entities by a step `dt` seconds long, pulling them down and sliding
+/// them along the solid blocks of `world`. Blocks of the height of the world that aren't
+/// loaded stop them too, so they don't fall through the world before it's generated, but
+/// nothing stops them below or above it. Bodies stuck inside solid blocks, like when terrain is
+/// generated around them, are lifted out a block a step.
+pub fn physics_system(entities: &mut hecs::World, world: &World, dt: f32) {
+    let is_solid = |position: BlockPos| match world.get_block(position) {
+        Some(block) => block.is_solid(),
+        None => (0..CHUNK_HEIGHT as i32).contains(&position.1),
+    };
+    for (_, (transform, velocity, body)) in
+        entities.query_mut::<(&mut Transform, &mut Velocity, &mut Body)>()
+    {
+        let aabb = body.aabb(transform.position);
+        let stuck = collision::blocks_within(&aabb).any(|position| {
+            world
+                .get_block(position)
+                .is_some_and(|block| block.is_solid())
+        });
+        if stuck {
+            transform.position.y += BLOCK_SIZE;
+            velocity.0 = Vector3::zero();
+            body.on_ground = false;
+            continue;
+        }
+
+        velocity.0.y = (velocity.0.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        let sweep = collision::sweep(aabb, velocity.0 * dt, is_solid);
+        transform.position += sweep.movement;
+        body.on_ground = sweep.blocked[1] && velocity.0.y < 0.0;
+        for axis in 0..3 {
+            if sweep.blocked[axis] {
+                velocity.0[axis] = 0.0;
+            }
+        }
+    }
+}
+
+/// Returns the instances of every model to draw for the entities, lit like the block around
+/// their feet, or fully by the sky where it isn't loaded.
+pub fn extract_instances(
+    entities: &hecs::World,
+    world: &World,
+) -> HashMap<Model, Vec<EntityInstance>> {
+    let mut instances: HashMap<Model, Vec<EntityInstance>> = HashMap::new();
+    for (_, (transform, body, model)) in entities.query::<(&Transform, &Body, &Model)>().iter() {
+        let model_matrix = Matrix4::from_translation(transform.position.to_vec())
+            * Matrix4::from_angle_y(-transform.yaw)
+            * Matrix4::from_nonuniform_scale(body.width, body.height, body.width);
+        let light = world
+            .light_at(BlockPos::from_world(
+                transform.position + Vector3::unit_y() * 0.5,
+            ))
+            .unwrap_or([1.0, 0.0]);
+        instances.entry(*model).or_default().push(EntityInstance {
+            model: model_matrix.into(),
+            light,
+            _padding: [0.0; 2],
+        });
+    }
+    instances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::FlatGenerator;
+
+    /// Opens a world in a fresh directory with no chunks loaded yet.
+    fn empty_world(name: &str) -> (World, std::path::PathBuf) {
+        let directory = std::env::temp_dir().join(format!("craft-{name}-{}", std::process::id()));
+        let world = World::load(1, &directory, Box::new(FlatGenerator::default())).unwrap();
+        (world, directory)
+    }
+
+    /// Runs the physics for `seconds` in steps of a 60th of a second.
+    fn simulate(entities: &mut hecs::World, world: &World, seconds: f32) {
+        let dt = 1.0 / 60.0;
+        for _ in 0..(seconds / dt) as usize {
+            physics_system(entities, world, dt);
+        }
+    }
+
+    #[test]
+    fn body_below_the_world_falls_out_of_it() {
+        let (world, directory) = empty_world("void");
+        let mut entities = hecs::World::new();
+        let eye = Point3::new(0.0, -BLOCK_SIZE + EYE_HEIGHT, 0.0);
+        let player = spawn_player(&mut entities, eye);
+        assert!(!fell_out_of_world(&entities, player));
+
+        simulate(&mut entities, &world, 3.0);
+        assert!(fell_out_of_world(&entities, player));
+        std::fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn body_rests_on_terrain_that_is_not_loaded() {
+        let (world, directory) = empty_world("unloaded");
+        let mut entities = hecs::World::new();
+        let eye = Point3::new(0.0, 10.5 * BLOCK_SIZE + EYE_HEIGHT, 0.0);
+        let player = spawn_player(&mut entities, eye);
+
+        simulate(&mut entities, &world, 1.0);
+        let feet = entities.get::<&Transform>(player).unwrap().position;
+        assert!(feet.y >= 10.0 * BLOCK_SIZE, "fell to {}", feet.y);
+        std::fs::remove_dir_all(directory).ok();
+    }
+}
@@ -0,0 +1,30 @@
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many simulation steps a block waits before falling once nothing holds it up.
+pub const FALL_DELAY: u64 = 2;
+
+/// Drops the block at `position` if it falls and the block below it can be replaced, like
+/// sand over a dug out hole. It lands on the first block below that can't be replaced, or
+/// stays put if it would fall into a chunk that isn't loaded.
+pub fn update_falling(world: &mut World, position: BlockPos) {
+    let Some(block) = world.get_block(position).filter(|block| block.falls()) else {
+        return;
+    };
+
+    let mut landing = position;
+    loop {
+        let below = landing.offset(0, -1, 0);
+        match world.get_block(below) {
+            Some(block) if block.is_replaceable() => landing = below,
+            Some(_) => break,
+            None => return,
+        }
+    }
+
+    if landing != position {
+        world.set_block(position, BlockType::Air);
+        world.set_block(landing, block);
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::{Point3, Rad};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+/// How far a replayed flight advances every frame, in seconds. It advances the same amount
+/// whatever the frame rate, so every run draws the same views.
+const PLAYBACK_STEP: f32 = 1.0 / 60.0;
+
+/// Where the camera was and which way it looked at a moment of a flight.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// The time since the flight started, in seconds.
+    pub time: f32,
+    pub position: [f32; 3],
+    /// The yaw and pitch of the camera, in radians.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Keyframe {
+    /// Returns the keyframe between `self` and `other`, `t` of the way from one to the other.
+    fn lerp(&self, other: &Keyframe, t: f32) -> Keyframe {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Keyframe {
+            time: lerp(self.time, other.time),
+            position: std::array::from_fn(|i| lerp(self.position[i], other.position[i])),
+            yaw: lerp(self.yaw, other.yaw),
+            pitch: lerp(self.pitch, other.pitch),
+        }
+    }
+
+    /// Moves and turns `camera` to the keyframe.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = Point3::from(self.position);
+        camera.set_orientation(Rad(self.yaw), Rad(self.pitch));
+    }
+}
+
+/// A flight of the camera, saved as JSON so the same flight can be replayed to compare runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// The keyframes of the flight, in the order of their times.
+    keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a flight from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let flight: CameraPath = serde_json::from_str(&contents)?;
+        if flight.keyframes.is_empty() {
+            anyhow::bail!("the flight has no keyframes");
+        }
+        Ok(flight)
+    }
+
+    /// Writes the flight to the JSON file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Adds where `camera` is and which way it looks `time` seconds into the flight.
+    pub fn record(&mut self, time: f32, camera: &Camera) {
+        self.keyframes.push(Keyframe {
+            time,
+            position: camera.position.into(),
+            yaw: camera.yaw().0,
+            pitch: camera.pitch().0,
+        });
+    }
+
+    /// Returns how long the flight takes, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Returns where the camera is `time` seconds into the flight, between the keyframes
+    /// around it, or `None` if the flight has no keyframes.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes.first().copied();
+        }
+        let previous = &self.keyframes[next - 1];
+        let Some(next) = self.keyframes.get(next) else {
+            return Some(*previous);
+        };
+        let t = (time - previous.time) / (next.time - previous.time);
+        Some(previous.lerp(next, t))
+    }
+}
+
+/// Records the flight of the camera, to be saved to a file when done.
+#[derive(Debug)]
+pub struct FlightRecorder {
+    file: PathBuf,
+    flight: CameraPath,
+    /// The time since recording started, in seconds.
+    time: f32,
+}
+
+impl FlightRecorder {
+    /// Starts recording a flight to be saved to `file`.
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: file.into(),
+            flight: CameraPath::new(),
+            time: 0.0,
+        }
+    }
+
+    /// Records where `camera` is after a frame that took `delta` seconds.
+    pub fn record(&mut self, delta: f32, camera: &Camera) {
+        if !self.flight.keyframes.is_empty() {
+            self.time += delta;
+        }
+        self.flight.record(self.time, camera);
+    }
+
+    /// Saves the flight recorded so far to its file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.flight.save(&self.file)
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+}
+
+/// Replays a recorded flight, collecting how long every frame drawn along it took.
+#[derive(Debug)]
+pub struct FlightPlayback {
+    flight: CameraPath,
+    /// How far into the flight the camera is, in seconds.
+    time: f32,
+    /// How long every frame since the playback started took, in seconds.
+    frame_times: Vec<f32>,
+}
+
+impl FlightPlayback {
+    pub fn new(flight: CameraPath) -> Self {
+        Self {
+            flight,
+            time: 0.0,
+            frame_times: Vec::new(),
+        }
+    }
+
+    /// Records that the last frame took `delta` seconds and moves `camera` a step further
+    /// along the flight. Returns `false` once the flight is over.
+    pub fn advance(&mut self, camera: &mut Camera, delta: f32) -> bool {
+        // The first frame took however long starting up did.
+        if self.time > 0.0 {
+            self.frame_times.push(delta);
+        }
+        if let Some(keyframe) = self.flight.sample(self.time) {
+            keyframe.apply(camera);
+        }
+        self.time += PLAYBACK_STEP;
+        self.time <= self.flight.duration() + PLAYBACK_STEP
+    }
+
+    /// Returns the frame time statistics of the playback as one line of text.
+    pub fn summary(&self) -> String {
+        if self.frame_times.is_empty() {
+            return "no frames drawn".to_string();
+        }
+
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| {
+            let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+            sorted[index] * 1000.0
+        };
+        let average = sorted.iter().sum::<f32>() / sorted.len() as f32;
+        format!(
+            "{} frames, {:.0} FPS, {:.2} ms average, {:.2} ms median, {:.2} ms 95th percentile, \
+             {:.2} ms 99th percentile, {:.2} ms worst",
+            sorted.len(),
+            1.0 / average,
+            average * 1000.0,
+            percentile(0.5),
+            percentile(0.95),
+            percentile(0.99),
+            percentile(1.0),
+        )
+    }
+}
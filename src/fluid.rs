@@ -0,0 +1,78 @@
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many simulation steps water waits before flowing on.
+pub const WATER_DELAY: u64 = 5;
+/// How many blocks water flows out to the sides from a source.
+const MAX_WATER_DISTANCE: u8 = 7;
+
+/// The offsets of the blocks beside a block, on the same level.
+const SIDES: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// Lets the water at `position` flow, if there is any. Flowing water takes the distance of
+/// the closest water feeding it, or dries up once nothing does. Water then falls into the
+/// block below it, or spreads out to the sides over whatever holds it up, one block further
+/// from its source, until it's too far away. Every block changed schedules its own updates,
+/// so water keeps flowing a block at a time.
+pub fn update_water(world: &mut World, position: BlockPos) {
+    let Some(distance) = world.get_block(position).and_then(|b| b.water_distance()) else {
+        return;
+    };
+    let water_at = |world: &World, position: BlockPos| {
+        world
+            .get_block(position)
+            .and_then(|block| block.water_distance())
+    };
+
+    if distance > 0 {
+        let fed = if water_at(world, position.offset(0, 1, 0)).is_some() {
+            Some(1)
+        } else {
+            SIDES
+                .iter()
+                .filter_map(|&(x, z)| water_at(world, position.offset(x, 0, z)))
+                .min()
+                .map(|distance| distance + 1)
+        };
+        match fed.filter(|&fed| fed <= MAX_WATER_DISTANCE) {
+            None => {
+                world.set_block(position, BlockType::Air);
+                return;
+            }
+            Some(fed) if fed != distance => {
+                world.set_block(position, BlockType::water(fed));
+                return;
+            }
+            Some(_) => {}
+        }
+    }
+
+    let below = position.offset(0, -1, 0);
+    match world.get_block(below) {
+        None => return,
+        Some(block) if block.is_liquid() => return,
+        Some(block) if block.is_replaceable() => {
+            world.set_block(below, BlockType::water(1));
+            return;
+        }
+        Some(_) => {}
+    }
+
+    if distance >= MAX_WATER_DISTANCE {
+        return;
+    }
+    for (x, z) in SIDES {
+        let side = position.offset(x, 0, z);
+        let Some(block) = world.get_block(side) else {
+            continue;
+        };
+        let further = match block.water_distance() {
+            Some(other) => other > distance + 1,
+            None => block.is_replaceable(),
+        };
+        if further {
+            world.set_block(side, BlockType::water(distance + 1));
+        }
+    }
+}
@@ -1,21 +1,98 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use cgmath::{Quaternion, Rotation3, SquareMatrix, Vector3};
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Rotation3, SquareMatrix, Vector3,
+};
 use wgpu::Color;
 use winit::{
-    event::{DeviceEvent, ElementState, Event, KeyEvent, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
     event_loop::EventLoop,
     keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+    window::CursorGrabMode,
 };
 
 use crate::{
-    camera::{self, Camera, CameraController, CameraUniform, Projection},
-    chunk::{generate_chunks, Chunk, ChunkList},
-    renderer::{self, block::Block, renderer::Renderer},
+    camera::{
+        self, Camera, CameraController, CameraUniform, MovementMode, Projection, ShadowCascade,
+    },
+    chunk::{Chunk, ChunkManager, BLOCK_SIZE, CHUNK_WIDTH},
+    door,
+    entities::{self, Controls, Model},
+    flight::{CameraPath, FlightPlayback, FlightRecorder},
+    generator::{GenerationProgress, NoiseGenerator, TerrainGenerator, TerrainSettings},
+    hotbar::Hotbar,
+    input::{Action, KeyBindings},
+    item::{self, Item, ItemStack},
+    position::{BlockPos, ChunkPos},
+    raycast::{self, RayHit},
+    renderer::{
+        self,
+        block::{BlockShape, BlockType, Face},
+        buffer::MeshBuffers,
+        entity::{self, EntityBatch, EntityInstance, EntityVertex},
+        frame::Viewport,
+        held::HeldBlock,
+        renderer::Renderer,
+        shadow::{CASCADE_SPLITS, SHADOW_CASCADES, SHADOW_MAP_SIZE},
+    },
+    settings::{Settings, KEY_BINDINGS_FILE, SETTINGS_FILE},
+    stats::FrameStats,
+    structure,
+    time::{CelestialUniform, SkyUniform, WorldTime},
+    torch,
+    watch::FileWatcher,
+    world::{GameMode, World},
 };
 
 struct KeyEntry(KeyCode, ElementState);
 
+struct MouseEntry(MouseButton, ElementState);
+
+/// What the game is doing, which decides whether the world is simulated and what the mouse
+/// and keys do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    /// The area around the spawn is being loaded. The camera can look around, but the world
+    /// isn't simulated until it's done.
+    Loading,
+    /// The world is simulated and the player controls the camera.
+    Playing,
+    /// The simulation and the time of day are stopped and the cursor is released, with the
+    /// pause symbol shown in front of the camera.
+    Paused,
+    /// The menu opened from the pause, which closes the game when quitting again.
+    Menu,
+}
+
+/// A block being broken, which takes a while holding the break button.
+struct Mining {
+    /// The block the camera pointed at when it was hit.
+    hit: RayHit,
+    /// How far along breaking the block is, from 0 to 1.
+    progress: f32,
+}
+
+/// A container block whose slots are shown in front of the camera, like an open chest.
+struct OpenContainer {
+    position: BlockPos,
+    /// The slot of the container items are swapped with the slot selected in the hotbar.
+    selected: usize,
+}
+
+/// The player left behind while the camera is detached from it.
+struct Spectator {
+    /// Where the player was and which way it looked when the camera was detached.
+    camera: Camera,
+    mode: MovementMode,
+    collision: bool,
+}
+
 pub struct Game<'a> {
     // The window of the game.
     window: &'a winit::window::Window,
@@ -25,60 +102,1602 @@ pub struct Game<'a> {
 
     /// The time in seconds since the last frame.
     delta: f32,
+    /// Real time passed that doesn't make up a whole simulation step yet, in seconds.
+    accumulator: f32,
+    stats: FrameStats,
     /// The key events that have been received since the last frame.
     key_events: Vec<KeyEntry>,
+    /// The mouse button events that have been received since the last frame.
+    mouse_events: Vec<MouseEntry>,
+    /// How far the mouse wheel turned since the last frame, in lines.
+    scroll: f32,
+    /// Whether the break button is held.
+    breaking: bool,
+    /// The block being broken, if any.
+    mining: Option<Mining>,
+    /// The container whose slots are shown, if one is open. The mouse picks its slots instead
+    /// of breaking and placing blocks while it is.
+    open_container: Option<OpenContainer>,
+    state: GameState,
     /// Whether the game should close.
     should_close: bool,
+    /// Whether the cursor is held in the window and hidden, which it is while the mouse
+    /// looks around.
+    cursor_grabbed: bool,
 
+    /// The options picked by the player, saved whenever they change.
+    settings: Settings,
+    /// When the last autosave started.
+    last_autosave: Instant,
+    /// Whether an autosave is under way.
+    autosaving: bool,
+    /// When the last autosave was done, if one was.
+    autosaved: Option<Instant>,
+    /// Notices when the settings file is edited, to apply it.
+    settings_watcher: FileWatcher,
+    /// Notices when the terrain settings file is edited, to generate new chunks with it, if
+    /// the world is generated by the default generator.
+    terrain_watcher: Option<FileWatcher>,
+    key_bindings: KeyBindings,
     camera_controller: CameraController,
+    /// Everything moving around the world, simulated by the systems of [`entities`].
+    entities: hecs::World,
+    /// The player entity, which the camera looks from while walking and carries along while
+    /// flying.
+    player: hecs::Entity,
+    /// The meshes of the models entities are drawn with, created when first drawn.
+    entity_meshes: HashMap<Model, MeshBuffers<EntityVertex>>,
+    /// The camera, placed between its positions after the last two simulation steps while a
+    /// frame is drawn.
     camera: Camera,
+    /// Where the camera was after the last two simulation steps.
+    previous_position: Point3<f32>,
+    position: Point3<f32>,
 
-    chunk_list: ChunkList,
+    world: World,
+    /// Where the shadows of the sun were last rendered from.
+    shadow_cascades: [ShadowCascade; SHADOW_CASCADES],
+    /// The progress of loading the area around the spawn, and when it started, until it's done.
+    loading: Option<(Receiver<GenerationProgress>, Instant)>,
+    /// The blocks at hand to place.
+    hotbar: Hotbar,
+    /// The block drawn in the corner of the view, if any, which is the one selected in the
+    /// hotbar.
+    held_block: Option<HeldBlock>,
+    /// How much dark light levels are raised, from 0 to 1.
+    brightness: f32,
+    /// Whether the world is also drawn from above the camera, next to the view of the camera,
+    /// showing which chunks it draws.
+    overview: bool,
+    /// The player while the camera flies around detached from it, if it is. Chunks keep
+    /// being loaded and culled around the player, so both can be watched from a distance.
+    spectator: Option<Spectator>,
+    /// Records the flight of the camera to a file, if it's being recorded.
+    recorder: Option<FlightRecorder>,
+    /// Flies the camera along a recorded flight instead of the controls, if one is replayed.
+    playback: Option<FlightPlayback>,
+}
+
+/// The title of the window, followed by the frame statistics.
+const WINDOW_TITLE: &str = "craft";
+/// How many chunks around the camera are kept loaded unless changed.
+pub const LOAD_DISTANCE: usize = 8;
+/// The smallest and largest render distances that can be picked, in chunks.
+pub const MIN_RENDER_DISTANCE: usize = 2;
+pub const MAX_RENDER_DISTANCE: usize = 32;
+/// How long a step of the simulation is, in seconds. The simulation advances in steps of the
+/// same length whatever the frame rate, so it behaves the same at any frame rate.
+const SIMULATION_STEP: f32 = 1.0 / 60.0;
+/// The most simulation steps run per frame, so a slow frame doesn't make the next one slower
+/// catching up.
+const MAX_STEPS_PER_FRAME: u32 = 10;
+/// The vertical field of view unless changed.
+pub const DEFAULT_FOV: cgmath::Deg<f32> = cgmath::Deg(70.0);
+/// The narrowest and widest vertical fields of view that can be picked.
+pub const MIN_FOV: cgmath::Deg<f32> = cgmath::Deg(30.0);
+pub const MAX_FOV: cgmath::Deg<f32> = cgmath::Deg(110.0);
+/// How much the field of view changes per key press.
+const FOV_STEP: cgmath::Deg<f32> = cgmath::Deg(5.0);
+/// How much the brightness changes per key press.
+const BRIGHTNESS_STEP: f32 = 0.1;
+/// The directory the world is saved in.
+const WORLD_DIRECTORY: &str = "world";
+/// The directory structures placed during world generation are loaded from.
+const STRUCTURES_DIRECTORY: &str = "assets/structures";
+/// The file the terrain settings are loaded from.
+const TERRAIN_SETTINGS_FILE: &str = "worldgen.toml";
+/// How often the chunks changed since they were last saved are saved.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// How many chunks an autosave serializes per frame, so it doesn't make frames take longer.
+/// They're written in the background.
+const AUTOSAVE_CHUNKS_PER_FRAME: usize = 8;
+/// How long the window title shows the world being saved at least, so it shows up when the
+/// statistics in it are refreshed.
+const SAVE_INDICATOR_TIME: Duration = Duration::from_secs(2);
+/// How many pixels of scrolling count as turning the mouse wheel a line, for touchpads.
+const PIXELS_PER_LINE: f32 = 40.0;
+/// How high above the camera the overview is drawn from, as a fraction of the view distance.
+const OVERVIEW_HEIGHT: f32 = 0.5;
+/// How far from the camera blocks can be pointed at, in world units.
+const REACH: f32 = 10.0;
+/// How much darker a block being broken is drawn once it's almost broken, from 0 to 1.
+const MINING_DARKENING: f32 = 0.7;
+/// How much larger than the block the darkening drawn over a block being broken is, so it
+/// doesn't flicker with the faces of the block.
+const MINING_OVERLAY_SCALE: f32 = 1.005;
+/// How many slots of an open container are shown per row.
+const CONTAINER_COLUMNS: usize = 9;
+/// How far in front of the camera the slots of an open container are shown, in world units.
+const CONTAINER_DISTANCE: f32 = 1.2;
+/// How large the slots of an open container are shown, in world units.
+const CONTAINER_SLOT_SIZE: f32 = 0.2;
+/// How large items are shown in the slots of an open container, as a fraction of the slots.
+const CONTAINER_ITEM_SCALE: f32 = 0.5;
+/// How light the slots of an open container are, and the selected one, like block light.
+const CONTAINER_LIGHT: [f32; 2] = [0.0, 0.8];
+const CONTAINER_SELECTED_LIGHT: [f32; 2] = [0.0, 1.0];
+/// How far in front of the camera the symbols of the pause and the menu are shown, in world
+/// units.
+const OVERLAY_DISTANCE: f32 = 1.2;
+/// How tall the symbols of the pause and the menu are, in world units.
+const OVERLAY_SIZE: f32 = 0.3;
+/// How fast the cube shown while loading turns, in degrees per second.
+const LOADING_SPIN: f32 = 90.0;
+
+/// Returns how far from the camera terrain is visible with a render distance of `distance`
+/// chunks, in world units.
+fn view_distance(distance: usize) -> f32 {
+    (distance * CHUNK_WIDTH) as f32 * BLOCK_SIZE
+}
+
+/// Returns where the eyes of the player are when it stands at the spawn point `spawn`.
+fn spawn_eye_position(spawn: BlockPos) -> Point3<f32> {
+    let feet = spawn.center() - Vector3::unit_y() * (BLOCK_SIZE / 2.0);
+    Point3::from_vec(feet) + Vector3::unit_y() * entities::EYE_HEIGHT
+}
+
+/// Returns the generator used unless another one is picked, with the terrain settings from
+/// the settings file if there is one.
+pub fn default_generator() -> Box<dyn TerrainGenerator> {
+    let settings = if Path::new(TERRAIN_SETTINGS_FILE).exists() {
+        TerrainSettings::load(TERRAIN_SETTINGS_FILE).unwrap_or_else(|err| {
+            eprintln!("Failed to load {TERRAIN_SETTINGS_FILE}: {err}");
+            TerrainSettings::default()
+        })
+    } else {
+        TerrainSettings::default()
+    };
+    noise_generator(settings)
+}
+
+/// Returns a noise generator making terrain with `settings`, placing the structures from the
+/// structures directory.
+fn noise_generator(settings: TerrainSettings) -> Box<dyn TerrainGenerator> {
+    let mut generator = NoiseGenerator::new(settings);
+    generator.set_structures(structure::load_structures(STRUCTURES_DIRECTORY));
+    Box::new(generator)
+}
+
+/// Returns the settings from the settings file if there is one, or else the default ones with
+/// the key bindings from the old key bindings file.
+pub fn load_settings() -> Settings {
+    if !Path::new(SETTINGS_FILE).exists() {
+        return migrate_key_bindings();
+    }
+    Settings::load(SETTINGS_FILE).unwrap_or_else(|err| {
+        eprintln!("Failed to load {SETTINGS_FILE}: {err}");
+        Settings::default()
+    })
+}
+
+/// Returns the default settings with the key bindings from the key bindings file, if there is
+/// one, saving them to the settings file since the key bindings file is no longer read.
+fn migrate_key_bindings() -> Settings {
+    let mut settings = Settings::default();
+    if !Path::new(KEY_BINDINGS_FILE).exists() {
+        return settings;
+    }
+
+    match Settings::load_key_bindings(KEY_BINDINGS_FILE) {
+        Ok(keybinds) => {
+            settings.keybinds = keybinds;
+            match settings.save(SETTINGS_FILE) {
+                Ok(()) => println!(
+                    "Moved the key bindings from {KEY_BINDINGS_FILE} to {SETTINGS_FILE}, \
+                     {KEY_BINDINGS_FILE} is no longer used"
+                ),
+                Err(err) => eprintln!("Failed to save {SETTINGS_FILE}: {err}"),
+            }
+        }
+        Err(err) => eprintln!(
+            "Failed to load {KEY_BINDINGS_FILE}: {err}, it is no longer used and key bindings \
+             go in {SETTINGS_FILE}"
+        ),
+    }
+    settings
+}
+
+/// Generates and saves the world within `radius` chunks of the spawn without opening a window,
+/// so it doesn't need to be generated while playing.
+pub fn pregenerate(radius: usize, generator: Box<dyn TerrainGenerator>) -> anyhow::Result<()> {
+    let mut world = World::load(LOAD_DISTANCE, WORLD_DIRECTORY, generator)?;
+    let spawn = world.spawn().chunk();
+
+    // Print from another thread, since generating blocks this one.
+    let progress = world.chunks_mut().subscribe();
+    let printer = thread::spawn(move || {
+        for progress in progress {
+            let GenerationProgress { stage, done, total } = progress;
+            println!("Pre-generation {}: {done}/{total} chunks", stage.name());
+        }
+    });
+
+    let start = Instant::now();
+    let result = world.pregenerate(spawn, radius).and_then(|()| world.save());
+    drop(world);
+    printer.join().ok();
+    result?;
+    println!("Pre-generation took {:.2?}", start.elapsed());
+
+    Ok(())
 }
 
 impl<'a> Game<'a> {
-    pub fn new(window: &'a winit::window::Window, renderer: Renderer<'a>) -> Self {
+    pub fn new(
+        window: &'a winit::window::Window,
+        renderer: Renderer<'a>,
+        generator: Box<dyn TerrainGenerator>,
+        settings: Settings,
+    ) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let projection =
-            camera::Projection::new(size.width, size.height, cgmath::Deg(45.0), 0.5, 100.0);
+        let render_distance = settings
+            .render_distance
+            .clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+        let brightness = settings.brightness.clamp(0.0, 1.0);
+        let projection = camera::Projection::new(
+            size.width,
+            size.height,
+            cgmath::Deg(settings.fov.clamp(MIN_FOV.0, MAX_FOV.0)),
+            0.5,
+            Self::far_plane(render_distance),
+        );
+        let mut world = World::load(render_distance, WORLD_DIRECTORY, generator)?;
         let camera = camera::Camera::new(
-            (0.0, 5.0, 10.0),
+            spawn_eye_position(world.spawn()),
             cgmath::Deg(-90.0),
             cgmath::Deg(-20.0),
             projection,
         );
-        Self {
+        let loading = Some((world.chunks_mut().subscribe(), Instant::now()));
+        let shadow_cascades = camera.shadow_cascades(
+            world.time().light_direction(),
+            &CASCADE_SPLITS,
+            SHADOW_MAP_SIZE,
+        );
+        window.set_title(WINDOW_TITLE);
+        let hotbar = Hotbar::default();
+        let held_block = hotbar
+            .selected_block()
+            .map(|block| HeldBlock::new(renderer.device(), block));
+        let mut entities = hecs::World::new();
+        let player = entities::spawn_player(&mut entities, camera.position);
+        let mut camera_controller = CameraController::new(settings.speed, settings.sensitivity);
+        camera_controller.set_can_fly(world.game_mode() == GameMode::Creative);
+
+        Ok(Self {
             window,
             renderer,
             delta: 0.0,
+            accumulator: 0.0,
+            stats: FrameStats::new(),
             key_events: Vec::new(),
+            mouse_events: Vec::new(),
+            scroll: 0.0,
+            breaking: false,
+            mining: None,
+            open_container: None,
+            state: GameState::Loading,
             should_close: false,
-            camera_controller: CameraController::new(10.0, 4.0),
+            cursor_grabbed: false,
+            key_bindings: KeyBindings::new(&settings.keybinds),
+            settings,
+            last_autosave: Instant::now(),
+            autosaving: false,
+            autosaved: None,
+            settings_watcher: FileWatcher::new(SETTINGS_FILE),
+            terrain_watcher: None,
+            camera_controller,
+            entities,
+            player,
+            entity_meshes: HashMap::new(),
+            previous_position: camera.position,
+            position: camera.position,
             camera,
-            chunk_list: ChunkList::new(generate_chunks(16)),
+            world,
+            shadow_cascades,
+            loading,
+            hotbar,
+            held_block,
+            brightness,
+            overview: false,
+            spectator: None,
+            recorder: None,
+            playback: None,
+        })
+    }
+
+    /// Returns how many chunks around the camera are loaded and drawn.
+    pub fn render_distance(&self) -> usize {
+        self.world.chunks().load_distance()
+    }
+
+    /// Sets how many chunks around the camera are loaded and drawn, clamped between
+    /// [`MIN_RENDER_DISTANCE`] and [`MAX_RENDER_DISTANCE`]. Moves the fog and the far plane of
+    /// the camera along with it.
+    pub fn set_render_distance(&mut self, distance: usize) {
+        let distance = distance.clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+        self.world.chunks_mut().set_load_distance(distance);
+        self.camera.projection.set_zfar(Self::far_plane(distance));
+    }
+
+    /// Returns the far plane of the camera for a render distance of `distance` chunks, far
+    /// enough to include the corners of the loaded area.
+    fn far_plane(distance: usize) -> f32 {
+        view_distance(distance + 1) * std::f32::consts::SQRT_2
+    }
+
+    /// Returns the block drawn in the corner of the view, if any.
+    pub fn held_block(&self) -> Option<BlockType> {
+        self.held_block.as_ref().map(HeldBlock::block)
+    }
+
+    /// Sets the block drawn in the corner of the view, or hides it with `None`.
+    fn set_held_block(&mut self, block: Option<BlockType>) {
+        if self.held_block() == block {
+            return;
+        }
+        self.held_block = block.map(|block| HeldBlock::new(self.renderer.device(), block));
+    }
+
+    pub fn hotbar(&self) -> &Hotbar {
+        &self.hotbar
+    }
+
+    /// Selects `slot` of the hotbar, holding its block.
+    pub fn select_slot(&mut self, slot: usize) {
+        self.hotbar.select(slot);
+        self.set_held_block(self.hotbar.selected_block());
+    }
+
+    /// Returns the name of the item selected in the hotbar, or that nothing is held. How
+    /// many are left is shown in survival, where placing blocks uses them up.
+    fn held_item_name(&self) -> String {
+        let survival = self.world.game_mode() == GameMode::Survival;
+        self.hotbar
+            .selected_stack()
+            .map_or("nothing".to_string(), |stack| {
+                if survival {
+                    stack.name()
+                } else {
+                    stack.item.name()
+                }
+            })
+    }
+
+    /// Sets how many blocks of every loaded chunk are picked at random each simulation step
+    /// to grow or spread, like grass and saplings.
+    pub fn set_random_tick_speed(&mut self, speed: u32) {
+        self.world.set_random_tick_speed(speed);
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        self.world.game_mode()
+    }
+
+    /// Switches the world to `game_mode`. Flying is only allowed in creative, so switching to
+    /// survival lands the player.
+    pub fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.world.set_game_mode(game_mode);
+        let creative = game_mode == GameMode::Creative;
+        self.camera_controller.set_can_fly(creative);
+        if !creative {
+            match &mut self.spectator {
+                Some(spectator) => spectator.mode = MovementMode::Walking,
+                None => self.camera_controller.set_mode(MovementMode::Walking),
+            }
+        }
+        self.breaking = false;
+        self.mining = None;
+    }
+
+    /// Teleports the player to the spawn point of the world, standing still. The camera goes
+    /// along unless it's spectating.
+    pub fn teleport_to_spawn(&mut self) {
+        let eye_position = spawn_eye_position(self.world.spawn());
+        entities::teleport(&mut self.entities, self.player, eye_position);
+        match &mut self.spectator {
+            Some(spectator) => spectator.camera.position = eye_position,
+            None => {
+                self.camera.position = eye_position;
+                self.previous_position = eye_position;
+                self.position = eye_position;
+            }
+        }
+        self.mining = None;
+        self.close_container();
+    }
+
+    /// Respawns the player once it fell far enough below the world while walking, since
+    /// nothing can bring it back up.
+    fn check_death(&mut self) {
+        let walking = self.camera_controller.mode() == MovementMode::Walking;
+        if walking && entities::fell_out_of_world(&self.entities, self.player) {
+            println!("Fell out of the world");
+            self.teleport_to_spawn();
+        }
+    }
+
+    /// Returns whether the camera is inside a water block.
+    pub fn is_underwater(&self) -> bool {
+        let position = BlockPos::from_world(self.camera.position);
+        self.world
+            .get_block(position)
+            .is_some_and(|block| block.is_liquid())
+    }
+
+    /// Returns the block the camera points at within reach, and the face it points at, if any.
+    pub fn target(&self) -> Option<RayHit> {
+        raycast::raycast(
+            self.camera.position,
+            self.camera.forward(),
+            REACH,
+            |position| {
+                self.world
+                    .get_block(position)
+                    .is_some_and(|block| block.is_targetable())
+            },
+        )
+    }
+
+    /// Breaks the block the camera points at, if it can be broken. In survival the block and
+    /// what it held are picked up into the hotbar, as far as there's room. Returns whether a
+    /// block was broken.
+    pub fn break_block(&mut self) -> bool {
+        let Some(hit) = self.target() else {
+            return false;
+        };
+        let Some(block) = self
+            .world
+            .get_block(hit.position)
+            .filter(|block| block.is_breakable())
+        else {
+            return false;
+        };
+        let held: Vec<ItemStack> = self
+            .world
+            .block_entity(hit.position)
+            .and_then(|entity| entity.container())
+            .map_or(Vec::new(), |container| {
+                container.slots().iter().flatten().copied().collect()
+            });
+        if !self.world.set_block(hit.position, BlockType::Air) {
+            return false;
+        }
+
+        if self.world.game_mode() == GameMode::Survival {
+            let items = block
+                .dropped()
+                .map(|block| ItemStack::new(Item::Block(block), 1));
+            let mut full = false;
+            for stack in items.into_iter().chain(held) {
+                for _ in 0..stack.count {
+                    full |= !self.hotbar.add(stack.item);
+                }
+            }
+            if full {
+                println!("The hotbar is full");
+            }
+            self.set_held_block(self.hotbar.selected_block());
+        }
+        true
+    }
+
+    /// Keeps breaking the block the camera points at for a step `dt` seconds long while the
+    /// break button is held, taking as long as the block and the selected tool call for.
+    /// Pointing at another block starts over.
+    fn mine(&mut self, dt: f32) {
+        let target = (self.breaking && self.cursor_grabbed)
+            .then(|| self.target())
+            .flatten();
+        let Some((hit, block)) =
+            target.and_then(|hit| Some((hit, self.world.get_block(hit.position)?)))
+        else {
+            self.mining = None;
+            return;
+        };
+        let Some(time) = item::break_time(block, self.hotbar.selected_tool()) else {
+            self.mining = None;
+            return;
+        };
+
+        let progress = match &self.mining {
+            Some(mining) if mining.hit.position == hit.position => mining.progress,
+            _ => 0.0,
+        };
+        let progress = if time > 0.0 {
+            progress + dt / time
+        } else {
+            1.0
+        };
+        if progress >= 1.0 {
+            self.break_block();
+            self.mining = None;
+        } else {
+            self.mining = Some(Mining { hit, progress });
+        }
+    }
+
+    /// Returns the darkening drawn over the block being broken, which gets darker the further
+    /// along breaking it is. It's only drawn over full cubes.
+    fn mining_overlay(&self) -> Option<(Model, EntityInstance)> {
+        let mining = self.mining.as_ref()?;
+        let block = self.world.get_block(mining.hit.position)?;
+        if block.shape() != BlockShape::Cube {
+            return None;
+        }
+
+        // Lit like the face pointed at, which is what shows.
+        let light = self
+            .world
+            .light_at(mining.hit.adjacent())
+            .unwrap_or([1.0, 0.0])
+            .map(|light| light * (1.0 - mining.progress * MINING_DARKENING));
+        let size = BLOCK_SIZE * MINING_OVERLAY_SCALE;
+        let bottom = mining.hit.position.center() - Vector3::unit_y() * (size / 2.0);
+        let model = Matrix4::from_translation(bottom) * Matrix4::from_scale(size);
+        Some((
+            Model(block),
+            EntityInstance {
+                model: model.into(),
+                light,
+                _padding: [0.0; 2],
+            },
+        ))
+    }
+
+    /// Uses the block the camera points at, like opening a door or a chest. Returns whether
+    /// the block did anything.
+    pub fn interact(&mut self) -> bool {
+        let Some(hit) = self.target() else {
+            return false;
+        };
+        let container = self
+            .world
+            .get_block(hit.position)
+            .is_some_and(|block| block.is_container());
+        if container {
+            return self.open_container(hit.position);
+        }
+        self.world.interact(hit.position)
+    }
+
+    /// Opens the container at `position`, showing its slots and what they hold in front of the
+    /// camera. Returns whether there is a container there.
+    fn open_container(&mut self, position: BlockPos) -> bool {
+        let is_container = self
+            .world
+            .block_entity(position)
+            .is_some_and(|entity| entity.container().is_some());
+        if !is_container {
+            return false;
+        }
+        self.open_container = Some(OpenContainer {
+            position,
+            selected: 0,
+        });
+        self.breaking = false;
+        self.mining = None;
+        true
+    }
+
+    /// Closes the open container, if any.
+    fn close_container(&mut self) {
+        if self.open_container.take().is_some() {
+            println!("Closed the container");
+        }
+    }
+
+    /// Closes the open container once it's gone, like when it's broken, or out of reach.
+    fn check_open_container(&mut self) {
+        let Some(open) = &self.open_container else {
+            return;
+        };
+        let exists = self.world.block_entity(open.position).is_some();
+        let eye = self.player_camera().position.to_vec();
+        let in_reach = (open.position.center() - eye).magnitude() <= REACH + BLOCK_SIZE;
+        if !exists || !in_reach {
+            self.close_container();
+        }
+    }
+
+    /// Swaps the item in the selected slot of the open container with the one selected in the
+    /// hotbar.
+    fn swap_container_slot(&mut self) {
+        let Some(open) = &self.open_container else {
+            return;
+        };
+        let selected = open.selected;
+        let Some(container) = self
+            .world
+            .block_entity_mut(open.position)
+            .and_then(|entity| entity.container_mut())
+        else {
+            return;
+        };
+        let taken = container.replace(selected, self.hotbar.selected_stack());
+        self.hotbar.set_slot(self.hotbar.selected(), taken);
+        self.set_held_block(self.hotbar.selected_block());
+    }
+
+    /// Returns the slots of the open container and what they hold, shown in a grid in front of
+    /// the camera with the selected slot lighter. Blocks are shown as small cubes, and tools
+    /// as sticks.
+    fn container_instances(&self) -> Vec<(Model, EntityInstance)> {
+        let Some(open) = &self.open_container else {
+            return Vec::new();
+        };
+        let Some(container) = self
+            .world
+            .block_entity(open.position)
+            .and_then(|entity| entity.container())
+        else {
+            return Vec::new();
+        };
+
+        let to_world = self.camera.view().invert().unwrap_or(Matrix4::identity());
+        let rows = container.len().div_ceil(CONTAINER_COLUMNS);
+        let mut instances = Vec::new();
+        for (index, item) in container.slots().iter().enumerate() {
+            let (row, column) = (index / CONTAINER_COLUMNS, index % CONTAINER_COLUMNS);
+            let center = Vector3::new(
+                (column as f32 - (CONTAINER_COLUMNS - 1) as f32 / 2.0) * CONTAINER_SLOT_SIZE,
+                ((rows - 1) as f32 / 2.0 - row as f32) * CONTAINER_SLOT_SIZE,
+                -CONTAINER_DISTANCE,
+            );
+            let light = if index == open.selected {
+                CONTAINER_SELECTED_LIGHT
+            } else {
+                CONTAINER_LIGHT
+            };
+            let mut push = |model, transform: Matrix4<f32>| {
+                instances.push((
+                    Model(model),
+                    EntityInstance {
+                        model: (to_world * Matrix4::from_translation(center) * transform).into(),
+                        light,
+                        _padding: [0.0; 2],
+                    },
+                ));
+            };
+
+            // The slot is a thin tile behind what it holds, with a gap around it.
+            let tile = CONTAINER_SLOT_SIZE * 0.9;
+            push(
+                BlockType::Cobblestone,
+                Matrix4::from_translation(Vector3::new(0.0, -tile / 2.0, -tile / 2.0))
+                    * Matrix4::from_nonuniform_scale(tile, tile, tile * 0.1),
+            );
+            let size = CONTAINER_SLOT_SIZE * CONTAINER_ITEM_SCALE;
+            match item.map(|stack| stack.item) {
+                Some(Item::Block(block)) => push(
+                    block,
+                    Matrix4::from_angle_x(cgmath::Deg(20.0))
+                        * Matrix4::from_angle_y(cgmath::Deg(45.0))
+                        * Matrix4::from_translation(Vector3::new(0.0, -size / 2.0, 0.0))
+                        * Matrix4::from_scale(size),
+                ),
+                Some(Item::Tool(_)) => push(
+                    BlockType::Log,
+                    Matrix4::from_angle_z(cgmath::Deg(-45.0))
+                        * Matrix4::from_translation(Vector3::new(0.0, -size, 0.0))
+                        * Matrix4::from_nonuniform_scale(size * 0.2, size * 2.0, size * 0.2),
+                ),
+                None => {}
+            }
+        }
+        instances
+    }
+
+    /// Places the block selected in the hotbar against the face of the block the camera points
+    /// at. Blocks are only placed into air, liquids and tall grass, and solid blocks aren't
+    /// placed where the player is. Doors take up the block above too, and torches hang on the
+    /// block they're placed against. Returns whether a block was placed.
+    pub fn place_block(&mut self) -> bool {
+        let (Some(hit), Some(block)) = (self.target(), self.hotbar.selected_block()) else {
+            return false;
+        };
+        let block = block.facing(Face::horizontal(self.camera.forward()));
+        let Some(block) = block.attached(hit.face.opposite()) else {
+            return false;
+        };
+        let position = hit.adjacent();
+        let replaceable = self
+            .world
+            .get_block(position)
+            .is_some_and(|block| block.is_replaceable());
+        let height = if block.door_state().is_some() { 2 } else { 1 };
+        let occupied = entities::aabb(&self.entities, self.player).is_some_and(|player| {
+            (0..height).any(|y| position.offset(0, y, 0).aabb().intersects(&player))
+        });
+        let supported = torch::is_supported(&self.world, position, block);
+        if !replaceable || !supported || (block.is_solid() && occupied) {
+            return false;
+        }
+        let placed = if block.door_state().is_some() {
+            door::place_door(&mut self.world, position, block)
+        } else {
+            self.world.set_block(position, block)
+        };
+        // Survival uses up the blocks placed, creative has as many as wanted.
+        if placed && self.world.game_mode() == GameMode::Survival {
+            self.hotbar.take_selected();
+            self.set_held_block(self.hotbar.selected_block());
+        }
+        placed
+    }
+
+    /// Handles the mouse button events and scrolling since the last frame. Clicking while the
+    /// cursor is free grabs it instead of doing anything in the world, and scrolling moves the
+    /// selection of the hotbar.
+    fn process_mouse_events(&mut self) {
+        let lines = self.scroll.trunc();
+        if let (true, Some(open)) = (lines != 0.0, &mut self.open_container) {
+            let slots = self
+                .world
+                .block_entity(open.position)
+                .and_then(|entity| entity.container())
+                .map_or(1, |container| container.len().max(1));
+            open.selected = (open.selected as i32 - lines as i32).rem_euclid(slots as i32) as usize;
+            self.scroll -= lines;
+        } else if lines != 0.0 {
+            // Scrolling down moves to the right, like reading.
+            self.hotbar.scroll(-lines as i32);
+            self.set_held_block(self.hotbar.selected_block());
+            self.scroll -= lines;
+            println!("Holding {}", self.held_item_name());
+        }
+
+        for MouseEntry(button, state) in std::mem::take(&mut self.mouse_events) {
+            if state != ElementState::Pressed {
+                if button == MouseButton::Left {
+                    self.breaking = false;
+                }
+                continue;
+            }
+            match self.state {
+                // Clicking resumes the game while paused, but does nothing in the menu.
+                GameState::Paused => {
+                    self.resume();
+                    continue;
+                }
+                GameState::Menu => continue,
+                GameState::Loading | GameState::Playing => {}
+            }
+            if !self.cursor_grabbed {
+                self.set_cursor_grabbed(true);
+                continue;
+            }
+            if self.state == GameState::Loading {
+                continue;
+            }
+            if self.open_container.is_some() {
+                match button {
+                    MouseButton::Left => self.swap_container_slot(),
+                    MouseButton::Right => self.close_container(),
+                    _ => {}
+                }
+                continue;
+            }
+            match button {
+                // Creative breaks blocks at once, survival over time while held.
+                MouseButton::Left if self.world.game_mode() == GameMode::Creative => {
+                    self.break_block();
+                }
+                MouseButton::Left => self.breaking = true,
+                // Using a block takes the place of placing one against it.
+                MouseButton::Right => {
+                    if !self.interact() {
+                        self.place_block();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the vertical field of view of the camera, without the widening while sprinting.
+    pub fn fov(&self) -> cgmath::Deg<f32> {
+        self.camera.projection.fovy().into()
+    }
+
+    /// Sets the vertical field of view of the camera, clamped between [`MIN_FOV`] and
+    /// [`MAX_FOV`].
+    pub fn set_fov(&mut self, fov: cgmath::Deg<f32>) {
+        let fov = cgmath::Deg(fov.0.clamp(MIN_FOV.0, MAX_FOV.0));
+        self.camera.projection.set_fovy(fov);
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Saves the settings, with the render distance, field of view and brightness last picked
+    /// in the game.
+    fn save_settings(&mut self) {
+        if let Err(err) = self.settings.save(SETTINGS_FILE) {
+            eprintln!("Failed to save {SETTINGS_FILE}: {err}");
+        }
+        self.settings_watcher.mark_seen();
+    }
+
+    /// Applies `settings`, switching everything they cover at once, like the sensitivity of
+    /// the mouse, the render distance and the fog with it, and the key bindings.
+    pub fn apply_settings(&mut self, mut settings: Settings) {
+        settings.clamp();
+        self.camera_controller.set_sensitivity(settings.sensitivity);
+        self.camera_controller.set_speed(settings.speed);
+        self.set_fov(cgmath::Deg(settings.fov));
+        self.set_render_distance(settings.render_distance);
+        self.set_brightness(settings.brightness);
+        if settings.vsync != self.settings.vsync {
+            self.renderer.set_present_mode(settings.present_mode());
+        }
+        self.key_bindings = KeyBindings::new(&settings.keybinds);
+        self.settings = settings;
+    }
+
+    /// Generates new chunks with the terrain settings file whenever it's edited while the game
+    /// runs. Chunks that were already generated stay as they are.
+    pub fn watch_terrain_settings(&mut self) {
+        self.terrain_watcher = Some(FileWatcher::new(TERRAIN_SETTINGS_FILE));
+    }
+
+    /// Starts saving the chunks changed since they were last saved every
+    /// [`AUTOSAVE_INTERVAL`], and continues the autosave under way a few chunks at a time.
+    fn autosave(&mut self) {
+        if !self.autosaving {
+            if self.last_autosave.elapsed() < AUTOSAVE_INTERVAL {
+                return;
+            }
+            self.last_autosave = Instant::now();
+            self.world.start_autosave();
+            self.autosaving = true;
+        }
+
+        match self.world.autosave(AUTOSAVE_CHUNKS_PER_FRAME) {
+            Ok(done) => {
+                self.autosaving = !done;
+                if done {
+                    self.autosaved = Some(Instant::now());
+                }
+            }
+            Err(err) => {
+                eprintln!("Failed to save the world: {err}");
+                self.autosaving = false;
+            }
+        }
+    }
+
+    /// Returns whether the world is being saved, or was just saved by an autosave.
+    fn is_saving(&self) -> bool {
+        self.autosaving
+            || self.world.chunks().is_saving()
+            || self
+                .autosaved
+                .is_some_and(|autosaved| autosaved.elapsed() < SAVE_INDICATOR_TIME)
+    }
+
+    /// Applies the settings and terrain settings files if they were edited since they were
+    /// last read. Files that fail to load or have values out of range are reported and the
+    /// current settings are kept.
+    fn reload_changed_files(&mut self) {
+        if self.settings_watcher.changed() {
+            match Settings::load(SETTINGS_FILE) {
+                Ok(settings) => {
+                    self.apply_settings(settings);
+                    println!("Reloaded {SETTINGS_FILE}");
+                }
+                Err(err) => eprintln!("Failed to reload {SETTINGS_FILE}: {err}"),
+            }
+        }
+        if self
+            .terrain_watcher
+            .as_mut()
+            .is_some_and(FileWatcher::changed)
+        {
+            match TerrainSettings::load(TERRAIN_SETTINGS_FILE) {
+                Ok(settings) => {
+                    self.world.set_generator(noise_generator(settings));
+                    println!("Reloaded {TERRAIN_SETTINGS_FILE}, new chunks use it");
+                }
+                Err(err) => eprintln!("Failed to reload {TERRAIN_SETTINGS_FILE}: {err}"),
+            }
+        }
+    }
+
+    /// Returns how much dark light levels are raised, from 0 for the plain falloff of light to
+    /// 1 for the brightest caves and nights.
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Sets how much dark light levels are raised, clamped between 0 and 1.
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    /// Switches to `state`, grabbing the cursor when resuming and releasing it when pausing.
+    /// Pausing stops breaking blocks.
+    pub fn set_state(&mut self, state: GameState) {
+        if state == self.state {
+            return;
+        }
+
+        match state {
+            GameState::Loading | GameState::Playing => {
+                if matches!(self.state, GameState::Paused | GameState::Menu) {
+                    self.set_cursor_grabbed(true);
+                }
+            }
+            GameState::Paused => {
+                self.set_cursor_grabbed(false);
+                self.breaking = false;
+                self.mining = None;
+                println!(
+                    "Paused, press {} to resume or {} for the menu",
+                    self.key_name(Action::Menu),
+                    self.key_name(Action::Quit)
+                );
+            }
+            GameState::Menu => println!(
+                "Press {} again to quit, or {} to go back",
+                self.key_name(Action::Quit),
+                self.key_name(Action::Menu)
+            ),
+        }
+        if self.state == GameState::Paused && state == GameState::Playing {
+            println!("Resumed");
+        }
+        self.state = state;
+    }
+
+    /// Goes back to loading or playing from the pause, depending on whether the area around
+    /// the spawn has loaded.
+    fn resume(&mut self) {
+        if self.loading.is_some() {
+            self.set_state(GameState::Loading);
+        } else {
+            self.set_state(GameState::Playing);
+        }
+    }
+
+    /// Returns the name of the first key bound to `action`, or that it's unbound.
+    fn key_name(&self, action: Action) -> String {
+        self.key_bindings
+            .keys(action)
+            .first()
+            .map_or("nothing".to_string(), |key| format!("{key:?}"))
+    }
+
+    /// Returns what's shown in front of the camera for the state of the game: a turning cube
+    /// while loading, two bars while paused, and a cross in the menu.
+    fn overlay_instances(&self) -> Vec<(Model, EntityInstance)> {
+        let bar = |x: f32, angle: cgmath::Deg<f32>| {
+            Matrix4::from_translation(Vector3::new(x, 0.0, 0.0))
+                * Matrix4::from_angle_z(angle)
+                * Matrix4::from_translation(Vector3::new(0.0, -OVERLAY_SIZE / 2.0, 0.0))
+                * Matrix4::from_nonuniform_scale(
+                    OVERLAY_SIZE / 4.0,
+                    OVERLAY_SIZE,
+                    OVERLAY_SIZE / 8.0,
+                )
+        };
+        let (model, transforms) = match self.state {
+            GameState::Playing => return Vec::new(),
+            GameState::Loading => {
+                let Some((_, start)) = &self.loading else {
+                    return Vec::new();
+                };
+                let angle = cgmath::Deg(start.elapsed().as_secs_f32() * LOADING_SPIN);
+                let size = OVERLAY_SIZE / 2.0;
+                let cube = Matrix4::from_angle_x(cgmath::Deg(20.0))
+                    * Matrix4::from_angle_y(angle)
+                    * Matrix4::from_translation(Vector3::new(0.0, -size / 2.0, 0.0))
+                    * Matrix4::from_scale(size);
+                (BlockType::Grass, vec![cube])
+            }
+            GameState::Paused => {
+                let gap = OVERLAY_SIZE / 4.0;
+                (
+                    BlockType::Snow,
+                    vec![bar(-gap, cgmath::Deg(0.0)), bar(gap, cgmath::Deg(0.0))],
+                )
+            }
+            GameState::Menu => (
+                BlockType::Snow,
+                vec![bar(0.0, cgmath::Deg(45.0)), bar(0.0, cgmath::Deg(-45.0))],
+            ),
+        };
+
+        let to_world = self.camera.view().invert().unwrap_or(Matrix4::identity());
+        let center = Matrix4::from_translation(Vector3::new(0.0, 0.0, -OVERLAY_DISTANCE));
+        transforms
+            .into_iter()
+            .map(|transform| {
+                (
+                    Model(model),
+                    EntityInstance {
+                        model: (to_world * center * transform).into(),
+                        light: [0.0, 1.0],
+                        _padding: [0.0; 2],
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Holds the cursor in the window and hides it, so the mouse looks around, or shows and
+    /// releases it. Platforms lock the cursor in place or confine it to the window, depending
+    /// on which they support.
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        if grabbed == self.cursor_grabbed {
+            return;
+        }
+
+        let result = if grabbed {
+            self.window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to grab the cursor: {err}");
+            return;
+        }
+        self.window.set_cursor_visible(!grabbed);
+        self.cursor_grabbed = grabbed;
+    }
+
+    pub fn overview(&self) -> bool {
+        self.overview
+    }
+
+    /// Sets whether the world is also drawn from above the camera, splitting the window
+    /// between the two views.
+    pub fn set_overview(&mut self, overview: bool) {
+        self.overview = overview;
+        let size = self.window.inner_size();
+        self.resize_views(size.width, size.height);
+    }
+
+    /// Records the flight of the camera from now on, saving it to `file` when the game closes.
+    pub fn record_flight(&mut self, file: impl Into<PathBuf>) {
+        self.recorder = Some(FlightRecorder::new(file));
+    }
+
+    /// Flies the camera along `flight` instead of the controls once the area around the spawn
+    /// has loaded, then prints how long frames took and closes the game.
+    pub fn replay_flight(&mut self, flight: CameraPath) {
+        self.playback = Some(FlightPlayback::new(flight));
+    }
+
+    /// Moves the camera a step further along the replayed flight, closing the game once it's
+    /// over.
+    fn replay(&mut self) {
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+
+        if !playback.advance(&mut self.camera, self.delta) {
+            println!("Replayed the flight: {}", playback.summary());
+            self.playback = None;
+            self.should_close = true;
+        }
+        self.previous_position = self.camera.position;
+        self.position = self.camera.position;
+    }
+
+    pub fn spectating(&self) -> bool {
+        self.spectator.is_some()
+    }
+
+    /// Detaches the camera from the player so it flies freely through blocks, or snaps it back
+    /// to the player, which moves and turns as the player did before.
+    pub fn set_spectating(&mut self, spectating: bool) {
+        if spectating == self.spectating() {
+            return;
+        }
+
+        if spectating {
+            self.spectator = Some(Spectator {
+                camera: self.camera.clone(),
+                mode: self.camera_controller.mode(),
+                collision: self.camera_controller.collision(),
+            });
+            self.camera_controller.set_mode(MovementMode::Flying);
+            self.camera_controller.set_collision(false);
+        } else if let Some(spectator) = self.spectator.take() {
+            self.camera.look_from(&spectator.camera);
+            self.previous_position = self.camera.position;
+            self.position = self.camera.position;
+            self.camera_controller.set_mode(spectator.mode);
+            self.camera_controller.set_collision(spectator.collision);
+        }
+    }
+
+    /// Returns the camera of the player, which chunks are loaded and culled around. It's the
+    /// camera drawn from unless spectating.
+    fn player_camera(&self) -> Camera {
+        let mut camera = self.camera.clone();
+        if let Some(spectator) = &self.spectator {
+            camera.look_from(&spectator.camera);
+        }
+        camera
+    }
+
+    /// Returns where in a window of `width` by `height` pixels the camera and the overview are
+    /// drawn, or `None` if the camera fills the window.
+    fn viewports(&self, width: u32, height: u32) -> Option<Vec<Viewport>> {
+        self.overview.then(|| Viewport::split(width, height, 2))
+    }
+
+    /// Fits the projection of the camera to its part of a window of `width` by `height`
+    /// pixels.
+    fn resize_views(&mut self, width: u32, height: u32) {
+        match self.viewports(width, height) {
+            Some(viewports) => self
+                .camera
+                .projection
+                .resize(viewports[0].width, viewports[0].height),
+            None => self.camera.projection.resize(width, height),
+        }
+    }
+
+    /// Returns the camera looking straight down from above the camera into `viewport`, far
+    /// enough up to see around it.
+    fn overview_camera(&self, viewport: Viewport) -> Camera {
+        let height = view_distance(self.render_distance()) * OVERVIEW_HEIGHT;
+        let projection = Projection::new(
+            viewport.width,
+            viewport.height,
+            cgmath::Deg(90.0),
+            0.5,
+            self.camera.projection.zfar() + height,
+        );
+        Camera::new(
+            self.camera.position + Vector3::unit_y() * height,
+            cgmath::Deg(-90.0),
+            cgmath::Deg(-89.0),
+            projection,
+        )
+    }
+
+    pub fn time(&self) -> &WorldTime {
+        self.world.time()
+    }
+
+    pub fn time_mut(&mut self) -> &mut WorldTime {
+        self.world.time_mut()
+    }
+
+    /// Prints the progress of loading the area around the spawn until it's done.
+    fn report_loading(&mut self) {
+        let Some((progress, start)) = &self.loading else {
+            return;
+        };
+
+        for progress in progress.try_iter() {
+            let GenerationProgress { stage, done, total } = progress;
+            println!("Loading the world, {}: {done}/{total} chunks", stage.name());
+
+            if progress.is_finished() {
+                println!("Loaded the world in {:.2?}", start.elapsed());
+                self.loading = None;
+                if self.state == GameState::Loading {
+                    self.set_state(GameState::Playing);
+                }
+                return;
+            }
         }
     }
 
     fn update(&mut self) {
+        self.reload_changed_files();
+        self.autosave();
+
+        let mut render_distance = self.render_distance();
+        let mut brightness = self.brightness();
+        let mut overview = self.overview();
+        let mut fov = self.fov();
+        let mut game_state = self.state;
+        let mut close_container = false;
+        let mut go_to_spawn = false;
+        let mut save = false;
+        let mut game_mode = self.game_mode();
+        let mode = self.camera_controller.mode();
+        let mut collision = self.camera_controller.collision();
+        let mut spectating = self.spectating();
+        let mut slot = self.hotbar.selected();
         self.key_events.iter().for_each(|KeyEntry(key, state)| {
-            if *state == ElementState::Pressed && *key == KeyCode::Escape {
-                self.should_close = true
-            } else {
-                self.camera_controller.process_keyboard(*key, *state);
+            let Some(action) = self.key_bindings.action(*key) else {
+                return;
+            };
+            let pressed = *state == ElementState::Pressed;
+            if let (true, Some(action_slot)) = (pressed, action.slot()) {
+                slot = action_slot;
+                return;
+            }
+            match action {
+                // The menu key closes the open container first, then pauses the game. It
+                // resumes the game while paused, and goes back to the pause from the menu.
+                Action::Menu if pressed => match game_state {
+                    GameState::Loading | GameState::Playing if self.open_container.is_some() => {
+                        close_container = true
+                    }
+                    GameState::Loading | GameState::Playing => game_state = GameState::Paused,
+                    GameState::Paused => game_state = GameState::Playing,
+                    GameState::Menu => game_state = GameState::Paused,
+                },
+                Action::Quit if pressed => match game_state {
+                    GameState::Paused => game_state = GameState::Menu,
+                    GameState::Menu => self.should_close = true,
+                    GameState::Loading | GameState::Playing => {}
+                },
+                Action::DecreaseRenderDistance if pressed => {
+                    render_distance = render_distance.saturating_sub(1)
+                }
+                Action::IncreaseRenderDistance if pressed => render_distance += 1,
+                Action::DecreaseBrightness if pressed => brightness -= BRIGHTNESS_STEP,
+                Action::IncreaseBrightness if pressed => brightness += BRIGHTNESS_STEP,
+                Action::DecreaseFov if pressed => fov -= FOV_STEP,
+                Action::IncreaseFov if pressed => fov += FOV_STEP,
+                Action::ToggleOverview if pressed => overview = !overview,
+                Action::ToggleFlying if pressed => self.camera_controller.toggle_mode(),
+                Action::ToggleCollision if pressed => collision = !collision,
+                Action::ToggleSpectator if pressed => spectating = !spectating,
+                Action::Spawn if pressed => go_to_spawn = true,
+                Action::Save if pressed => save = true,
+                Action::ToggleGameMode if pressed => {
+                    game_mode = match game_mode {
+                        GameMode::Creative => GameMode::Survival,
+                        GameMode::Survival => GameMode::Creative,
+                    }
+                }
+                _ => {
+                    self.camera_controller.process_action(action, *state);
+                }
             }
         });
 
         self.key_events.clear();
-        self.camera_controller
-            .update_camera(&mut self.camera, self.delta);
+        if close_container {
+            self.close_container();
+        }
+        if game_mode != self.game_mode() {
+            self.set_game_mode(game_mode);
+            println!("Game mode: {}", game_mode.name());
+        }
+        if save {
+            match self.world.save() {
+                Ok(()) => println!("Saved the world"),
+                Err(err) => eprintln!("Failed to save the world: {err}"),
+            }
+        }
+        if go_to_spawn && self.state == GameState::Playing {
+            self.teleport_to_spawn();
+            println!("Teleported to the spawn");
+        }
+        if game_state != self.state {
+            if game_state == GameState::Playing {
+                self.resume();
+            } else {
+                self.set_state(game_state);
+            }
+        }
+        // Only values picked here go into the settings, so a field of view given on the
+        // command line isn't saved. They're taken after the setters clamp them, so a key
+        // pressed at a limit saves nothing.
+        let settings_before = (
+            self.settings.render_distance,
+            self.settings.brightness,
+            self.settings.fov,
+        );
+        if render_distance != self.render_distance() {
+            self.set_render_distance(render_distance);
+            self.settings.render_distance = self.render_distance();
+            println!("Render distance: {} chunks", self.render_distance());
+        }
+        if brightness != self.brightness() {
+            self.set_brightness(brightness);
+            self.settings.brightness = self.brightness();
+            println!("Brightness: {:.0}%", self.brightness() * 100.0);
+        }
+        if fov != self.fov() {
+            self.set_fov(fov);
+            self.settings.fov = self.fov().0;
+            println!("Field of view: {:.0}°", self.fov().0);
+        }
+        let settings_after = (
+            self.settings.render_distance,
+            self.settings.brightness,
+            self.settings.fov,
+        );
+        if settings_after != settings_before {
+            self.save_settings();
+        }
+        if slot != self.hotbar.selected() {
+            self.select_slot(slot);
+            println!("Holding {}", self.held_item_name());
+        }
+        if spectating != self.spectating() {
+            self.set_spectating(spectating);
+            if spectating {
+                println!("Spectating");
+            } else {
+                println!("Back to the player");
+            }
+        }
+        if mode != self.camera_controller.mode() && !spectating {
+            match self.camera_controller.mode() {
+                MovementMode::Flying => println!("Flying"),
+                MovementMode::Walking => println!("Walking"),
+            }
+        }
+        if collision != self.camera_controller.collision() {
+            self.camera_controller.set_collision(collision);
+            if collision {
+                println!("Camera collision on");
+            } else {
+                println!("Camera collision off");
+            }
+        }
+        if overview != self.overview() {
+            self.set_overview(overview);
+        }
+        self.process_mouse_events();
+        let playing = self.state == GameState::Playing;
+        if self.playback.is_none() {
+            if self.state != GameState::Paused && self.state != GameState::Menu {
+                self.camera_controller
+                    .rotate_camera(&mut self.camera, self.delta);
+            }
+            if playing {
+                self.simulate();
+            }
+        } else if playing {
+            self.replay();
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.delta, &self.camera);
+        }
+
+        let player = self.player_camera();
+        self.world
+            .chunks_mut()
+            .update(player.position, player.forward());
+        self.report_loading();
+
+        if playing {
+            self.world.time_mut().advance(self.delta);
+        }
 
         let camera_uniform = CameraUniform::init(&self.camera);
         self.renderer.update_camera_uniform(camera_uniform);
+        let size = self.window.inner_size();
+        if let Some(viewports) = self.viewports(size.width, size.height) {
+            let overview = self.overview_camera(viewports[1]);
+            self.renderer
+                .update_view_camera_uniform(1, CameraUniform::init(&overview));
+        }
+        let fog_end = view_distance(self.render_distance());
+        let mut sky = SkyUniform::init(self.world.time(), fog_end, self.brightness);
+        let mut celestial = CelestialUniform::init(self.world.time());
+        if self.is_underwater() {
+            sky = sky.underwater();
+            celestial = celestial.underwater();
+        }
+        self.renderer.update_sky_uniform(sky);
+        self.renderer.update_celestial_uniform(celestial);
+
+        let cascades = self.camera.shadow_cascades(
+            self.world.time().light_direction(),
+            &CASCADE_SPLITS,
+            SHADOW_MAP_SIZE,
+        );
+        self.renderer
+            .update_shadows(&cascades, self.world.time().shadow_strength());
+        self.shadow_cascades = cascades;
+
+        if let Some(held_block) = &self.held_block {
+            // Lit like the air the camera is in, fully by the sky until it's loaded.
+            let light = self
+                .world
+                .light_at(BlockPos::from_world(self.camera.position))
+                .unwrap_or([1.0, 0.0]);
+            held_block.update(self.renderer.queue(), &self.camera, light);
+        }
+    }
+
+    /// Runs the simulation steps the time since the last frame makes up, and places the
+    /// camera between where the last two steps left it, as far as the time into the next
+    /// step.
+    fn simulate(&mut self) {
+        self.accumulator += self.delta;
+        let mut steps = 0;
+        while self.accumulator >= SIMULATION_STEP {
+            if steps == MAX_STEPS_PER_FRAME {
+                self.accumulator %= SIMULATION_STEP;
+                break;
+            }
+            self.step();
+            self.accumulator -= SIMULATION_STEP;
+            steps += 1;
+        }
+
+        let t = self.accumulator / SIMULATION_STEP;
+        self.camera.position =
+            self.previous_position + (self.position - self.previous_position) * t;
+    }
+
+    /// Advances the simulation by one step.
+    fn step(&mut self) {
+        self.camera.position = self.position;
+        let walking = self.camera_controller.mode() == MovementMode::Walking;
+        let controls = if walking {
+            self.camera_controller
+                .controls(&mut self.camera, SIMULATION_STEP)
+        } else {
+            self.camera_controller
+                .move_camera(&mut self.camera, &self.world, SIMULATION_STEP);
+            Controls::default()
+        };
+        if let Ok(mut player) = self.entities.get::<&mut Controls>(self.player) {
+            *player = controls;
+        }
+
+        let mut rng = rand::thread_rng();
+        if let Some(eye_position) = entities::eye_position(&self.entities, self.player) {
+            entities::spawn_system(&mut self.entities, &self.world, eye_position, &mut rng);
+        }
+        entities::wander_system(&mut self.entities, &mut rng, SIMULATION_STEP);
+        entities::control_system(&mut self.entities);
+        entities::physics_system(&mut self.entities, &self.world, SIMULATION_STEP);
+
+        if walking {
+            if let Some(eye_position) = entities::eye_position(&self.entities, self.player) {
+                self.camera.position = eye_position;
+            }
+        } else if self.spectator.is_none() {
+            // The player is carried along by the camera, unless it stays behind spectating.
+            entities::teleport(&mut self.entities, self.player, self.camera.position);
+        }
+        self.mine(SIMULATION_STEP);
+        self.world.tick();
+        self.check_open_container();
+        self.previous_position = self.position;
+        self.position = self.camera.position;
+        self.check_death();
     }
 
     fn render(&mut self) {
-        let mesh = self.chunk_list.mesh();
-        self.renderer.draw_terrain(&mesh);
+        self.world
+            .chunks_mut()
+            .update_meshes(self.renderer.device(), self.renderer.queue());
+
+        let player = self.player_camera();
+        let chunks = self.world.chunks();
+        let meshes = chunks.visible_meshes(&player);
+        let casters = self
+            .shadow_cascades
+            .map(|cascade| chunks.meshes_within(&cascade.frustum()));
+        let vertices = meshes.iter().map(|mesh| mesh.vertex_count() as usize).sum();
+        let mut instances = entities::extract_instances(&self.entities, &self.world);
+        if let Some((model, instance)) = self.mining_overlay() {
+            instances.entry(model).or_default().push(instance);
+        }
+        let mut in_front = self.container_instances();
+        in_front.extend(self.overlay_instances());
+        for model in instances
+            .keys()
+            .chain(in_front.iter().map(|(model, _)| model))
+        {
+            self.entity_meshes.entry(*model).or_insert_with(|| {
+                let Model(block) = *model;
+                let (vertices, indices) =
+                    entity::cuboid([-0.5, 0.0, -0.5], [0.5, 1.0, 0.5], |face| block.layer(face));
+                MeshBuffers::new(self.renderer.device(), &vertices, &indices)
+            });
+        }
+        let batches: Vec<_> = instances
+            .iter()
+            .map(|(model, instances)| EntityBatch {
+                mesh: &self.entity_meshes[model],
+                instances,
+            })
+            .collect();
+        let mut in_front_instances: HashMap<Model, Vec<EntityInstance>> = HashMap::new();
+        for (model, instance) in in_front {
+            in_front_instances.entry(model).or_default().push(instance);
+        }
+        let in_front_batches: Vec<_> = in_front_instances
+            .iter()
+            .map(|(model, instances)| EntityBatch {
+                mesh: &self.entity_meshes[model],
+                instances,
+            })
+            .collect();
+        match self.renderer.begin_frame() {
+            Ok(mut frame) => {
+                let size = self.window.inner_size();
+                let viewports = self.viewports(size.width, size.height);
+                if let Some(viewports) = &viewports {
+                    frame.set_view(0, Some(viewports[0]));
+                }
+                self.renderer.draw_sky(&mut frame);
+                self.renderer.draw_terrain(&mut frame, &meshes, &casters);
+                self.renderer.draw_entities(&mut frame, &batches);
+                self.renderer.draw_transparent_terrain(&mut frame, &meshes);
+                self.renderer.draw_clouds(&mut frame);
+                if let Some(viewports) = &viewports {
+                    // The overview draws what the camera does, to show what it culls.
+                    frame.set_view(1, Some(viewports[1]));
+                    self.renderer.draw_sky(&mut frame);
+                    self.renderer.draw_terrain(&mut frame, &meshes, &casters);
+                    self.renderer.draw_transparent_terrain(&mut frame, &meshes);
+                    self.renderer.draw_clouds(&mut frame);
+                    frame.set_view(0, Some(viewports[0]));
+                }
+                if let Some(held_block) = &self.held_block {
+                    self.renderer.draw_held_block(&mut frame, held_block);
+                }
+                self.renderer
+                    .draw_entities_in_front(&mut frame, &in_front_batches);
+                self.renderer.submit_frame(frame);
+                if let Some(timings) = self.renderer.take_gpu_timings() {
+                    self.stats.record_gpu_timings(&timings);
+                }
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                eprintln!("Out of memory for drawing frames, closing");
+                self.should_close = true;
+            }
+            Err(err) => eprintln!("Failed to start drawing a frame: {err}"),
+        }
+
+        if self.stats.record(self.delta, vertices) {
+            let mut title = format!("{WINDOW_TITLE} - {}", self.stats.summary());
+            // The GPU time of each pass follows the total in the summary.
+            if let Some(passes) = self.stats.gpu_summary() {
+                title += &format!(" ({passes})");
+            }
+            if self.is_saving() {
+                title += " - saving";
+            }
+            self.window.set_title(&title);
+        }
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
     }
 
     pub async fn run(&mut self, event_loop: EventLoop<()>) {
@@ -93,11 +1712,30 @@ impl<'a> Game<'a> {
                 }
 
                 match event {
+                    Event::LoopExiting => {
+                        if let Some(recorder) = &self.recorder {
+                            match recorder.save() {
+                                Ok(()) => {
+                                    println!("Saved the flight to {}", recorder.file().display())
+                                }
+                                Err(err) => eprintln!("Failed to save the flight: {err}"),
+                            }
+                        }
+                        if let Err(err) = self.world.save() {
+                            eprintln!("Failed to save the world: {err}");
+                        }
+                        if let Err(err) = self.renderer.save_pipeline_cache() {
+                            eprintln!("Failed to save the pipeline cache: {err}");
+                        }
+                    }
                     Event::DeviceEvent {
                         event: DeviceEvent::MouseMotion { delta },
                         ..
                     } => {
-                        self.camera_controller.process_mouse(delta.0, delta.1);
+                        // The mouse only looks around while the cursor is grabbed.
+                        if self.cursor_grabbed {
+                            self.camera_controller.process_mouse(delta.0, delta.1);
+                        }
                     }
                     Event::WindowEvent {
                         ref event,
@@ -105,12 +1743,35 @@ impl<'a> Game<'a> {
                     } if event_window_id == window_id => match event {
                         WindowEvent::Resized(physical_size) => {
                             self.renderer.on_resize(*physical_size);
-                            self.camera
-                                .projection
-                                .resize(physical_size.width, physical_size.height);
-                            surface_configured = true;
+                            // Keeps the last aspect ratio while minimized.
+                            if !self.renderer.is_minimized() {
+                                self.resize_views(physical_size.width, physical_size.height);
+                                surface_configured = true;
+                            }
                         }
                         WindowEvent::CloseRequested => control_flow.exit(),
+                        // Switching to another window pauses the game, and switching back
+                        // leaves it paused until it's resumed.
+                        WindowEvent::Focused(true) => {
+                            if matches!(self.state, GameState::Loading | GameState::Playing) {
+                                self.set_cursor_grabbed(true);
+                            }
+                        }
+                        WindowEvent::Focused(false) => match self.state {
+                            GameState::Playing => self.set_state(GameState::Paused),
+                            _ => self.set_cursor_grabbed(false),
+                        },
+                        WindowEvent::MouseInput { state, button, .. } => {
+                            self.mouse_events.push(MouseEntry(*button, *state))
+                        }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            self.scroll += match delta {
+                                MouseScrollDelta::LineDelta(_, y) => *y,
+                                MouseScrollDelta::PixelDelta(position) => {
+                                    position.y as f32 / PIXELS_PER_LINE
+                                }
+                            }
+                        }
                         WindowEvent::KeyboardInput {
                             event:
                                 KeyEvent {
@@ -131,10 +1792,11 @@ impl<'a> Game<'a> {
                             self.delta = (now - last_frame_time).as_secs_f32();
                             last_frame_time = now;
 
-                            println!("FPS: {}", 1.0 / self.delta);
-
                             self.update();
-                            self.render();
+                            // Nothing can be drawn until the window is restored.
+                            if !self.renderer.is_minimized() {
+                                self.render();
+                            }
                         }
                         _ => {}
                     },
@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::biome::{Biome, BiomeMap, SurfaceConfig};
+use crate::chunk::{Chunk, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH, DEFAULT_SEED, SEA_LEVEL};
+use crate::decoration::{self, random};
+use crate::noise::{FbmConfig, HeightNoise, MountainConfig};
+use crate::position::{BlockPos, ChunkPos};
+use crate::renderer::block::BlockType;
+use crate::structure::Structure;
+
+/// How far from the origin dry land is looked for to spawn on, in blocks.
+const SPAWN_SEARCH_RADIUS: i32 = 256;
+/// How far apart the columns looked at for dry land to spawn on are, in blocks.
+const SPAWN_SEARCH_STEP: i32 = 4;
+
+/// Fills newly created chunks with terrain. Chunks are generated on several threads at once,
+/// so generators are shared between them.
+pub trait TerrainGenerator: Send + Sync {
+    /// Returns the biome of the column at the given block coordinates.
+    fn biome_at(&self, x: i32, z: i32) -> Biome;
+
+    /// Returns the height below which the terrain is filled with water.
+    fn sea_level(&self) -> i32 {
+        SEA_LEVEL
+    }
+
+    /// Switches to deriving the terrain from `seed`, the seed of the world. Generators that
+    /// don't pick anything at random ignore it.
+    fn set_seed(&mut self, _seed: u32) {}
+
+    /// Returns where players start and come back to after dying, on the surface: the block
+    /// their feet are in, with the ground below it.
+    fn spawn_point(&self) -> BlockPos;
+
+    /// Returns which generator this is and its settings, saved with the world.
+    fn config(&self) -> GeneratorConfig;
+
+    /// Switches to the settings in `config`, those a saved world was generated with. Fails if
+    /// `config` is for another kind of generator.
+    fn set_config(&mut self, config: &GeneratorConfig) -> anyhow::Result<()>;
+
+    /// Fills `chunk`, which starts out as air with its biomes already set. Returns the blocks
+    /// that were placed outside of it, like parts of structures crossing into neighboring
+    /// chunks, which are placed once those chunks exist.
+    fn generate(&self, chunk: &mut Chunk) -> Vec<(BlockPos, BlockType)>;
+}
+
+/// Which generator fills a world and with what settings, saved with the world so its chunks
+/// keep being generated the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GeneratorConfig {
+    Noise(TerrainSettings),
+    Flat {
+        /// The names of the block types of the layers, from the bottom up.
+        layers: Vec<String>,
+    },
+}
+
+impl GeneratorConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorConfig::Noise(_) => "noise",
+            GeneratorConfig::Flat { .. } => "flat",
+        }
+    }
+}
+
+/// What world generation is busy with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationStage {
+    /// Reading saved chunks.
+    Loading,
+    /// Generating chunks that were never saved.
+    Generating,
+    /// Writing generated chunks to disk.
+    Saving,
+}
+
+impl GenerationStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenerationStage::Loading => "loading",
+            GenerationStage::Generating => "generating",
+            GenerationStage::Saving => "saving",
+        }
+    }
+}
+
+/// How far world generation has come, as sent to subscribers of the chunk manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationProgress {
+    pub stage: GenerationStage,
+    /// The number of chunks done.
+    pub done: usize,
+    /// The number of chunks to do.
+    pub total: usize,
+}
+
+impl GenerationProgress {
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.total
+    }
+
+    /// Returns how much is done, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+}
+
+/// The height of the unbreakable floor of the world.
+pub const BEDROCK_LEVEL: i32 = 0;
+
+/// Separates the random numbers used for structures from those used for decoration.
+const STRUCTURE_SALT: u32 = 100;
+
+/// The parameters of the terrain made by [`NoiseGenerator`], usually loaded from
+/// `worldgen.toml` so they can be tweaked without recompiling. Missing values keep their
+/// defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainSettings {
+    /// How many blocks the terrain takes to change noticeably.
+    pub scale: f64,
+    /// The height of the lowest valleys, before mountains are added.
+    pub height_min: f32,
+    /// The height of the highest hills, before mountains are added.
+    pub height_max: f32,
+    /// The height below which the terrain is filled with water.
+    pub sea_level: i32,
+    /// How many blocks above sea level shores are covered in sand.
+    pub beach_height: i32,
+    /// The height from which the surface is bare stone.
+    pub rock_height: i32,
+    /// The height from which the surface is snow covered stone.
+    pub snow_height: i32,
+    pub fbm: FbmConfig,
+    pub mountains: MountainConfig,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            scale: 100.0,
+            height_min: 0.0,
+            height_max: 15.0,
+            sea_level: SEA_LEVEL,
+            beach_height: 1,
+            rock_height: 11,
+            snow_height: 12,
+            fbm: FbmConfig::default(),
+            mountains: MountainConfig::default(),
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Reads settings from the TOML file at `path`, failing if any is out of range.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let settings: TerrainSettings = toml::from_str(&contents)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Checks that the settings make sensible terrain.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            anyhow::bail!("scale must be above 0, got {}", self.scale);
+        }
+        if self.height_min > self.height_max {
+            anyhow::bail!(
+                "height_min must not be above height_max, got {} and {}",
+                self.height_min,
+                self.height_max
+            );
+        }
+        if !(0..CHUNK_HEIGHT as i32).contains(&self.sea_level) {
+            anyhow::bail!(
+                "sea_level must be between 0 and {}, got {}",
+                CHUNK_HEIGHT - 1,
+                self.sea_level
+            );
+        }
+        if self.beach_height < 0 {
+            anyhow::bail!(
+                "beach_height must not be negative, got {}",
+                self.beach_height
+            );
+        }
+        if self.rock_height > self.snow_height {
+            anyhow::bail!(
+                "rock_height must not be above snow_height, got {} and {}",
+                self.rock_height,
+                self.snow_height
+            );
+        }
+        if self.fbm.octaves == 0 {
+            anyhow::bail!("fbm.octaves must be at least 1");
+        }
+        if !(self.fbm.lacunarity.is_finite() && self.fbm.lacunarity > 0.0) {
+            anyhow::bail!(
+                "fbm.lacunarity must be above 0, got {}",
+                self.fbm.lacunarity
+            );
+        }
+        if !(self.fbm.persistence.is_finite() && self.fbm.persistence > 0.0) {
+            anyhow::bail!(
+                "fbm.persistence must be above 0, got {}",
+                self.fbm.persistence
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Generates rolling hills and mountain ranges from seeded noise, with biomes, water and trees.
+pub struct NoiseGenerator {
+    /// The seed the noise and the placement of trees and structures are derived from.
+    seed: u32,
+    /// The terrain height of every column.
+    height_noise: HeightNoise,
+    /// Decides the biome of every column.
+    biome_map: BiomeMap,
+    /// The ground of every biome.
+    surfaces: HashMap<Biome, SurfaceConfig>,
+    settings: TerrainSettings,
+    /// The structures that can appear in the world, tried in order.
+    structures: Vec<Structure>,
+}
+
+impl NoiseGenerator {
+    /// Creates a generator making terrain with `settings` from [`DEFAULT_SEED`], until another
+    /// seed is set.
+    pub fn new(settings: TerrainSettings) -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            height_noise: Self::height_noise(DEFAULT_SEED, &settings),
+            biome_map: BiomeMap::new(DEFAULT_SEED),
+            surfaces: Biome::ALL
+                .into_iter()
+                .map(|biome| (biome, biome.default_surface()))
+                .collect(),
+            settings,
+            structures: Vec::new(),
+        }
+    }
+
+    /// Returns the noise giving the terrain height of every column from `seed`.
+    fn height_noise(seed: u32, settings: &TerrainSettings) -> HeightNoise {
+        let mut height_noise = HeightNoise::new(
+            seed,
+            settings.fbm,
+            settings.scale,
+            settings.height_min,
+            settings.height_max,
+        );
+        height_noise.set_mountains(settings.mountains);
+        height_noise
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    /// Returns the ground of `biome`.
+    pub fn surface(&self, biome: Biome) -> SurfaceConfig {
+        self.surfaces
+            .get(&biome)
+            .copied()
+            .unwrap_or_else(|| biome.default_surface())
+    }
+
+    pub fn set_surface(&mut self, biome: Biome, surface: SurfaceConfig) {
+        self.surfaces.insert(biome, surface);
+    }
+
+    pub fn structures(&self) -> &[Structure] {
+        &self.structures
+    }
+
+    pub fn set_structures(&mut self, structures: Vec<Structure>) {
+        self.structures = structures;
+    }
+
+    /// Places at most one structure with its origin in `chunk`, on top of the terrain.
+    fn place_structures(&self, chunk: &mut Chunk, deferred: &mut Vec<(BlockPos, BlockType)>) {
+        let ChunkPos(chunk_x, _, chunk_z) = chunk.position;
+
+        for (index, structure) in self.structures.iter().enumerate() {
+            let salt = STRUCTURE_SALT + index as u32 * 3;
+            if random(self.seed, chunk_x, chunk_z, salt) >= structure.chance {
+                continue;
+            }
+
+            let x = (random(self.seed, chunk_x, chunk_z, salt + 1) * CHUNK_WIDTH as f32) as usize;
+            let z = (random(self.seed, chunk_x, chunk_z, salt + 2) * CHUNK_DEPTH as f32) as usize;
+            let BlockPos(world_x, _, world_z) = chunk.position.block(x, 0, z);
+
+            let height = self.height_at(world_x, world_z);
+            if height + 1 < self.settings.sea_level || !structure.allows(chunk.biome(x, z)) {
+                continue;
+            }
+
+            let origin = BlockPos(world_x, height + structure.y_offset, world_z);
+            structure.place(chunk, origin, deferred);
+            return;
+        }
+    }
+
+    /// Returns the ground of a column of height `height` in `biome`. Shores and the bottom of
+    /// the sea are sand instead, and mountain tops are stone and snow.
+    fn surface_blocks(&self, height: i32, biome: Biome) -> SurfaceConfig {
+        let surface = self.surface(biome);
+        let settings = &self.settings;
+        let (top, filler) = if height < settings.sea_level + settings.beach_height {
+            (BlockType::Sand, BlockType::Sand)
+        } else if height >= settings.snow_height {
+            (BlockType::SnowyStone, BlockType::Stone)
+        } else if height >= settings.rock_height {
+            (BlockType::Stone, BlockType::Stone)
+        } else {
+            return surface;
+        };
+
+        SurfaceConfig {
+            surface: top,
+            filler,
+            ..surface
+        }
+    }
+
+    /// Returns the terrain height of the column at the given block coordinates, kept within
+    /// the chunk so peaks aren't cut off.
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let height = self.height_noise.height_at(x, z).floor() as i32;
+        height.min(CHUNK_HEIGHT as i32 - 1)
+    }
+}
+
+impl TerrainGenerator for NoiseGenerator {
+    fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.biome_map.biome_at(x, z)
+    }
+
+    fn sea_level(&self) -> i32 {
+        self.settings.sea_level
+    }
+
+    fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.height_noise = Self::height_noise(seed, &self.settings);
+        self.biome_map = BiomeMap::new(seed);
+    }
+
+    /// Finds dry land closest to the origin, looking in growing squares around it, or stands
+    /// on the water at the origin if there's none close.
+    fn spawn_point(&self) -> BlockPos {
+        let dry = |x: i32, z: i32| {
+            let height = self.height_at(x, z);
+            (height >= self.settings.sea_level).then_some(BlockPos(x, height + 1, z))
+        };
+        for radius in (0..=SPAWN_SEARCH_RADIUS).step_by(SPAWN_SEARCH_STEP as usize) {
+            for x in (-radius..=radius).step_by(SPAWN_SEARCH_STEP as usize) {
+                for z in (-radius..=radius).step_by(SPAWN_SEARCH_STEP as usize) {
+                    if x.abs() != radius && z.abs() != radius {
+                        continue;
+                    }
+                    if let Some(position) = dry(x, z) {
+                        return position;
+                    }
+                }
+            }
+        }
+        BlockPos(0, self.height_at(0, 0).max(self.settings.sea_level) + 1, 0)
+    }
+
+    fn config(&self) -> GeneratorConfig {
+        GeneratorConfig::Noise(self.settings.clone())
+    }
+
+    fn set_config(&mut self, config: &GeneratorConfig) -> anyhow::Result<()> {
+        let GeneratorConfig::Noise(settings) = config else {
+            anyhow::bail!("expected noise settings, got {} ones", config.name());
+        };
+        settings.validate()?;
+        self.height_noise = Self::height_noise(self.seed, settings);
+        self.settings = settings.clone();
+        Ok(())
+    }
+
+    fn generate(&self, chunk: &mut Chunk) -> Vec<(BlockPos, BlockType)> {
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                let BlockPos(world_x, _, world_z) = chunk.position.block(x, 0, z);
+                let terrain_height = self.height_at(world_x, world_z);
+
+                let ground = self.surface_blocks(terrain_height, chunk.biome(x, z));
+
+                for y in 0..CHUNK_HEIGHT {
+                    let world_y = chunk.position.block(x, y, z).1;
+
+                    let block_type = if world_y == BEDROCK_LEVEL {
+                        BlockType::Bedrock
+                    } else if world_y < BEDROCK_LEVEL {
+                        continue;
+                    } else if world_y == terrain_height {
+                        ground.surface
+                    } else if world_y < terrain_height - ground.filler_depth {
+                        ground.rock
+                    } else if world_y < terrain_height {
+                        ground.filler
+                    } else if world_y < self.settings.sea_level {
+                        BlockType::Water
+                    } else {
+                        continue;
+                    };
+
+                    chunk.set_block(x, y, z, block_type);
+                }
+            }
+        }
+
+        decoration::decorate(chunk, &self.biome_map, self.seed, |x, z| {
+            let height = self.height_at(x, z);
+            let ground = self.surface_blocks(height, self.biome_map.biome_at(x, z));
+            Some((height, ground.surface))
+        });
+
+        let mut deferred = Vec::new();
+        self.place_structures(chunk, &mut deferred);
+        deferred
+    }
+}
+
+/// Generates a flat world of horizontal layers, which is handy for testing building and
+/// physics without terrain getting in the way.
+pub struct FlatGenerator {
+    /// The block type of every layer, starting at the bottom of the world.
+    layers: Vec<BlockType>,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: Vec<BlockType>) -> Self {
+        Self { layers }
+    }
+
+    /// Parses a comma separated list of layers from the bottom up, where each layer is a block
+    /// name optionally preceded by a repeat count, like `stone,3*dirt,grass`. Fails if there
+    /// are more layers than the world is tall.
+    pub fn parse(layers: &str) -> anyhow::Result<Self> {
+        let mut parsed = Vec::new();
+        for layer in layers.split(',').map(str::trim) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => (count.trim().parse::<usize>()?, name.trim()),
+                None => (1, layer),
+            };
+            if count > CHUNK_HEIGHT - parsed.len() {
+                anyhow::bail!("more than {CHUNK_HEIGHT} layers");
+            }
+
+            let Some(block_type) = BlockType::from_name(name) else {
+                anyhow::bail!("unknown block type {name:?}");
+            };
+            parsed.extend(std::iter::repeat(block_type).take(count));
+        }
+
+        Ok(Self::new(parsed))
+    }
+}
+
+impl Default for FlatGenerator {
+    fn default() -> Self {
+        Self::new(vec![
+            BlockType::Bedrock,
+            BlockType::Stone,
+            BlockType::Stone,
+            BlockType::Dirt,
+            BlockType::Dirt,
+            BlockType::Grass,
+        ])
+    }
+}
+
+impl TerrainGenerator for FlatGenerator {
+    fn biome_at(&self, _x: i32, _z: i32) -> Biome {
+        Biome::Plains
+    }
+
+    /// Stands on the top layer at the origin.
+    fn spawn_point(&self) -> BlockPos {
+        BlockPos(0, self.layers.len() as i32, 0)
+    }
+
+    fn config(&self) -> GeneratorConfig {
+        GeneratorConfig::Flat {
+            layers: self
+                .layers
+                .iter()
+                .map(|block_type| block_type.name().to_string())
+                .collect(),
+        }
+    }
+
+    fn set_config(&mut self, config: &GeneratorConfig) -> anyhow::Result<()> {
+        let GeneratorConfig::Flat { layers } = config else {
+            anyhow::bail!("expected flat layers, got {} settings", config.name());
+        };
+        if layers.len() > CHUNK_HEIGHT {
+            anyhow::bail!("more than {CHUNK_HEIGHT} layers");
+        }
+        self.layers = layers
+            .iter()
+            .map(|name| {
+                BlockType::from_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown block type {name:?}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(())
+    }
+
+    fn generate(&self, chunk: &mut Chunk) -> Vec<(BlockPos, BlockType)> {
+        for y in 0..CHUNK_HEIGHT {
+            let world_y = chunk.position.block(0, y, 0).1;
+            let Some(&block_type) = usize::try_from(world_y)
+                .ok()
+                .and_then(|layer| self.layers.get(layer))
+            else {
+                continue;
+            };
+
+            for x in 0..CHUNK_WIDTH {
+                for z in 0..CHUNK_DEPTH {
+                    chunk.set_block(x, y, z, block_type);
+                }
+            }
+        }
+
+        Vec::new()
+    }
+}
@@ -0,0 +1,103 @@
+use rand::Rng;
+
+use crate::decoration;
+use crate::light::MAX_LIGHT;
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many blocks of every loaded chunk are picked at random each simulation step to grow or
+/// spread, unless changed.
+pub const RANDOM_TICK_SPEED: u32 = 3;
+/// The light level plants need above them to grow and grass needs to spread.
+const GROWTH_LIGHT: u8 = 9;
+/// How likely a sapling is to grow into a tree when picked.
+const SAPLING_CHANCE: f64 = 1.0 / 7.0;
+/// How likely wheat is to grow a stage when picked.
+const WHEAT_CHANCE: f64 = 1.0 / 3.0;
+
+/// Returns whether the block at `position` is lit enough for plants to grow in it, by the sky
+/// or by blocks giving off light.
+fn is_lit(world: &World, position: BlockPos) -> bool {
+    world
+        .light_at(position)
+        .is_some_and(|[sky, block]| sky.max(block) * MAX_LIGHT as f32 >= GROWTH_LIGHT as f32)
+}
+
+/// Returns whether the block above `position` lets light and air through to it.
+fn is_uncovered(world: &World, position: BlockPos) -> bool {
+    world
+        .get_block(position.offset(0, 1, 0))
+        .is_some_and(|block| !block.is_opaque())
+}
+
+/// Turns the grass at `position` back into dirt if something opaque covers it, or else, if
+/// it's lit, spreads it to a dirt block nearby, up to three blocks below it or one above it,
+/// that isn't covered either.
+pub fn spread_grass(world: &mut World, position: BlockPos, rng: &mut impl Rng) {
+    if !is_uncovered(world, position) {
+        world.set_block(position, BlockType::Dirt);
+        return;
+    }
+    if !is_lit(world, position.offset(0, 1, 0)) {
+        return;
+    }
+
+    let target = position.offset(
+        rng.gen_range(-1..=1),
+        rng.gen_range(-3..=1),
+        rng.gen_range(-1..=1),
+    );
+    if world.get_block(target) == Some(BlockType::Dirt)
+        && is_uncovered(world, target)
+        && is_lit(world, target.offset(0, 1, 0))
+    {
+        world.set_block(target, BlockType::Grass);
+    }
+}
+
+/// Now and then grows the sapling at `position` into a tree, if it's lit, stands on grass or
+/// dirt and there's room for the trunk above it.
+pub fn grow_sapling(world: &mut World, position: BlockPos, rng: &mut impl Rng) {
+    let ground = world.get_block(position.offset(0, -1, 0));
+    if !matches!(ground, Some(BlockType::Grass | BlockType::Dirt))
+        || !is_lit(world, position)
+        || !rng.gen_bool(SAPLING_CHANCE)
+    {
+        return;
+    }
+
+    let tree = decoration::tree(world.seed(), position);
+    let room = tree
+        .iter()
+        .filter(|&&(at, block_type)| block_type == BlockType::Log && at != position)
+        .all(|&(at, _)| world.get_block(at) == Some(BlockType::Air));
+    if !room {
+        return;
+    }
+    for (at, block_type) in tree {
+        let replaces = world
+            .get_block(at)
+            .is_some_and(|existing| decoration::tree_replaces(block_type, existing));
+        if replaces {
+            world.set_block(at, block_type);
+        }
+    }
+}
+
+/// Now and then grows the wheat at `position` a stage, if it's lit and stands on grass or
+/// dirt, until it's ripe.
+pub fn grow_wheat(world: &mut World, position: BlockPos, rng: &mut impl Rng) {
+    let Some(stage) = world.get_block(position).and_then(|b| b.wheat_stage()) else {
+        return;
+    };
+    let ground = world.get_block(position.offset(0, -1, 0));
+    if stage >= BlockType::RIPE_WHEAT
+        || !matches!(ground, Some(BlockType::Grass | BlockType::Dirt))
+        || !is_lit(world, position)
+        || !rng.gen_bool(WHEAT_CHANCE)
+    {
+        return;
+    }
+    world.set_block(position, BlockType::wheat(stage + 1));
+}
@@ -0,0 +1,119 @@
+use crate::item::{Item, ItemStack, Tool, ToolKind, ToolTier};
+use crate::renderer::block::BlockType;
+
+/// The number of slots in the hotbar.
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// The blocks at hand to place and tools to break blocks with, one of which is selected at a
+/// time.
+#[derive(Debug, Clone)]
+pub struct Hotbar {
+    slots: [Option<ItemStack>; HOTBAR_SLOTS],
+    selected: usize,
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        Self::new(
+            [
+                Item::Tool(Tool::new(ToolKind::Pickaxe, ToolTier::Iron)),
+                Item::Tool(Tool::new(ToolKind::Shovel, ToolTier::Iron)),
+                Item::Tool(Tool::new(ToolKind::Axe, ToolTier::Iron)),
+                Item::Block(BlockType::Cobblestone),
+                Item::Block(BlockType::Chest),
+                Item::Block(BlockType::Torch),
+                Item::Block(BlockType::Sapling),
+                Item::Block(BlockType::DoorNorth),
+                Item::Block(BlockType::Wheat1),
+            ]
+            .map(|item| Some(ItemStack::full(item))),
+        )
+    }
+}
+
+impl Hotbar {
+    /// Creates a hotbar holding `slots`, with the first slot selected.
+    pub fn new(slots: [Option<ItemStack>; HOTBAR_SLOTS]) -> Self {
+        Self { slots, selected: 0 }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>; HOTBAR_SLOTS] {
+        &self.slots
+    }
+
+    /// Puts `stack` in `slot`, or empties it with `None`.
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.slots[slot] = stack;
+    }
+
+    /// Returns the index of the selected slot.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects `slot`, ignoring slots past the end of the hotbar.
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection `steps` slots to the right, or to the left if negative, wrapping
+    /// around at the ends.
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+
+    /// Returns the stack in the selected slot, if any.
+    pub fn selected_stack(&self) -> Option<ItemStack> {
+        self.slots[self.selected]
+    }
+
+    /// Returns the item in the selected slot, if any.
+    pub fn selected_item(&self) -> Option<Item> {
+        self.selected_stack().map(|stack| stack.item)
+    }
+
+    /// Returns the block in the selected slot, which is the one placed, if any.
+    pub fn selected_block(&self) -> Option<BlockType> {
+        self.selected_item().and_then(|item| item.block())
+    }
+
+    /// Returns the tool in the selected slot, which blocks are broken with, if any.
+    pub fn selected_tool(&self) -> Option<Tool> {
+        self.selected_item().and_then(|item| item.tool())
+    }
+
+    /// Takes one item from the selected slot, emptying it after the last one.
+    pub fn take_selected(&mut self) -> Option<Item> {
+        let slot = &mut self.slots[self.selected];
+        let stack = slot.as_mut()?;
+        let item = stack.item;
+        stack.count -= 1;
+        if stack.count == 0 {
+            *slot = None;
+        }
+        Some(item)
+    }
+
+    /// Adds `item` to the first stack of the same item with room for it, or else to the first
+    /// empty slot. Returns whether there was room for it.
+    pub fn add(&mut self, item: Item) -> bool {
+        let stack = self
+            .slots
+            .iter_mut()
+            .flatten()
+            .find(|stack| stack.item == item && stack.count < item.max_stack());
+        if let Some(stack) = stack {
+            stack.count += 1;
+            return true;
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(ItemStack::new(item, 1));
+                true
+            }
+            None => false,
+        }
+    }
+}
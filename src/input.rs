@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+/// Something the player does with a key, bound to keys by [`KeyBindings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Moves up, or jumps while walking.
+    Jump,
+    /// Moves down.
+    Descend,
+    /// Moves faster while held.
+    Sprint,
+    /// Switches between flying and walking, which pressing jump twice does too.
+    ToggleFlying,
+    /// Switches whether the camera stops against solid blocks or moves through them.
+    ToggleCollision,
+    /// Pauses the game, or resumes it while paused.
+    Menu,
+    /// Opens the menu while paused, and closes the game from the menu.
+    Quit,
+    DecreaseRenderDistance,
+    IncreaseRenderDistance,
+    DecreaseBrightness,
+    IncreaseBrightness,
+    /// Narrows the field of view.
+    DecreaseFov,
+    /// Widens the field of view.
+    IncreaseFov,
+    /// Shows or hides the view of the world from above the camera.
+    ToggleOverview,
+    /// Detaches the camera from the player to fly around freely, or snaps it back.
+    ToggleSpectator,
+    /// Teleports the player back to the spawn point.
+    Spawn,
+    /// Switches the world between creative and survival.
+    ToggleGameMode,
+    /// Saves the world, which also happens when the game is closed.
+    Save,
+    /// Selects a slot of the hotbar, from the left.
+    SelectSlot1,
+    SelectSlot2,
+    SelectSlot3,
+    SelectSlot4,
+    SelectSlot5,
+    SelectSlot6,
+    SelectSlot7,
+    SelectSlot8,
+    SelectSlot9,
+}
+
+impl Action {
+    pub const ALL: [Action; 31] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Descend,
+        Action::Sprint,
+        Action::ToggleFlying,
+        Action::ToggleCollision,
+        Action::Menu,
+        Action::Quit,
+        Action::DecreaseRenderDistance,
+        Action::IncreaseRenderDistance,
+        Action::DecreaseBrightness,
+        Action::IncreaseBrightness,
+        Action::DecreaseFov,
+        Action::IncreaseFov,
+        Action::ToggleOverview,
+        Action::ToggleSpectator,
+        Action::Spawn,
+        Action::ToggleGameMode,
+        Action::Save,
+        Action::SelectSlot1,
+        Action::SelectSlot2,
+        Action::SelectSlot3,
+        Action::SelectSlot4,
+        Action::SelectSlot5,
+        Action::SelectSlot6,
+        Action::SelectSlot7,
+        Action::SelectSlot8,
+        Action::SelectSlot9,
+    ];
+
+    /// Returns the index of the hotbar slot the action selects, if it selects one.
+    pub fn slot(self) -> Option<usize> {
+        match self {
+            Action::SelectSlot1 => Some(0),
+            Action::SelectSlot2 => Some(1),
+            Action::SelectSlot3 => Some(2),
+            Action::SelectSlot4 => Some(3),
+            Action::SelectSlot5 => Some(4),
+            Action::SelectSlot6 => Some(5),
+            Action::SelectSlot7 => Some(6),
+            Action::SelectSlot8 => Some(7),
+            Action::SelectSlot9 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Returns the keys the action is bound to unless rebound.
+    fn default_keys(self) -> &'static [KeyCode] {
+        match self {
+            Action::MoveForward => &[KeyCode::KeyW, KeyCode::ArrowUp],
+            Action::MoveBackward => &[KeyCode::KeyS, KeyCode::ArrowDown],
+            Action::MoveLeft => &[KeyCode::KeyA, KeyCode::ArrowLeft],
+            Action::MoveRight => &[KeyCode::KeyD, KeyCode::ArrowRight],
+            Action::Jump => &[KeyCode::Space],
+            Action::Descend => &[KeyCode::ShiftLeft],
+            Action::Sprint => &[KeyCode::ControlLeft],
+            Action::ToggleFlying => &[KeyCode::KeyF],
+            Action::ToggleCollision => &[KeyCode::KeyC],
+            Action::Menu => &[KeyCode::Escape],
+            Action::Quit => &[KeyCode::KeyQ],
+            Action::DecreaseRenderDistance => &[KeyCode::Minus],
+            Action::IncreaseRenderDistance => &[KeyCode::Equal],
+            Action::DecreaseBrightness => &[KeyCode::BracketLeft],
+            Action::IncreaseBrightness => &[KeyCode::BracketRight],
+            Action::DecreaseFov => &[KeyCode::Comma],
+            Action::IncreaseFov => &[KeyCode::Period],
+            Action::ToggleOverview => &[KeyCode::F4],
+            Action::ToggleSpectator => &[KeyCode::F6],
+            Action::Spawn => &[KeyCode::KeyH],
+            Action::ToggleGameMode => &[KeyCode::KeyG],
+            Action::Save => &[KeyCode::F5],
+            Action::SelectSlot1 => &[KeyCode::Digit1],
+            Action::SelectSlot2 => &[KeyCode::Digit2],
+            Action::SelectSlot3 => &[KeyCode::Digit3],
+            Action::SelectSlot4 => &[KeyCode::Digit4],
+            Action::SelectSlot5 => &[KeyCode::Digit5],
+            Action::SelectSlot6 => &[KeyCode::Digit6],
+            Action::SelectSlot7 => &[KeyCode::Digit7],
+            Action::SelectSlot8 => &[KeyCode::Digit8],
+            Action::SelectSlot9 => &[KeyCode::Digit9],
+        }
+    }
+}
+
+/// Which keys trigger which actions, usually rebound by the `keybinds` of the settings, see
+/// [`crate::settings::Settings::keybinds`].
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    actions: HashMap<KeyCode, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new())
+    }
+}
+
+impl KeyBindings {
+    /// Binds the actions in `bindings` to their keys, and the other actions to their default
+    /// keys.
+    pub fn new(bindings: &BTreeMap<Action, Vec<KeyCode>>) -> Self {
+        let mut actions = HashMap::new();
+        for action in Action::ALL {
+            let keys = match bindings.get(&action) {
+                Some(keys) => keys.as_slice(),
+                None => action.default_keys(),
+            };
+            for &key in keys {
+                if let Some(other) = actions.insert(key, action) {
+                    eprintln!(
+                        "{key:?} is bound to both {other:?} and {action:?}, using {action:?}"
+                    );
+                }
+            }
+        }
+        Self { actions }
+    }
+
+    /// Returns the action `key` is bound to, if any.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+
+    /// Returns the keys bound to `action`, ordered by name.
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        let mut keys: Vec<KeyCode> = self
+            .actions
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort_by_key(|key| format!("{key:?}"));
+        keys
+    }
+}
@@ -0,0 +1,210 @@
+use std::io::{self, Read, Write};
+
+use crate::renderer::block::BlockType;
+use crate::save::read_u32;
+
+/// How long breaking a block of hardness 1 takes by hand, in seconds.
+const SECONDS_PER_HARDNESS: f32 = 1.5;
+/// How much longer breaking a block takes without the tool it needs, like stone by hand.
+const WRONG_TOOL_PENALTY: f32 = 10.0 / 3.0;
+/// The most blocks of the same type a slot holds.
+pub const MAX_STACK: u32 = 64;
+
+/// The kinds of tools, each breaking some blocks faster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolKind {
+    /// Breaks stone.
+    Pickaxe,
+    /// Digs dirt, sand and gravel.
+    Shovel,
+    /// Chops wood.
+    Axe,
+}
+
+impl ToolKind {
+    pub const ALL: [ToolKind; 3] = [ToolKind::Pickaxe, ToolKind::Shovel, ToolKind::Axe];
+}
+
+/// What a tool is made of. Better materials break blocks faster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ToolTier {
+    Wood,
+    Stone,
+    Iron,
+    Diamond,
+}
+
+impl ToolTier {
+    pub const ALL: [ToolTier; 4] = [
+        ToolTier::Wood,
+        ToolTier::Stone,
+        ToolTier::Iron,
+        ToolTier::Diamond,
+    ];
+
+    /// Returns how many times faster than by hand a tool of the tier breaks the blocks it's
+    /// made for.
+    pub fn speed(&self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Iron => 6.0,
+            ToolTier::Diamond => 8.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToolTier::Wood => "wooden",
+            ToolTier::Stone => "stone",
+            ToolTier::Iron => "iron",
+            ToolTier::Diamond => "diamond",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tool {
+    pub kind: ToolKind,
+    pub tier: ToolTier,
+}
+
+impl Tool {
+    pub fn new(kind: ToolKind, tier: ToolTier) -> Self {
+        Self { kind, tier }
+    }
+
+    pub fn name(&self) -> String {
+        let kind = match self.kind {
+            ToolKind::Pickaxe => "pickaxe",
+            ToolKind::Shovel => "shovel",
+            ToolKind::Axe => "axe",
+        };
+        format!("{}_{kind}", self.tier.name())
+    }
+}
+
+/// Something held in a slot of the hotbar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Item {
+    /// A block to place.
+    Block(BlockType),
+    /// A tool to break blocks with.
+    Tool(Tool),
+}
+
+impl Item {
+    pub fn name(&self) -> String {
+        match self {
+            Item::Block(block) => block.name().to_string(),
+            Item::Tool(tool) => tool.name(),
+        }
+    }
+
+    pub fn block(&self) -> Option<BlockType> {
+        match self {
+            Item::Block(block) => Some(*block),
+            Item::Tool(_) => None,
+        }
+    }
+
+    pub fn tool(&self) -> Option<Tool> {
+        match self {
+            Item::Block(_) => None,
+            Item::Tool(tool) => Some(*tool),
+        }
+    }
+
+    /// Returns the most items of the kind a slot holds. Tools don't stack.
+    pub fn max_stack(&self) -> u32 {
+        match self {
+            Item::Block(_) => MAX_STACK,
+            Item::Tool(_) => 1,
+        }
+    }
+}
+
+/// Items of the same kind held in a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: Item, count: u32) -> Self {
+        Self { item, count }
+    }
+
+    /// Returns a stack of as many of `item` as a slot holds.
+    pub fn full(item: Item) -> Self {
+        Self::new(item, item.max_stack())
+    }
+
+    /// Returns how many items are shown with their name, like `64 cobblestone`.
+    pub fn name(&self) -> String {
+        format!("{} {}", self.count, self.item.name())
+    }
+
+    /// Writes what a slot holds, `stack` or nothing.
+    pub fn write_slot(stack: Option<ItemStack>, writer: &mut impl Write) -> io::Result<()> {
+        let Some(stack) = stack else {
+            return writer.write_all(&[0]);
+        };
+        match stack.item {
+            Item::Block(block) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&(block as u32).to_le_bytes())?;
+            }
+            Item::Tool(tool) => writer.write_all(&[2, tool.kind as u8, tool.tier as u8])?,
+        }
+        writer.write_all(&stack.count.to_le_bytes())
+    }
+
+    /// Reads what a slot holds, previously written with [`ItemStack::write_slot`].
+    pub fn read_slot(reader: &mut impl Read) -> io::Result<Option<ItemStack>> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut kind = [0];
+        reader.read_exact(&mut kind)?;
+        let item = match kind[0] {
+            0 => return Ok(None),
+            1 => {
+                let block = BlockType::from_id(read_u32(reader)?)
+                    .ok_or_else(|| invalid("unknown block type"))?;
+                Item::Block(block)
+            }
+            2 => {
+                let mut tool = [0; 2];
+                reader.read_exact(&mut tool)?;
+                let kind = ToolKind::ALL
+                    .into_iter()
+                    .find(|kind| *kind as u8 == tool[0])
+                    .ok_or_else(|| invalid("unknown tool kind"))?;
+                let tier = ToolTier::ALL
+                    .into_iter()
+                    .find(|tier| *tier as u8 == tool[1])
+                    .ok_or_else(|| invalid("unknown tool tier"))?;
+                Item::Tool(Tool::new(kind, tier))
+            }
+            _ => return Err(invalid("unknown item")),
+        };
+        Ok(Some(ItemStack::new(item, read_u32(reader)?)))
+    }
+}
+
+/// Returns how long breaking `block` takes holding `tool`, or by hand if `None`, in seconds,
+/// or `None` if it can't be broken. The tool the block is made for speeds it up by its tier,
+/// and blocks needing a tool take much longer without it.
+pub fn break_time(block: BlockType, tool: Option<Tool>) -> Option<f32> {
+    let hardness = block.hardness()?;
+    let time = hardness * SECONDS_PER_HARDNESS;
+    let Some(kind) = block.tool() else {
+        return Some(time);
+    };
+    Some(match tool {
+        Some(tool) if tool.kind == kind => time / tool.tier.speed(),
+        _ if block.needs_tool() => time * WRONG_TOOL_PENALTY,
+        _ => time,
+    })
+}
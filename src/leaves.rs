@@ -0,0 +1,86 @@
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+
+use rand::Rng;
+
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many simulation steps leaves wait before decaying once a block next to them changed,
+/// picked at random so a canopy falls apart bit by bit.
+pub const LEAF_DECAY_DELAY: Range<u64> = 10..60;
+/// How many leaves away from a log leaves stay up.
+const LEAF_DECAY_RADIUS: u32 = 4;
+/// How likely decaying leaves are to drop a sapling.
+const SAPLING_DROP_CHANCE: f64 = 1.0 / 20.0;
+/// How far below decaying leaves a dropped sapling looks for ground to land on, in blocks.
+const SAPLING_DROP_HEIGHT: i32 = 16;
+
+/// The offsets of the blocks sharing a face with a block.
+const NEIGHBORS: [(i32, i32, i32); 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+/// Returns whether the leaves at `position` are held up by a log, through leaves at most
+/// [`LEAF_DECAY_RADIUS`] blocks away, or `None` if the search reached a chunk that isn't
+/// loaded, where a log may be.
+fn is_held_up(world: &World, position: BlockPos) -> Option<bool> {
+    let mut visited = HashSet::from([position]);
+    let mut queue = VecDeque::from([(position, 0)]);
+    while let Some((leaves, distance)) = queue.pop_front() {
+        for (x, y, z) in NEIGHBORS {
+            let neighbor = leaves.offset(x, y, z);
+            match world.get_block(neighbor)? {
+                BlockType::Log => return Some(true),
+                BlockType::Leaves if distance + 1 < LEAF_DECAY_RADIUS => {
+                    if visited.insert(neighbor) {
+                        queue.push_back((neighbor, distance + 1));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(false)
+}
+
+/// Drops a sapling from `position`, which lands on the first block below that isn't air or
+/// leaves, taking root if that's grass or dirt.
+fn drop_sapling(world: &mut World, position: BlockPos) {
+    for depth in 1..=SAPLING_DROP_HEIGHT {
+        let below = position.offset(0, -depth, 0);
+        match world.get_block(below) {
+            Some(BlockType::Air | BlockType::Leaves) => continue,
+            Some(BlockType::Grass | BlockType::Dirt) => {
+                let landing = below.offset(0, 1, 0);
+                if world.get_block(landing) == Some(BlockType::Air) {
+                    world.set_block(landing, BlockType::Sapling);
+                }
+                return;
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Decays the leaves at `position` into air if no log holds them up, now and then dropping a
+/// sapling. Every leaf decaying updates the leaves next to it, so a canopy without a trunk
+/// decays leaf by leaf.
+pub fn update_leaves(world: &mut World, position: BlockPos, rng: &mut impl Rng) {
+    if world.get_block(position) != Some(BlockType::Leaves) {
+        return;
+    }
+    if is_held_up(world, position) != Some(false) {
+        return;
+    }
+    world.set_block(position, BlockType::Air);
+    if rng.gen_bool(SAPLING_DROP_CHANCE) {
+        drop_sapling(world, position);
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::chunk::{
+    block_index, BlockArray, Chunk, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_VOLUME, CHUNK_WIDTH,
+};
+use crate::position::{BlockPos, ChunkPos};
+use crate::renderer::block::{BlockType, Face};
+
+/// The brightest light level, found under the open sky and right next to bright light sources.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The two kinds of light, which spread the same way but are shaded separately so the sky can
+/// darken at night while torches stay bright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light from the sky, which falls straight down without getting dimmer.
+    Sky,
+    /// Light given off by blocks.
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    fn shift(&self) -> u8 {
+        match self {
+            LightChannel::Sky => 4,
+            LightChannel::Block => 0,
+        }
+    }
+}
+
+/// The sky and block light level of every block of a chunk, indexed with [`block_index`]. Each
+/// block takes one byte, with the sky light in the upper four bits.
+#[derive(Debug, Clone)]
+pub struct LightMap {
+    levels: Box<[u8]>,
+}
+
+impl LightMap {
+    /// Creates a light map where every block is dark.
+    pub fn new() -> Self {
+        Self {
+            levels: vec![0; CHUNK_VOLUME].into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&self, index: usize, channel: LightChannel) -> u8 {
+        unpack(self.levels[index], channel)
+    }
+
+    pub fn set(&mut self, index: usize, channel: LightChannel, level: u8) {
+        let shift = channel.shift();
+        let level = level.min(MAX_LIGHT) << shift;
+        self.levels[index] = (self.levels[index] & !(0xF << shift)) | level;
+    }
+
+    /// Returns both light levels of the block at `index`, packed the same way as in the map.
+    pub fn packed(&self, index: usize) -> u8 {
+        self.levels[index]
+    }
+
+    /// Returns the packed light levels of the layer bordering `face`, indexed by
+    /// `v * width + u` along the axes of the face like [`crate::mesher::ChunkBorder`].
+    pub fn border(&self, face: Face) -> Vec<u8> {
+        let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+        let (axis, u_axis, v_axis) = face.axes();
+        let layer = if face.normal()[axis] < 0 {
+            0
+        } else {
+            dimensions[axis] - 1
+        };
+
+        let mut levels = Vec::with_capacity(dimensions[u_axis] * dimensions[v_axis]);
+        for v in 0..dimensions[v_axis] {
+            for u in 0..dimensions[u_axis] {
+                let mut position = [0; 3];
+                position[axis] = layer;
+                position[u_axis] = u;
+                position[v_axis] = v;
+
+                let [x, y, z] = position;
+                levels.push(self.levels[block_index(x, y, z)]);
+            }
+        }
+
+        levels
+    }
+}
+
+/// Returns the level of `channel` from light levels packed like in a [`LightMap`].
+pub fn unpack(packed: u8, channel: LightChannel) -> u8 {
+    (packed >> channel.shift()) & 0xF
+}
+
+/// Blocks and light levels that light spreads through.
+trait LightVolume {
+    /// Returns the block at `position`, or `None` if it lies outside of the volume.
+    fn block(&self, position: BlockPos) -> Option<BlockType>;
+    fn light(&self, position: BlockPos, channel: LightChannel) -> u8;
+    fn set_light(&mut self, position: BlockPos, channel: LightChannel, level: u8);
+}
+
+/// A single chunk on its own, addressed in local coordinates.
+struct ChunkVolume<'a> {
+    blocks: &'a BlockArray,
+    light: &'a mut LightMap,
+}
+
+impl ChunkVolume<'_> {
+    fn index(position: BlockPos) -> Option<usize> {
+        let BlockPos(x, y, z) = position;
+        let inside = |v: i32, size: usize| (0..size as i32).contains(&v);
+        (inside(x, CHUNK_WIDTH) && inside(y, CHUNK_HEIGHT) && inside(z, CHUNK_DEPTH))
+            .then(|| block_index(x as usize, y as usize, z as usize))
+    }
+}
+
+impl LightVolume for ChunkVolume<'_> {
+    fn block(&self, position: BlockPos) -> Option<BlockType> {
+        Self::index(position).map(|index| self.blocks[index])
+    }
+
+    fn light(&self, position: BlockPos, channel: LightChannel) -> u8 {
+        Self::index(position).map_or(0, |index| self.light.get(index, channel))
+    }
+
+    fn set_light(&mut self, position: BlockPos, channel: LightChannel, level: u8) {
+        if let Some(index) = Self::index(position) {
+            self.light.set(index, channel, level);
+        }
+    }
+}
+
+/// Every loaded chunk, addressed in world coordinates. Keeps track of the chunks whose light
+/// changed so they can be remeshed.
+struct WorldVolume<'a> {
+    chunks: &'a mut HashMap<ChunkPos, Chunk>,
+    changed: HashSet<ChunkPos>,
+}
+
+impl LightVolume for WorldVolume<'_> {
+    fn block(&self, position: BlockPos) -> Option<BlockType> {
+        let (x, y, z) = position.local();
+        self.chunks
+            .get(&position.chunk())
+            .map(|chunk| chunk.block(x, y, z))
+    }
+
+    fn light(&self, position: BlockPos, channel: LightChannel) -> u8 {
+        let (x, y, z) = position.local();
+        self.chunks
+            .get(&position.chunk())
+            .map_or(0, |chunk| chunk.light().get(block_index(x, y, z), channel))
+    }
+
+    fn set_light(&mut self, position: BlockPos, channel: LightChannel, level: u8) {
+        let (x, y, z) = position.local();
+        if let Some(chunk) = self.chunks.get_mut(&position.chunk()) {
+            chunk.light_mut().set(block_index(x, y, z), channel, level);
+            self.changed.insert(position.chunk());
+        }
+    }
+}
+
+/// Returns the light level reaching a neighboring `block_type` from a block lit at `level`.
+/// Light gets dimmer with every block it passes, except for full sky light falling straight
+/// down through clear blocks.
+fn spread(channel: LightChannel, level: u8, face: Face, block_type: BlockType) -> u8 {
+    let filter = block_type.light_filter();
+    if channel == LightChannel::Sky && face == Face::Bottom && level == MAX_LIGHT {
+        level.saturating_sub(filter)
+    } else {
+        level.saturating_sub(1 + filter)
+    }
+}
+
+fn neighbor(position: BlockPos, face: Face) -> BlockPos {
+    let [x, y, z] = face.normal();
+    position.offset(x as i32, y as i32, z as i32)
+}
+
+/// Spreads light outward from every position in `queue` until it fades out or is stopped by
+/// opaque blocks.
+fn propagate(volume: &mut impl LightVolume, mut queue: VecDeque<(BlockPos, LightChannel)>) {
+    while let Some((position, channel)) = queue.pop_front() {
+        let level = volume.light(position, channel);
+        if level <= 1 {
+            continue;
+        }
+
+        for face in Face::ALL {
+            let next = neighbor(position, face);
+            let Some(block_type) = volume.block(next) else {
+                continue;
+            };
+            if block_type.is_opaque() {
+                continue;
+            }
+
+            let next_level = spread(channel, level, face, block_type);
+            if next_level > volume.light(next, channel) {
+                volume.set_light(next, channel, next_level);
+                queue.push_back((next, channel));
+            }
+        }
+    }
+}
+
+/// Lights a chunk on its own, with sky light falling in from the top as if nothing was above
+/// it. Light from neighboring chunks is added by [`stitch`] once the chunk is loaded.
+pub fn compute(blocks: &BlockArray) -> LightMap {
+    let mut light = LightMap::new();
+    let mut volume = ChunkVolume {
+        blocks,
+        light: &mut light,
+    };
+    let mut queue = VecDeque::new();
+
+    let top = CHUNK_HEIGHT as i32 - 1;
+    for x in 0..CHUNK_WIDTH as i32 {
+        for z in 0..CHUNK_DEPTH as i32 {
+            let position = BlockPos(x, top, z);
+            let block_type = blocks[block_index(x as usize, top as usize, z as usize)];
+            if !block_type.is_opaque() {
+                let level = MAX_LIGHT.saturating_sub(block_type.light_filter());
+                volume.set_light(position, LightChannel::Sky, level);
+                queue.push_back((position, LightChannel::Sky));
+            }
+        }
+    }
+
+    for x in 0..CHUNK_WIDTH {
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_DEPTH {
+                let emission = blocks[block_index(x, y, z)].light_emission();
+                if emission > 0 {
+                    let position = BlockPos(x as i32, y as i32, z as i32);
+                    volume.set_light(position, LightChannel::Block, emission);
+                    queue.push_back((position, LightChannel::Block));
+                }
+            }
+        }
+    }
+
+    propagate(&mut volume, queue);
+    light
+}
+
+/// Lets light flow between the chunk at `position` and its loaded neighbors, in both
+/// directions. Returns the chunks whose light changed.
+pub fn stitch(chunks: &mut HashMap<ChunkPos, Chunk>, position: ChunkPos) -> HashSet<ChunkPos> {
+    let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+    let origin = position.origin();
+    let mut queue = VecDeque::new();
+
+    for face in Face::ALL {
+        if !chunks.contains_key(&position.neighbor(face)) {
+            continue;
+        }
+
+        let (axis, u_axis, v_axis) = face.axes();
+        let layer = if face.normal()[axis] < 0 {
+            0
+        } else {
+            dimensions[axis] - 1
+        };
+
+        for v in 0..dimensions[v_axis] {
+            for u in 0..dimensions[u_axis] {
+                let mut local = [0; 3];
+                local[axis] = layer;
+                local[u_axis] = u;
+                local[v_axis] = v;
+
+                let [x, y, z] = local;
+                let inner = origin + BlockPos(x as i32, y as i32, z as i32);
+                for channel in LightChannel::ALL {
+                    queue.push_back((inner, channel));
+                    queue.push_back((neighbor(inner, face), channel));
+                }
+            }
+        }
+    }
+
+    let mut volume = WorldVolume {
+        chunks,
+        changed: HashSet::new(),
+    };
+    propagate(&mut volume, queue);
+    volume.changed
+}
+
+/// Updates the light around `position` after the block there changed, removing the light it
+/// now blocks or no longer emits and letting light flow into it. Returns the chunks whose light
+/// changed.
+pub fn relight(chunks: &mut HashMap<ChunkPos, Chunk>, position: BlockPos) -> HashSet<ChunkPos> {
+    let mut volume = WorldVolume {
+        chunks,
+        changed: HashSet::new(),
+    };
+    let Some(block_type) = volume.block(position) else {
+        return volume.changed;
+    };
+
+    // Darken everything that was lit through the block, remembering the brighter blocks
+    // bordering the darkened area so their light can flow back in.
+    let mut removal = VecDeque::new();
+    let mut refill = VecDeque::new();
+    let mut emitters = Vec::new();
+    for channel in LightChannel::ALL {
+        let level = volume.light(position, channel);
+        if level > 0 {
+            volume.set_light(position, channel, 0);
+            removal.push_back((position, channel, level));
+        }
+    }
+
+    while let Some((removed, channel, level)) = removal.pop_front() {
+        for face in Face::ALL {
+            let next = neighbor(removed, face);
+            if volume.block(next).is_none() {
+                continue;
+            }
+
+            let next_level = volume.light(next, channel);
+            if next_level == 0 {
+                continue;
+            }
+
+            let lit_through = next_level < level
+                || (channel == LightChannel::Sky
+                    && face == Face::Bottom
+                    && level == MAX_LIGHT
+                    && next_level == MAX_LIGHT);
+            if lit_through {
+                volume.set_light(next, channel, 0);
+                removal.push_back((next, channel, next_level));
+                if channel == LightChannel::Block {
+                    emitters.push(next);
+                }
+            } else {
+                refill.push_back((next, channel));
+            }
+        }
+    }
+
+    // Light sources that were darkened, and the changed block itself, shine again.
+    emitters.push(position);
+    for emitter in emitters {
+        let emission = volume.block(emitter).map_or(0, |b| b.light_emission());
+        if emission > 0 {
+            volume.set_light(emitter, LightChannel::Block, emission);
+            refill.push_back((emitter, LightChannel::Block));
+        }
+    }
+
+    if !block_type.is_opaque() {
+        // Nothing above a loaded chunk counts as open sky, like when chunks are first lit.
+        if volume.block(neighbor(position, Face::Top)).is_none() {
+            let level = MAX_LIGHT.saturating_sub(block_type.light_filter());
+            volume.set_light(position, LightChannel::Sky, level);
+            refill.push_back((position, LightChannel::Sky));
+        }
+
+        for face in Face::ALL {
+            for channel in LightChannel::ALL {
+                refill.push_back((neighbor(position, face), channel));
+            }
+        }
+    }
+
+    propagate(&mut volume, refill);
+    volume.changed
+}
@@ -1,19 +1,255 @@
 #![allow(warnings)]
 use game::Game;
+use generator::{FlatGenerator, TerrainGenerator};
+use renderer::renderer::RendererSettings;
+use settings::Settings;
 use winit::{event_loop::EventLoop, window::Window};
 
+mod aabb;
+mod biome;
+mod block_entity;
 mod camera;
 mod chunk;
+mod collision;
+mod container;
+mod decoration;
+mod door;
+mod entities;
+mod falling;
+mod flight;
+mod fluid;
 mod game;
+mod generator;
+mod growth;
+mod hotbar;
+mod input;
+mod item;
+mod leaves;
+mod light;
+mod mesher;
 mod noise;
+mod palette;
+mod position;
+mod raycast;
 mod renderer;
+mod save;
+mod scheduler;
+mod settings;
+mod stats;
+mod structure;
+mod time;
+mod torch;
+mod visibility;
+mod watch;
+mod world;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let generator = match generator_from_args(&args) {
+        Ok(generator) => generator,
+        Err(err) => {
+            eprintln!("Invalid flat world layers: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(radius) = value_from_args(&args, "--pregen").transpose() {
+        let Some(radius) = radius.ok().and_then(|radius| radius.parse().ok()) else {
+            eprintln!("Usage: craft --pregen <radius in chunks>");
+            std::process::exit(1);
+        };
+
+        if let Err(err) = game::pregenerate(radius, generator) {
+            eprintln!("Failed to pre-generate the world: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--list-adapters") {
+        for info in renderer::renderer::list_adapters(wgpu::Backends::all()) {
+            println!("{} ({:?}, {:?})", info.name, info.backend, info.device_type);
+        }
+        return;
+    }
+
     let event_loop = EventLoop::new().unwrap();
     let window = Window::new(&event_loop).unwrap();
-    let renderer = renderer::renderer::Renderer::new(&window).await;
+    let settings = game::load_settings();
+    let renderer_settings = match renderer_settings_from_args(&args, &settings) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Invalid renderer settings: {err}");
+            std::process::exit(1);
+        }
+    };
+    let renderer = match renderer::renderer::Renderer::new(&window, renderer_settings).await {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            eprintln!("Failed to create the renderer: {err:?}");
+            std::process::exit(1);
+        }
+    };
+
+    let fov = match fov_from_args(&args) {
+        Ok(fov) => fov,
+        Err(err) => {
+            eprintln!("Invalid field of view: {err}");
+            std::process::exit(1);
+        }
+    };
 
-    let mut game = Game::new(&window, renderer);
+    // `--replay` flies the camera along a recorded flight and prints how long frames took
+    // before closing the game, and `--record` records the flight of the camera to a file when
+    // the game closes.
+    let replay = match value_from_args(&args, "--replay") {
+        Ok(Some(file)) => match flight::CameraPath::load(file) {
+            Ok(flight) => Some(flight),
+            Err(err) => {
+                eprintln!("Failed to load the flight {file}: {err}");
+                std::process::exit(1);
+            }
+        },
+        Ok(None) => None,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let record = match value_from_args(&args, "--record") {
+        Ok(file) => file.cloned(),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let random_tick_speed = match random_tick_speed_from_args(&args) {
+        Ok(speed) => speed,
+        Err(err) => {
+            eprintln!("Invalid random tick speed: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut game = match Game::new(&window, renderer, generator, settings) {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("Failed to open the world: {err}");
+            std::process::exit(1);
+        }
+    };
+    if !args.iter().any(|arg| arg == "--flat") {
+        game.watch_terrain_settings();
+    }
+    if let Some(fov) = fov {
+        game.set_fov(cgmath::Deg(fov));
+    }
+    if let Some(speed) = random_tick_speed {
+        game.set_random_tick_speed(speed);
+    }
+    if let Some(file) = record {
+        game.record_flight(file);
+    }
+    if let Some(flight) = replay {
+        game.replay_flight(flight);
+    }
     game.run(event_loop).await;
 }
+
+/// Picks the renderer settings, waiting for vsync as picked in `game_settings`. `--msaa` sets
+/// the number of samples per pixel, like `--msaa 4`, where `--msaa 1` turns multisampling off.
+/// `--present-mode` picks one of `fifo`, `mailbox` or `immediate`. `--ssao` turns on screen
+/// space ambient occlusion, `--occlusion-culling` culls chunks hidden behind terrain, and
+/// `--gpu-timings` shows how long each pass takes on the GPU in the window title. `--backend`
+/// picks one of `vulkan`, `dx12`, `metal`, `gl` or `all`, `--power` prefers a `low` power or
+/// `high` performance adapter, and `--adapter` picks the adapter with the given name, as listed
+/// by `--list-adapters`. The `WGPU_BACKEND`, `WGPU_POWER_PREF` and `WGPU_ADAPTER_NAME`
+/// environment variables pick them too, unless overridden by the arguments.
+fn renderer_settings_from_args(
+    args: &[String],
+    game_settings: &Settings,
+) -> anyhow::Result<RendererSettings> {
+    let mut settings = RendererSettings {
+        present_mode: game_settings.present_mode(),
+        ..RendererSettings::default()
+    };
+    if let Some(sample_count) = value_from_args(args, "--msaa")? {
+        let sample_count: u32 = sample_count.parse()?;
+        if !matches!(sample_count, 1 | 2 | 4 | 8 | 16) {
+            anyhow::bail!("expected 1, 2, 4, 8 or 16 samples, got {sample_count}");
+        }
+        settings.sample_count = sample_count;
+    }
+    if let Some(present_mode) = value_from_args(args, "--present-mode")? {
+        settings.present_mode = renderer::renderer::parse_present_mode(present_mode)
+            .ok_or_else(|| anyhow::anyhow!("unknown present mode {present_mode}"))?;
+    }
+    settings.ssao = args.iter().any(|arg| arg == "--ssao");
+    settings.occlusion_culling = args.iter().any(|arg| arg == "--occlusion-culling");
+    settings.gpu_timings = args.iter().any(|arg| arg == "--gpu-timings");
+
+    if let Some(backends) = wgpu::util::backend_bits_from_env() {
+        settings.backends = backends;
+    }
+    if let Some(backend) = value_from_args(args, "--backend")? {
+        settings.backends = renderer::renderer::parse_backend(backend)
+            .ok_or_else(|| anyhow::anyhow!("unknown backend {backend}"))?;
+    }
+    if let Some(power_preference) = wgpu::util::power_preference_from_env() {
+        settings.power_preference = power_preference;
+    }
+    if let Some(power) = value_from_args(args, "--power")? {
+        settings.power_preference = renderer::renderer::parse_power_preference(power)
+            .ok_or_else(|| anyhow::anyhow!("expected low or high power, got {power}"))?;
+    }
+    settings.adapter = value_from_args(args, "--adapter")?
+        .cloned()
+        .or_else(|| std::env::var("WGPU_ADAPTER_NAME").ok());
+    Ok(settings)
+}
+
+/// Returns the value following the argument `name`, like the file in `--record flight.json`,
+/// or `None` if the argument isn't given.
+fn value_from_args<'a>(args: &'a [String], name: &str) -> anyhow::Result<Option<&'a String>> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+
+    args.get(index + 1)
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("{name} needs a value"))
+}
+
+/// Picks the vertical field of view from `--fov`, in degrees, like `--fov 90`.
+fn fov_from_args(args: &[String]) -> anyhow::Result<Option<f32>> {
+    value_from_args(args, "--fov")?
+        .map(|fov| Ok(fov.parse()?))
+        .transpose()
+}
+
+/// Picks how many blocks of every loaded chunk grow or spread each simulation step from
+/// `--random-tick-speed`, like `--random-tick-speed 10`, where 0 stops them.
+fn random_tick_speed_from_args(args: &[String]) -> anyhow::Result<Option<u32>> {
+    value_from_args(args, "--random-tick-speed")?
+        .map(|speed| Ok(speed.parse()?))
+        .transpose()
+}
+
+/// Picks the world generator. `--flat` creates a flat world, optionally followed by its layers
+/// from the bottom up, like `--flat stone,3*dirt,grass`.
+fn generator_from_args(args: &[String]) -> anyhow::Result<Box<dyn TerrainGenerator>> {
+    let Some(index) = args.iter().position(|arg| arg == "--flat") else {
+        return Ok(game::default_generator());
+    };
+
+    match args
+        .get(index + 1)
+        .filter(|layers| !layers.starts_with("--"))
+    {
+        Some(layers) => Ok(Box::new(FlatGenerator::parse(layers)?)),
+        None => Ok(Box::new(FlatGenerator::default())),
+    }
+}
@@ -0,0 +1,551 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use rayon::prelude::*;
+
+use crate::chunk::{block_index, BlockArray, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::light::{self, LightChannel, LightMap, MAX_LIGHT};
+use crate::position::ChunkPos;
+use crate::renderer::block::{
+    BlockQuad, BlockShape, BlockType, Face, FaceShading, MeshPass, TerrainMesh,
+};
+use crate::visibility::ChunkVisibility;
+
+/// A copy of the blocks of a chunk at a given revision, which can be meshed off the main thread
+/// while the chunk itself keeps changing.
+pub struct ChunkSnapshot {
+    pub position: ChunkPos,
+    /// The revision of the chunk the snapshot was taken at.
+    pub revision: u64,
+    pub blocks: BlockArray,
+    pub light: LightMap,
+    /// The borders of the neighboring chunks facing this one, indexed by [`Face`]. Faces
+    /// toward neighbors that are not loaded are always rendered.
+    pub neighbors: [Option<ChunkBorder>; 6],
+    /// The packed light levels of the same borders, laid out like [`LightMap::border`].
+    pub neighbor_light: [Option<Vec<u8>>; 6],
+}
+
+/// Where a block near a chunk is stored.
+enum Location {
+    /// Inside the chunk, at the given block index.
+    Inside(usize),
+    /// In the border of the neighbor toward the face, at the given `v * width + u` index.
+    Border(Face, usize),
+}
+
+impl ChunkSnapshot {
+    /// Finds the block at the given local coordinates, which may lie in the border of a
+    /// neighboring chunk. Returns `None` if the coordinates lie outside along more than one
+    /// axis, since only the borders of the chunks sharing a face are known.
+    fn locate(x: isize, y: isize, z: isize) -> Option<Location> {
+        let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+        let position = [x, y, z];
+
+        let mut outside = Face::ALL.into_iter().filter(|face| {
+            let (axis, _, _) = face.axes();
+            match face.normal()[axis] {
+                -1 => position[axis] < 0,
+                _ => position[axis] >= dimensions[axis] as isize,
+            }
+        });
+
+        let Some(face) = outside.next() else {
+            let index = block_index(x as usize, y as usize, z as usize);
+            return Some(Location::Inside(index));
+        };
+        if outside.next().is_some() {
+            return None;
+        }
+
+        let (_, u_axis, v_axis) = face.axes();
+        let index = position[v_axis] as usize * dimensions[u_axis] + position[u_axis] as usize;
+        Some(Location::Border(face, index))
+    }
+
+    /// Returns the block at the given local coordinates, looking into the neighboring chunk if
+    /// they lie outside this one. Returns `None` if that chunk is not loaded, or if the block
+    /// is not known, see [`ChunkSnapshot::locate`].
+    fn block_at(&self, x: isize, y: isize, z: isize) -> Option<BlockType> {
+        match Self::locate(x, y, z)? {
+            Location::Inside(index) => Some(self.blocks[index]),
+            Location::Border(face, index) => {
+                Some(self.neighbors[face as usize].as_ref()?.get(index))
+            }
+        }
+    }
+
+    /// Returns the packed light levels at the given local coordinates, like
+    /// [`ChunkSnapshot::block_at`].
+    fn light_at(&self, x: isize, y: isize, z: isize) -> Option<u8> {
+        match Self::locate(x, y, z)? {
+            Location::Inside(index) => Some(self.light.packed(index)),
+            Location::Border(face, index) => {
+                Some(self.neighbor_light[face as usize].as_ref()?[index])
+            }
+        }
+    }
+
+    /// Returns whether `face` of `block_type` toward the block at the given local coordinates
+    /// is visible. Faces toward chunks that are not loaded are.
+    fn should_render_face(
+        &self,
+        block_type: BlockType,
+        face: Face,
+        x: isize,
+        y: isize,
+        z: isize,
+    ) -> bool {
+        self.block_at(x, y, z)
+            .map_or(true, |neighbor| block_type.is_face_visible(face, neighbor))
+    }
+
+    /// Returns the shading of `face` of the block at the given local coordinates. Each corner is
+    /// occluded by the opaque blocks among the three touching it, and a corner between two
+    /// opaque blocks is fully occluded whatever is in the corner itself. The light at each
+    /// corner is the average of the clear ones and the block in front, so light fades smoothly
+    /// across faces. Faces toward chunks that are not loaded are lit by the full sky.
+    fn face_shading(&self, face: Face, x: usize, y: usize, z: usize) -> FaceShading {
+        let (_, u_axis, v_axis) = face.axes();
+        let normal = face.normal();
+        let front = [
+            x as isize + normal[0],
+            y as isize + normal[1],
+            z as isize + normal[2],
+        ];
+
+        let offset = |u: isize, v: isize| {
+            let mut position = front;
+            position[u_axis] += u;
+            position[v_axis] += v;
+            position
+        };
+        let is_opaque = |u: isize, v: isize| {
+            let [x, y, z] = offset(u, v);
+            self.block_at(x, y, z)
+                .is_some_and(|block_type| block_type.is_opaque())
+        };
+        let light_at = |u: isize, v: isize| {
+            let [x, y, z] = offset(u, v);
+            self.light_at(x, y, z)
+        };
+
+        let Some(front_light) = light_at(0, 0) else {
+            return FaceShading {
+                occlusion: [0; 4],
+                light: [[MAX_LIGHT, 0]; 4],
+            };
+        };
+
+        let mut shading = FaceShading::default();
+        for (i, corner) in face.corners().into_iter().enumerate() {
+            let u = corner[u_axis] as isize;
+            let v = corner[v_axis] as isize;
+            let (side_u, side_v) = (is_opaque(u, 0), is_opaque(0, v));
+            let diagonal = !(side_u && side_v) && is_opaque(u, v);
+
+            shading.occlusion[i] = if side_u && side_v {
+                3
+            } else {
+                side_u as u8 + side_v as u8 + diagonal as u8
+            };
+
+            // The diagonal can't shine through two opaque sides.
+            let mut samples = vec![front_light];
+            samples.extend((!side_u).then(|| light_at(u, 0)).flatten());
+            samples.extend((!side_v).then(|| light_at(0, v)).flatten());
+            if !(side_u && side_v) && !diagonal {
+                samples.extend(light_at(u, v));
+            }
+
+            shading.light[i] = LightChannel::ALL.map(|channel| {
+                let total: u32 = samples
+                    .iter()
+                    .map(|&packed| light::unpack(packed, channel) as u32)
+                    .sum();
+                (total / samples.len() as u32) as u8
+            });
+        }
+
+        shading
+    }
+
+    /// Generates a mesh with one quad per visible block face.
+    pub fn generate_mesh(&self, mesh: &mut TerrainMesh) {
+        mesh.clear();
+        let blocks = &self.blocks;
+
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    let block_type = blocks[block_index(x, y, z)];
+                    if block_type.shape() != BlockShape::Cube {
+                        continue;
+                    }
+
+                    let position = self.position.block(x, y, z).center().into();
+                    for face in Face::ALL {
+                        let [dx, dy, dz] = face.normal();
+                        let (nx, ny, nz) = (x as isize + dx, y as isize + dy, z as isize + dz);
+                        if self.should_render_face(block_type, face, nx, ny, nz) {
+                            let shading = self.face_shading(face, x, y, z);
+                            let quad = BlockQuad::new(
+                                face,
+                                position,
+                                [1.0; 3],
+                                block_type.layer(face),
+                                shading,
+                            );
+                            mesh.add_face_to(block_type.pass(), quad);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.add_cross_blocks(mesh);
+        self.add_shaped_blocks(mesh);
+    }
+
+    /// Generates a mesh where coplanar visible faces of the same block type and shading are
+    /// merged into larger quads, which keeps vertex counts low for flat terrain.
+    pub fn generate_greedy_mesh(&self, mesh: &mut TerrainMesh) {
+        mesh.clear();
+
+        let blocks = &self.blocks;
+        let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+
+        for face in Face::ALL {
+            let (axis, u_axis, v_axis) = face.axes();
+            let normal = face.normal();
+            let (width, height) = (dimensions[u_axis], dimensions[v_axis]);
+
+            // The block type and corner shading of every visible face in the current slice.
+            // Only faces matching in both are merged, so the shading stays per block.
+            let mut mask: Vec<Option<(BlockType, FaceShading)>> = vec![None; width * height];
+
+            for slice in 0..dimensions[axis] {
+                for v in 0..height {
+                    for u in 0..width {
+                        let mut position = [0; 3];
+                        position[axis] = slice;
+                        position[u_axis] = u;
+                        position[v_axis] = v;
+
+                        let [x, y, z] = position;
+                        let block_type = blocks[block_index(x, y, z)];
+
+                        let visible = block_type.shape() == BlockShape::Cube
+                            && self.should_render_face(
+                                block_type,
+                                face,
+                                x as isize + normal[0],
+                                y as isize + normal[1],
+                                z as isize + normal[2],
+                            );
+
+                        mask[v * width + u] =
+                            visible.then(|| (block_type, self.face_shading(face, x, y, z)));
+                    }
+                }
+
+                for v in 0..height {
+                    let mut u = 0;
+                    while u < width {
+                        let Some(key) = mask[v * width + u] else {
+                            u += 1;
+                            continue;
+                        };
+
+                        // Grow the quad along u, then along v for as long as every row matches.
+                        // Water is left unmerged, so its waves have vertices to move.
+                        let mergeable = key.0.pass() != MeshPass::Water;
+                        let mut quad_width = 1;
+                        while mergeable
+                            && u + quad_width < width
+                            && mask[v * width + u + quad_width] == Some(key)
+                        {
+                            quad_width += 1;
+                        }
+
+                        let mut quad_height = 1;
+                        'grow: while mergeable && v + quad_height < height {
+                            for k in 0..quad_width {
+                                if mask[(v + quad_height) * width + u + k] != Some(key) {
+                                    break 'grow;
+                                }
+                            }
+                            quad_height += 1;
+                        }
+
+                        for j in 0..quad_height {
+                            for k in 0..quad_width {
+                                mask[(v + j) * width + u + k] = None;
+                            }
+                        }
+
+                        let mut position = [0; 3];
+                        position[axis] = slice;
+                        position[u_axis] = u;
+                        position[v_axis] = v;
+                        let [x, y, z] = position;
+
+                        let mut size = [1.0; 3];
+                        size[u_axis] = quad_width as f32;
+                        size[v_axis] = quad_height as f32;
+
+                        let (block_type, shading) = key;
+                        let quad = BlockQuad::new(
+                            face,
+                            self.position.block(x, y, z).center().into(),
+                            size,
+                            block_type.layer(face),
+                            shading,
+                        );
+                        mesh.add_face_to(block_type.pass(), quad);
+
+                        u += quad_width;
+                    }
+                }
+            }
+        }
+
+        self.add_cross_blocks(mesh);
+        self.add_shaped_blocks(mesh);
+    }
+
+    /// Adds the faces of every slab and stairs, which are never merged. Faces on the sides of
+    /// the block are hidden and shaded like those of cubes, and faces inside it are always
+    /// drawn, lit by the block itself.
+    fn add_shaped_blocks(&self, mesh: &mut TerrainMesh) {
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    let block_type = self.blocks[block_index(x, y, z)];
+                    let faces = block_type.shape().faces();
+                    if faces.is_empty() {
+                        continue;
+                    }
+
+                    let position = self.position.block(x, y, z).center().into();
+                    let packed = self.light.packed(block_index(x, y, z));
+                    let light = LightChannel::ALL.map(|channel| light::unpack(packed, channel));
+                    for part in faces {
+                        let shading = if part.on_side {
+                            let [dx, dy, dz] = part.face.normal();
+                            let (nx, ny, nz) = (x as isize + dx, y as isize + dy, z as isize + dz);
+                            if !self.should_render_face(block_type, part.face, nx, ny, nz) {
+                                continue;
+                            }
+                            self.face_shading(part.face, x, y, z)
+                        } else {
+                            FaceShading {
+                                occlusion: [0; 4],
+                                light: [light; 4],
+                            }
+                        };
+
+                        let quad = BlockQuad::part(
+                            part.face,
+                            position,
+                            part.min,
+                            part.max,
+                            block_type.layer(part.face),
+                            shading,
+                        );
+                        mesh.add_face_to(block_type.pass(), quad);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the quads of every plant, which are never merged or hidden by their neighbors.
+    fn add_cross_blocks(&self, mesh: &mut TerrainMesh) {
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    let block_type = self.blocks[block_index(x, y, z)];
+                    if block_type.shape() != BlockShape::Cross {
+                        continue;
+                    }
+
+                    let position = self.position.block(x, y, z).center().into();
+                    let packed = self.light.packed(block_index(x, y, z));
+                    let light = LightChannel::ALL.map(|channel| light::unpack(packed, channel));
+                    for quad in BlockQuad::cross(position, block_type.layer(Face::Top), light) {
+                        mesh.add_face(quad);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The blocks of the layer of a chunk bordering one of its faces.
+#[derive(Debug, Clone)]
+pub enum ChunkBorder {
+    /// Every block of the layer has the same type.
+    Uniform(BlockType),
+    /// The type of each block, indexed by `v * width + u` along the axes of the face.
+    Blocks(Vec<BlockType>),
+}
+
+impl ChunkBorder {
+    /// Returns the block at `v * width + u`.
+    pub fn get(&self, index: usize) -> BlockType {
+        match self {
+            ChunkBorder::Uniform(block_type) => *block_type,
+            ChunkBorder::Blocks(blocks) => blocks[index],
+        }
+    }
+}
+
+/// A mesh generated from a [`ChunkSnapshot`].
+pub struct MeshedChunk {
+    pub position: ChunkPos,
+    /// The revision of the chunk the mesh was generated from.
+    pub revision: u64,
+    pub mesh: TerrainMesh,
+    /// Which faces of the chunk can see each other.
+    pub visibility: ChunkVisibility,
+}
+
+struct MeshJob {
+    snapshot: ChunkSnapshot,
+    /// The mesh to generate into, so allocations can be recycled.
+    mesh: TerrainMesh,
+}
+
+/// A background thread that meshes chunk snapshots and sends the results back.
+pub struct Mesher {
+    jobs: Option<Sender<MeshJob>>,
+    results: Receiver<MeshedChunk>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Mesher {
+    pub fn new() -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<MeshJob>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("mesher".to_string())
+            .spawn(move || {
+                // Block until there is work, then mesh everything queued up so far at once.
+                while let Ok(job) = job_receiver.recv() {
+                    let mut jobs = vec![job];
+                    jobs.extend(job_receiver.try_iter());
+
+                    let meshed: Vec<MeshedChunk> = jobs
+                        .into_par_iter()
+                        .map(|MeshJob { snapshot, mut mesh }| {
+                            snapshot.generate_greedy_mesh(&mut mesh);
+                            MeshedChunk {
+                                position: snapshot.position,
+                                revision: snapshot.revision,
+                                mesh,
+                                visibility: ChunkVisibility::compute(&snapshot.blocks),
+                            }
+                        })
+                        .collect();
+
+                    for result in meshed {
+                        if result_sender.send(result).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the mesher thread");
+
+        Self {
+            jobs: Some(job_sender),
+            results: result_receiver,
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues a snapshot for meshing into `mesh`.
+    pub fn submit(&self, snapshot: ChunkSnapshot, mesh: TerrainMesh) {
+        if let Some(jobs) = &self.jobs {
+            // The thread only stops once the sender is dropped, so this can't fail.
+            let _ = jobs.send(MeshJob { snapshot, mesh });
+        }
+    }
+
+    /// Returns the meshes completed since the last call, without blocking.
+    pub fn drain(&self) -> impl Iterator<Item = MeshedChunk> + '_ {
+        self.results.try_iter()
+    }
+}
+
+impl Drop for Mesher {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread once it finishes its current batch.
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_VOLUME;
+
+    /// Builds a snapshot with no loaded neighbors, where `solid` picks the stone blocks.
+    fn snapshot(solid: impl Fn(usize, usize, usize) -> bool) -> ChunkSnapshot {
+        let mut blocks: BlockArray = vec![BlockType::Air; CHUNK_VOLUME]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap();
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    if solid(x, y, z) {
+                        blocks[block_index(x, y, z)] = BlockType::Stone;
+                    }
+                }
+            }
+        }
+
+        ChunkSnapshot {
+            position: ChunkPos(0, 0, 0),
+            revision: 0,
+            light: light::compute(&blocks),
+            blocks,
+            neighbors: Default::default(),
+            neighbor_light: Default::default(),
+        }
+    }
+
+    /// Returns the triangle counts of the greedy and the naive mesh of `snapshot`.
+    fn triangle_counts(snapshot: &ChunkSnapshot) -> (usize, usize) {
+        let mut greedy = TerrainMesh::new();
+        snapshot.generate_greedy_mesh(&mut greedy);
+        let mut naive = TerrainMesh::new();
+        snapshot.generate_mesh(&mut naive);
+        (greedy.triangle_count(), naive.triangle_count())
+    }
+
+    #[test]
+    fn greedy_mesh_merges_flat_terrain() {
+        let (greedy, naive) = triangle_counts(&snapshot(|_, y, _| y < 4));
+        assert!(greedy < naive, "greedy {greedy}, naive {naive}");
+    }
+
+    #[test]
+    fn greedy_mesh_is_never_larger_for_a_checkerboard() {
+        let (greedy, naive) = triangle_counts(&snapshot(|x, y, z| (x + y + z) % 2 == 0));
+        assert!(greedy <= naive, "greedy {greedy}, naive {naive}");
+    }
+
+    #[test]
+    fn greedy_mesh_of_a_single_block() {
+        let (greedy, naive) = triangle_counts(&snapshot(|x, y, z| (x, y, z) == (8, 8, 8)));
+        assert_eq!(naive, 12);
+        assert!(greedy <= naive, "greedy {greedy}, naive {naive}");
+    }
+}
@@ -1,29 +1,200 @@
-use std::collections::HashMap;
-
 use noise::{NoiseFn, Perlin};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Settings for fractal Brownian motion, which layers several octaves of noise at increasing
+/// frequencies and decreasing amplitudes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbmConfig {
+    /// The number of layers of noise.
+    pub octaves: u32,
+    /// How much the frequency grows with every octave.
+    pub lacunarity: f64,
+    /// How much the amplitude shrinks with every octave.
+    pub persistence: f64,
+}
+
+impl Default for FbmConfig {
+    fn default() -> Self {
+        Self {
+            octaves: 4,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+}
+
+/// Samples fractal noise at `point`, roughly between -1 and 1.
+pub fn fbm(perlin: &Perlin, point: [f64; 2], config: &FbmConfig) -> f64 {
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    let mut max_amplitude = 0.0;
+
+    for octave in 0..config.octaves {
+        // Offset every octave so the layers don't all line up at the origin.
+        let offset = octave as f64 * 17.31;
+        total +=
+            perlin.get([point[0] * frequency + offset, point[1] * frequency + offset]) * amplitude;
 
-pub fn generate_perlin_noise(
-    chunk_width: usize,
-    chunk_depth: usize,
+        max_amplitude += amplitude;
+        frequency *= config.lacunarity;
+        amplitude *= config.persistence;
+    }
+
+    if max_amplitude > 0.0 {
+        total / max_amplitude
+    } else {
+        0.0
+    }
+}
+
+/// Settings for the mountain ranges raised on top of the base terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MountainConfig {
+    /// How many blocks the sample points are pushed around by, which bends ridges and
+    /// coastlines into less regular shapes.
+    pub warp_strength: f64,
+    /// How many blocks the warp takes to change direction.
+    pub warp_scale: f64,
+    /// How many blocks apart neighboring ridges roughly are.
+    pub ridge_scale: f64,
+    /// How many blocks mountains rise above the base terrain at most. Zero disables them.
+    pub ridge_height: f32,
+    /// The height of the steps mountains are cut into, giving plateaus separated by cliffs.
+    /// Zero disables them.
+    pub terrace_height: f32,
+}
+
+impl Default for MountainConfig {
+    fn default() -> Self {
+        Self {
+            warp_strength: 40.0,
+            warp_scale: 150.0,
+            ridge_scale: 120.0,
+            ridge_height: 14.0,
+            terrace_height: 4.0,
+        }
+    }
+}
+
+/// Cuts `height` into steps of `step` blocks, each mostly flat and ending in a steep rise.
+fn terrace(height: f32, step: f32) -> f32 {
+    if step <= 0.0 {
+        return height;
+    }
+
+    let steps = height / step;
+    let rise = ((steps.fract() - 0.7) / 0.3).clamp(0.0, 1.0);
+    (steps.floor() + rise) * step
+}
+
+/// Smoothly maps `value` from 0 at `edge0` to 1 at `edge1`.
+fn smoothstep(edge0: f64, edge1: f64, value: f64) -> f64 {
+    let t = ((value - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Seeded fractal noise giving the terrain height of every column. Heights only depend on
+/// the seed and the column, so any part of the world can be sampled in any order.
+pub struct HeightNoise {
+    perlin: Perlin,
+    /// Pushes the sample points around.
+    warp: Perlin,
+    /// Shapes the ridges and decides where the mountain ranges are.
+    ridge: Perlin,
+    config: FbmConfig,
+    mountains: MountainConfig,
+    /// How many blocks the terrain takes to change noticeably.
     scale: f64,
-    seed: u32,
     height_min: f32,
     height_max: f32,
-) -> HashMap<(usize, usize), f32> {
-    let mut height_map = HashMap::new();
-    let perlin = Perlin::new(seed);
+}
+
+impl HeightNoise {
+    pub fn new(seed: u32, config: FbmConfig, scale: f64, height_min: f32, height_max: f32) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            warp: Perlin::new(seed.wrapping_add(1)),
+            ridge: Perlin::new(seed.wrapping_add(2)),
+            config,
+            mountains: MountainConfig::default(),
+            scale,
+            height_min,
+            height_max,
+        }
+    }
+
+    pub fn mountains(&self) -> &MountainConfig {
+        &self.mountains
+    }
+
+    pub fn set_mountains(&mut self, mountains: MountainConfig) {
+        self.mountains = mountains;
+    }
 
-    for x in 0..chunk_width {
-        for z in 0..chunk_depth {
-            let noise_value = perlin.get([x as f64 / scale, z as f64 / scale]);
+    /// Returns the terrain height of the column at the given block coordinates.
+    pub fn height_at(&self, x: i32, z: i32) -> f32 {
+        let (x, z) = self.warp(x as f64, z as f64);
 
-            let normalized_height = (noise_value + 1.0) * 0.5;
+        let point = [x / self.scale, z / self.scale];
+        let noise_value = fbm(&self.perlin, point, &self.config);
 
-            let terrain_height = height_min + normalized_height as f32 * (height_max - height_min);
+        let normalized_height = ((noise_value + 1.0) * 0.5).clamp(0.0, 1.0);
+        let height =
+            self.height_min + normalized_height as f32 * (self.height_max - self.height_min);
 
-            height_map.insert((x, z), terrain_height);
+        height + self.mountain_height(x, z)
+    }
+
+    /// Offsets the given block coordinates by the warp noise.
+    fn warp(&self, x: f64, z: f64) -> (f64, f64) {
+        let MountainConfig {
+            warp_strength,
+            warp_scale,
+            ..
+        } = self.mountains;
+        if warp_strength == 0.0 {
+            return (x, z);
         }
+
+        let point = [x / warp_scale, z / warp_scale];
+        // Sample far apart so the two offsets are unrelated.
+        let offset_x = fbm(&self.warp, point, &self.config);
+        let offset_z = fbm(&self.warp, [point[0] + 91.7, point[1] - 53.3], &self.config);
+
+        (x + offset_x * warp_strength, z + offset_z * warp_strength)
+    }
+
+    /// Returns how far mountains raise the column above the base terrain.
+    fn mountain_height(&self, x: f64, z: f64) -> f32 {
+        let MountainConfig {
+            ridge_scale,
+            ridge_height,
+            terrace_height,
+            ..
+        } = self.mountains;
+        if ridge_height <= 0.0 {
+            return 0.0;
+        }
+
+        // Ranges only rise where low frequency noise is high, leaving the rest of the world as
+        // rolling hills.
+        let range_point = [x / (ridge_scale * 4.0), z / (ridge_scale * 4.0)];
+        let range = smoothstep(0.0, 0.3, fbm(&self.ridge, range_point, &self.config));
+        if range == 0.0 {
+            return 0.0;
+        }
+
+        // Folding the noise around zero turns its zero crossings into sharp crests.
+        let ridge_point = [x / ridge_scale + 37.1, z / ridge_scale - 12.9];
+        let ridge = 1.0 - fbm(&self.ridge, ridge_point, &self.config).abs();
+
+        terrace(
+            (ridge * ridge * range) as f32 * ridge_height,
+            terrace_height,
+        )
     }
-    height_map
 }
@@ -0,0 +1,187 @@
+use std::io::{self, Read, Write};
+
+use crate::renderer::block::BlockType;
+use crate::save::read_u32;
+
+/// Block storage that keeps a palette of the distinct block types in use and stores a
+/// bit-packed palette index per block, using as few bits as the palette size allows.
+#[derive(Debug, Clone)]
+pub struct PaletteStorage {
+    /// The distinct block types stored, indexed by the packed values.
+    palette: Vec<BlockType>,
+    /// The number of bits used per packed index.
+    bits: u32,
+    /// The packed indices. Indices never straddle two words.
+    data: Vec<u64>,
+    /// The number of blocks stored.
+    len: usize,
+}
+
+impl PaletteStorage {
+    /// Creates storage for `len` blocks, all set to `block_type`.
+    pub fn new(len: usize, block_type: BlockType) -> Self {
+        Self {
+            palette: vec![block_type],
+            bits: 0,
+            data: Vec::new(),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn palette(&self) -> &[BlockType] {
+        &self.palette
+    }
+
+    pub fn get(&self, index: usize) -> BlockType {
+        self.palette[self.get_packed(index)]
+    }
+
+    pub fn set(&mut self, index: usize, block_type: BlockType) {
+        let palette_index = match self.palette.iter().position(|&b| b == block_type) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(block_type);
+                let required_bits = usize::BITS - (self.palette.len() - 1).leading_zeros();
+                if required_bits > self.bits {
+                    self.repack(required_bits);
+                }
+                self.palette.len() - 1
+            }
+        };
+
+        self.set_packed(index, palette_index);
+    }
+
+    /// Decodes every block into `out`, which must hold exactly `len` blocks.
+    pub fn unpack(&self, out: &mut [BlockType]) {
+        assert_eq!(out.len(), self.len);
+
+        if self.bits == 0 {
+            out.fill(self.palette[0]);
+            return;
+        }
+
+        let entries_per_word = self.entries_per_word();
+        let mask = (1u64 << self.bits) - 1;
+
+        for (blocks, word) in out.chunks_mut(entries_per_word).zip(&self.data) {
+            for (i, block) in blocks.iter_mut().enumerate() {
+                *block = self.palette[((word >> (i as u32 * self.bits)) & mask) as usize];
+            }
+        }
+    }
+
+    /// Returns the number of bytes used by the packed indices.
+    pub fn packed_size(&self) -> usize {
+        self.data.len() * std::mem::size_of::<u64>()
+    }
+
+    fn entries_per_word(&self) -> usize {
+        (u64::BITS / self.bits) as usize
+    }
+
+    fn get_packed(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let entries_per_word = self.entries_per_word();
+        let word = self.data[index / entries_per_word];
+        let shift = (index % entries_per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+
+        ((word >> shift) & mask) as usize
+    }
+
+    fn set_packed(&mut self, index: usize, value: usize) {
+        if self.bits == 0 {
+            return;
+        }
+
+        let entries_per_word = self.entries_per_word();
+        let word = &mut self.data[index / entries_per_word];
+        let shift = (index % entries_per_word) as u32 * self.bits;
+        let mask = (1u64 << self.bits) - 1;
+
+        *word = (*word & !(mask << shift)) | ((value as u64 & mask) << shift);
+    }
+
+    /// Re-encodes every index with `bits` bits per entry.
+    fn repack(&mut self, bits: u32) {
+        let values: Vec<usize> = (0..self.len).map(|i| self.get_packed(i)).collect();
+
+        self.bits = bits;
+        let entries_per_word = self.entries_per_word();
+        self.data = vec![0; self.len.div_ceil(entries_per_word)];
+
+        for (index, value) in values.into_iter().enumerate() {
+            self.set_packed(index, value);
+        }
+    }
+}
+
+impl PaletteStorage {
+    /// Writes the palette and the packed indices.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&(self.len as u32).to_le_bytes())?;
+        writer.write_all(&(self.palette.len() as u32).to_le_bytes())?;
+        for block_type in &self.palette {
+            writer.write_all(&(*block_type as u32).to_le_bytes())?;
+        }
+        writer.write_all(&self.bits.to_le_bytes())?;
+        for word in &self.data {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads storage previously written with [`PaletteStorage::write`].
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let len = read_u32(reader)? as usize;
+        let palette_len = read_u32(reader)? as usize;
+        if palette_len == 0 || palette_len > len.max(1) {
+            return Err(invalid("invalid palette length"));
+        }
+
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            let id = read_u32(reader)?;
+            palette.push(BlockType::from_id(id).ok_or_else(|| invalid("unknown block type"))?);
+        }
+
+        let bits = read_u32(reader)?;
+        if bits > 16 || (bits == 0 && palette_len > 1) {
+            return Err(invalid("invalid index size"));
+        }
+
+        let mut storage = Self {
+            palette,
+            bits,
+            data: Vec::new(),
+            len,
+        };
+
+        if bits > 0 {
+            let words = len.div_ceil(storage.entries_per_word());
+            storage.data.reserve(words);
+            for _ in 0..words {
+                let mut bytes = [0; 8];
+                reader.read_exact(&mut bytes)?;
+                storage.data.push(u64::from_le_bytes(bytes));
+            }
+
+            if (0..len).any(|i| storage.get_packed(i) >= storage.palette.len()) {
+                return Err(invalid("palette index out of range"));
+            }
+        }
+
+        Ok(storage)
+    }
+}
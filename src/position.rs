@@ -0,0 +1,93 @@
+use std::ops::Add;
+
+use cgmath::EuclideanSpace;
+
+use crate::aabb::Aabb;
+use crate::chunk::{BLOCK_SIZE, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::renderer::block::Face;
+
+/// The position of a chunk, in units of whole chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos(pub i32, pub i32, pub i32);
+
+/// The position of a block in the world, in units of whole blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockPos(pub i32, pub i32, pub i32);
+
+impl ChunkPos {
+    /// Returns the chunk containing the given world position.
+    pub fn from_world(position: cgmath::Point3<f32>) -> Self {
+        BlockPos::from_world(position).chunk()
+    }
+
+    /// Returns the block at the minimum corner of the chunk.
+    pub fn origin(&self) -> BlockPos {
+        BlockPos(
+            self.0 * CHUNK_WIDTH as i32,
+            self.1 * CHUNK_HEIGHT as i32,
+            self.2 * CHUNK_DEPTH as i32,
+        )
+    }
+
+    /// Returns the world position of the block at the given local coordinates.
+    pub fn block(&self, x: usize, y: usize, z: usize) -> BlockPos {
+        self.origin() + BlockPos(x as i32, y as i32, z as i32)
+    }
+
+    /// Returns the chunk next to this one in the direction of `face`.
+    pub fn neighbor(&self, face: Face) -> ChunkPos {
+        let [x, y, z] = face.normal();
+        ChunkPos(self.0 + x as i32, self.1 + y as i32, self.2 + z as i32)
+    }
+}
+
+impl BlockPos {
+    /// Returns the block containing the given world position.
+    pub fn from_world(position: cgmath::Point3<f32>) -> Self {
+        // Blocks are centered on multiples of the block size, so shift by half a block.
+        let block = |v: f32| (v / BLOCK_SIZE + 0.5).floor() as i32;
+        Self(block(position.x), block(position.y), block(position.z))
+    }
+
+    /// Returns the world position of the center of the block.
+    pub fn center(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(self.0 as f32, self.1 as f32, self.2 as f32) * BLOCK_SIZE
+    }
+
+    /// Returns the box the block fills in the world.
+    pub fn aabb(&self) -> Aabb {
+        let center = cgmath::Point3::from_vec(self.center());
+        let half = cgmath::Vector3::new(1.0, 1.0, 1.0) * (BLOCK_SIZE / 2.0);
+        Aabb::new(center - half, center + half)
+    }
+
+    /// Returns the chunk containing the block.
+    pub fn chunk(&self) -> ChunkPos {
+        ChunkPos(
+            self.0.div_euclid(CHUNK_WIDTH as i32),
+            self.1.div_euclid(CHUNK_HEIGHT as i32),
+            self.2.div_euclid(CHUNK_DEPTH as i32),
+        )
+    }
+
+    /// Returns the coordinates of the block within its chunk.
+    pub fn local(&self) -> (usize, usize, usize) {
+        (
+            self.0.rem_euclid(CHUNK_WIDTH as i32) as usize,
+            self.1.rem_euclid(CHUNK_HEIGHT as i32) as usize,
+            self.2.rem_euclid(CHUNK_DEPTH as i32) as usize,
+        )
+    }
+
+    pub fn offset(&self, x: i32, y: i32, z: i32) -> Self {
+        Self(self.0 + x, self.1 + y, self.2 + z)
+    }
+}
+
+impl Add for BlockPos {
+    type Output = BlockPos;
+
+    fn add(self, other: BlockPos) -> BlockPos {
+        BlockPos(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
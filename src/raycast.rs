@@ -0,0 +1,90 @@
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+
+use crate::chunk::BLOCK_SIZE;
+use crate::position::BlockPos;
+use crate::renderer::block::Face;
+
+/// A block hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The block the ray hit.
+    pub position: BlockPos,
+    /// The face of the block the ray entered it through.
+    pub face: Face,
+    /// How far along the ray the block was hit, in world units.
+    pub distance: f32,
+}
+
+impl RayHit {
+    /// Returns the block next to the hit one on the side the ray came from, where a block
+    /// placed against the hit face goes.
+    pub fn adjacent(&self) -> BlockPos {
+        let [x, y, z] = self.face.normal();
+        self.position.offset(x as i32, y as i32, z as i32)
+    }
+}
+
+/// Follows the ray from `origin` in `direction` through every block it passes, in order,
+/// and returns the first one `is_hit` returns `true` for within `reach` world units. The
+/// block the ray starts in is skipped, since it isn't entered through any face. This is the
+/// voxel traversal of Amanatides and Woo.
+pub fn raycast(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    reach: f32,
+    is_hit: impl Fn(BlockPos) -> bool,
+) -> Option<RayHit> {
+    // In block units, with block `b` spanning from `b` to `b + 1` so blocks start at whole
+    // coordinates.
+    let start = origin.to_vec() / BLOCK_SIZE + Vector3::new(0.5, 0.5, 0.5);
+    let reach = reach / BLOCK_SIZE;
+    let mut block = [start.x, start.y, start.z].map(|v| v.floor() as i32);
+
+    // How far along the ray the next block boundary is along each axis, and how far apart
+    // the boundaries are.
+    let mut step = [0; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        let d = direction[axis];
+        if d > 0.0 {
+            step[axis] = 1;
+            t_delta[axis] = 1.0 / d;
+            t_max[axis] = (block[axis] as f32 + 1.0 - start[axis]) / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            t_delta[axis] = -1.0 / d;
+            t_max[axis] = (start[axis] - block[axis] as f32) / -d;
+        }
+    }
+
+    // The parameter of the ray counts lengths of `direction`, which needn't be normalized.
+    let length = direction.magnitude();
+    if length == 0.0 {
+        return None;
+    }
+
+    loop {
+        let axis = (0..3)
+            .min_by(|&a, &b| t_max[a].total_cmp(&t_max[b]))
+            .unwrap();
+        let t = t_max[axis];
+        if t * length > reach {
+            return None;
+        }
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        let [x, y, z] = block;
+        let position = BlockPos(x, y, z);
+        if is_hit(position) {
+            let mut normal = [0; 3];
+            normal[axis] = -step[axis] as isize;
+            return Some(RayHit {
+                position,
+                face: Face::from_normal(normal)?,
+                distance: t * length * BLOCK_SIZE,
+            });
+        }
+    }
+}
@@ -1,18 +1,31 @@
+use std::sync::OnceLock;
+
 use cgmath::{Vector3, Zero};
 use winit::dpi::Position;
 
+use crate::aabb::Aabb;
+use crate::item::ToolKind;
+use crate::light::MAX_LIGHT;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 
 pub struct BlockVertex {
     pub position: [f32; 3],
+    /// Texture coordinates within the block texture, repeating every whole unit.
     pub tex_coords: [f32; 2],
+    /// The layer of the terrain texture array the texture is sampled from.
+    pub layer: u32,
+    /// How much the corner is darkened by the blocks around it, from 0 to 1.
+    pub occlusion: f32,
+    /// The sky and block light level reaching the corner, from 0 to 1.
+    pub light: [f32; 2],
 }
 
 impl BlockVertex {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<BlockVertex>() as wgpu::BufferAddress, // 20 bytes
+            array_stride: std::mem::size_of::<BlockVertex>() as wgpu::BufferAddress, // 36 bytes
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -25,43 +38,105 @@ impl BlockVertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                wgpu::VertexAttribute {
+                    offset: 20,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: 24,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: 28,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
 
+/// The passes terrain faces are drawn in, in the order they are drawn.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MeshPass {
+    /// Opaque and cut out faces, drawn without blending.
+    Opaque,
+    /// Translucent faces blended over everything drawn before them.
+    Transparent,
+    /// Water surfaces, blended like translucent faces but animated.
+    Water,
+}
+
+impl MeshPass {
+    pub const ALL: [MeshPass; 3] = [MeshPass::Opaque, MeshPass::Transparent, MeshPass::Water];
+}
+
 pub struct TerrainMesh {
     vertices: Vec<BlockVertex>,
-    indices: Vec<u32>,
+    /// The indices of the faces drawn in each pass, indexed by [`MeshPass`].
+    indices: [Vec<u32>; 3],
 }
 
 impl TerrainMesh {
     pub fn new() -> Self {
         Self {
             vertices: Vec::new(),
-            indices: Vec::new(),
+            indices: Default::default(),
         }
     }
 
+    /// Removes every face, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.iter_mut().for_each(Vec::clear);
+    }
+
+    /// Adds a face drawn in the opaque pass.
     pub fn add_face(&mut self, face: BlockQuad) {
+        self.add_face_to(MeshPass::Opaque, face);
+    }
+
+    /// Adds a face drawn in `pass`.
+    pub fn add_face_to(&mut self, pass: MeshPass, face: BlockQuad) {
         let base_index = self.vertices.len() as u32;
 
         self.vertices.extend_from_slice(&face.vertices);
 
-        // 1 face = 2 triangles = 6 indices = 4 vertices
-        self.indices.push(base_index + 0);
-        self.indices.push(base_index + 1);
-        self.indices.push(base_index + 2);
-
-        self.indices.push(base_index + 0);
-        self.indices.push(base_index + 2);
-        self.indices.push(base_index + 3);
+        // 1 face = 2 triangles = 6 indices = 4 vertices. Split along the diagonal between the
+        // less occluded corners, so the shading is interpolated evenly along the darker edge.
+        let occlusion = face.vertices.map(|vertex| vertex.occlusion);
+        let order = if occlusion[0] + occlusion[2] > occlusion[1] + occlusion[3] {
+            [1, 2, 3, 1, 3, 0]
+        } else {
+            [0, 1, 2, 0, 2, 3]
+        };
+        self.indices[pass as usize].extend(order.into_iter().map(|i| base_index + i));
     }
 
     pub fn vertices(&self) -> &[BlockVertex] {
         &self.vertices
     }
 
+    /// Returns the box around every vertex of the mesh, `None` if it has no vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let (first, rest) = self.vertices.split_first()?;
+        let mut min = first.position;
+        let mut max = first.position;
+        for vertex in rest {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+        Some(Aabb::new(min.into(), max.into()))
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.iter().map(Vec::len).sum::<usize>() / 3
+    }
+
     pub fn vertices_mut(&mut self) -> &mut Vec<BlockVertex> {
         &mut self.vertices
     }
@@ -70,16 +145,45 @@ impl TerrainMesh {
         self.vertices = vertices;
     }
 
+    /// Returns the indices of the faces drawn in the opaque pass.
     pub fn indices(&self) -> &[u32] {
-        &self.indices
+        &self.indices[MeshPass::Opaque as usize]
     }
 
     pub fn indices_mut(&mut self) -> &mut Vec<u32> {
-        &mut self.indices
+        &mut self.indices[MeshPass::Opaque as usize]
     }
 
     pub fn set_indices(&mut self, indices: Vec<u32>) {
-        self.indices = indices;
+        self.indices[MeshPass::Opaque as usize] = indices;
+    }
+
+    /// Returns the indices of the faces drawn in `pass`.
+    pub fn pass_indices(&self, pass: MeshPass) -> &[u32] {
+        &self.indices[pass as usize]
+    }
+}
+
+/// How the corners of a face are shaded, in the order of [`Face::corners`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaceShading {
+    /// The number of occluding blocks at each corner, from 0 to 3.
+    pub occlusion: [u8; 4],
+    /// The sky and block light level at each corner, up to [`MAX_LIGHT`].
+    pub light: [[u8; 2]; 4],
+}
+
+impl FaceShading {
+    /// The shading of a corner, converted to the ranges of a [`BlockVertex`].
+    fn vertex(&self, i: usize) -> (f32, [f32; 2]) {
+        let [sky, block] = self.light[i];
+        (
+            self.occlusion[i] as f32 / 3.0,
+            [
+                sky as f32 / MAX_LIGHT as f32,
+                block as f32 / MAX_LIGHT as f32,
+            ],
+        )
     }
 }
 
@@ -93,210 +197,429 @@ impl BlockQuad {
     }
 }
 
-#[inline]
-fn combine(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
-    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
-}
-
 impl BlockQuad {
-    pub fn top(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
-        Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([-1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([-1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
-        }
-    }
+    /// Creates a quad for `face` starting at the block centered on `position` and spanning
+    /// `size` blocks along each axis. Textures repeat once per block.
+    pub fn new(
+        face: Face,
+        position: [f32; 3],
+        size: [f32; 3],
+        layer: u32,
+        shading: FaceShading,
+    ) -> Self {
+        let corners = face.corners();
+        let tex_coords = face.tex_coords(size);
 
-    pub fn bottom(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
-        Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([-1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([-1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
-        }
-    }
+        let vertex = |i: usize| {
+            let corner = corners[i];
+            let mut vertex_position = [0.0; 3];
+            for axis in 0..3 {
+                // Corners are -1/+1 offsets from the block center, so stretch the +1 side.
+                vertex_position[axis] = position[axis] - 1.0 + (corner[axis] + 1.0) * size[axis];
+            }
 
-    pub fn left(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
-        Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([-1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([-1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([-1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([-1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
-        }
-    }
+            let (occlusion, light) = shading.vertex(i);
+            BlockVertex {
+                position: vertex_position,
+                tex_coords: tex_coords[i],
+                layer,
+                occlusion,
+                light,
+            }
+        };
 
-    pub fn right(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
         Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
+            vertices: [vertex(0), vertex(1), vertex(2), vertex(3)],
         }
     }
 
-    pub fn front(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
+    /// Creates a quad for `face` of part of the block centered on `position`, spanning from
+    /// `min` to `max` as offsets from its center, which go from -1 to 1 across the block.
+    /// Textures repeat once per block, starting at the corner of the quad.
+    pub fn part(
+        face: Face,
+        position: [f32; 3],
+        min: [f32; 3],
+        max: [f32; 3],
+        layer: u32,
+        shading: FaceShading,
+    ) -> Self {
+        let corners = face.corners();
+        let size: [f32; 3] = std::array::from_fn(|axis| (max[axis] - min[axis]) / 2.0);
+        let tex_coords = face.tex_coords(size);
+
+        let vertex = |i: usize| {
+            let corner = corners[i];
+            let vertex_position = std::array::from_fn(|axis| {
+                let t = (corner[axis] + 1.0) / 2.0;
+                position[axis] + min[axis] + (max[axis] - min[axis]) * t
+            });
+
+            let (occlusion, light) = shading.vertex(i);
+            BlockVertex {
+                position: vertex_position,
+                tex_coords: tex_coords[i],
+                layer,
+                occlusion,
+                light,
+            }
+        };
+
         Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([-1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([1.0, -1.0, -1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([-1.0, 1.0, -1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
+            vertices: [vertex(0), vertex(1), vertex(2), vertex(3)],
         }
     }
 
-    pub fn back(tex_coords: [[f32; 2]; 4], position: [f32; 3]) -> Self {
-        Self {
-            vertices: [
-                BlockVertex {
-                    position: combine([-1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[0],
-                },
-                BlockVertex {
-                    position: combine([1.0, -1.0, 1.0], position),
-                    tex_coords: tex_coords[1],
-                },
-                BlockVertex {
-                    position: combine([1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[2],
-                },
-                BlockVertex {
-                    position: combine([-1.0, 1.0, 1.0], position),
-                    tex_coords: tex_coords[3],
-                },
-            ],
-        }
+    /// Creates the two diagonal quads of a plant in the block centered on `position`, lit
+    /// evenly by the sky and block `light` levels of the block.
+    pub fn cross(position: [f32; 3], layer: u32, light: [u8; 2]) -> [Self; 2] {
+        let [x, y, z] = position;
+        let light = light.map(|level| level as f32 / MAX_LIGHT as f32);
+        let quad = |from: [f32; 2], to: [f32; 2]| {
+            let vertex = |[dx, dz]: [f32; 2], dy: f32, tex_coords: [f32; 2]| BlockVertex {
+                position: [x + dx, y + dy, z + dz],
+                tex_coords,
+                layer,
+                occlusion: 0.0,
+                light,
+            };
+
+            Self {
+                vertices: [
+                    vertex(from, -1.0, [0.0, 1.0]),
+                    vertex(to, -1.0, [1.0, 1.0]),
+                    vertex(to, 1.0, [1.0, 0.0]),
+                    vertex(from, 1.0, [0.0, 0.0]),
+                ],
+            }
+        };
+
+        [
+            quad([-1.0, -1.0], [1.0, 1.0]),
+            quad([-1.0, 1.0], [1.0, -1.0]),
+        ]
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Block {
-    pub block_type: BlockType,
-    pub position: cgmath::Vector3<f32>,
-}
+/// The width and height of a block texture in the atlas, in pixels.
+pub const TILE_SIZE: u32 = 16;
 
-impl Block {
-    pub fn new(block_type: BlockType, position: cgmath::Vector3<f32>) -> Self {
-        Self {
-            block_type,
-            position,
-        }
-    }
+/// The atlas tiles used by block faces, which become the layers of the terrain texture array,
+/// and the layer used by every face of every block type.
+struct TextureLayers {
+    tiles: Vec<(u32, u32)>,
+    /// Indexed by block type id, then by face.
+    faces: Vec<[u32; 6]>,
+}
 
-    pub fn is_air(&self) -> bool {
-        self.block_type == BlockType::Air
-    }
+fn texture_layers() -> &'static TextureLayers {
+    static LAYERS: OnceLock<TextureLayers> = OnceLock::new();
+    LAYERS.get_or_init(|| {
+        let mut tiles = Vec::new();
+        let mut faces = vec![[0; 6]; BlockType::ALL.len()];
 
-    pub fn generate_face(&self, face: Face) -> BlockQuad {
-        match face {
-            Face::Top => {
-                BlockQuad::top(self.block_type.tex_coords(Face::Top), self.position.into())
-            }
-            Face::Bottom => BlockQuad::bottom(
-                self.block_type.tex_coords(Face::Bottom),
-                self.position.into(),
-            ),
-            Face::Left => {
-                BlockQuad::left(self.block_type.tex_coords(Face::Left), self.position.into())
-            }
-            Face::Right => BlockQuad::right(
-                self.block_type.tex_coords(Face::Right),
-                self.position.into(),
-            ),
-            Face::Front => BlockQuad::front(
-                self.block_type.tex_coords(Face::Front),
-                self.position.into(),
-            ),
-            Face::Back => {
-                BlockQuad::back(self.block_type.tex_coords(Face::Back), self.position.into())
+        for block_type in BlockType::ALL {
+            for face in Face::ALL {
+                let tile = block_type.tile(face);
+                let layer = match tiles.iter().position(|&t| t == tile) {
+                    Some(layer) => layer,
+                    None => {
+                        tiles.push(tile);
+                        tiles.len() - 1
+                    }
+                };
+                faces[block_type as usize][face as usize] = layer as u32;
             }
         }
-    }
+
+        TextureLayers { tiles, faces }
+    })
 }
 
-const ATLAS_SIZE: f32 = 256.0;
-const BLOCK_SIZE: f32 = 16.0;
+/// Returns the atlas tiles making up the layers of the terrain texture array, in order.
+pub fn texture_tiles() -> &'static [(u32, u32)] {
+    &texture_layers().tiles
+}
 
 #[repr(u32)]
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum BlockType {
     Dirt,
     Grass,
     Stone,
     Air,
+    Sand,
+    Snow,
+    Log,
+    Leaves,
+    TallGrass,
+    Rose,
+    Dandelion,
+    Water,
+    Bedrock,
+    SnowyStone,
+    Cobblestone,
+    Planks,
+    Sandstone,
+    Glass,
+    CobblestoneSlab,
+    PlanksSlab,
+    /// Planks stairs, named after the direction their full height side faces.
+    PlanksStairsNorth,
+    PlanksStairsSouth,
+    PlanksStairsWest,
+    PlanksStairsEast,
+    /// Water flowing away from a source, numbered by how many blocks away it is.
+    FlowingWater1,
+    FlowingWater2,
+    FlowingWater3,
+    FlowingWater4,
+    FlowingWater5,
+    FlowingWater6,
+    FlowingWater7,
+    Gravel,
+    /// Doors, named after the side their panel is on while closed. Doors are two blocks high,
+    /// a bottom half with a top half above it.
+    DoorNorth,
+    DoorNorthOpen,
+    DoorSouth,
+    DoorSouthOpen,
+    DoorWest,
+    DoorWestOpen,
+    DoorEast,
+    DoorEastOpen,
+    DoorTopNorth,
+    DoorTopNorthOpen,
+    DoorTopSouth,
+    DoorTopSouthOpen,
+    DoorTopWest,
+    DoorTopWestOpen,
+    DoorTopEast,
+    DoorTopEastOpen,
+    /// Trapdoors, named after the side their panel swings up to when opened.
+    TrapdoorNorth,
+    TrapdoorNorthOpen,
+    TrapdoorSouth,
+    TrapdoorSouthOpen,
+    TrapdoorWest,
+    TrapdoorWestOpen,
+    TrapdoorEast,
+    TrapdoorEastOpen,
+    /// Torches, standing on the block below them or, named after the side it's on, hanging on
+    /// the wall of the block beside them.
+    Torch,
+    TorchNorth,
+    TorchSouth,
+    TorchWest,
+    TorchEast,
+    Chest,
+    /// Grows into a tree.
+    Sapling,
+    /// Wheat, numbered by how far it has grown, ripe at 4.
+    Wheat1,
+    Wheat2,
+    Wheat3,
+    Wheat4,
+}
+
+/// How a block is meshed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BlockShape {
+    /// Nothing is drawn.
+    Empty,
+    /// A full cube, with faces hidden by neighboring opaque blocks.
+    Cube,
+    /// Two diagonal quads crossing in the middle of the block, used for plants.
+    Cross,
+    /// The bottom half of a cube.
+    Slab,
+    /// A slab with a step on the half toward the face, which is the full height side.
+    Stairs(Face),
+    /// A thin board against the side toward the face, used for doors and trapdoors.
+    Panel(Face),
+    /// A thin stick standing on the bottom of the block, or leaning on the side toward the
+    /// face, used for torches.
+    Torch(Face),
+}
+
+/// How thick panels are, as a part of the width of a block from 0 to 2 like the offsets of
+/// [`ShapeFace`].
+const PANEL_THICKNESS: f32 = 0.375;
+
+/// How wide and tall torches are, and how far above the bottom of the block torches on walls
+/// start, in the same units.
+const TORCH_WIDTH: f32 = 0.25;
+const TORCH_HEIGHT: f32 = 1.25;
+const TORCH_WALL_HEIGHT: f32 = 0.5;
+
+/// A face of a shape smaller than a cube, spanning from `min` to `max` as offsets from the
+/// block center, which go from -1 to 1 across the block.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShapeFace {
+    pub face: Face,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    /// Whether the face lies on the side of the block, where a neighbor covering that side
+    /// hides it. Faces inside the block are always drawn.
+    pub on_side: bool,
+}
+
+impl ShapeFace {
+    /// Returns the face turned around the vertical axis so that what faced `Face::Back`
+    /// faces `facing`, which has to be a side face.
+    fn rotated(&self, facing: Face) -> ShapeFace {
+        let rotate = |[x, y, z]: [f32; 3]| match facing {
+            Face::Right => [z, y, -x],
+            Face::Front => [-x, y, -z],
+            Face::Left => [-z, y, x],
+            _ => [x, y, z],
+        };
+        let [nx, ny, nz] = self.face.normal().map(|n| n as f32);
+        let [nx, ny, nz] = rotate([nx, ny, nz]);
+        let (a, b) = (rotate(self.min), rotate(self.max));
+
+        ShapeFace {
+            face: Face::from_normal([nx as isize, ny as isize, nz as isize]).unwrap_or(self.face),
+            min: std::array::from_fn(|axis| a[axis].min(b[axis])),
+            max: std::array::from_fn(|axis| a[axis].max(b[axis])),
+            on_side: self.on_side,
+        }
+    }
+}
+
+impl BlockShape {
+    /// Returns whether the shape fills the whole side of the block toward `face`.
+    pub fn covers(&self, face: Face) -> bool {
+        match self {
+            BlockShape::Cube => true,
+            BlockShape::Slab => face == Face::Bottom,
+            BlockShape::Stairs(facing) => face == Face::Bottom || face == *facing,
+            BlockShape::Panel(side) => face == *side,
+            BlockShape::Empty | BlockShape::Cross | BlockShape::Torch(_) => false,
+        }
+    }
+
+    /// Returns the faces of slabs, stairs, panels and torches. Cubes and plants are meshed
+    /// differently, and have none.
+    pub fn faces(&self) -> Vec<ShapeFace> {
+        let face = |face: Face, min: [f32; 3], max: [f32; 3], on_side: bool| ShapeFace {
+            face,
+            min,
+            max,
+            on_side,
+        };
+
+        match self {
+            BlockShape::Slab => vec![
+                face(Face::Top, [-1.0, 0.0, -1.0], [1.0, 0.0, 1.0], false),
+                face(Face::Bottom, [-1.0, -1.0, -1.0], [1.0, -1.0, 1.0], true),
+                face(Face::Left, [-1.0, -1.0, -1.0], [-1.0, 0.0, 1.0], true),
+                face(Face::Right, [1.0, -1.0, -1.0], [1.0, 0.0, 1.0], true),
+                face(Face::Front, [-1.0, -1.0, -1.0], [1.0, 0.0, -1.0], true),
+                face(Face::Back, [-1.0, -1.0, 1.0], [1.0, 0.0, 1.0], true),
+            ],
+            // Laid out for stairs whose full height side faces `Face::Back`, then turned.
+            BlockShape::Stairs(facing) => [
+                face(Face::Top, [-1.0, 0.0, -1.0], [1.0, 0.0, 0.0], false),
+                face(Face::Front, [-1.0, 0.0, 0.0], [1.0, 1.0, 0.0], false),
+                face(Face::Top, [-1.0, 1.0, 0.0], [1.0, 1.0, 1.0], true),
+                face(Face::Bottom, [-1.0, -1.0, -1.0], [1.0, -1.0, 1.0], true),
+                face(Face::Back, [-1.0, -1.0, 1.0], [1.0, 1.0, 1.0], true),
+                face(Face::Front, [-1.0, -1.0, -1.0], [1.0, 0.0, -1.0], true),
+                face(Face::Left, [-1.0, -1.0, -1.0], [-1.0, 0.0, 1.0], true),
+                face(Face::Left, [-1.0, 0.0, 0.0], [-1.0, 1.0, 1.0], true),
+                face(Face::Right, [1.0, -1.0, -1.0], [1.0, 0.0, 1.0], true),
+                face(Face::Right, [1.0, 0.0, 0.0], [1.0, 1.0, 1.0], true),
+            ]
+            .map(|face| face.rotated(*facing))
+            .to_vec(),
+            BlockShape::Panel(Face::Top | Face::Bottom) => {
+                let inner = PANEL_THICKNESS - 1.0;
+                vec![
+                    face(Face::Top, [-1.0, inner, -1.0], [1.0, inner, 1.0], false),
+                    face(Face::Bottom, [-1.0, -1.0, -1.0], [1.0, -1.0, 1.0], true),
+                    face(Face::Left, [-1.0, -1.0, -1.0], [-1.0, inner, 1.0], true),
+                    face(Face::Right, [1.0, -1.0, -1.0], [1.0, inner, 1.0], true),
+                    face(Face::Front, [-1.0, -1.0, -1.0], [1.0, inner, -1.0], true),
+                    face(Face::Back, [-1.0, -1.0, 1.0], [1.0, inner, 1.0], true),
+                ]
+            }
+            // Laid out for a panel against the `Face::Back` side, then turned.
+            BlockShape::Panel(side) => {
+                let inner = 1.0 - PANEL_THICKNESS;
+                [
+                    face(Face::Top, [-1.0, 1.0, inner], [1.0, 1.0, 1.0], true),
+                    face(Face::Bottom, [-1.0, -1.0, inner], [1.0, -1.0, 1.0], true),
+                    face(Face::Front, [-1.0, -1.0, inner], [1.0, 1.0, inner], false),
+                    face(Face::Back, [-1.0, -1.0, 1.0], [1.0, 1.0, 1.0], true),
+                    face(Face::Left, [-1.0, -1.0, inner], [-1.0, 1.0, 1.0], true),
+                    face(Face::Right, [1.0, -1.0, inner], [1.0, 1.0, 1.0], true),
+                ]
+                .map(|face| face.rotated(*side))
+                .to_vec()
+            }
+            BlockShape::Torch(side) => {
+                // Torches on walls sit higher, against the wall, instead of on the ground.
+                let w = TORCH_WIDTH / 2.0;
+                let (bottom, z) = if *side == Face::Bottom {
+                    (-1.0, 0.0)
+                } else {
+                    (-1.0 + TORCH_WALL_HEIGHT, 1.0 - w)
+                };
+                let top = bottom + TORCH_HEIGHT;
+                let (min, max) = ([-w, bottom, z - w], [w, top, z + w]);
+                let faces = [
+                    face(
+                        Face::Top,
+                        [min[0], top, min[2]],
+                        [max[0], top, max[2]],
+                        false,
+                    ),
+                    face(
+                        Face::Bottom,
+                        [min[0], bottom, min[2]],
+                        [max[0], bottom, max[2]],
+                        false,
+                    ),
+                    face(
+                        Face::Left,
+                        [min[0], bottom, min[2]],
+                        [min[0], top, max[2]],
+                        false,
+                    ),
+                    face(
+                        Face::Right,
+                        [max[0], bottom, min[2]],
+                        [max[0], top, max[2]],
+                        false,
+                    ),
+                    face(
+                        Face::Front,
+                        [min[0], bottom, min[2]],
+                        [max[0], top, min[2]],
+                        false,
+                    ),
+                    face(
+                        Face::Back,
+                        [min[0], bottom, max[2]],
+                        [max[0], top, max[2]],
+                        false,
+                    ),
+                ];
+                if *side == Face::Bottom {
+                    faces.to_vec()
+                } else {
+                    // Laid out for a torch on the wall on the `Face::Back` side, then turned.
+                    faces.map(|face| face.rotated(*side)).to_vec()
+                }
+            }
+            BlockShape::Empty | BlockShape::Cube | BlockShape::Cross => Vec::new(),
+        }
+    }
 }
 
 #[repr(u32)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Face {
     Top,
     Bottom,
@@ -306,33 +629,121 @@ pub enum Face {
     Back,
 }
 
-impl BlockType {
-    pub fn tex_coords(&self, face: Face) -> [[f32; 2]; 4] {
-        let (x, y) = match self {
-            BlockType::Grass => match face {
-                Face::Top => (0, 0),
-                Face::Bottom => (2, 0),
-                Face::Left | Face::Right | Face::Front | Face::Back => (3, 0),
-            },
-            BlockType::Dirt => (2, 0),
-            BlockType::Stone => (1, 0),
-            BlockType::Air => (3, 0),
-        };
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Top,
+        Face::Bottom,
+        Face::Left,
+        Face::Right,
+        Face::Front,
+        Face::Back,
+    ];
 
-        let u_min = x as f32 * BLOCK_SIZE / ATLAS_SIZE;
-        let v_min = y as f32 * BLOCK_SIZE / ATLAS_SIZE;
-        let u_max = u_min + BLOCK_SIZE / ATLAS_SIZE;
-        let v_max = v_min + BLOCK_SIZE / ATLAS_SIZE;
+    /// Returns the direction the face points in.
+    pub fn normal(&self) -> [isize; 3] {
+        match self {
+            Face::Top => [0, 1, 0],
+            Face::Bottom => [0, -1, 0],
+            Face::Left => [-1, 0, 0],
+            Face::Right => [1, 0, 0],
+            Face::Front => [0, 0, -1],
+            Face::Back => [0, 0, 1],
+        }
+    }
 
-        let mut uv_coords = [
-            [u_min, v_min],
-            [u_max, v_min],
-            [u_max, v_max],
-            [u_min, v_max],
-        ];
+    /// Returns the face pointing in the direction `normal`, if it's one of the six.
+    pub fn from_normal(normal: [isize; 3]) -> Option<Face> {
+        Face::ALL.into_iter().find(|face| face.normal() == normal)
+    }
+
+    /// Returns the side face pointing closest to `direction`, ignoring its vertical part.
+    pub fn horizontal(direction: Vector3<f32>) -> Face {
+        if direction.x.abs() > direction.z.abs() {
+            if direction.x > 0.0 {
+                Face::Right
+            } else {
+                Face::Left
+            }
+        } else if direction.z > 0.0 {
+            Face::Back
+        } else {
+            Face::Front
+        }
+    }
+
+    /// Returns the face pointing in the opposite direction.
+    pub fn opposite(&self) -> Face {
+        match self {
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+            Face::Left => Face::Right,
+            Face::Right => Face::Left,
+            Face::Front => Face::Back,
+            Face::Back => Face::Front,
+        }
+    }
+
+    /// Returns the axis the face is perpendicular to, followed by the two axes spanning it.
+    pub fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            Face::Top | Face::Bottom => (1, 0, 2),
+            Face::Left | Face::Right => (0, 2, 1),
+            Face::Front | Face::Back => (2, 0, 1),
+        }
+    }
+
+    /// The corners of the face as offsets from the block center, in winding order.
+    pub fn corners(&self) -> [[f32; 3]; 4] {
+        match self {
+            Face::Top => [
+                [-1.0, 1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [1.0, 1.0, 1.0],
+                [-1.0, 1.0, 1.0],
+            ],
+            Face::Bottom => [
+                [-1.0, -1.0, -1.0],
+                [1.0, -1.0, -1.0],
+                [1.0, -1.0, 1.0],
+                [-1.0, -1.0, 1.0],
+            ],
+            Face::Left => [
+                [-1.0, -1.0, -1.0],
+                [-1.0, 1.0, -1.0],
+                [-1.0, 1.0, 1.0],
+                [-1.0, -1.0, 1.0],
+            ],
+            Face::Right => [
+                [1.0, -1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [1.0, 1.0, 1.0],
+                [1.0, -1.0, 1.0],
+            ],
+            Face::Front => [
+                [-1.0, -1.0, -1.0],
+                [1.0, -1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [-1.0, 1.0, -1.0],
+            ],
+            Face::Back => [
+                [-1.0, -1.0, 1.0],
+                [1.0, -1.0, 1.0],
+                [1.0, 1.0, 1.0],
+                [-1.0, 1.0, 1.0],
+            ],
+        }
+    }
+
+    /// Returns the tile-local texture coordinates of the face corners for a quad spanning
+    /// `size` blocks. Each whole unit maps to one repeat of the block texture.
+    fn tex_coords(&self, size: [f32; 3]) -> [[f32; 2]; 4] {
+        let (_, u_axis, v_axis) = self.axes();
+        let (u, v) = (size[u_axis], size[v_axis]);
+
+        let mut uv_coords = [[0.0, 0.0], [u, 0.0], [u, v], [0.0, v]];
 
         // Fix uv coordinates for the sides of a block.
-        match face {
+        match self {
             Face::Front | Face::Back => uv_coords.rotate_right(2),
             Face::Left | Face::Right => uv_coords.rotate_right(1),
             _ => {}
@@ -341,3 +752,681 @@ impl BlockType {
         uv_coords
     }
 }
+
+impl BlockType {
+    pub const ALL: [BlockType; 67] = [
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Stone,
+        BlockType::Air,
+        BlockType::Sand,
+        BlockType::Snow,
+        BlockType::Log,
+        BlockType::Leaves,
+        BlockType::TallGrass,
+        BlockType::Rose,
+        BlockType::Dandelion,
+        BlockType::Water,
+        BlockType::Bedrock,
+        BlockType::SnowyStone,
+        BlockType::Cobblestone,
+        BlockType::Planks,
+        BlockType::Sandstone,
+        BlockType::Glass,
+        BlockType::CobblestoneSlab,
+        BlockType::PlanksSlab,
+        BlockType::PlanksStairsNorth,
+        BlockType::PlanksStairsSouth,
+        BlockType::PlanksStairsWest,
+        BlockType::PlanksStairsEast,
+        BlockType::FlowingWater1,
+        BlockType::FlowingWater2,
+        BlockType::FlowingWater3,
+        BlockType::FlowingWater4,
+        BlockType::FlowingWater5,
+        BlockType::FlowingWater6,
+        BlockType::FlowingWater7,
+        BlockType::Gravel,
+        BlockType::DoorNorth,
+        BlockType::DoorNorthOpen,
+        BlockType::DoorSouth,
+        BlockType::DoorSouthOpen,
+        BlockType::DoorWest,
+        BlockType::DoorWestOpen,
+        BlockType::DoorEast,
+        BlockType::DoorEastOpen,
+        BlockType::DoorTopNorth,
+        BlockType::DoorTopNorthOpen,
+        BlockType::DoorTopSouth,
+        BlockType::DoorTopSouthOpen,
+        BlockType::DoorTopWest,
+        BlockType::DoorTopWestOpen,
+        BlockType::DoorTopEast,
+        BlockType::DoorTopEastOpen,
+        BlockType::TrapdoorNorth,
+        BlockType::TrapdoorNorthOpen,
+        BlockType::TrapdoorSouth,
+        BlockType::TrapdoorSouthOpen,
+        BlockType::TrapdoorWest,
+        BlockType::TrapdoorWestOpen,
+        BlockType::TrapdoorEast,
+        BlockType::TrapdoorEastOpen,
+        BlockType::Torch,
+        BlockType::TorchNorth,
+        BlockType::TorchSouth,
+        BlockType::TorchWest,
+        BlockType::TorchEast,
+        BlockType::Chest,
+        BlockType::Sapling,
+        BlockType::Wheat1,
+        BlockType::Wheat2,
+        BlockType::Wheat3,
+        BlockType::Wheat4,
+    ];
+
+    /// The flowing water blocks, by how far they are from a source minus one.
+    const FLOWING_WATER: [BlockType; 7] = [
+        BlockType::FlowingWater1,
+        BlockType::FlowingWater2,
+        BlockType::FlowingWater3,
+        BlockType::FlowingWater4,
+        BlockType::FlowingWater5,
+        BlockType::FlowingWater6,
+        BlockType::FlowingWater7,
+    ];
+
+    /// How far wheat grows.
+    pub const RIPE_WHEAT: u8 = 4;
+
+    /// The wheat blocks, by how far they have grown minus one.
+    const WHEAT: [BlockType; 4] = [
+        BlockType::Wheat1,
+        BlockType::Wheat2,
+        BlockType::Wheat3,
+        BlockType::Wheat4,
+    ];
+
+    /// The side faces, in the order door and trapdoor facings are listed in.
+    const HORIZONTAL_FACES: [Face; 4] = [Face::Front, Face::Back, Face::Left, Face::Right];
+
+    /// The door blocks, by half, then facing, then whether they're open.
+    const DOORS: [BlockType; 16] = [
+        BlockType::DoorNorth,
+        BlockType::DoorNorthOpen,
+        BlockType::DoorSouth,
+        BlockType::DoorSouthOpen,
+        BlockType::DoorWest,
+        BlockType::DoorWestOpen,
+        BlockType::DoorEast,
+        BlockType::DoorEastOpen,
+        BlockType::DoorTopNorth,
+        BlockType::DoorTopNorthOpen,
+        BlockType::DoorTopSouth,
+        BlockType::DoorTopSouthOpen,
+        BlockType::DoorTopWest,
+        BlockType::DoorTopWestOpen,
+        BlockType::DoorTopEast,
+        BlockType::DoorTopEastOpen,
+    ];
+
+    /// The trapdoor blocks, by facing, then whether they're open.
+    const TRAPDOORS: [BlockType; 8] = [
+        BlockType::TrapdoorNorth,
+        BlockType::TrapdoorNorthOpen,
+        BlockType::TrapdoorSouth,
+        BlockType::TrapdoorSouthOpen,
+        BlockType::TrapdoorWest,
+        BlockType::TrapdoorWestOpen,
+        BlockType::TrapdoorEast,
+        BlockType::TrapdoorEastOpen,
+    ];
+
+    /// Returns the name of the block type, as used in settings and commands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Stone => "stone",
+            BlockType::Air => "air",
+            BlockType::Sand => "sand",
+            BlockType::Snow => "snow",
+            BlockType::Log => "log",
+            BlockType::Leaves => "leaves",
+            BlockType::TallGrass => "tall_grass",
+            BlockType::Rose => "rose",
+            BlockType::Dandelion => "dandelion",
+            BlockType::Water => "water",
+            BlockType::Bedrock => "bedrock",
+            BlockType::SnowyStone => "snowy_stone",
+            BlockType::Cobblestone => "cobblestone",
+            BlockType::Planks => "planks",
+            BlockType::Sandstone => "sandstone",
+            BlockType::Glass => "glass",
+            BlockType::CobblestoneSlab => "cobblestone_slab",
+            BlockType::PlanksSlab => "planks_slab",
+            BlockType::PlanksStairsNorth => "planks_stairs_north",
+            BlockType::PlanksStairsSouth => "planks_stairs_south",
+            BlockType::PlanksStairsWest => "planks_stairs_west",
+            BlockType::PlanksStairsEast => "planks_stairs_east",
+            BlockType::FlowingWater1 => "flowing_water_1",
+            BlockType::FlowingWater2 => "flowing_water_2",
+            BlockType::FlowingWater3 => "flowing_water_3",
+            BlockType::FlowingWater4 => "flowing_water_4",
+            BlockType::FlowingWater5 => "flowing_water_5",
+            BlockType::FlowingWater6 => "flowing_water_6",
+            BlockType::FlowingWater7 => "flowing_water_7",
+            BlockType::Gravel => "gravel",
+            BlockType::DoorNorth => "door_north",
+            BlockType::DoorNorthOpen => "door_north_open",
+            BlockType::DoorSouth => "door_south",
+            BlockType::DoorSouthOpen => "door_south_open",
+            BlockType::DoorWest => "door_west",
+            BlockType::DoorWestOpen => "door_west_open",
+            BlockType::DoorEast => "door_east",
+            BlockType::DoorEastOpen => "door_east_open",
+            BlockType::DoorTopNorth => "door_top_north",
+            BlockType::DoorTopNorthOpen => "door_top_north_open",
+            BlockType::DoorTopSouth => "door_top_south",
+            BlockType::DoorTopSouthOpen => "door_top_south_open",
+            BlockType::DoorTopWest => "door_top_west",
+            BlockType::DoorTopWestOpen => "door_top_west_open",
+            BlockType::DoorTopEast => "door_top_east",
+            BlockType::DoorTopEastOpen => "door_top_east_open",
+            BlockType::TrapdoorNorth => "trapdoor_north",
+            BlockType::TrapdoorNorthOpen => "trapdoor_north_open",
+            BlockType::TrapdoorSouth => "trapdoor_south",
+            BlockType::TrapdoorSouthOpen => "trapdoor_south_open",
+            BlockType::TrapdoorWest => "trapdoor_west",
+            BlockType::TrapdoorWestOpen => "trapdoor_west_open",
+            BlockType::TrapdoorEast => "trapdoor_east",
+            BlockType::TrapdoorEastOpen => "trapdoor_east_open",
+            BlockType::Torch => "torch",
+            BlockType::TorchNorth => "torch_north",
+            BlockType::TorchSouth => "torch_south",
+            BlockType::TorchWest => "torch_west",
+            BlockType::TorchEast => "torch_east",
+            BlockType::Chest => "chest",
+            BlockType::Sapling => "sapling",
+            BlockType::Wheat1 => "wheat_1",
+            BlockType::Wheat2 => "wheat_2",
+            BlockType::Wheat3 => "wheat_3",
+            BlockType::Wheat4 => "wheat_4",
+        }
+    }
+
+    /// Returns the block type with the given name.
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    /// Returns the block type with the given numeric id, as produced by `block_type as u32`.
+    pub fn from_id(id: u32) -> Option<BlockType> {
+        Self::ALL.into_iter().find(|b| *b as u32 == id)
+    }
+
+    pub fn is_air(&self) -> bool {
+        *self == BlockType::Air
+    }
+
+    pub fn shape(&self) -> BlockShape {
+        match self {
+            BlockType::Air => BlockShape::Empty,
+            BlockType::TallGrass
+            | BlockType::Rose
+            | BlockType::Dandelion
+            | BlockType::Sapling
+            | BlockType::Wheat1
+            | BlockType::Wheat2
+            | BlockType::Wheat3
+            | BlockType::Wheat4 => BlockShape::Cross,
+            BlockType::CobblestoneSlab | BlockType::PlanksSlab => BlockShape::Slab,
+            BlockType::PlanksStairsNorth => BlockShape::Stairs(Face::Front),
+            BlockType::PlanksStairsSouth => BlockShape::Stairs(Face::Back),
+            BlockType::PlanksStairsWest => BlockShape::Stairs(Face::Left),
+            BlockType::PlanksStairsEast => BlockShape::Stairs(Face::Right),
+            BlockType::Torch => BlockShape::Torch(Face::Bottom),
+            BlockType::TorchNorth => BlockShape::Torch(Face::Front),
+            BlockType::TorchSouth => BlockShape::Torch(Face::Back),
+            BlockType::TorchWest => BlockShape::Torch(Face::Left),
+            BlockType::TorchEast => BlockShape::Torch(Face::Right),
+            _ => {
+                if let Some((facing, open, _)) = self.door_state() {
+                    BlockShape::Panel(if open { Self::swung(facing) } else { facing })
+                } else if let Some((facing, open)) = self.trapdoor_state() {
+                    BlockShape::Panel(if open { facing } else { Face::Bottom })
+                } else {
+                    BlockShape::Cube
+                }
+            }
+        }
+    }
+
+    /// Returns the block turned so it faces `facing`, for stairs, doors and trapdoors, which
+    /// face away from whoever places them. Other blocks don't turn.
+    pub fn facing(&self, facing: Face) -> BlockType {
+        if !Self::HORIZONTAL_FACES.contains(&facing) {
+            return *self;
+        }
+        if let Some((_, open, upper)) = self.door_state() {
+            return Self::door(facing, open, upper);
+        }
+        if let Some((_, open)) = self.trapdoor_state() {
+            return Self::trapdoor(facing, open);
+        }
+        if !matches!(self.shape(), BlockShape::Stairs(_)) {
+            return *self;
+        }
+        match facing {
+            Face::Front => BlockType::PlanksStairsNorth,
+            Face::Back => BlockType::PlanksStairsSouth,
+            Face::Left => BlockType::PlanksStairsWest,
+            Face::Right => BlockType::PlanksStairsEast,
+            Face::Top | Face::Bottom => *self,
+        }
+    }
+
+    /// Returns whether the block fills the side toward `face` with something that can't be
+    /// seen through, hiding the face of the neighbor on that side.
+    pub fn covers(&self, face: Face) -> bool {
+        self.shape().covers(face) && !matches!(self, BlockType::Leaves) && !self.is_translucent()
+    }
+
+    /// Returns whether the block hides everything behind it. Leaves have holes in their
+    /// texture and translucent blocks can be seen through, so they don't.
+    pub fn is_opaque(&self) -> bool {
+        self.shape() == BlockShape::Cube
+            && !matches!(self, BlockType::Leaves)
+            && !self.is_translucent()
+    }
+
+    /// Returns whether the block is see-through and drawn blended in the transparent pass.
+    /// Leaves and plants only have fully clear holes, so they are cut out in the opaque pass.
+    pub fn is_translucent(&self) -> bool {
+        self.is_liquid() || *self == BlockType::Glass
+    }
+
+    /// Returns the pass faces of the block are drawn in.
+    pub fn pass(&self) -> MeshPass {
+        match self {
+            _ if self.is_liquid() => MeshPass::Water,
+            _ if self.is_translucent() => MeshPass::Transparent,
+            _ => MeshPass::Opaque,
+        }
+    }
+
+    /// Returns whether the block stops things moving into it. Plants, liquids and open doors
+    /// and trapdoors can be moved through, and slabs, stairs and closed doors and trapdoors
+    /// stop things like full cubes.
+    pub fn is_solid(&self) -> bool {
+        match self.shape() {
+            BlockShape::Cube | BlockShape::Slab | BlockShape::Stairs(_) => !self.is_liquid(),
+            BlockShape::Panel(_) => !self.is_open(),
+            BlockShape::Empty | BlockShape::Cross | BlockShape::Torch(_) => false,
+        }
+    }
+
+    /// Returns whether the block can be pointed at, to break it or place blocks against it.
+    /// Liquids can't.
+    pub fn is_targetable(&self) -> bool {
+        self.shape() != BlockShape::Empty && !self.is_liquid()
+    }
+
+    /// Returns whether placing a block where this one is replaces it. Air, liquids and tall
+    /// grass can be placed into.
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self, BlockType::Air | BlockType::TallGrass) || self.is_liquid()
+    }
+
+    /// Returns whether players can break the block.
+    pub fn is_breakable(&self) -> bool {
+        self.hardness().is_some()
+    }
+
+    /// Returns how hard the block is to break, which is how long breaking it by hand takes
+    /// relative to other blocks, or `None` if it can't be broken. Blocks of hardness 0 break
+    /// right away.
+    pub fn hardness(&self) -> Option<f32> {
+        match self {
+            BlockType::Air | BlockType::Bedrock => None,
+            _ if self.is_liquid() => None,
+            BlockType::TallGrass | BlockType::Rose | BlockType::Dandelion => Some(0.0),
+            BlockType::Sapling => Some(0.0),
+            _ if self.wheat_stage().is_some() => Some(0.0),
+            _ if self.attachment().is_some() => Some(0.0),
+            BlockType::Leaves => Some(0.2),
+            BlockType::Glass => Some(0.3),
+            BlockType::Dirt | BlockType::Sand => Some(0.5),
+            BlockType::Grass | BlockType::Snow | BlockType::Gravel => Some(0.6),
+            BlockType::Sandstone => Some(0.8),
+            BlockType::Stone | BlockType::SnowyStone => Some(1.5),
+            BlockType::Chest => Some(2.5),
+            _ => Some(2.0),
+        }
+    }
+
+    /// Returns the kind of tool that breaks the block faster, if any.
+    pub fn tool(&self) -> Option<ToolKind> {
+        match self {
+            BlockType::Stone
+            | BlockType::SnowyStone
+            | BlockType::Cobblestone
+            | BlockType::CobblestoneSlab
+            | BlockType::Sandstone => Some(ToolKind::Pickaxe),
+            BlockType::Dirt
+            | BlockType::Grass
+            | BlockType::Snow
+            | BlockType::Sand
+            | BlockType::Gravel => Some(ToolKind::Shovel),
+            BlockType::Log
+            | BlockType::Planks
+            | BlockType::PlanksSlab
+            | BlockType::PlanksStairsNorth
+            | BlockType::PlanksStairsSouth
+            | BlockType::PlanksStairsWest
+            | BlockType::PlanksStairsEast
+            | BlockType::Chest => Some(ToolKind::Axe),
+            _ if self.is_interactive() => Some(ToolKind::Axe),
+            _ => None,
+        }
+    }
+
+    /// Returns whether breaking the block without its tool takes much longer, which it does
+    /// for stone.
+    pub fn needs_tool(&self) -> bool {
+        self.tool() == Some(ToolKind::Pickaxe)
+    }
+
+    /// Returns how much dimmer light gets when passing through the block, on top of the usual
+    /// falloff. Opaque blocks stop light entirely.
+    pub fn light_filter(&self) -> u8 {
+        match self {
+            BlockType::Leaves => 1,
+            _ if self.is_liquid() => 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns the block light level the block gives off.
+    pub fn light_emission(&self) -> u8 {
+        match self.shape() {
+            BlockShape::Torch(_) => MAX_LIGHT - 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns the side of the block the block holding it up is on, for torches, which break
+    /// without it.
+    pub fn attachment(&self) -> Option<Face> {
+        match self.shape() {
+            BlockShape::Torch(side) => Some(side),
+            _ => None,
+        }
+    }
+
+    /// Returns the block held up by the block on its `side`, for torches, which stand on
+    /// blocks below them and hang on walls but can't hang from ceilings. Other blocks don't
+    /// change.
+    pub fn attached(&self, side: Face) -> Option<BlockType> {
+        if self.attachment().is_none() {
+            return Some(*self);
+        }
+        match side {
+            Face::Bottom => Some(BlockType::Torch),
+            Face::Front => Some(BlockType::TorchNorth),
+            Face::Back => Some(BlockType::TorchSouth),
+            Face::Left => Some(BlockType::TorchWest),
+            Face::Right => Some(BlockType::TorchEast),
+            Face::Top => None,
+        }
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        self.water_distance().is_some()
+    }
+
+    /// Returns whether the block falls when nothing holds it up.
+    pub fn falls(&self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
+
+    /// Returns how many blocks away from a water source the block is, 0 for sources, or
+    /// `None` if it isn't water.
+    pub fn water_distance(&self) -> Option<u8> {
+        match self {
+            BlockType::Water => Some(0),
+            _ => Self::FLOWING_WATER
+                .iter()
+                .position(|block| block == self)
+                .map(|i| i as u8 + 1),
+        }
+    }
+
+    /// Returns the water `distance` blocks away from a source, from 0 for a source up to 7.
+    pub fn water(distance: u8) -> BlockType {
+        match distance {
+            0 => BlockType::Water,
+            _ => Self::FLOWING_WATER[(distance as usize - 1).min(Self::FLOWING_WATER.len() - 1)],
+        }
+    }
+
+    /// Returns how far wheat has grown, from 1 up to [`BlockType::RIPE_WHEAT`], or `None` if
+    /// the block isn't wheat.
+    pub fn wheat_stage(&self) -> Option<u8> {
+        Self::WHEAT
+            .iter()
+            .position(|block| block == self)
+            .map(|i| i as u8 + 1)
+    }
+
+    /// Returns wheat grown as far as `stage`, from 1 up to [`BlockType::RIPE_WHEAT`].
+    pub fn wheat(stage: u8) -> BlockType {
+        Self::WHEAT[(stage.max(1) as usize - 1).min(Self::WHEAT.len() - 1)]
+    }
+
+    /// Returns the side a door panel facing `facing` swings to when the door opens.
+    fn swung(facing: Face) -> Face {
+        match facing {
+            Face::Front => Face::Left,
+            Face::Left => Face::Back,
+            Face::Back => Face::Right,
+            Face::Right => Face::Front,
+            Face::Top | Face::Bottom => facing,
+        }
+    }
+
+    /// Returns the side the panel of the door is on while closed, whether it's open and
+    /// whether it's the top half, or `None` if the block isn't a door.
+    pub fn door_state(&self) -> Option<(Face, bool, bool)> {
+        let index = Self::DOORS.iter().position(|block| block == self)?;
+        Some((
+            Self::HORIZONTAL_FACES[index / 2 % 4],
+            index % 2 == 1,
+            index >= 8,
+        ))
+    }
+
+    /// Returns the half of a door facing `facing`, the top half if `upper`.
+    pub fn door(facing: Face, open: bool, upper: bool) -> BlockType {
+        let facing = Self::HORIZONTAL_FACES
+            .iter()
+            .position(|&face| face == facing)
+            .unwrap_or(0);
+        Self::DOORS[usize::from(upper) * 8 + facing * 2 + usize::from(open)]
+    }
+
+    /// Returns the side the panel of the trapdoor swings up to and whether it's open, or
+    /// `None` if the block isn't a trapdoor.
+    pub fn trapdoor_state(&self) -> Option<(Face, bool)> {
+        let index = Self::TRAPDOORS.iter().position(|block| block == self)?;
+        Some((Self::HORIZONTAL_FACES[index / 2], index % 2 == 1))
+    }
+
+    /// Returns the trapdoor swinging up to the side `facing` when open.
+    pub fn trapdoor(facing: Face, open: bool) -> BlockType {
+        let facing = Self::HORIZONTAL_FACES
+            .iter()
+            .position(|&face| face == facing)
+            .unwrap_or(0);
+        Self::TRAPDOORS[facing * 2 + usize::from(open)]
+    }
+
+    /// Returns whether the block is an open door or trapdoor.
+    pub fn is_open(&self) -> bool {
+        self.door_state().is_some_and(|(_, open, _)| open)
+            || self.trapdoor_state().is_some_and(|(_, open)| open)
+    }
+
+    /// Returns the block opened if it's a closed door or trapdoor, or closed if it's open.
+    /// Other blocks stay the same.
+    pub fn toggled(&self) -> BlockType {
+        if let Some((facing, open, upper)) = self.door_state() {
+            Self::door(facing, !open, upper)
+        } else if let Some((facing, open)) = self.trapdoor_state() {
+            Self::trapdoor(facing, !open)
+        } else {
+            *self
+        }
+    }
+
+    /// Returns the block picked up when the block is broken, turned the way it's held to be
+    /// placed again, or `None` if it gives nothing, like liquids and the top halves of doors.
+    pub fn dropped(&self) -> Option<BlockType> {
+        if self.is_air() || self.is_liquid() {
+            return None;
+        }
+        if let Some((_, _, upper)) = self.door_state() {
+            return (!upper).then(|| Self::door(Face::Front, false, false));
+        }
+        if self.trapdoor_state().is_some() {
+            return Some(Self::trapdoor(Face::Front, false));
+        }
+        if self.attachment().is_some() {
+            return Some(BlockType::Torch);
+        }
+        if self.wheat_stage().is_some() {
+            return Some(BlockType::Wheat1);
+        }
+        Some(self.facing(Face::Front))
+    }
+
+    /// Returns whether the block holds items, which are shown when it's used.
+    pub fn is_container(&self) -> bool {
+        *self == BlockType::Chest
+    }
+
+    /// Returns whether the block does something when used, like doors opening.
+    pub fn is_interactive(&self) -> bool {
+        self.door_state().is_some() || self.trapdoor_state().is_some()
+    }
+
+    /// Returns whether `face` of the block is drawn when `neighbor` is on the other side of it.
+    /// Faces are hidden by neighbors covering the whole side facing them, so slabs don't hide
+    /// the blocks beside them. Faces between two translucent blocks of the same type, or two
+    /// liquids, are hidden, so bodies of water and panes of glass only show their surface.
+    pub fn is_face_visible(&self, face: Face, neighbor: BlockType) -> bool {
+        let same = neighbor == *self || (self.is_liquid() && neighbor.is_liquid());
+        !neighbor.covers(face.opposite()) && !(self.is_translucent() && same)
+    }
+
+    /// Returns the layer of the terrain texture array used for `face`.
+    pub fn layer(&self, face: Face) -> u32 {
+        texture_layers().faces[*self as usize][face as usize]
+    }
+
+    /// Returns the column and row of the atlas tile used for `face`.
+    pub fn tile(&self, face: Face) -> (u32, u32) {
+        match self {
+            BlockType::Grass => match face {
+                Face::Top => (0, 0),
+                Face::Bottom => (2, 0),
+                Face::Left | Face::Right | Face::Front | Face::Back => (3, 0),
+            },
+            BlockType::Snow => match face {
+                Face::Top => (2, 4),
+                Face::Bottom => (2, 0),
+                Face::Left | Face::Right | Face::Front | Face::Back => (4, 4),
+            },
+            BlockType::Dirt => (2, 0),
+            BlockType::Stone => (1, 0),
+            BlockType::Sand => (2, 1),
+            BlockType::Gravel => (3, 1),
+            BlockType::Log => match face {
+                Face::Top | Face::Bottom => (5, 1),
+                Face::Left | Face::Right | Face::Front | Face::Back => (4, 1),
+            },
+            BlockType::Leaves => (6, 1),
+            BlockType::TallGrass => (5, 2),
+            BlockType::Rose => (6, 2),
+            BlockType::Dandelion => (7, 2),
+            BlockType::Sapling => (15, 0),
+            BlockType::Wheat1 => (8, 2),
+            BlockType::Wheat2 => (9, 2),
+            BlockType::Wheat3 => (10, 2),
+            BlockType::Wheat4 => (11, 2),
+            BlockType::Water
+            | BlockType::FlowingWater1
+            | BlockType::FlowingWater2
+            | BlockType::FlowingWater3
+            | BlockType::FlowingWater4
+            | BlockType::FlowingWater5
+            | BlockType::FlowingWater6
+            | BlockType::FlowingWater7 => (14, 0),
+            BlockType::Glass => (1, 3),
+            BlockType::Bedrock => (1, 1),
+            BlockType::Cobblestone | BlockType::CobblestoneSlab => (0, 1),
+            BlockType::Planks
+            | BlockType::PlanksSlab
+            | BlockType::PlanksStairsNorth
+            | BlockType::PlanksStairsSouth
+            | BlockType::PlanksStairsWest
+            | BlockType::PlanksStairsEast
+            | BlockType::DoorNorth
+            | BlockType::DoorNorthOpen
+            | BlockType::DoorSouth
+            | BlockType::DoorSouthOpen
+            | BlockType::DoorWest
+            | BlockType::DoorWestOpen
+            | BlockType::DoorEast
+            | BlockType::DoorEastOpen
+            | BlockType::DoorTopNorth
+            | BlockType::DoorTopNorthOpen
+            | BlockType::DoorTopSouth
+            | BlockType::DoorTopSouthOpen
+            | BlockType::DoorTopWest
+            | BlockType::DoorTopWestOpen
+            | BlockType::DoorTopEast
+            | BlockType::DoorTopEastOpen
+            | BlockType::TrapdoorNorth
+            | BlockType::TrapdoorNorthOpen
+            | BlockType::TrapdoorSouth
+            | BlockType::TrapdoorSouthOpen
+            | BlockType::TrapdoorWest
+            | BlockType::TrapdoorWestOpen
+            | BlockType::TrapdoorEast
+            | BlockType::TrapdoorEastOpen => (4, 0),
+            BlockType::Sandstone => match face {
+                Face::Top => (6, 4),
+                Face::Bottom => (8, 4),
+                Face::Left | Face::Right | Face::Front | Face::Back => (7, 4),
+            },
+            BlockType::SnowyStone => match face {
+                Face::Top => (2, 4),
+                Face::Bottom => (1, 0),
+                Face::Left | Face::Right | Face::Front | Face::Back => (5, 4),
+            },
+            BlockType::Torch
+            | BlockType::TorchNorth
+            | BlockType::TorchSouth
+            | BlockType::TorchWest
+            | BlockType::TorchEast => match face {
+                Face::Top => (2, 1),
+                Face::Bottom | Face::Left | Face::Right | Face::Front | Face::Back => (4, 1),
+            },
+            BlockType::Chest => match face {
+                Face::Top | Face::Bottom => (4, 0),
+                Face::Left | Face::Right | Face::Front | Face::Back => (4, 1),
+            },
+            BlockType::Air => (3, 0),
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
 
 use bytemuck::Pod;
 
+use crate::aabb::Aabb;
+
 pub struct Buffer<T: Copy + Pod> {
     pub buf: wgpu::Buffer,
     len: usize,
@@ -60,6 +64,10 @@ impl<T: Copy + Pod> DynamicBuffer<T> {
         self.0.len()
     }
 
+    pub fn into_buffer(self) -> Buffer<T> {
+        self.0
+    }
+
     pub fn buf_mut(&mut self) -> &Buffer<T> {
         &self.0
     }
@@ -68,3 +76,171 @@ impl<T: Copy + Pod> DynamicBuffer<T> {
         &self.0
     }
 }
+
+/// The vertex and index buffers of a mesh uploaded to the GPU. The mesh may only take up part
+/// of the buffers, which can be shared with other meshes, and its room may be larger than the
+/// mesh it currently holds, so it can be reused for other meshes.
+pub struct MeshBuffers<V: Copy + Pod> {
+    pub vertex: Arc<Buffer<V>>,
+    pub index: Arc<Buffer<u32>>,
+    /// The room of the mesh in the buffers, in vertices and indices.
+    vertex_range: Range<u32>,
+    index_range: Range<u32>,
+    vertex_count: u32,
+    index_count: u32,
+    /// Where each group of indices written ends, see [`MeshBuffers::write`].
+    group_ends: Vec<u32>,
+    /// The box around the mesh in world coordinates, `None` if unknown.
+    bounds: Option<Aabb>,
+}
+
+impl<V: Copy + Pod> MeshBuffers<V> {
+    pub fn new(device: &wgpu::Device, vertices: &[V], indices: &[u32]) -> Self {
+        Self {
+            vertex: Arc::new(Buffer::new(device, wgpu::BufferUsages::VERTEX, vertices)),
+            index: Arc::new(Buffer::new(device, wgpu::BufferUsages::INDEX, indices)),
+            vertex_range: 0..vertices.len() as u32,
+            index_range: 0..indices.len() as u32,
+            vertex_count: vertices.len() as u32,
+            index_count: indices.len() as u32,
+            group_ends: vec![indices.len() as u32],
+            bounds: None,
+        }
+    }
+
+    /// Creates empty buffers with room for the given number of vertices and indices.
+    pub fn with_capacity(
+        device: &wgpu::Device,
+        vertex_capacity: usize,
+        index_capacity: usize,
+    ) -> Self {
+        Self::shared(
+            Arc::new(DynamicBuffer::new(device, vertex_capacity, wgpu::BufferUsages::VERTEX).0),
+            Arc::new(DynamicBuffer::new(device, index_capacity, wgpu::BufferUsages::INDEX).0),
+            0..vertex_capacity as u32,
+            0..index_capacity as u32,
+        )
+    }
+
+    /// Creates an empty mesh taking up `vertex_range` and `index_range` of buffers shared with
+    /// other meshes.
+    pub fn shared(
+        vertex: Arc<Buffer<V>>,
+        index: Arc<Buffer<u32>>,
+        vertex_range: Range<u32>,
+        index_range: Range<u32>,
+    ) -> Self {
+        Self {
+            vertex,
+            index,
+            vertex_range,
+            index_range,
+            vertex_count: 0,
+            index_count: 0,
+            group_ends: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    /// Returns whether the mesh has room for the given number of vertices and indices.
+    pub fn fits(&self, vertex_count: usize, index_count: usize) -> bool {
+        vertex_count <= self.vertex_range.len() && index_count <= self.index_range.len()
+    }
+
+    /// Returns the room of the mesh in the vertex buffer, in vertices.
+    pub fn vertex_range(&self) -> Range<u32> {
+        self.vertex_range.clone()
+    }
+
+    /// Returns the room of the mesh in the index buffer, in indices.
+    pub fn index_range(&self) -> Range<u32> {
+        self.index_range.clone()
+    }
+
+    /// Returns whether the mesh is in the same buffers as `other`, so both can be drawn
+    /// without binding other buffers.
+    pub fn shares_buffers(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.vertex, &other.vertex) && Arc::ptr_eq(&self.index, &other.index)
+    }
+
+    /// Replaces the contents of the mesh with the given one, which must fit. The groups of
+    /// indices are stored one after another, so each can be drawn on its own with
+    /// [`MeshBuffers::group`].
+    pub fn write(&mut self, queue: &wgpu::Queue, vertices: &[V], groups: &[&[u32]]) {
+        let index_count: usize = groups.iter().map(|indices| indices.len()).sum();
+        assert!(self.fits(vertices.len(), index_count));
+
+        let vertex_offset = self.vertex_range.start as u64 * std::mem::size_of::<V>() as u64;
+        queue.write_buffer(
+            &self.vertex.buf,
+            vertex_offset,
+            bytemuck::cast_slice(vertices),
+        );
+
+        self.group_ends.clear();
+        let mut end = 0;
+        for indices in groups {
+            if !indices.is_empty() {
+                let offset =
+                    ((self.index_range.start as usize + end) * std::mem::size_of::<u32>()) as u64;
+                queue.write_buffer(&self.index.buf, offset, bytemuck::cast_slice(indices));
+            }
+            end += indices.len();
+            self.group_ends.push(end as u32);
+        }
+
+        self.vertex_count = vertices.len() as u32;
+        self.index_count = index_count as u32;
+    }
+
+    /// Returns the part of the vertex buffer in use by the mesh.
+    pub fn vertex_slice(&self) -> wgpu::BufferSlice {
+        let size = std::mem::size_of::<V>() as u64;
+        let start = self.vertex_range.start as u64 * size;
+        self.vertex
+            .buf
+            .slice(start..start + self.vertex_count as u64 * size)
+    }
+
+    /// Returns the part of the index buffer in use by the mesh.
+    pub fn index_slice(&self) -> wgpu::BufferSlice {
+        let size = std::mem::size_of::<u32>() as u64;
+        let start = self.index_range.start as u64 * size;
+        self.index
+            .buf
+            .slice(start..start + self.index_count as u64 * size)
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    /// Sets the box around the mesh, which lets it be culled when it is hidden.
+    pub fn set_bounds(&mut self, bounds: Option<Aabb>) {
+        self.bounds = bounds;
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// Returns the range of indices of the group written at `index`, which is empty if there
+    /// is no such group. The range is relative to the start of the mesh, see
+    /// [`MeshBuffers::index_slice`].
+    pub fn group(&self, index: usize) -> Range<u32> {
+        let start = match index {
+            0 => 0,
+            _ => self
+                .group_ends
+                .get(index - 1)
+                .copied()
+                .unwrap_or(self.index_count),
+        };
+        let end = self.group_ends.get(index).copied().unwrap_or(start);
+        start..end
+    }
+}
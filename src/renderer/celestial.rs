@@ -0,0 +1,134 @@
+use std::borrow::Cow;
+
+use crate::time::CelestialUniform;
+
+use super::{
+    buffer,
+    frame::FrameTargets,
+    renderer::{BindGroupLayouts, BindGroups},
+};
+
+/// The number of stars, matching the shader.
+pub const STAR_COUNT: u32 = 1024;
+
+/// The sun, the moon and the stars, following the time of day across the sky. They are
+/// drawn as squares facing the camera, over the color of the sky and behind everything else.
+pub struct Celestial {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: buffer::DynamicBuffer<CelestialUniform>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Celestial {
+    /// Creates the pipeline drawing the sky into frames of `texture_format` with
+    /// `sample_count` samples per pixel. It reads the camera bound by `bind_group_layouts`
+    /// and its own uniform.
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let uniform_buffer = buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::UNIFORM);
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Celestial Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Celestial Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.buf().buf.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Celestial shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/shaders/celestial.wgsl"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Celestial Pipeline Layout"),
+            bind_group_layouts: &[bind_group_layouts.camera, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Celestial Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Drawn before the terrain, which covers it without testing against it.
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, celestial: CelestialUniform) {
+        self.uniform_buffer.update(queue, &[celestial], 0);
+    }
+
+    /// Clears the frame in `targets` to `sky_color` and draws the sun, moon and stars over it.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+        sky_color: wgpu::Color,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky pass"),
+            color_attachments: &[Some(
+                targets.color_attachment(wgpu::LoadOp::Clear(sky_color)),
+            )],
+            ..Default::default()
+        });
+        targets.set_viewport(&mut render_pass);
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_groups.camera, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..(2 + STAR_COUNT) * 6, 0..1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use super::{
+    frame::FrameTargets,
+    renderer::{BindGroupLayouts, BindGroups},
+};
+
+/// A layer of blocky clouds high above the terrain, drifting with the time and lit by the sky.
+/// The clouds are drawn on a single plane around the camera, filled in cells where a noise
+/// pattern is above the coverage.
+pub struct Clouds {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Clouds {
+    /// Creates the pipeline drawing clouds into frames of `texture_format` with
+    /// `sample_count` samples per pixel. The clouds read the camera, sky and time uniforms
+    /// bound by `bind_group_layouts`.
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cloud shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/shaders/clouds.wgsl"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloud Pipeline Layout"),
+            bind_group_layouts: &[
+                bind_group_layouts.camera,
+                bind_group_layouts.sky,
+                bind_group_layouts.water,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cloud Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            // Seen from above and below.
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Hidden behind terrain, without hiding the translucent faces behind them.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        });
+
+        Self { pipeline }
+    }
+
+    /// Draws the clouds over the frame in `targets`.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Cloud pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            ..Default::default()
+        });
+        targets.set_viewport(&mut render_pass);
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_groups.camera, &[]);
+        render_pass.set_bind_group(1, bind_groups.sky, &[]);
+        render_pass.set_bind_group(2, bind_groups.water, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,359 @@
+use std::{borrow::Cow, ops::Range};
+
+use super::{
+    block::Face,
+    buffer::{self, MeshBuffers},
+    frame::FrameTargets,
+    renderer::{BindGroupLayouts, BindGroups},
+};
+
+/// A corner of an entity mesh, relative to the origin of the entity.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EntityVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    /// The layer of the terrain texture array the texture is sampled from.
+    pub layer: u32,
+    /// The direction the face points in, which lights it.
+    pub normal: [f32; 3],
+}
+
+impl EntityVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Uint32, 3 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<EntityVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Where an entity mesh is drawn and how bright it is there.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EntityInstance {
+    /// Moves the mesh from the origin of the entity into the world.
+    pub model: [[f32; 4]; 4],
+    /// The sky and block light level at the entity, from 0 to 1.
+    pub light: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+impl EntityInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x2,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<EntityInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Returns the vertices and indices of a box from `min` to `max`, with each face showing a
+/// whole texture from the layer returned by `layer`.
+pub fn cuboid(
+    min: [f32; 3],
+    max: [f32; 3],
+    layer: impl Fn(Face) -> u32,
+) -> (Vec<EntityVertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for face in Face::ALL {
+        let (_, u_axis, v_axis) = face.axes();
+        let normal = face.normal().map(|n| n as f32);
+        let start = vertices.len() as u32;
+        for corner in face.corners() {
+            let position = [0, 1, 2].map(|axis| {
+                let t = (corner[axis] + 1.0) * 0.5;
+                min[axis] + (max[axis] - min[axis]) * t
+            });
+            // Textures are upright on the sides, with the top of the texture facing up.
+            let u = (corner[u_axis] + 1.0) * 0.5;
+            let v = 1.0 - (corner[v_axis] + 1.0) * 0.5;
+            vertices.push(EntityVertex {
+                position,
+                tex_coords: [u, v],
+                layer: layer(face),
+                normal,
+            });
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| start + i));
+    }
+    (vertices, indices)
+}
+
+/// Instances of an entity mesh to draw.
+pub struct EntityBatch<'a> {
+    pub mesh: &'a MeshBuffers<EntityVertex>,
+    pub instances: &'a [EntityInstance],
+}
+
+/// An entity mesh ready to be drawn, with the range of its instances in the instance buffer.
+pub struct EntityDraw<'a> {
+    mesh: &'a MeshBuffers<EntityVertex>,
+    instances: Range<u32>,
+}
+
+/// Draws entities, like players, mobs and dropped items, which move every frame. Entities are
+/// meshes textured from the terrain texture array drawn once per instance, so many entities
+/// sharing a mesh are drawn with a single call.
+pub struct EntityPipeline {
+    pipeline: wgpu::RenderPipeline,
+    /// The instances of all batches of the current frame, one after another.
+    instance_buffer: buffer::DynamicBuffer<EntityInstance>,
+    /// The instances of the batches drawn in front of everything else in the current frame.
+    front_instance_buffer: buffer::DynamicBuffer<EntityInstance>,
+}
+
+impl EntityPipeline {
+    /// Creates the pipeline drawing entities into frames of `texture_format` with
+    /// `sample_count` samples per pixel, reading the camera, terrain textures and sky bound by
+    /// `bind_group_layouts`.
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layouts: &BindGroupLayouts,
+        texture_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Entity shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/shaders/entity.wgsl"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Entity Pipeline Layout"),
+            bind_group_layouts: &[
+                bind_group_layouts.camera,
+                bind_group_layouts.terrain,
+                bind_group_layouts.sky,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Entity Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[EntityVertex::desc(), EntityInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                front_face: wgpu::FrontFace::Ccw,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            pipeline,
+            instance_buffer: buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::VERTEX),
+            front_instance_buffer: buffer::DynamicBuffer::new(
+                device,
+                1,
+                wgpu::BufferUsages::VERTEX,
+            ),
+        }
+    }
+
+    /// Uploads the instances of `batches`, growing the instance buffer if they don't fit, and
+    /// returns the draws for [`EntityPipeline::draw`].
+    pub fn prepare<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batches: &[EntityBatch<'a>],
+    ) -> Vec<EntityDraw<'a>> {
+        upload(device, queue, &mut self.instance_buffer, batches)
+    }
+
+    /// Uploads the instances of `batches` like [`EntityPipeline::prepare`], for
+    /// [`EntityPipeline::draw_in_front_of_all`] instead.
+    pub fn prepare_in_front<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batches: &[EntityBatch<'a>],
+    ) -> Vec<EntityDraw<'a>> {
+        upload(device, queue, &mut self.front_instance_buffer, batches)
+    }
+
+    /// Draws the entities prepared with [`EntityPipeline::prepare`] into the frame in
+    /// `targets`.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+        draws: &[EntityDraw],
+    ) {
+        self.draw_batches(
+            encoder,
+            targets,
+            bind_groups,
+            draws,
+            &self.instance_buffer.buf().buf,
+            wgpu::LoadOp::Load,
+        );
+    }
+
+    /// Draws the meshes prepared with [`EntityPipeline::prepare_in_front`] over everything in
+    /// the frame in `targets`, clearing the depth buffer first like
+    /// [`EntityPipeline::draw_in_front`].
+    pub fn draw_in_front_of_all(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+        draws: &[EntityDraw],
+    ) {
+        self.draw_batches(
+            encoder,
+            targets,
+            bind_groups,
+            draws,
+            &self.front_instance_buffer.buf().buf,
+            wgpu::LoadOp::Clear(1.0),
+        );
+    }
+
+    fn draw_batches(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+        draws: &[EntityDraw],
+        instance_buffer: &wgpu::Buffer,
+        depth: wgpu::LoadOp<f32>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Entity pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(depth)),
+            ..Default::default()
+        });
+        targets.set_viewport(&mut render_pass);
+
+        self.bind(&mut render_pass, bind_groups, instance_buffer);
+
+        for draw in draws {
+            if draw.instances.is_empty() {
+                continue;
+            }
+            render_pass.set_vertex_buffer(0, draw.mesh.vertex_slice());
+            render_pass.set_index_buffer(draw.mesh.index_slice(), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..draw.mesh.index_count(), 0, draw.instances.clone());
+        }
+    }
+
+    /// Draws `mesh` once, with the first instance in `instance_buffer`, over everything in the
+    /// frame in `targets`. The depth buffer is cleared first, so the mesh never clips into the
+    /// terrain in front of it, which is meant for things right in front of the camera drawn
+    /// last.
+    pub fn draw_in_front(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        bind_groups: &BindGroups,
+        mesh: &MeshBuffers<EntityVertex>,
+        instance_buffer: &wgpu::Buffer,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Entity in front pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Clear(1.0))),
+            ..Default::default()
+        });
+        targets.set_viewport(&mut render_pass);
+
+        self.bind(&mut render_pass, bind_groups, instance_buffer);
+        render_pass.set_vertex_buffer(0, mesh.vertex_slice());
+        render_pass.set_index_buffer(mesh.index_slice(), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.index_count(), 0, 0..1);
+    }
+
+    fn bind(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        bind_groups: &BindGroups,
+        instance_buffer: &wgpu::Buffer,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_groups.camera, &[]);
+        render_pass.set_bind_group(1, bind_groups.terrain, &[]);
+        render_pass.set_bind_group(2, bind_groups.sky, &[]);
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+    }
+}
+
+/// Uploads the instances of `batches` one after another into `instance_buffer`, growing it if
+/// they don't fit, and returns the draws of the batches.
+fn upload<'a>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    instance_buffer: &mut buffer::DynamicBuffer<EntityInstance>,
+    batches: &[EntityBatch<'a>],
+) -> Vec<EntityDraw<'a>> {
+    let instances: Vec<EntityInstance> = batches
+        .iter()
+        .flat_map(|batch| batch.instances.iter().copied())
+        .collect();
+    if instances.len() > instance_buffer.len() {
+        *instance_buffer = buffer::DynamicBuffer::new(
+            device,
+            instances.len().next_power_of_two(),
+            wgpu::BufferUsages::VERTEX,
+        );
+    }
+    instance_buffer.update(queue, &instances, 0);
+
+    let mut start = 0;
+    batches
+        .iter()
+        .map(|batch| {
+            let end = start + batch.instances.len() as u32;
+            let draw = EntityDraw {
+                mesh: batch.mesh,
+                instances: start..end,
+            };
+            start = end;
+            draw
+        })
+        .collect()
+}
@@ -0,0 +1,248 @@
+use super::{profiler::GpuProfiler, renderer::Renderer};
+
+/// A part of a frame drawn from one camera, in pixels from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Splits a frame of `width` by `height` pixels into `count` viewports of the same size,
+    /// side by side in as square a grid as fits them, row by row from the top left.
+    pub fn split(width: u32, height: u32, count: u32) -> Vec<Self> {
+        let count = count.max(1);
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+        let (cell_width, cell_height) = ((width / columns).max(1), (height / rows).max(1));
+        (0..count)
+            .map(|index| Self {
+                x: index % columns * cell_width,
+                y: index / columns * cell_height,
+                width: cell_width,
+                height: cell_height,
+            })
+            .collect()
+    }
+}
+
+/// The targets shared by the passes of a frame.
+pub struct FrameTargets<'a> {
+    /// The color target passes draw into, which is multisampled with multisampling.
+    pub color: &'a wgpu::TextureView,
+    /// The surface the color target is resolved to at the end of the frame, `None` if passes
+    /// draw to the surface directly.
+    pub resolve: Option<&'a wgpu::TextureView>,
+    /// The depth buffer of the color target.
+    pub depth: &'a wgpu::TextureView,
+    /// The camera the pass draws from, see [`Frame::set_view`].
+    pub view: usize,
+    /// The part of the targets the pass draws into, `None` for all of it.
+    pub viewport: Option<Viewport>,
+}
+
+impl FrameTargets<'_> {
+    /// Returns an attachment drawing into the color target, loaded with `load`. Clearing
+    /// would clear the other viewports too, so with a viewport the target is loaded instead,
+    /// and the frame is cleared once before its first pass.
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment {
+        let load = match self.viewport {
+            Some(_) => wgpu::LoadOp::Load,
+            None => load,
+        };
+        wgpu::RenderPassColorAttachment {
+            view: self.color,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    /// Returns an attachment testing against and writing to the depth buffer, loaded with
+    /// `load`.
+    pub fn depth_attachment(
+        &self,
+        load: wgpu::LoadOp<f32>,
+    ) -> wgpu::RenderPassDepthStencilAttachment {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: self.depth,
+            depth_ops: Some(wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }
+    }
+
+    /// Limits `render_pass` to the viewport of the pass, if there is one.
+    pub fn set_viewport(&self, render_pass: &mut wgpu::RenderPass) {
+        if let Some(Viewport {
+            x,
+            y,
+            width,
+            height,
+        }) = self.viewport
+        {
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+    }
+}
+
+/// A pass of a frame, recording its commands into the encoder of the frame. Passes are given
+/// the renderer when they run, so they don't keep it borrowed until then.
+type FramePass<'a> = Box<dyn FnOnce(&Renderer, &mut wgpu::CommandEncoder, &FrameTargets) + 'a>;
+
+/// A frame being drawn. Passes are added to it and run in the order they were added against
+/// the shared targets once the frame is submitted, see [`super::renderer::Renderer::submit_frame`].
+/// A frame can be drawn from several cameras, each into its own viewport, by setting the view
+/// before adding its passes.
+pub struct Frame<'a> {
+    surface: wgpu::SurfaceTexture,
+    /// The view of the surface texture.
+    view: wgpu::TextureView,
+    encoder: wgpu::CommandEncoder,
+    /// The passes with the camera and viewport each draws with.
+    passes: Vec<(&'static str, usize, Option<Viewport>, FramePass<'a>)>,
+    /// The camera and viewport passes added now draw with.
+    camera: usize,
+    viewport: Option<Viewport>,
+}
+
+impl<'a> Frame<'a> {
+    /// Starts a frame drawing to `surface`, viewed as `format`.
+    pub(super) fn new(
+        surface: wgpu::SurfaceTexture,
+        format: wgpu::TextureFormat,
+        encoder: wgpu::CommandEncoder,
+    ) -> Self {
+        let view = surface.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(format),
+            ..Default::default()
+        });
+
+        Self {
+            surface,
+            view,
+            encoder,
+            passes: Vec::new(),
+            camera: 0,
+            viewport: None,
+        }
+    }
+
+    /// Adds a pass named `name` after the passes added before it.
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        pass: impl FnOnce(&Renderer, &mut wgpu::CommandEncoder, &FrameTargets) + 'a,
+    ) {
+        self.passes
+            .push((name, self.camera, self.viewport, Box::new(pass)));
+    }
+
+    /// Makes the passes added from now on draw from the camera of view `view` into
+    /// `viewport`, or into the whole frame with `None`. Frames start drawing from the camera
+    /// of view 0, see [`super::renderer::Renderer::update_view_camera_uniform`].
+    pub fn set_view(&mut self, view: usize, viewport: Option<Viewport>) {
+        self.camera = view;
+        self.viewport = viewport;
+    }
+
+    /// Returns the view passes added now draw from.
+    pub fn view(&self) -> usize {
+        self.camera
+    }
+
+    /// Returns the viewport passes added now draw into, `None` for the whole frame.
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    /// Records the passes in order with `renderer`, grouping the commands of each under its
+    /// name for graphics debuggers. Passes draw into `msaa_view` with multisampling, which is
+    /// resolved to the surface at the end, or else into the surface directly. Each pass is
+    /// timed with `profiler` if there is one. Returns the recorded commands, the surface to
+    /// present once they are submitted and the names of the timed passes.
+    pub(super) fn finish(
+        self,
+        renderer: &Renderer,
+        msaa_view: Option<&wgpu::TextureView>,
+        depth: &wgpu::TextureView,
+        profiler: Option<&GpuProfiler>,
+    ) -> (wgpu::CommandBuffer, wgpu::SurfaceTexture, Vec<&'static str>) {
+        let Self {
+            surface,
+            view,
+            mut encoder,
+            passes,
+            ..
+        } = self;
+
+        let (color, resolve) = match msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&view)),
+            None => (&view, None),
+        };
+        let mut targets = FrameTargets {
+            color,
+            resolve,
+            depth,
+            view: 0,
+            viewport: None,
+        };
+
+        // Passes drawing into viewports don't clear the frame, so it's cleared for them.
+        if passes.iter().any(|(_, _, viewport, _)| viewport.is_some()) {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Clear pass"),
+                color_attachments: &[Some(
+                    targets.color_attachment(wgpu::LoadOp::Clear(renderer.clear_color())),
+                )],
+                ..Default::default()
+            });
+        }
+
+        let mut timed = Vec::new();
+        for (index, (name, camera, viewport, pass)) in passes.into_iter().enumerate() {
+            targets.view = camera;
+            targets.viewport = viewport;
+            encoder.push_debug_group(name);
+            if let Some(profiler) = profiler {
+                profiler.write_timestamp(&mut encoder, index, false);
+            }
+            pass(renderer, &mut encoder, &targets);
+            if let Some(profiler) = profiler {
+                profiler.write_timestamp(&mut encoder, index, true);
+                timed.push(name);
+            }
+            encoder.pop_debug_group();
+        }
+        if let Some(profiler) = profiler {
+            profiler.resolve(&mut encoder, timed.len());
+        }
+
+        if let Some(resolve) = targets.resolve {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Resolve pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: targets.color,
+                    resolve_target: Some(resolve),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Discard,
+                    },
+                })],
+                ..Default::default()
+            });
+        }
+
+        (encoder.finish(), surface, timed)
+    }
+}
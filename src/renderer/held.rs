@@ -0,0 +1,67 @@
+use cgmath::{Deg, Matrix4, SquareMatrix, Vector3};
+
+use crate::camera::Camera;
+
+use super::{
+    block::BlockType,
+    buffer::{self, MeshBuffers},
+    entity::{self, EntityInstance, EntityVertex},
+};
+
+/// Where the held block is drawn relative to the camera, right, down and ahead in world
+/// units. It stays past the near plane of the camera.
+const HELD_OFFSET: Vector3<f32> = Vector3::new(0.55, -0.4, -1.2);
+/// The width of the held block, in world units.
+const HELD_SIZE: f32 = 0.25;
+/// How far the held block is turned around the vertical axis and towards the camera, so two
+/// sides and the top show.
+const HELD_YAW: Deg<f32> = Deg(-35.0);
+const HELD_PITCH: Deg<f32> = Deg(20.0);
+
+/// The block the player holds, drawn as a small cube in the bottom right corner of the view.
+pub struct HeldBlock {
+    block: BlockType,
+    mesh: MeshBuffers<EntityVertex>,
+    /// The single instance placing the cube in front of the camera.
+    instance_buffer: buffer::DynamicBuffer<EntityInstance>,
+}
+
+impl HeldBlock {
+    pub fn new(device: &wgpu::Device, block: BlockType) -> Self {
+        let (vertices, indices) = entity::cuboid([-0.5; 3], [0.5; 3], |face| block.layer(face));
+        Self {
+            block,
+            mesh: MeshBuffers::new(device, &vertices, &indices),
+            instance_buffer: buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::VERTEX),
+        }
+    }
+
+    pub fn block(&self) -> BlockType {
+        self.block
+    }
+
+    pub fn mesh(&self) -> &MeshBuffers<EntityVertex> {
+        &self.mesh
+    }
+
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        &self.instance_buffer.buf().buf
+    }
+
+    /// Moves the held block in front of `camera`, lit with the sky and block `light` levels
+    /// at the camera from 0 to 1.
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, light: [f32; 2]) {
+        let to_world = camera.view().invert().unwrap_or(Matrix4::identity());
+        let model = to_world
+            * Matrix4::from_translation(HELD_OFFSET)
+            * Matrix4::from_angle_x(HELD_PITCH)
+            * Matrix4::from_angle_y(HELD_YAW)
+            * Matrix4::from_scale(HELD_SIZE);
+        let instance = EntityInstance {
+            model: model.into(),
+            light,
+            _padding: [0.0; 2],
+        };
+        self.instance_buffer.update(queue, &[instance], 0);
+    }
+}
@@ -0,0 +1,226 @@
+use std::ops::Range;
+
+use crate::aabb::Aabb;
+
+use super::block::BlockVertex;
+use super::buffer::MeshBuffers;
+
+/// The arguments of an indexed draw, laid out as indirect draws read them.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+/// Draws of meshes grouped by the buffers they are in, so each group can be drawn with a
+/// single call.
+pub struct MeshDraws<'a> {
+    batches: Vec<DrawBatch<'a>>,
+}
+
+struct DrawBatch<'a> {
+    vertex: &'a wgpu::Buffer,
+    index: &'a wgpu::Buffer,
+    /// Where the arguments of the batch start in the indirect buffer, in draws.
+    first: u32,
+    draws: Vec<DrawArgs>,
+    /// The bounds of the mesh of each draw.
+    bounds: Vec<Option<Aabb>>,
+}
+
+impl MeshDraws<'_> {
+    /// Returns where the draws are in the indirect buffer, in draws.
+    pub fn range(&self) -> Range<u32> {
+        match (self.batches.first(), self.batches.last()) {
+            (Some(first), Some(last)) => first.first..last.first + last.draws.len() as u32,
+            _ => 0..0,
+        }
+    }
+
+    /// Returns the bounds of the mesh of each draw, in the order of [`MeshDraws::range`].
+    pub fn bounds(&self) -> impl Iterator<Item = Option<Aabb>> + '_ {
+        self.batches
+            .iter()
+            .flat_map(|batch| batch.bounds.iter().copied())
+    }
+}
+
+/// The arguments of the indirect draws of a frame. Meshes sharing buffers are drawn with one
+/// `multi_draw_indexed_indirect` call where the device supports it, or else with one draw
+/// call per mesh.
+pub struct IndirectDraws {
+    buffer: wgpu::Buffer,
+    /// How many draws fit in the buffer.
+    capacity: usize,
+    /// The arguments added this frame, see [`IndirectDraws::clear`].
+    pending: Vec<DrawArgs>,
+    /// How many of the pending arguments are uploaded already.
+    uploaded: usize,
+    multi_draw: bool,
+    /// Whether the arguments are read from the buffer without multi-draw too, so they can be
+    /// changed on the GPU.
+    indirect: bool,
+}
+
+impl IndirectDraws {
+    /// Creates the buffer for the arguments, drawing with multi-draw if `multi_draw` is set,
+    /// which needs [`wgpu::Features::MULTI_DRAW_INDIRECT`].
+    pub fn new(device: &wgpu::Device, multi_draw: bool) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, 1, false),
+            capacity: 1,
+            pending: Vec::new(),
+            uploaded: 0,
+            multi_draw,
+            indirect: false,
+        }
+    }
+
+    /// Creates a buffer for `capacity` draws, which compute shaders can write to if `storage`
+    /// is set.
+    fn create_buffer(device: &wgpu::Device, capacity: usize, storage: bool) -> wgpu::Buffer {
+        let storage = if storage {
+            wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::empty()
+        };
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Indirect draw buffer"),
+            size: (capacity * std::mem::size_of::<DrawArgs>()) as u64,
+            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST | storage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Sets whether the arguments are read from the buffer even without multi-draw, which
+    /// lets compute shaders change them before they are drawn, see [`IndirectDraws::buffer`].
+    /// Needs [`wgpu::DownlevelFlags::INDIRECT_EXECUTION`].
+    pub fn set_indirect(&mut self, device: &wgpu::Device, indirect: bool) {
+        if indirect != self.indirect {
+            self.indirect = indirect;
+            self.buffer = Self::create_buffer(device, self.capacity, indirect);
+            self.uploaded = 0;
+        }
+    }
+
+    /// Returns the buffer the uploaded arguments are in, which compute shaders can write to
+    /// while reading the arguments indirectly is set, see [`IndirectDraws::set_indirect`].
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Adds draws of the group of indices at `group` of each of `meshes`, skipping empty
+    /// groups. Meshes in the same buffers keep their order within the batch they are drawn in.
+    pub fn add<'a>(
+        &mut self,
+        meshes: &[&'a MeshBuffers<BlockVertex>],
+        group: usize,
+    ) -> MeshDraws<'a> {
+        let mut batches: Vec<(
+            &'a MeshBuffers<BlockVertex>,
+            Vec<DrawArgs>,
+            Vec<Option<Aabb>>,
+        )> = Vec::new();
+        for &mesh in meshes {
+            let indices = mesh.group(group);
+            if indices.is_empty() {
+                continue;
+            }
+
+            let args = DrawArgs {
+                index_count: indices.len() as u32,
+                instance_count: 1,
+                first_index: mesh.index_range().start + indices.start,
+                base_vertex: mesh.vertex_range().start as i32,
+                first_instance: 0,
+            };
+            match batches
+                .iter_mut()
+                .find(|(first, _, _)| first.shares_buffers(mesh))
+            {
+                Some((_, draws, bounds)) => {
+                    draws.push(args);
+                    bounds.push(mesh.bounds());
+                }
+                None => batches.push((mesh, vec![args], vec![mesh.bounds()])),
+            }
+        }
+
+        let batches = batches
+            .into_iter()
+            .map(|(mesh, draws, bounds)| {
+                let first = self.pending.len() as u32;
+                self.pending.extend_from_slice(&draws);
+                DrawBatch {
+                    vertex: &mesh.vertex.buf,
+                    index: &mesh.index.buf,
+                    first,
+                    draws,
+                    bounds,
+                }
+            })
+            .collect();
+        MeshDraws { batches }
+    }
+
+    /// Uploads the draws added since the last upload, after the ones uploaded before in the
+    /// same frame. Must be called before the draws are submitted.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !(self.multi_draw || self.indirect) || self.uploaded == self.pending.len() {
+            self.uploaded = self.pending.len();
+            return;
+        }
+
+        if self.pending.len() > self.capacity {
+            // The new buffer lacks the draws uploaded before, so they are uploaded again.
+            self.capacity = self.pending.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity, self.indirect);
+            self.uploaded = 0;
+        }
+        let size = std::mem::size_of::<DrawArgs>() as u64;
+        queue.write_buffer(
+            &self.buffer,
+            self.uploaded as u64 * size,
+            bytemuck::cast_slice(&self.pending[self.uploaded..]),
+        );
+        self.uploaded = self.pending.len();
+    }
+
+    /// Forgets the draws of the frame once it's submitted, so the next frame's draws replace
+    /// them.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.uploaded = 0;
+    }
+
+    /// Records `draws` into `render_pass`, which has its pipeline and bind groups set.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, draws: &MeshDraws) {
+        let size = std::mem::size_of::<DrawArgs>() as u64;
+
+        for batch in &draws.batches {
+            render_pass.set_vertex_buffer(0, batch.vertex.slice(..));
+            render_pass.set_index_buffer(batch.index.slice(..), wgpu::IndexFormat::Uint32);
+
+            if self.multi_draw {
+                render_pass.multi_draw_indexed_indirect(
+                    &self.buffer,
+                    batch.first as u64 * size,
+                    batch.draws.len() as u32,
+                );
+            } else if self.indirect {
+                for draw in batch.first..batch.first + batch.draws.len() as u32 {
+                    render_pass.draw_indexed_indirect(&self.buffer, draw as u64 * size);
+                }
+            } else {
+                for args in &batch.draws {
+                    let indices = args.first_index..args.first_index + args.index_count;
+                    render_pass.draw_indexed(indices, args.base_vertex, 0..1);
+                }
+            }
+        }
+    }
+}
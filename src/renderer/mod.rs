@@ -1,4 +1,16 @@
 pub mod block;
 pub mod buffer;
+pub mod celestial;
+pub mod clouds;
+pub mod entity;
+pub mod frame;
+pub mod held;
+pub mod indirect;
+pub mod occlusion;
+pub mod pipeline;
+pub mod pool;
+pub mod profiler;
 pub mod renderer;
+pub mod shadow;
+pub mod ssao;
 pub mod texture;
@@ -0,0 +1,442 @@
+use crate::camera::CameraUniform;
+
+use super::{
+    buffer,
+    indirect::{IndirectDraws, MeshDraws},
+};
+
+/// The maximum number of levels of the pyramid, enough for depth buffers up to 65536 pixels
+/// wide.
+const MAX_LEVELS: usize = 16;
+/// The number of draws each workgroup of the culling shader tests.
+const WORKGROUP_SIZE: u32 = 64;
+/// The width and height of the block of texels each workgroup building the pyramid writes.
+const PYRAMID_WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    view_proj: [[f32; 4]; 4],
+    depth_size: [f32; 2],
+    level_count: u32,
+    first: u32,
+    count: u32,
+    _padding: [u32; 3],
+    /// Where each level starts in the pyramid, followed by its width and height.
+    levels: [[u32; 4]; MAX_LEVELS],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LevelUniform {
+    offset: u32,
+    width: u32,
+    height: u32,
+    previous_offset: u32,
+    previous_width: u32,
+    previous_height: u32,
+    _padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawBounds {
+    min: [f32; 4],
+    max: [f32; 4],
+}
+
+/// Occlusion culling against the depth of the previous frame. After the opaque terrain is
+/// drawn, its depth is reduced into a Hi-Z pyramid holding the farthest depth of ever larger
+/// blocks of pixels. The next frame, a compute shader tests the bounds of each opaque draw
+/// against the pyramid and hides the draws of meshes behind the terrain.
+///
+/// Meshes that came into view since the previous frame can't be tested and are always drawn,
+/// so culling never hides terrain that is in view.
+pub struct OcclusionCulling {
+    pyramid_bind_group_layout: wgpu::BindGroupLayout,
+    cull_bind_group_layout: wgpu::BindGroupLayout,
+    downsample_depth_pipeline: wgpu::ComputePipeline,
+    downsample_pipeline: wgpu::ComputePipeline,
+    cull_pipeline: wgpu::ComputePipeline,
+    uniform_buffer: buffer::DynamicBuffer<CullUniform>,
+    bounds_buffer: buffer::DynamicBuffer<DrawBounds>,
+    /// The pyramid and the bind group building it, which follow the size of the frame.
+    pyramid: HiZPyramid,
+    /// The camera of the frame being drawn.
+    camera: [[f32; 4]; 4],
+    /// The camera the pyramid was last built with, `None` until it is built.
+    pyramid_camera: Option<[[f32; 4]; 4]>,
+    /// Binds the draws culled this frame, `None` if nothing is culled.
+    cull_bind_group: Option<wgpu::BindGroup>,
+    /// The number of draws culled this frame.
+    count: u32,
+}
+
+/// The levels of the pyramid, stored one after another in a buffer. Kept in a buffer rather
+/// than the mips of a texture, since not every backend can write to one mip while reading
+/// another.
+struct HiZPyramid {
+    buffer: wgpu::Buffer,
+    /// Where each level starts in the buffer, followed by its width and height.
+    levels: Vec<[u32; 4]>,
+    /// The distance between the [`LevelUniform`] of each level in the bind group's uniform
+    /// buffer.
+    level_stride: u32,
+    bind_group: wgpu::BindGroup,
+    /// The size of the depth buffer, in pixels.
+    depth_size: [f32; 2],
+}
+
+impl OcclusionCulling {
+    /// Creates the passes for frames configured by `config` with `sample_count` samples per
+    /// pixel, building the pyramid from `depth_view`. Needs
+    /// [`wgpu::DownlevelFlags::COMPUTE_SHADERS`].
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let mut source = include_str!("../../assets/shaders/occlusion.wgsl").to_string();
+        if sample_count > 1 {
+            source = source.replace(
+                "alias DepthTexture = texture_2d<f32>;",
+                "alias DepthTexture = texture_multisampled_2d<f32>;",
+            );
+        }
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Occlusion culling shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let buffer_entry = |binding, ty, has_dynamic_offset| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                has_dynamic_offset,
+                min_binding_size: None,
+                ty,
+            },
+            count: None,
+        };
+        let storage = |read_only| wgpu::BufferBindingType::Storage { read_only };
+
+        let pyramid_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: sample_count > 1,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            // Read as plain floats, which every backend can load from.
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    buffer_entry(1, wgpu::BufferBindingType::Uniform, true),
+                    buffer_entry(2, storage(false), false),
+                ],
+            });
+        let cull_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Occlusion Culling Bind Group Layout"),
+                entries: &[
+                    buffer_entry(3, wgpu::BufferBindingType::Uniform, false),
+                    buffer_entry(4, storage(true), false),
+                    buffer_entry(5, storage(false), false),
+                    buffer_entry(6, storage(true), false),
+                ],
+            });
+
+        let create_pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache,
+            })
+        };
+        let downsample_depth_pipeline = create_pipeline(
+            "Hi-Z Depth Pipeline",
+            &pyramid_bind_group_layout,
+            "cs_downsample_depth",
+        );
+        let downsample_pipeline = create_pipeline(
+            "Hi-Z Level Pipeline",
+            &pyramid_bind_group_layout,
+            "cs_downsample",
+        );
+        let cull_pipeline = create_pipeline(
+            "Occlusion Culling Pipeline",
+            &cull_bind_group_layout,
+            "cs_cull",
+        );
+
+        let pyramid = HiZPyramid::new(device, config, depth_view, &pyramid_bind_group_layout);
+
+        Self {
+            pyramid_bind_group_layout,
+            cull_bind_group_layout,
+            downsample_depth_pipeline,
+            downsample_pipeline,
+            cull_pipeline,
+            uniform_buffer: buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::UNIFORM),
+            bounds_buffer: buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::STORAGE),
+            pyramid,
+            camera: [[0.0; 4]; 4],
+            pyramid_camera: None,
+            cull_bind_group: None,
+            count: 0,
+        }
+    }
+
+    /// Recreates the pyramid after the frame was resized, building it from the new
+    /// `depth_view`. Nothing is culled until it is built again.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.pyramid = HiZPyramid::new(device, config, depth_view, &self.pyramid_bind_group_layout);
+        self.pyramid_camera = None;
+    }
+
+    /// Sets the camera of the frame being drawn, which the pyramid is built with.
+    pub fn set_camera(&mut self, camera: &CameraUniform) {
+        self.camera = camera.view_proj;
+    }
+
+    /// Forgets the pyramid when a frame doesn't build it, like frames drawn into viewports
+    /// whose depth buffer doesn't show the whole frame from one camera. Nothing is culled
+    /// until it is built again.
+    pub fn discard_pyramid(&mut self) {
+        self.pyramid_camera = None;
+    }
+
+    /// Prepares culling `draws` this frame, which have to be uploaded to `indirect` already
+    /// with [`IndirectDraws::set_indirect`] on. Nothing is culled in the first frame after the
+    /// pyramid was recreated.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indirect: &IndirectDraws,
+        draws: &MeshDraws,
+    ) {
+        // This frame builds the pyramid that the next one is culled with.
+        let pyramid_camera = self.pyramid_camera.replace(self.camera);
+
+        let range = draws.range();
+        self.count = range.len() as u32;
+        self.cull_bind_group = None;
+        let Some(view_proj) = pyramid_camera else {
+            return;
+        };
+        if self.count == 0 {
+            return;
+        }
+
+        let bounds: Vec<DrawBounds> = draws
+            .bounds()
+            .map(|bounds| match bounds {
+                Some(bounds) => DrawBounds {
+                    min: [bounds.min.x, bounds.min.y, bounds.min.z, 1.0],
+                    max: [bounds.max.x, bounds.max.y, bounds.max.z, 1.0],
+                },
+                None => DrawBounds {
+                    min: [0.0; 4],
+                    max: [0.0; 4],
+                },
+            })
+            .collect();
+        if bounds.len() > self.bounds_buffer.len() {
+            self.bounds_buffer = buffer::DynamicBuffer::new(
+                device,
+                bounds.len().next_power_of_two(),
+                wgpu::BufferUsages::STORAGE,
+            );
+        }
+        self.bounds_buffer.update(queue, &bounds, 0);
+
+        let mut levels = [[0; 4]; MAX_LEVELS];
+        levels[..self.pyramid.levels.len()].copy_from_slice(&self.pyramid.levels);
+        let uniform = CullUniform {
+            view_proj,
+            depth_size: self.pyramid.depth_size,
+            level_count: self.pyramid.levels.len() as u32,
+            first: range.start,
+            count: self.count,
+            _padding: [0; 3],
+            levels,
+        };
+        self.uniform_buffer.update(queue, &[uniform], 0);
+
+        self.cull_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.cull_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.uniform_buffer.buf().buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.bounds_buffer.buf().buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: indirect.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: self.pyramid.buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Occlusion Culling Bind Group"),
+        }));
+    }
+
+    /// Hides the draws prepared with [`OcclusionCulling::prepare`] that are occluded in the
+    /// pyramid of the previous frame.
+    pub fn cull(&self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(bind_group) = &self.cull_bind_group else {
+            return;
+        };
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Occlusion culling pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Builds the pyramid from the depth of the opaque terrain drawn this frame.
+    pub fn build_pyramid(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Hi-Z pass"),
+            timestamp_writes: None,
+        });
+        for (index, &[_, width, height, _]) in self.pyramid.levels.iter().enumerate() {
+            let pipeline = match index {
+                0 => &self.downsample_depth_pipeline,
+                _ => &self.downsample_pipeline,
+            };
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(
+                0,
+                &self.pyramid.bind_group,
+                &[index as u32 * self.pyramid.level_stride],
+            );
+            compute_pass.dispatch_workgroups(
+                width.div_ceil(PYRAMID_WORKGROUP_SIZE),
+                height.div_ceil(PYRAMID_WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+}
+
+impl HiZPyramid {
+    fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let (width, height) = (config.width.max(1), config.height.max(1));
+
+        // Each level is half the size of the one before, rounded up, down to a single texel.
+        let mut levels = Vec::new();
+        let mut size = (width.div_ceil(2), height.div_ceil(2));
+        let mut offset = 0;
+        loop {
+            levels.push([offset, size.0, size.1, 0]);
+            offset += size.0 * size.1;
+            if size == (1, 1) || levels.len() == MAX_LEVELS {
+                break;
+            }
+            size = (size.0.div_ceil(2), size.1.div_ceil(2));
+        }
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Hi-Z buffer"),
+            size: offset as u64 * std::mem::size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let level_stride = device
+            .limits()
+            .min_uniform_buffer_offset_alignment
+            .max(std::mem::size_of::<LevelUniform>() as u32);
+        let mut level_data = vec![0; level_stride as usize * levels.len()];
+        for (index, &[offset, width, height, _]) in levels.iter().enumerate() {
+            let [previous_offset, previous_width, previous_height, _] =
+                levels[index.saturating_sub(1)];
+            let uniform = LevelUniform {
+                offset,
+                width,
+                height,
+                previous_offset,
+                previous_width,
+                previous_height,
+                _padding: [0; 2],
+            };
+            let start = index * level_stride as usize;
+            level_data[start..start + std::mem::size_of::<LevelUniform>()]
+                .copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+        let level_buffer = {
+            use wgpu::util::DeviceExt;
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Hi-Z level buffer"),
+                contents: &level_data,
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &level_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<LevelUniform>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("Hi-Z Bind Group"),
+        });
+
+        Self {
+            buffer,
+            levels,
+            level_stride,
+            bind_group,
+            depth_size: [width as f32, height as f32],
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+/// The directory compiled pipelines are cached in between runs.
+pub const PIPELINE_CACHE_DIRECTORY: &str = "cache";
+
+/// Compiled pipelines kept on disk, so later runs with the same driver don't compile them
+/// again. Only some backends support it, elsewhere the cache stays empty.
+pub struct PipelineCache {
+    /// The cache with the file it is saved to, `None` if the device can't cache pipelines.
+    cache: Option<(wgpu::PipelineCache, PathBuf)>,
+}
+
+impl PipelineCache {
+    /// Returns the features `adapter` needs for caching pipelines, empty if it can't.
+    pub fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+        adapter.features() & wgpu::Features::PIPELINE_CACHE
+    }
+
+    /// Loads the cache of `adapter` from `directory`, or starts an empty one if there is none
+    /// or it is outdated. `device` has to be created with
+    /// [`PipelineCache::required_features`].
+    pub fn load(device: &wgpu::Device, adapter: &wgpu::Adapter, directory: &Path) -> Self {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return Self { cache: None };
+        }
+        let Some(key) = wgpu::util::pipeline_cache_key(&adapter.get_info()) else {
+            return Self { cache: None };
+        };
+
+        let path = directory.join(key);
+        let data = fs::read(&path).ok();
+        // Safety: the data was saved from `get_data` by a previous run, and caches of other
+        // drivers or corrupted files are replaced by an empty cache.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Self {
+            cache: Some((cache, path)),
+        }
+    }
+
+    /// Returns the cache to create pipelines with.
+    pub fn get(&self) -> Option<&wgpu::PipelineCache> {
+        self.cache.as_ref().map(|(cache, _)| cache)
+    }
+
+    /// Saves the pipelines compiled so far for the next run.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some((cache, path)) = &self.cache else {
+            return Ok(());
+        };
+        let Some(data) = cache.get_data() else {
+            return Ok(());
+        };
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Runs `create` with the validation errors of the device captured, so a shader that fails to
+/// compile is returned as an error rather than panicking the device.
+pub fn catch_errors<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> anyhow::Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    // Native backends know about errors as soon as the scope is popped, so the future is
+    // ready right away.
+    let error = pin!(device.pop_error_scope()).poll(&mut Context::from_waker(Waker::noop()));
+    match error {
+        Poll::Ready(Some(error)) => Err(anyhow::anyhow!("{error}")),
+        _ => Ok(value),
+    }
+}
@@ -0,0 +1,174 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::block::{BlockVertex, MeshPass, TerrainMesh};
+use super::buffer::{Buffer, DynamicBuffer, MeshBuffers};
+
+/// The maximum number of idle meshes kept around for reuse.
+const MAX_POOLED: usize = 64;
+/// How many vertices and indices the shared buffers hold, unless a mesh needs more.
+const PAGE_VERTICES: u32 = 1 << 20;
+const PAGE_INDICES: u32 = PAGE_VERTICES / 2 * 3;
+/// What the room taken by meshes is rounded up to, so freed room is more likely to fit the
+/// next mesh.
+const ALIGNMENT: u32 = 256;
+
+/// Recycles terrain mesh allocations across remeshes. Meshes are uploaded into a few large
+/// buffers shared between chunks, so many chunks can be drawn without switching buffers.
+pub struct MeshPool {
+    meshes: Vec<TerrainMesh>,
+    pages: Vec<MeshPage>,
+}
+
+/// A pair of shared vertex and index buffers, and the room left in them.
+struct MeshPage {
+    vertex: Arc<Buffer<BlockVertex>>,
+    index: Arc<Buffer<u32>>,
+    free_vertices: FreeList,
+    free_indices: FreeList,
+}
+
+/// The unused ranges of a buffer, ordered by where they start with no two touching.
+struct FreeList(Vec<Range<u32>>);
+
+impl MeshPool {
+    pub fn new() -> Self {
+        Self {
+            meshes: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Returns an empty mesh, reusing a previously recycled allocation if there is one.
+    pub fn take_mesh(&mut self) -> TerrainMesh {
+        self.meshes.pop().unwrap_or_else(TerrainMesh::new)
+    }
+
+    pub fn recycle_mesh(&mut self, mut mesh: TerrainMesh) {
+        if self.meshes.len() < MAX_POOLED {
+            mesh.clear();
+            self.meshes.push(mesh);
+        }
+    }
+
+    /// Uploads `mesh` into free room in the shared buffers, adding buffers if none have room.
+    /// Returns `None` for empty meshes.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mesh: &TerrainMesh,
+    ) -> Option<MeshBuffers<BlockVertex>> {
+        let vertices = mesh.vertices();
+        let groups = MeshPass::ALL.map(|pass| mesh.pass_indices(pass));
+        let index_count: usize = groups.iter().map(|indices| indices.len()).sum();
+        if index_count == 0 {
+            return None;
+        }
+
+        let vertex_room = (vertices.len() as u32).next_multiple_of(ALIGNMENT);
+        let index_room = (index_count as u32).next_multiple_of(ALIGNMENT);
+
+        let allocation = self
+            .pages
+            .iter_mut()
+            .find_map(|page| page.allocate(vertex_room, index_room));
+        let mut buffers = match allocation {
+            Some(buffers) => buffers,
+            None => {
+                let mut page = MeshPage::new(
+                    device,
+                    vertex_room.max(PAGE_VERTICES),
+                    index_room.max(PAGE_INDICES),
+                );
+                let buffers = page.allocate(vertex_room, index_room)?;
+                self.pages.push(page);
+                buffers
+            }
+        };
+
+        buffers.write(queue, vertices, &groups);
+        buffers.set_bounds(mesh.bounds());
+        Some(buffers)
+    }
+
+    /// Frees the room `buffers` took up in the shared buffers.
+    pub fn recycle_buffers(&mut self, buffers: MeshBuffers<BlockVertex>) {
+        if let Some(page) = self
+            .pages
+            .iter_mut()
+            .find(|page| Arc::ptr_eq(&page.vertex, &buffers.vertex))
+        {
+            page.free_vertices.free(buffers.vertex_range());
+            page.free_indices.free(buffers.index_range());
+        }
+    }
+}
+
+impl MeshPage {
+    fn new(device: &wgpu::Device, vertex_capacity: u32, index_capacity: u32) -> Self {
+        let vertex =
+            DynamicBuffer::new(device, vertex_capacity as usize, wgpu::BufferUsages::VERTEX);
+        let index = DynamicBuffer::new(device, index_capacity as usize, wgpu::BufferUsages::INDEX);
+
+        Self {
+            vertex: Arc::new(vertex.into_buffer()),
+            index: Arc::new(index.into_buffer()),
+            free_vertices: FreeList(vec![0..vertex_capacity]),
+            free_indices: FreeList(vec![0..index_capacity]),
+        }
+    }
+
+    /// Takes room for the given number of vertices and indices, if there is enough.
+    fn allocate(&mut self, vertex_room: u32, index_room: u32) -> Option<MeshBuffers<BlockVertex>> {
+        let vertex_range = self.free_vertices.allocate(vertex_room)?;
+        let Some(index_range) = self.free_indices.allocate(index_room) else {
+            self.free_vertices.free(vertex_range);
+            return None;
+        };
+
+        Some(MeshBuffers::shared(
+            self.vertex.clone(),
+            self.index.clone(),
+            vertex_range,
+            index_range,
+        ))
+    }
+}
+
+impl FreeList {
+    /// Takes `len` from the smallest free range it fits in.
+    fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        let (i, range) = self
+            .0
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, range)| range.len() >= len as usize)
+            .min_by_key(|(_, range)| range.len())?;
+
+        let allocated = range.start..range.start + len;
+        range.start += len;
+        if range.is_empty() {
+            self.0.remove(i);
+        }
+        Some(allocated)
+    }
+
+    /// Returns `range` to the free ranges, merging it with the ones it touches.
+    fn free(&mut self, range: Range<u32>) {
+        let i = self.0.partition_point(|free| free.start < range.start);
+        let mut range = range;
+
+        if let Some(next) = self.0.get(i).filter(|next| next.start == range.end) {
+            range.end = next.end;
+            self.0.remove(i);
+        }
+        match i
+            .checked_sub(1)
+            .and_then(|previous| self.0.get_mut(previous))
+        {
+            Some(previous) if previous.end == range.start => previous.end = range.end,
+            _ => self.0.insert(i, range),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::mpsc::{self, Receiver};
+
+/// The most passes of a frame that are timed, later passes are left out.
+pub const MAX_TIMED_PASSES: usize = 32;
+
+/// The size of a timestamp, in bytes.
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+/// Times the passes of frames on the GPU with timestamp queries, so a slow frame can be
+/// pinned on the passes that took long. Timings are read back once the GPU is done with them
+/// without waiting, and frames drawn while they are still read back aren't timed.
+pub struct GpuProfiler {
+    /// The timestamps written before and after each pass.
+    query_set: wgpu::QuerySet,
+    /// The timestamps of the last timed frame, resolved from the query set.
+    resolve_buffer: wgpu::Buffer,
+    /// The copy of the resolved timestamps mapped for reading.
+    readback_buffer: wgpu::Buffer,
+    /// The passes whose timestamps are being read back, with the result of mapping the
+    /// readback buffer once it's mapped.
+    pending: Option<(
+        Vec<&'static str>,
+        Receiver<Result<(), wgpu::BufferAsyncError>>,
+    )>,
+    /// How many nanoseconds a timestamp tick is.
+    period: f32,
+    /// The time each pass of the last timed frame took, in seconds, until they are taken.
+    timings: Option<Vec<(&'static str, f32)>>,
+}
+
+impl GpuProfiler {
+    /// Returns the features `adapter` needs for timing passes, empty if it can't.
+    pub fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+        let features =
+            wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS;
+        if adapter.features().contains(features) {
+            features
+        } else {
+            wgpu::Features::empty()
+        }
+    }
+
+    /// Creates the profiler, or returns `None` if `device` wasn't created with
+    /// [`GpuProfiler::required_features`].
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(
+            wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS,
+        ) {
+            return None;
+        }
+
+        let count = MAX_TIMED_PASSES as u32 * 2;
+        let size = count as u64 * TIMESTAMP_SIZE;
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Pass timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp resolve buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp readback buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            pending: None,
+            period: queue.get_timestamp_period(),
+            timings: None,
+        })
+    }
+
+    /// Returns whether the next frame can be timed, which it can't until the timestamps of
+    /// the last timed frame are read back.
+    pub fn is_ready(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    /// Writes the timestamp at the start of pass `index` of the frame, or at its end with
+    /// `end`.
+    pub fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: usize, end: bool) {
+        if index < MAX_TIMED_PASSES {
+            encoder.write_timestamp(&self.query_set, (index * 2 + end as usize) as u32);
+        }
+    }
+
+    /// Copies the timestamps of the first `count` passes of the frame to be read back.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, count: usize) {
+        let count = count.min(MAX_TIMED_PASSES) as u32 * 2;
+        if count == 0 {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            count as u64 * TIMESTAMP_SIZE,
+        );
+    }
+
+    /// Starts reading back the timestamps of `passes`, once the frame they were resolved in
+    /// is submitted.
+    pub fn read_back(&mut self, mut passes: Vec<&'static str>) {
+        passes.truncate(MAX_TIMED_PASSES);
+        if passes.is_empty() {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                sender.send(result).ok();
+            });
+        self.pending = Some((passes, receiver));
+    }
+
+    /// Collects the timings of the last timed frame if the GPU is done with it, without
+    /// waiting for it.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some((_, receiver)) = &self.pending else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let Ok(result) = receiver.try_recv() else {
+            return;
+        };
+
+        let (passes, _) = self.pending.take().unwrap();
+        if let Err(err) = result {
+            eprintln!("Failed to read back the GPU timings: {err}");
+            return;
+        }
+
+        let data = self.readback_buffer.slice(..).get_mapped_range();
+        let timestamps: &[u64] = bytemuck::cast_slice(&data);
+        let timings = passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let ticks = timestamps[index * 2 + 1].wrapping_sub(timestamps[index * 2]);
+                (*pass, ticks as f32 * self.period / 1e9)
+            })
+            .collect();
+        drop(data);
+        self.readback_buffer.unmap();
+        self.timings = Some(timings);
+    }
+
+    /// Takes the time each pass of the last timed frame took on the GPU, in seconds, or
+    /// `None` if no frame was timed since they were last taken.
+    pub fn take_timings(&mut self) -> Option<Vec<(&'static str, f32)>> {
+        self.timings.take()
+    }
+}
@@ -1,5 +1,6 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, path::Path, sync::Arc, time::Instant};
 
+use anyhow::Context;
 use bytemuck::Pod;
 use cgmath::Vector2;
 use wgpu::{
@@ -8,11 +9,23 @@ use wgpu::{
 };
 use winit::window::Window;
 
-use crate::camera::{self, CameraUniform};
+use crate::camera::{self, CameraUniform, ShadowCascade};
+use crate::time::{CelestialUniform, SkyUniform};
 
 use super::{
-    block::{BlockVertex, TerrainMesh},
-    buffer,
+    block::{self, BlockVertex, MeshPass},
+    buffer::{self, MeshBuffers},
+    celestial::Celestial,
+    clouds::Clouds,
+    entity::{EntityBatch, EntityPipeline},
+    frame::{Frame, FrameTargets},
+    held::HeldBlock,
+    indirect::{IndirectDraws, MeshDraws},
+    occlusion::OcclusionCulling,
+    pipeline::{catch_errors, PipelineCache, PIPELINE_CACHE_DIRECTORY},
+    profiler::GpuProfiler,
+    shadow::{ShadowMap, SHADOW_CASCADES},
+    ssao::Ssao,
 };
 
 pub struct Renderer<'a> {
@@ -20,6 +33,8 @@ pub struct Renderer<'a> {
     device: Arc<wgpu::Device>,
     queue: wgpu::Queue,
     surface_config: wgpu::SurfaceConfiguration,
+    /// The present modes the surface supports.
+    present_modes: Vec<wgpu::PresentMode>,
     size: winit::dpi::PhysicalSize<u32>,
     resolution: Vector2<u32>,
 
@@ -27,66 +42,370 @@ pub struct Renderer<'a> {
 
     camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
+    /// The cameras of the views after the first, see [`Renderer::update_view_camera_uniform`].
+    view_cameras: Vec<(buffer::DynamicBuffer<CameraUniform>, wgpu::BindGroup)>,
+
+    sky_buffer: buffer::DynamicBuffer<SkyUniform>,
+    /// The shadows of the sun, bound along with the sky.
+    shadow_map: ShadowMap,
+    sky_bind_group_layout: wgpu::BindGroupLayout,
+    sky_bind_group: wgpu::BindGroup,
+    /// The color the frame is cleared to, following the sky.
+    clear_color: wgpu::Color,
+
+    /// When the renderer was created, which animations are timed from.
+    start: Instant,
+    time_buffer: buffer::DynamicBuffer<TimeUniform>,
+    water_bind_group_layout: wgpu::BindGroupLayout,
+    water_bind_group: wgpu::BindGroup,
 
     depth_texture: super::texture::Texture,
+    /// How many samples are taken per pixel.
+    sample_count: u32,
+    /// The multisampled color target frames are drawn into and then resolved to the surface
+    /// from, `None` without multisampling.
+    msaa_view: Option<wgpu::TextureView>,
+    /// Darkens the frame where terrain is close around it, `None` when turned off.
+    ssao: Option<Ssao>,
+    /// Hides terrain behind other terrain, `None` when turned off.
+    occlusion: Option<OcclusionCulling>,
+    /// Whether the device can run occlusion culling.
+    supports_occlusion_culling: bool,
+    /// Times the passes of frames, `None` unless GPU timings are on.
+    profiler: Option<GpuProfiler>,
+    /// The pipelines compiled by previous runs.
+    pipeline_cache: PipelineCache,
 
     terrain_pipeline: TerrainPipeline,
+    celestial: Celestial,
+    clouds: Clouds,
+    entity_pipeline: EntityPipeline,
+    /// The arguments of the terrain draws of the current frame.
+    indirect: IndirectDraws,
     terrain_texture: super::texture::Texture,
     terrain_bind_group_layout: wgpu::BindGroupLayout,
     terrain_bind_group: wgpu::BindGroup,
 }
 
+/// The number of samples per pixel used unless configured otherwise.
+pub const DEFAULT_SAMPLE_COUNT: u32 = 4;
+
+/// Options the renderer is created with.
+#[derive(Debug, Clone)]
+pub struct RendererSettings {
+    /// How many samples are taken per pixel, 1 for no multisampling.
+    pub sample_count: u32,
+    /// How frames are presented. `Fifo` waits for vsync, `Mailbox` doesn't limit the frame rate
+    /// but never tears, and `Immediate` doesn't limit the frame rate and may tear.
+    pub present_mode: wgpu::PresentMode,
+    /// Whether screen space ambient occlusion darkens corners and crevices, on top of the
+    /// occlusion baked into the terrain meshes.
+    pub ssao: bool,
+    /// Whether chunks hidden behind other terrain are culled on the GPU, which helps at large
+    /// render distances.
+    pub occlusion_culling: bool,
+    /// Whether the passes of frames are timed on the GPU, if the device supports it.
+    pub gpu_timings: bool,
+    /// The graphics APIs adapters are picked from.
+    pub backends: wgpu::Backends,
+    /// Whether a fast adapter or one using less power, like an integrated GPU in a laptop, is
+    /// preferred.
+    pub power_preference: wgpu::PowerPreference,
+    /// Part of the name of the adapter to draw with, picked by the power preference if `None`
+    /// or no adapter matches.
+    pub adapter: Option<String>,
+}
+
+impl Default for RendererSettings {
+    fn default() -> Self {
+        Self {
+            sample_count: DEFAULT_SAMPLE_COUNT,
+            present_mode: wgpu::PresentMode::Fifo,
+            ssao: false,
+            occlusion_culling: false,
+            gpu_timings: false,
+            backends: DEFAULT_BACKENDS,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            adapter: None,
+        }
+    }
+}
+
+/// The graphics APIs adapters are picked from unless configured otherwise.
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_BACKENDS: wgpu::Backends = wgpu::Backends::GL;
+
+/// Parses the name of a backend, as used in settings and command line arguments. `primary`
+/// stands for the backends used unless configured otherwise, and `all` for every backend.
+pub fn parse_backend(name: &str) -> Option<wgpu::Backends> {
+    match name.to_lowercase().as_str() {
+        "vulkan" | "vk" => Some(wgpu::Backends::VULKAN),
+        "dx12" | "d3d12" => Some(wgpu::Backends::DX12),
+        "metal" => Some(wgpu::Backends::METAL),
+        "gl" | "opengl" | "gles" => Some(wgpu::Backends::GL),
+        "primary" => Some(DEFAULT_BACKENDS),
+        "all" => Some(wgpu::Backends::all()),
+        _ => None,
+    }
+}
+
+/// Parses a power preference, `low` or `high`, as used in settings and command line
+/// arguments.
+pub fn parse_power_preference(name: &str) -> Option<wgpu::PowerPreference> {
+    match name.to_lowercase().as_str() {
+        "low" | "low-power" => Some(wgpu::PowerPreference::LowPower),
+        "high" | "high-performance" => Some(wgpu::PowerPreference::HighPerformance),
+        _ => None,
+    }
+}
+
+/// Returns the adapters available on `backends`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    instance
+        .enumerate_adapters(backends)
+        .iter()
+        .map(wgpu::Adapter::get_info)
+        .collect()
+}
+
+/// Picks the adapter drawing to `surface` with `settings`, the one named by
+/// [`RendererSettings::adapter`] if there is one, or else the one the power preference
+/// prefers.
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    settings: &RendererSettings,
+) -> Option<wgpu::Adapter> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(name) = &settings.adapter {
+        let adapter = instance
+            .enumerate_adapters(settings.backends)
+            .into_iter()
+            .find(|adapter| {
+                adapter
+                    .get_info()
+                    .name
+                    .to_lowercase()
+                    .contains(&name.to_lowercase())
+                    && adapter.is_surface_supported(surface)
+            });
+        match adapter {
+            Some(adapter) => return Some(adapter),
+            None => eprintln!("No adapter named {name} can draw to the window, picking another"),
+        }
+    }
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: settings.power_preference,
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        })
+        .await
+}
+
+/// Parses the name of a present mode, as used in settings and command line arguments.
+pub fn parse_present_mode(name: &str) -> Option<wgpu::PresentMode> {
+    match name.to_lowercase().as_str() {
+        "fifo" | "vsync" => Some(wgpu::PresentMode::Fifo),
+        "fifo-relaxed" => Some(wgpu::PresentMode::FifoRelaxed),
+        "mailbox" => Some(wgpu::PresentMode::Mailbox),
+        "immediate" => Some(wgpu::PresentMode::Immediate),
+        _ => None,
+    }
+}
+
+/// Returns `requested` if the surface supports it, or else the closest supported present mode.
+/// Uncapped modes fall back to each other before falling back to `Fifo`, which is always
+/// supported.
+fn choose_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+
+    let fallbacks: &[wgpu::PresentMode] = match requested {
+        Immediate => &[Immediate, Mailbox],
+        Mailbox => &[Mailbox, Immediate],
+        FifoRelaxed => &[FifoRelaxed],
+        _ => &[],
+    };
+
+    let chosen = fallbacks
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(Fifo);
+    if chosen != requested {
+        eprintln!("Present mode {requested:?} is not supported, using {chosen:?}");
+    }
+    chosen
+}
+
+/// Picks the format of a surface supporting `formats`, returning the surface format and the
+/// format frames are drawn in. Shaders output linear colors, so frames are drawn in an sRGB
+/// format that encodes them for display. If the surface has no sRGB format, its first format
+/// is drawn to through an sRGB view where it has one.
+fn choose_surface_format(
+    formats: &[wgpu::TextureFormat],
+) -> (wgpu::TextureFormat, wgpu::TextureFormat) {
+    if let Some(&format) = formats.iter().find(|format| format.is_srgb()) {
+        return (format, format);
+    }
+
+    let format = formats[0];
+    let view_format = format.add_srgb_suffix();
+    if view_format == format {
+        eprintln!("The surface has no sRGB format, colors will look too dark");
+    }
+    (format, view_format)
+}
+
+/// Returns the format frames are drawn in for a surface configured by `config`, which is the
+/// sRGB view of the surface if it needs one.
+pub fn frame_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+    config
+        .view_formats
+        .first()
+        .copied()
+        .unwrap_or(config.format)
+}
+
+/// Creates the multisampled color target matching the surface, or returns `None` if
+/// `sample_count` is 1 and frames are drawn to the surface directly.
+fn create_msaa_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled color texture"),
+        size: wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: frame_format(config),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
 impl<'a> Renderer<'a> {
-    pub async fn new(window: &'a Window) -> Self {
+    /// Creates a renderer drawing to `window`. Settings the adapter or surface doesn't support
+    /// fall back to ones it does. Fails if there is no adapter or a shader doesn't compile.
+    pub async fn new(window: &'a Window, settings: RendererSettings) -> anyhow::Result<Self> {
+        let sample_count = settings.sample_count;
         let size = window.inner_size();
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::PRIMARY,
-            #[cfg(target_arch = "wasm32")]
-            backends: wgpu::Backends::GL,
-            ..Default::default()
-        });
-        let surface = instance
-            .create_surface(window)
-            .expect("Failed to create surface");
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
+        let create_surface = |backends| {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                ..Default::default()
+            });
+            let surface = instance
+                .create_surface(window)
+                .context("Failed to create surface")?;
+            anyhow::Ok((instance, surface))
+        };
+        let (mut instance, mut surface) = create_surface(settings.backends)?;
+        let mut adapter = request_adapter(&instance, &surface, &settings).await;
+        if adapter.is_none() && settings.backends != wgpu::Backends::all() {
+            eprintln!(
+                "No graphics adapter found on {:?}, trying every backend",
+                settings.backends
+            );
+            (instance, surface) = create_surface(wgpu::Backends::all())?;
+            let settings = RendererSettings {
+                backends: wgpu::Backends::all(),
+                ..settings.clone()
+            };
+            adapter = request_adapter(&instance, &surface, &settings).await;
+        }
+        let adapter = adapter.context("No graphics adapter found")?;
+        let info = adapter.get_info();
+        println!("Drawing with {} ({:?})", info.name, info.backend);
+        // Draw chunks sharing buffers with a single call where possible.
+        let multi_draw = adapter
+            .features()
+            .contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+        let required_features = if multi_draw {
+            wgpu::Features::MULTI_DRAW_INDIRECT
+        } else {
+            wgpu::Features::empty()
+        } | PipelineCache::required_features(&adapter);
+        let required_features = if settings.gpu_timings {
+            required_features | GpuProfiler::required_features(&adapter)
+        } else {
+            required_features
+        };
+        // Occlusion culling changes the draws in a compute shader, which needs them to be read
+        // from a buffer.
+        let supports_occlusion_culling = adapter.get_downlevel_capabilities().flags.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+        );
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::empty(),
+                    required_features,
                     required_limits: wgpu::Limits::default(),
                     ..Default::default()
                 },
                 None,
             )
-            .await
-            .unwrap();
+            .await?;
+        let pipeline_cache =
+            PipelineCache::load(&device, &adapter, Path::new(PIPELINE_CACHE_DIRECTORY));
 
         let surface_caps = surface.get_capabilities(&adapter);
 
-        let texture_format = surface_caps.formats[0];
+        let (surface_format, texture_format) = choose_surface_format(&surface_caps.formats);
+
+        let supported = adapter
+            .get_texture_format_features(texture_format)
+            .flags
+            .sample_count_supported(sample_count);
+        let sample_count = if supported {
+            sample_count
+        } else {
+            eprintln!("{sample_count}x multisampling is not supported, disabling it");
+            1
+        };
 
         let surface_configuration = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: texture_format,
+            format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: choose_present_mode(settings.present_mode, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
+            view_formats: if surface_format == texture_format {
+                vec![]
+            } else {
+                vec![texture_format]
+            },
             desired_maximum_frame_latency: 2,
         };
 
-        surface.configure(&device, &surface_configuration);
+        if size.width > 0 && size.height > 0 {
+            surface.configure(&device, &surface_configuration);
+        }
 
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -113,6 +432,31 @@ impl<'a> Renderer<'a> {
             label: Some("Camera Bind Group"),
         });
 
+        let water_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Water Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    count: None,
+                }],
+            });
+        let time_buffer = buffer::DynamicBuffer::new(&device, 1, wgpu::BufferUsages::UNIFORM);
+
+        let water_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &water_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: time_buffer.buf().buf.as_entire_binding(),
+            }],
+            label: Some("Water Bind Group"),
+        });
+
         let terrain_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("Texture Bind Group Layout"),
@@ -122,7 +466,7 @@ impl<'a> Renderer<'a> {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
@@ -138,10 +482,12 @@ impl<'a> Renderer<'a> {
 
         let terrain_atlas = include_bytes!("../../assets/terrain.png");
 
-        let terrain_texture = crate::renderer::texture::Texture::from_bytes(
+        let terrain_texture = crate::renderer::texture::Texture::array_from_atlas(
             &device,
             &queue,
             terrain_atlas,
+            block::texture_tiles(),
+            block::TILE_SIZE,
             "Terrain Texture",
         )
         .unwrap();
@@ -161,47 +507,244 @@ impl<'a> Renderer<'a> {
             label: Some("Texture Bind Group"),
         });
 
-        let terrain_pipeline = TerrainPipeline::new(
-            &BindGroups {
-                camera: &camera_bind_group,
-                terrain: &terrain_bind_group,
-            },
-            &BindGroupLayouts {
-                camera: &camera_bind_group_layout,
-                terrain: &terrain_bind_group_layout,
-            },
-            &device,
-            texture_format,
-        );
+        let sky_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sky Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                            ty: wgpu::BufferBindingType::Uniform,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                            ty: wgpu::BufferBindingType::Uniform,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                ],
+            });
+        let sky_buffer = buffer::DynamicBuffer::new(&device, 1, wgpu::BufferUsages::UNIFORM);
+        let shadow_map = catch_errors(&device, || {
+            ShadowMap::new(&device, &terrain_bind_group_layout, pipeline_cache.get())
+        })
+        .context("Failed to create the shadow pipeline")?;
+
+        let sky_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sky_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sky_buffer.buf().buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: shadow_map.uniform_buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(shadow_map.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(shadow_map.sampler()),
+                },
+            ],
+            label: Some("Sky Bind Group"),
+        });
+
+        let terrain_pipeline = catch_errors(&device, || {
+            TerrainPipeline::new(
+                &BindGroups {
+                    camera: &camera_bind_group,
+                    terrain: &terrain_bind_group,
+                    sky: &sky_bind_group,
+                    water: &water_bind_group,
+                },
+                &BindGroupLayouts {
+                    camera: &camera_bind_group_layout,
+                    terrain: &terrain_bind_group_layout,
+                    sky: &sky_bind_group_layout,
+                    water: &water_bind_group_layout,
+                },
+                &device,
+                texture_format,
+                sample_count,
+                pipeline_cache.get(),
+            )
+        })
+        .context("Failed to create the terrain pipelines")?;
+        let celestial = catch_errors(&device, || {
+            Celestial::new(
+                &device,
+                &BindGroupLayouts {
+                    camera: &camera_bind_group_layout,
+                    terrain: &terrain_bind_group_layout,
+                    sky: &sky_bind_group_layout,
+                    water: &water_bind_group_layout,
+                },
+                texture_format,
+                sample_count,
+                pipeline_cache.get(),
+            )
+        })
+        .context("Failed to create the celestial pipeline")?;
+        let clouds = catch_errors(&device, || {
+            Clouds::new(
+                &device,
+                &BindGroupLayouts {
+                    camera: &camera_bind_group_layout,
+                    terrain: &terrain_bind_group_layout,
+                    sky: &sky_bind_group_layout,
+                    water: &water_bind_group_layout,
+                },
+                texture_format,
+                sample_count,
+                pipeline_cache.get(),
+            )
+        })
+        .context("Failed to create the cloud pipeline")?;
+        let entity_pipeline = catch_errors(&device, || {
+            EntityPipeline::new(
+                &device,
+                &BindGroupLayouts {
+                    camera: &camera_bind_group_layout,
+                    terrain: &terrain_bind_group_layout,
+                    sky: &sky_bind_group_layout,
+                    water: &water_bind_group_layout,
+                },
+                texture_format,
+                sample_count,
+                pipeline_cache.get(),
+            )
+        })
+        .context("Failed to create the entity pipeline")?;
 
         let depth_texture = super::texture::Texture::create_depth_texture(
             &device,
             &surface_configuration,
+            sample_count,
             "Depth texture",
         );
+        let msaa_view = create_msaa_view(&device, &surface_configuration, sample_count);
+        let ssao = settings
+            .ssao
+            .then(|| {
+                catch_errors(&device, || {
+                    Ssao::new(
+                        &device,
+                        &surface_configuration,
+                        &depth_texture.view,
+                        sample_count,
+                        pipeline_cache.get(),
+                    )
+                })
+                .context("Failed to create the SSAO pipelines")
+            })
+            .transpose()?;
+        let profiler = settings
+            .gpu_timings
+            .then(|| GpuProfiler::new(&device, &queue))
+            .flatten();
+        if settings.gpu_timings && profiler.is_none() {
+            eprintln!("GPU timings are not supported, disabling them");
+        }
+        if settings.occlusion_culling && !supports_occlusion_culling {
+            eprintln!("Occlusion culling is not supported, disabling it");
+        }
+        let occlusion_culling = settings.occlusion_culling && supports_occlusion_culling;
+        let occlusion = occlusion_culling
+            .then(|| {
+                catch_errors(&device, || {
+                    OcclusionCulling::new(
+                        &device,
+                        &surface_configuration,
+                        &depth_texture.view,
+                        sample_count,
+                        pipeline_cache.get(),
+                    )
+                })
+                .context("Failed to create the occlusion culling pipelines")
+            })
+            .transpose()?;
+        let mut indirect = IndirectDraws::new(&device, multi_draw);
+        indirect.set_indirect(&device, occlusion_culling);
 
-        Self {
+        Ok(Self {
             surface,
             queue,
             surface_config: surface_configuration,
+            present_modes: surface_caps.present_modes,
             size,
             terrain_pipeline,
+            celestial,
+            clouds,
+            entity_pipeline,
+            indirect,
             resolution: Vector2::new(size.width, size.height),
             camera_buffer,
             device: Arc::new(device),
 
             depth_texture,
+            sample_count,
+            msaa_view,
+            ssao,
+            occlusion,
+            supports_occlusion_culling,
+            profiler,
+            pipeline_cache,
 
             camera_bind_group_layout,
             camera_bind_group,
+            view_cameras: Vec::new(),
+            sky_buffer,
+            shadow_map,
+            sky_bind_group_layout,
+            sky_bind_group,
+            clear_color: wgpu::Color::WHITE,
+            start: Instant::now(),
+            time_buffer,
+            water_bind_group_layout,
+            water_bind_group,
             terrain_texture,
             terrain_bind_group_layout,
             terrain_bind_group,
-        }
+        })
     }
 
+    /// Resizes the frame to `size`. A surface can't have a size of 0, so while the window is
+    /// minimized the targets keep their size until it is restored.
     pub fn on_resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
         self.size = size;
+        if self.is_minimized() {
+            return;
+        }
+
         self.resolution = Vector2::new(size.width, size.height);
         self.surface_config.width = size.width;
         self.surface_config.height = size.height;
@@ -210,26 +753,179 @@ impl<'a> Renderer<'a> {
         self.depth_texture = super::texture::Texture::create_depth_texture(
             &self.device,
             &self.surface_config,
+            self.sample_count,
             "Depth texture",
         );
+        self.msaa_view = create_msaa_view(&self.device, &self.surface_config, self.sample_count);
+        if let Some(ssao) = &mut self.ssao {
+            ssao.resize(&self.device, &self.surface_config, &self.depth_texture.view);
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.resize(&self.device, &self.surface_config, &self.depth_texture.view);
+        }
+    }
+
+    /// Returns whether the window has no area, like while it is minimized, in which case
+    /// frames can't be drawn.
+    pub fn is_minimized(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
     }
 
     pub fn device(&self) -> &wgpu::Device {
         &self.device
     }
 
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn ssao_enabled(&self) -> bool {
+        self.ssao.is_some()
+    }
+
+    /// Turns screen space ambient occlusion on or off. Stays off if its shaders don't compile.
+    pub fn set_ssao_enabled(&mut self, enabled: bool) {
+        if enabled == self.ssao.is_some() {
+            return;
+        }
+
+        self.ssao = enabled
+            .then(|| {
+                catch_errors(&self.device, || {
+                    Ssao::new(
+                        &self.device,
+                        &self.surface_config,
+                        &self.depth_texture.view,
+                        self.sample_count,
+                        self.pipeline_cache.get(),
+                    )
+                })
+            })
+            .transpose()
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to create the SSAO pipelines: {err}");
+                None
+            });
+    }
+
+    pub fn occlusion_culling_enabled(&self) -> bool {
+        self.occlusion.is_some()
+    }
+
+    /// Turns occlusion culling on or off. Stays off if the device doesn't support it or its
+    /// shaders don't compile.
+    pub fn set_occlusion_culling_enabled(&mut self, enabled: bool) {
+        let enabled = enabled && self.supports_occlusion_culling;
+        if enabled == self.occlusion.is_some() {
+            return;
+        }
+
+        self.occlusion = enabled
+            .then(|| {
+                catch_errors(&self.device, || {
+                    OcclusionCulling::new(
+                        &self.device,
+                        &self.surface_config,
+                        &self.depth_texture.view,
+                        self.sample_count,
+                        self.pipeline_cache.get(),
+                    )
+                })
+            })
+            .transpose()
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to create the occlusion culling pipelines: {err}");
+                None
+            });
+        self.indirect
+            .set_indirect(&self.device, self.occlusion.is_some());
+    }
+
+    /// Saves the pipelines compiled so far, so the next run doesn't compile them again.
+    pub fn save_pipeline_cache(&self) -> anyhow::Result<()> {
+        self.pipeline_cache.save()
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    /// Switches to `present_mode`, or the closest one the surface supports.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.surface_config.present_mode = choose_present_mode(present_mode, &self.present_modes);
+        if !self.is_minimized() {
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
     pub fn update_camera_uniform(&mut self, camera: CameraUniform) {
         self.camera_buffer.update(&self.queue, &[camera], 0);
+        if let Some(ssao) = &self.ssao {
+            ssao.update(&self.queue, &camera);
+        }
+        if let Some(occlusion) = &mut self.occlusion {
+            occlusion.set_camera(&camera);
+        }
+    }
+
+    /// Updates the camera that passes of `view` draw from, see [`Frame::set_view`]. View 0 is
+    /// the camera of [`Renderer::update_camera_uniform`], which culling and SSAO follow, and
+    /// other views are created when first updated.
+    pub fn update_view_camera_uniform(&mut self, view: usize, camera: CameraUniform) {
+        if view == 0 {
+            self.update_camera_uniform(camera);
+            return;
+        }
+
+        while self.view_cameras.len() < view {
+            let buffer = buffer::DynamicBuffer::new(&self.device, 1, wgpu::BufferUsages::UNIFORM);
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.camera_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.buf().buf.as_entire_binding(),
+                }],
+                label: Some("View Camera Bind Group"),
+            });
+            self.view_cameras.push((buffer, bind_group));
+        }
+        self.view_cameras[view - 1]
+            .0
+            .update(&self.queue, &[camera], 0);
     }
 
     pub fn camera_buffer(&self) -> &wgpu::Buffer {
         &self.camera_buffer.buf().buf
     }
 
+    /// Updates the sky uniform, and clears the following frames to the color of the sky.
+    pub fn update_sky_uniform(&mut self, sky: SkyUniform) {
+        self.sky_buffer.update(&self.queue, &[sky], 0);
+        let [r, g, b, a] = sky.color.map(f64::from);
+        self.clear_color = wgpu::Color { r, g, b, a };
+    }
+
+    /// Moves the sun, moon and stars to where they are in `celestial`.
+    pub fn update_celestial_uniform(&self, celestial: CelestialUniform) {
+        self.celestial.update(&self.queue, celestial);
+    }
+
+    /// Moves the shadow cascades to `cascades`, with shadows blocking `strength` of the direct
+    /// sky light.
+    pub fn update_shadows(&mut self, cascades: &[ShadowCascade; SHADOW_CASCADES], strength: f32) {
+        self.shadow_map.update(&self.queue, cascades, strength);
+    }
+
     pub fn bind_group_layouts(&self) -> BindGroupLayouts {
         BindGroupLayouts {
             camera: &self.camera_bind_group_layout,
             terrain: &self.terrain_bind_group_layout,
+            sky: &self.sky_bind_group_layout,
+            water: &self.water_bind_group_layout,
         }
     }
 
@@ -237,65 +933,325 @@ impl<'a> Renderer<'a> {
         BindGroups {
             camera: &self.camera_bind_group,
             terrain: &self.terrain_bind_group,
+            sky: &self.sky_bind_group,
+            water: &self.water_bind_group,
+        }
+    }
+
+    /// Returns the bind groups drawing from the camera of `view`, falling back to the first
+    /// view's camera for views never updated.
+    pub fn view_bind_groups(&self, view: usize) -> BindGroups {
+        let camera = match view
+            .checked_sub(1)
+            .and_then(|view| self.view_cameras.get(view))
+        {
+            Some((_, bind_group)) => bind_group,
+            None => &self.camera_bind_group,
+        };
+        BindGroups {
+            camera,
+            ..self.bind_groups()
         }
     }
 
-    pub fn draw_terrain(&mut self, mesh: &TerrainMesh) -> anyhow::Result<()> {
-        let surface = self.surface.get_current_texture()?;
+    /// Returns the color frames are cleared to, the color of the sky.
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
 
-        let surface_view = surface
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Starts drawing a frame to the surface. Passes added to the frame run once it's
+    /// submitted with [`Renderer::submit_frame`]. A surface that was lost or changed is
+    /// configured again and retried once, other errors mean the frame has to be skipped, or
+    /// with [`wgpu::SurfaceError::OutOfMemory`] that nothing can be drawn anymore.
+    pub fn begin_frame<'f>(&self) -> Result<Frame<'f>, wgpu::SurfaceError> {
+        let surface = match self.surface.get_current_texture() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                self.surface.get_current_texture()?
+            }
+            surface => surface?,
+        };
 
-        let mut encoder = self
+        let time = TimeUniform {
+            seconds: self.start.elapsed().as_secs_f32(),
+            _padding: [0.0; 3],
+        };
+        self.time_buffer.update(&self.queue, &[time], 0);
+
+        let encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Terrain Encoder"),
+                label: Some("Frame Encoder"),
+            });
+        Ok(Frame::new(
+            surface,
+            frame_format(&self.surface_config),
+            encoder,
+        ))
+    }
+
+    /// Runs the passes of `frame` in order and presents it. The passes are timed with GPU
+    /// timings on, unless the timings of an earlier frame are still being read back.
+    pub fn submit_frame(&mut self, frame: Frame) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.poll(&self.device);
+        }
+
+        let profiler = self
+            .profiler
+            .as_ref()
+            .filter(|profiler| profiler.is_ready());
+        let (commands, surface, timed) = frame.finish(
+            self,
+            self.msaa_view.as_ref(),
+            &self.depth_texture.view,
+            profiler,
+        );
+        self.queue.submit(std::iter::once(commands));
+        surface.present();
+        self.indirect.clear();
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.read_back(timed);
+        }
+    }
+
+    /// Takes the time each pass took on the GPU in the last timed frame, in seconds, or `None`
+    /// if GPU timings are off or no frame was timed since they were last taken.
+    pub fn take_gpu_timings(&mut self) -> Option<Vec<(&'static str, f32)>> {
+        self.profiler.as_mut()?.take_timings()
+    }
+
+    /// Adds the pass clearing `frame` to the color of the sky and drawing the sun, moon and
+    /// stars over it. Everything else is drawn over the sky, so it has to be added first.
+    pub fn draw_sky(&self, frame: &mut Frame) {
+        frame.add_pass("Sky", |renderer, encoder, targets| {
+            renderer.celestial.draw(
+                encoder,
+                targets,
+                &renderer.view_bind_groups(targets.view),
+                renderer.clear_color,
+            );
+        });
+    }
+
+    /// Adds the passes drawing the opaque faces of the given terrain meshes to `frame`. The
+    /// meshes are expected to be uploaded already and ordered nearest to the camera first. The
+    /// shadow casters of each cascade are drawn into the shadow maps first. Opaque faces are
+    /// drawn front to back, in batches of the chunks sharing buffers, skipping chunks occluded
+    /// in the previous frame if occlusion culling is on, and are darkened by SSAO if it's on.
+    /// Translucent faces are drawn after anything else opaque, see
+    /// [`Renderer::draw_transparent_terrain`]. With several views, see [`Frame::set_view`],
+    /// only the first one draws the shadow maps, and culling and SSAO are off while it's drawn
+    /// into a viewport.
+    pub fn draw_terrain<'f>(
+        &mut self,
+        frame: &mut Frame<'f>,
+        meshes: &'f [&'f MeshBuffers<BlockVertex>],
+        shadow_casters: &[Vec<&'f MeshBuffers<BlockVertex>>; SHADOW_CASCADES],
+    ) {
+        // Shadows follow the sun, so the first view draws them for the others. Culling and
+        // SSAO read the depth buffer as seen from the first view's camera, so they only run
+        // when it fills the frame.
+        let primary = frame.view() == 0;
+        let full = primary && frame.viewport().is_none();
+
+        let opaque = self.indirect.add(meshes, MeshPass::Opaque as usize);
+        let casters = primary.then(|| {
+            shadow_casters
+                .each_ref()
+                .map(|casters| self.indirect.add(casters, MeshPass::Opaque as usize))
+        });
+        self.indirect.upload(&self.device, &self.queue);
+        if let Some(occlusion) = &mut self.occlusion {
+            if full {
+                occlusion.prepare(&self.device, &self.queue, &self.indirect, &opaque);
+            } else if primary {
+                occlusion.discard_pyramid();
+            }
+        }
+
+        if let Some(casters) = casters {
+            frame.add_pass("Shadows", move |renderer, encoder, _| {
+                renderer.shadow_map.draw(
+                    encoder,
+                    &renderer.terrain_bind_group,
+                    &renderer.indirect,
+                    &casters,
+                );
+            });
+        }
+        if full && self.occlusion.is_some() {
+            frame.add_pass("Occlusion culling", |renderer, encoder, _| {
+                if let Some(occlusion) = &renderer.occlusion {
+                    occlusion.cull(encoder);
+                }
             });
+        }
+        frame.add_pass("Opaque terrain", move |renderer, encoder, targets| {
+            renderer.draw_opaque_terrain(encoder, targets, &opaque);
+        });
+        if full && self.occlusion.is_some() {
+            // Only opaque terrain hides what is behind it.
+            frame.add_pass("Hi-Z", |renderer, encoder, _| {
+                if let Some(occlusion) = &renderer.occlusion {
+                    occlusion.build_pyramid(encoder);
+                }
+            });
+        }
+        if full && self.ssao.is_some() {
+            // Darken the opaque terrain before anything is blended over it.
+            frame.add_pass("SSAO", |renderer, encoder, targets| {
+                if let Some(ssao) = &renderer.ssao {
+                    ssao.draw(encoder, targets.color);
+                }
+            });
+        }
+    }
 
+    /// Adds the pass drawing the instances of each entity mesh in `batches` to `frame`, over
+    /// the opaque terrain drawn before it.
+    pub fn draw_entities<'f>(&mut self, frame: &mut Frame<'f>, batches: &[EntityBatch<'f>]) {
+        if batches.is_empty() {
+            return;
+        }
+
+        let draws = self
+            .entity_pipeline
+            .prepare(&self.device, &self.queue, batches);
+        frame.add_pass("Entities", move |renderer, encoder, targets| {
+            renderer.entity_pipeline.draw(
+                encoder,
+                targets,
+                &renderer.view_bind_groups(targets.view),
+                &draws,
+            );
+        });
+    }
+
+    /// Adds the pass drawing `batches` of entity meshes over everything added to `frame`
+    /// before, like things held up in front of the camera.
+    pub fn draw_entities_in_front<'f>(
+        &mut self,
+        frame: &mut Frame<'f>,
+        batches: &[EntityBatch<'f>],
+    ) {
+        if batches.is_empty() {
+            return;
+        }
+
+        let draws = self
+            .entity_pipeline
+            .prepare_in_front(&self.device, &self.queue, batches);
+        frame.add_pass("Entities in front", move |renderer, encoder, targets| {
+            renderer.entity_pipeline.draw_in_front_of_all(
+                encoder,
+                targets,
+                &renderer.view_bind_groups(targets.view),
+                &draws,
+            );
+        });
+    }
+
+    /// Adds the pass drawing the translucent faces and water of the given terrain meshes to
+    /// `frame`, ordered like for [`Renderer::draw_terrain`]. They are drawn back to front so
+    /// they blend over what is behind them, so everything opaque has to be added before.
+    pub fn draw_transparent_terrain<'f>(
+        &self,
+        frame: &mut Frame<'f>,
+        meshes: &'f [&'f MeshBuffers<BlockVertex>],
+    ) {
+        frame.add_pass("Transparent terrain", move |renderer, encoder, targets| {
+            renderer.draw_transparent_meshes(encoder, targets, meshes);
+        });
+    }
+
+    /// Adds the pass drawing the clouds to `frame`, over the terrain drawn before it.
+    pub fn draw_clouds(&self, frame: &mut Frame) {
+        frame.add_pass("Clouds", |renderer, encoder, targets| {
+            renderer
+                .clouds
+                .draw(encoder, targets, &renderer.view_bind_groups(targets.view));
+        });
+    }
+
+    /// Adds the pass drawing `held` in front of the camera to `frame`. It is drawn over
+    /// everything, so it has to be added last.
+    pub fn draw_held_block<'f>(&self, frame: &mut Frame<'f>, held: &'f HeldBlock) {
+        frame.add_pass("Held block", move |renderer, encoder, targets| {
+            renderer.entity_pipeline.draw_in_front(
+                encoder,
+                targets,
+                &renderer.view_bind_groups(targets.view),
+                held.mesh(),
+                held.instance_buffer(),
+            );
+        });
+    }
+
+    /// Draws the opaque faces of the terrain over the sky, clearing the depth buffer first.
+    fn draw_opaque_terrain(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        draws: &MeshDraws,
+    ) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &surface_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Clear(1.0))),
             ..Default::default()
         });
+        targets.set_viewport(&mut render_pass);
 
-        let bind_groups = self.bind_groups();
+        let bind_groups = self.view_bind_groups(targets.view);
         render_pass.set_bind_group(0, bind_groups.camera, &[]);
         render_pass.set_bind_group(1, bind_groups.terrain, &[]);
+        render_pass.set_bind_group(2, bind_groups.sky, &[]);
         render_pass.set_pipeline(&self.terrain_pipeline.pipeline);
+        self.indirect.draw(&mut render_pass, draws);
+    }
 
-        let vertices = mesh.vertices();
-        let indices = mesh.indices();
-
-        let vertex = super::buffer::Buffer::new(&self.device, wgpu::BufferUsages::VERTEX, vertices);
-
-        let index = super::buffer::Buffer::new(&self.device, wgpu::BufferUsages::INDEX, indices);
+    /// Draws the translucent faces and water of `meshes` back to front. Faces are drawn mesh
+    /// by mesh instead of in batches, since they have to blend in order and switch pipelines.
+    fn draw_transparent_meshes(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        meshes: &[&MeshBuffers<BlockVertex>],
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparent render pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            ..Default::default()
+        });
+        targets.set_viewport(&mut render_pass);
 
-        render_pass.set_vertex_buffer(0, vertex.buf.slice(..));
-        render_pass.set_index_buffer(index.buf.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        let bind_groups = self.view_bind_groups(targets.view);
+        render_pass.set_bind_group(0, bind_groups.camera, &[]);
+        render_pass.set_bind_group(1, bind_groups.terrain, &[]);
+        render_pass.set_bind_group(2, bind_groups.sky, &[]);
+        render_pass.set_bind_group(3, bind_groups.water, &[]);
 
-        drop(render_pass);
-        let buffer = encoder.finish();
-        self.queue.submit(std::iter::once(buffer));
-        surface.present();
+        for mesh in meshes.iter().rev() {
+            let transparent = mesh.group(MeshPass::Transparent as usize);
+            let water = mesh.group(MeshPass::Water as usize);
+            if transparent.is_empty() && water.is_empty() {
+                continue;
+            }
 
-        Ok(())
+            render_pass.set_vertex_buffer(0, mesh.vertex_slice());
+            render_pass.set_index_buffer(mesh.index_slice(), wgpu::IndexFormat::Uint32);
+            if !transparent.is_empty() {
+                render_pass.set_pipeline(&self.terrain_pipeline.transparent_pipeline);
+                render_pass.draw_indexed(transparent, 0, 0..1);
+            }
+            if !water.is_empty() {
+                render_pass.set_pipeline(&self.terrain_pipeline.water_pipeline);
+                render_pass.draw_indexed(water, 0, 0..1);
+            }
+        }
     }
 }
 
@@ -303,17 +1259,33 @@ impl<'a> Renderer<'a> {
 pub struct BindGroupLayouts<'a> {
     pub camera: &'a wgpu::BindGroupLayout,
     pub terrain: &'a wgpu::BindGroupLayout,
+    pub sky: &'a wgpu::BindGroupLayout,
+    pub water: &'a wgpu::BindGroupLayout,
 }
 
 #[derive(Debug)]
 pub struct BindGroups<'a> {
     pub camera: &'a wgpu::BindGroup,
     pub terrain: &'a wgpu::BindGroup,
+    pub sky: &'a wgpu::BindGroup,
+    pub water: &'a wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TimeUniform {
+    pub seconds: f32,
+    pub _padding: [f32; 3],
 }
 
 #[derive(Debug)]
 pub struct TerrainPipeline {
     pub pipeline: wgpu::RenderPipeline,
+    /// Draws translucent faces blended over everything drawn before them, without writing
+    /// depth so faces further back still show through.
+    pub transparent_pipeline: wgpu::RenderPipeline,
+    /// Draws water like translucent faces, with waves and a drifting texture.
+    pub water_pipeline: wgpu::RenderPipeline,
 }
 
 impl TerrainPipeline {
@@ -322,6 +1294,8 @@ impl TerrainPipeline {
         bind_group_layouts: &BindGroupLayouts,
         device: &wgpu::Device,
         texture_format: wgpu::TextureFormat,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
     ) -> Self {
         let shader_src = include_str!("../../assets/shaders/terrain.wgsl");
 
@@ -337,50 +1311,109 @@ impl TerrainPipeline {
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Terrain Pipeline Layout"),
-            bind_group_layouts: &[bind_group_layouts.camera, bind_group_layouts.terrain],
+            bind_group_layouts: &[
+                bind_group_layouts.camera,
+                bind_group_layouts.terrain,
+                bind_group_layouts.sky,
+            ],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            vertex: wgpu::VertexState {
-                module: &vertex,
-                entry_point: Some("vs_main"),
-                buffers: &[BlockVertex::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fragment,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: texture_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            cache: None,
-            label: Some("Terrain Pipeline"),
-            layout: Some(&pipeline_layout),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            primitive: wgpu::PrimitiveState {
-                cull_mode: None,
-                front_face: wgpu::FrontFace::Ccw,
-                ..Default::default()
-            },
-            multiview: None,
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
+        let water_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Water shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/shaders/water.wgsl"
+            ))),
         });
 
-        Self { pipeline }
+        let water_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Water Pipeline Layout"),
+                bind_group_layouts: &[
+                    bind_group_layouts.camera,
+                    bind_group_layouts.terrain,
+                    bind_group_layouts.sky,
+                    bind_group_layouts.water,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let create_pipeline = |label,
+                               layout: &wgpu::PipelineLayout,
+                               vertex: &wgpu::ShaderModule,
+                               fragment: &wgpu::ShaderModule,
+                               entry_point,
+                               blend,
+                               depth_write_enabled| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                vertex: wgpu::VertexState {
+                    module: vertex,
+                    entry_point: Some("vs_main"),
+                    buffers: &[BlockVertex::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: fragment,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: texture_format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                cache,
+                label: Some(label),
+                layout: Some(layout),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    ..Default::default()
+                },
+                multiview: None,
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+            })
+        };
+
+        Self {
+            pipeline: create_pipeline(
+                "Terrain Pipeline",
+                &pipeline_layout,
+                &vertex,
+                &fragment,
+                "fs_main",
+                wgpu::BlendState::REPLACE,
+                true,
+            ),
+            transparent_pipeline: create_pipeline(
+                "Transparent Terrain Pipeline",
+                &pipeline_layout,
+                &vertex,
+                &fragment,
+                "fs_transparent",
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+            ),
+            water_pipeline: create_pipeline(
+                "Water Pipeline",
+                &water_pipeline_layout,
+                &water_shader,
+                &water_shader,
+                "fs_main",
+                wgpu::BlendState::ALPHA_BLENDING,
+                false,
+            ),
+        }
     }
 }
@@ -0,0 +1,272 @@
+use std::borrow::Cow;
+
+use crate::camera::ShadowCascade;
+
+use super::{
+    block::BlockVertex,
+    buffer,
+    indirect::{IndirectDraws, MeshDraws},
+};
+
+/// The number of slices the view is split into for shadows, each with its own shadow map.
+pub const SHADOW_CASCADES: usize = 3;
+/// How far from the camera each cascade ends. Cascades closer to the camera cover less space,
+/// so nearby shadows are sharper.
+pub const CASCADE_SPLITS: [f32; SHADOW_CASCADES] = [24.0, 72.0, 192.0];
+/// The width and height of the shadow map of each cascade, in texels.
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ShadowUniform {
+    /// Transforms world positions into the shadow map of each cascade.
+    pub cascades: [[[f32; 4]; 4]; SHADOW_CASCADES],
+    /// How much of the direct light shadows block, from 0 to 1.
+    pub strength: f32,
+    /// How far from the camera shadows end.
+    pub distance: f32,
+    /// The size of a texel of the shadow maps in texture coordinates.
+    pub texel_size: f32,
+    pub _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CascadeUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+/// The depth of the terrain as seen from the sun, rendered for every cascade so the terrain
+/// pass can tell which parts are in the shadow.
+pub struct ShadowMap {
+    #[allow(unused)]
+    texture: wgpu::Texture,
+    /// A view of all cascades, sampled while drawing the terrain.
+    view: wgpu::TextureView,
+    /// A view of each cascade, rendered into.
+    cascade_views: Vec<wgpu::TextureView>,
+    /// Compares depths with the shadow maps, blending between neighboring texels.
+    sampler: wgpu::Sampler,
+    uniform_buffer: buffer::DynamicBuffer<ShadowUniform>,
+    cascade_buffers: Vec<buffer::DynamicBuffer<CascadeUniform>>,
+    cascade_bind_groups: Vec<wgpu::BindGroup>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    /// Creates the shadow maps, drawing terrain with the textures bound by
+    /// `terrain_bind_group_layout` so the transparent parts of leaves and plants let light
+    /// through.
+    pub fn new(
+        device: &wgpu::Device,
+        terrain_bind_group_layout: &wgpu::BindGroupLayout,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: SHADOW_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let cascade_views = (0..SHADOW_CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow cascade"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform_buffer = buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::UNIFORM);
+
+        let cascade_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shadow Cascade Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    count: None,
+                }],
+            });
+        let cascade_buffers: Vec<buffer::DynamicBuffer<CascadeUniform>> = (0..SHADOW_CASCADES)
+            .map(|_| buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::UNIFORM))
+            .collect();
+        let cascade_bind_groups = cascade_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &cascade_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.buf().buf.as_entire_binding(),
+                    }],
+                    label: Some("Shadow Cascade Bind Group"),
+                })
+            })
+            .collect();
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../../assets/shaders/shadow.wgsl"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&cascade_bind_group_layout, terrain_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[BlockVertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                front_face: wgpu::FrontFace::Ccw,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Push the depth back a little, so surfaces don't shadow themselves.
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            texture,
+            view,
+            cascade_views,
+            sampler,
+            uniform_buffer,
+            cascade_buffers,
+            cascade_bind_groups,
+            pipeline,
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Returns the buffer holding the [`ShadowUniform`] the terrain pass reads the cascades
+    /// from.
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer.buf().buf
+    }
+
+    /// Moves the cascades to `cascades`, with shadows blocking `strength` of the direct light.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        cascades: &[ShadowCascade; SHADOW_CASCADES],
+        strength: f32,
+    ) {
+        let uniform = ShadowUniform {
+            cascades: cascades.map(|cascade| cascade.view_proj.into()),
+            strength,
+            distance: cascades[SHADOW_CASCADES - 1].far,
+            texel_size: 1.0 / SHADOW_MAP_SIZE as f32,
+            _padding: 0.0,
+        };
+        self.uniform_buffer.update(queue, &[uniform], 0);
+
+        for (buffer, cascade) in self.cascade_buffers.iter().zip(cascades) {
+            let uniform = CascadeUniform {
+                view_proj: cascade.view_proj.into(),
+            };
+            buffer.update(queue, &[uniform], 0);
+        }
+    }
+
+    /// Draws the opaque faces of the meshes casting shadows into each cascade, see
+    /// [`ShadowCascade::frustum`] for finding them.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        terrain_bind_group: &wgpu::BindGroup,
+        indirect: &IndirectDraws,
+        casters: &[MeshDraws; SHADOW_CASCADES],
+    ) {
+        for ((view, bind_group), draws) in self
+            .cascade_views
+            .iter()
+            .zip(&self.cascade_bind_groups)
+            .zip(casters)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_bind_group(1, terrain_bind_group, &[]);
+
+            indirect.draw(&mut render_pass, draws);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::camera::CameraUniform;
+
+use super::{buffer, renderer::frame_format};
+
+/// How far around a position is searched for terrain occluding it, in world units.
+const RADIUS: f32 = 1.5;
+/// How much fully occluded positions are darkened, from 0 to 1.
+const STRENGTH: f32 = 0.8;
+/// How far from the camera the occlusion fades out, where the difference isn't visible.
+const FADE_DISTANCE: f32 = 96.0;
+
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    view_proj: [[f32; 4]; 4],
+    inverse_view_proj: [[f32; 4]; 4],
+    view_position: [f32; 3],
+    radius: f32,
+    strength: f32,
+    distance: f32,
+    _padding: [f32; 2],
+}
+
+/// Screen space ambient occlusion, darkening the frame where terrain is close around it. The
+/// occlusion is found from the depth buffer, blurred, and multiplied into the frame after the
+/// opaque terrain is drawn.
+pub struct Ssao {
+    uniform_buffer: buffer::DynamicBuffer<SsaoUniform>,
+    occlusion_bind_group_layout: wgpu::BindGroupLayout,
+    blur_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// The occlusion before and after blurring, and the bind groups reading them, which
+    /// follow the size of the frame.
+    targets: SsaoTargets,
+}
+
+struct SsaoTargets {
+    occlusion_view: wgpu::TextureView,
+    blurred_view: wgpu::TextureView,
+    occlusion_bind_group: wgpu::BindGroup,
+    blur_bind_group: wgpu::BindGroup,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Ssao {
+    /// Creates the passes for frames configured by `config` with `sample_count` samples per
+    /// pixel, reading the depth of the terrain from `depth_view`.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        sample_count: u32,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let mut source = include_str!("../../assets/shaders/ssao.wgsl").to_string();
+        if sample_count > 1 {
+            source = source.replace(
+                "alias DepthTexture = texture_2d<f32>;",
+                "alias DepthTexture = texture_multisampled_2d<f32>;",
+            );
+        }
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSAO shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let texture_entry = |binding, sample_type, multisampled| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let unfiltered = wgpu::TextureSampleType::Float { filterable: false };
+
+        let occlusion_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SSAO Occlusion Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                            ty: wgpu::BufferBindingType::Uniform,
+                        },
+                        count: None,
+                    },
+                    // Read as plain floats, which every backend can load from.
+                    texture_entry(1, unfiltered, sample_count > 1),
+                ],
+            });
+        let blur_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SSAO Blur Bind Group Layout"),
+                entries: &[texture_entry(2, unfiltered, false)],
+            });
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("SSAO Composite Bind Group Layout"),
+                entries: &[texture_entry(3, unfiltered, false)],
+            });
+
+        let create_pipeline =
+            |label, layout: &wgpu::BindGroupLayout, entry_point, format, blend, sample_count| {
+                let pipeline_layout =
+                    device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some(label),
+                        bind_group_layouts: &[layout],
+                        push_constant_ranges: &[],
+                    });
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_fullscreen"),
+                        buffers: &[],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some(entry_point),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache,
+                })
+            };
+
+        // Multiplies the frame by the occlusion, keeping its alpha.
+        let multiply = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::Src,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let occlusion_pipeline = create_pipeline(
+            "SSAO Occlusion Pipeline",
+            &occlusion_bind_group_layout,
+            "fs_occlusion",
+            OCCLUSION_FORMAT,
+            None,
+            1,
+        );
+        let blur_pipeline = create_pipeline(
+            "SSAO Blur Pipeline",
+            &blur_bind_group_layout,
+            "fs_blur",
+            OCCLUSION_FORMAT,
+            None,
+            1,
+        );
+        let composite_pipeline = create_pipeline(
+            "SSAO Composite Pipeline",
+            &composite_bind_group_layout,
+            "fs_composite",
+            frame_format(config),
+            Some(multiply),
+            sample_count,
+        );
+
+        let uniform_buffer = buffer::DynamicBuffer::new(device, 1, wgpu::BufferUsages::UNIFORM);
+        let targets = SsaoTargets::new(
+            device,
+            config,
+            depth_view,
+            &uniform_buffer,
+            [
+                &occlusion_bind_group_layout,
+                &blur_bind_group_layout,
+                &composite_bind_group_layout,
+            ],
+        );
+
+        Self {
+            uniform_buffer,
+            occlusion_bind_group_layout,
+            blur_bind_group_layout,
+            composite_bind_group_layout,
+            occlusion_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            targets,
+        }
+    }
+
+    /// Recreates the targets after the frame was resized, reading the depth from the new
+    /// `depth_view`.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.targets = SsaoTargets::new(
+            device,
+            config,
+            depth_view,
+            &self.uniform_buffer,
+            [
+                &self.occlusion_bind_group_layout,
+                &self.blur_bind_group_layout,
+                &self.composite_bind_group_layout,
+            ],
+        );
+    }
+
+    /// Updates the camera the depth buffer is reconstructed with.
+    pub fn update(&self, queue: &wgpu::Queue, camera: &CameraUniform) {
+        let view_proj = Matrix4::from(camera.view_proj);
+        let inverse_view_proj = view_proj.invert().unwrap_or_else(Matrix4::identity);
+        let [x, y, z, _] = camera.view_position;
+
+        let uniform = SsaoUniform {
+            view_proj: camera.view_proj,
+            inverse_view_proj: inverse_view_proj.into(),
+            view_position: [x, y, z],
+            radius: RADIUS,
+            strength: STRENGTH,
+            distance: FADE_DISTANCE,
+            _padding: [0.0; 2],
+        };
+        self.uniform_buffer.update(queue, &[uniform], 0);
+    }
+
+    /// Finds the occlusion of the terrain in the depth buffer and darkens `color_view` by it.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, color_view: &wgpu::TextureView) {
+        let passes = [
+            (
+                &self.targets.occlusion_view,
+                &self.occlusion_pipeline,
+                &self.targets.occlusion_bind_group,
+                "SSAO occlusion pass",
+            ),
+            (
+                &self.targets.blurred_view,
+                &self.blur_pipeline,
+                &self.targets.blur_bind_group,
+                "SSAO blur pass",
+            ),
+            (
+                color_view,
+                &self.composite_pipeline,
+                &self.targets.composite_bind_group,
+                "SSAO composite pass",
+            ),
+        ];
+
+        for (view, pipeline, bind_group, label) in passes {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+impl SsaoTargets {
+    fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        uniform_buffer: &buffer::DynamicBuffer<SsaoUniform>,
+        [occlusion_layout, blur_layout, composite_layout]: [&wgpu::BindGroupLayout; 3],
+    ) -> Self {
+        let create_view = |label| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: config.width.max(1),
+                    height: config.height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: OCCLUSION_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            texture.create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let occlusion_view = create_view("SSAO occlusion texture");
+        let blurred_view = create_view("SSAO blurred texture");
+
+        let occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: occlusion_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.buf().buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+            label: Some("SSAO Occlusion Bind Group"),
+        });
+        let blur_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: blur_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&occlusion_view),
+            }],
+            label: Some("SSAO Blur Bind Group"),
+        });
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: composite_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&blurred_view),
+            }],
+            label: Some("SSAO Composite Bind Group"),
+        });
+
+        Self {
+            occlusion_view,
+            blurred_view,
+            occlusion_bind_group,
+            blur_bind_group,
+            composite_bind_group,
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use image::GenericImageView;
+use image::{GenericImageView, Rgba32FImage, RgbaImage};
+
+/// The format of textures loaded from images. Image files store their colors in sRGB, which is
+/// decoded to linear colors when the textures are sampled.
+const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 
 #[derive(Debug)]
 pub struct Texture {
@@ -33,7 +37,7 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let format = COLOR_FORMAT;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
@@ -76,11 +80,100 @@ impl Texture {
         })
     }
 
+    /// Creates a texture array from square tiles of `tile_size` pixels cut out of the atlas
+    /// image in `bytes`, one layer per tile in `tiles`, given by column and row. Every layer
+    /// has its own mipmaps, so neighboring tiles never bleed into each other.
+    pub fn array_from_atlas(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        tiles: &[(u32, u32)],
+        tile_size: u32,
+        label: &str,
+    ) -> anyhow::Result<Self> {
+        let atlas = image::load_from_memory(bytes)?.to_rgba8();
+        let mip_level_count = tile_size.ilog2() + 1;
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: tile_size,
+                height: tile_size,
+                depth_or_array_layers: tiles.len() as u32,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: COLOR_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, &(column, row)) in tiles.iter().enumerate() {
+            let (x, y) = (column * tile_size, row * tile_size);
+            if x + tile_size > atlas.width() || y + tile_size > atlas.height() {
+                anyhow::bail!("tile ({column}, {row}) is outside of the atlas");
+            }
+
+            let mut level =
+                image::imageops::crop_imm(&atlas, x, y, tile_size, tile_size).to_image();
+            for mip_level in 0..mip_level_count {
+                let size = level.width();
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        aspect: wgpu::TextureAspect::All,
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                    },
+                    &level,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * size),
+                        rows_per_image: Some(size),
+                    },
+                    wgpu::Extent3d {
+                        width: size,
+                        height: size,
+                        depth_or_array_layers: 1,
+                    },
+                );
+
+                level = downsample(&level);
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            // Merged quads repeat the texture instead of stretching it.
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Nearest,
+            mag_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
     const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -92,7 +185,7 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -120,3 +213,48 @@ impl Texture {
         }
     }
 }
+
+/// Halves the size of `image`, averaging its colors as linear light, so the mipmaps of sRGB
+/// textures don't get darker than the texture itself.
+fn downsample(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+    let linear = Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        image::Rgba([
+            srgb_to_linear(r),
+            srgb_to_linear(g),
+            srgb_to_linear(b),
+            a as f32 / 255.0,
+        ])
+    });
+    let halved = image::imageops::resize(&linear, width, height, image::imageops::Triangle);
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let [r, g, b, a] = halved.get_pixel(x, y).0;
+        image::Rgba([
+            linear_to_srgb(r),
+            linear_to_srgb(g),
+            linear_to_srgb(b),
+            (a * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round() as u8
+}
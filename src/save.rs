@@ -0,0 +1,824 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::block_entity;
+use crate::chunk::{Chunk, CHUNK_VOLUME};
+use crate::generator::GeneratorConfig;
+use crate::palette::PaletteStorage;
+use crate::position::{BlockPos, ChunkPos};
+use crate::renderer::block::BlockType;
+use crate::world::GameMode;
+
+/// The number of chunks along each horizontal axis of a region.
+pub const REGION_SIZE: i32 = 32;
+
+const REGION_MAGIC: &[u8; 4] = b"CRGN";
+const REGION_VERSION: u32 = 3;
+/// The version of region files that held every chunk one after another, without an offset
+/// table. They're converted when opened.
+const LEGACY_REGION_VERSION: u32 = 2;
+/// The number of chunk columns in a region.
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+/// The size of the sectors chunks are stored in within region files, in bytes.
+const SECTOR_SIZE: u64 = 4096;
+/// How many sectors the header of a region file takes: the magic, the version and the offset
+/// table, with the position and length of every chunk.
+const HEADER_SECTORS: u32 = (8 + REGION_CHUNKS as u64 * 8).div_ceil(SECTOR_SIZE) as u32;
+/// The most bytes a saved chunk may take, far more than even an uncompressed chunk full of
+/// containers, so a corrupt offset table can't make loading allocate gigabytes.
+const MAX_CHUNK_LEN: u32 = 1 << 20;
+
+/// The file blocks waiting for their chunks to be generated are kept in.
+const DEFERRED_FILE: &str = "deferred.bin";
+/// The file the metadata of the world is kept in.
+const METADATA_FILE: &str = "world.toml";
+/// The compression used for newly saved chunks.
+const COMPRESSION: Compression = Compression::RleDeflate;
+
+/// How the chunks inside a region file are encoded.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// The palette and packed indices as stored in memory.
+    None = 0,
+    /// Runs of identical blocks, compressed with deflate.
+    RleDeflate = 1,
+}
+
+impl Compression {
+    pub fn from_id(id: u8) -> Option<Compression> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::RleDeflate),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes a chunk, prefixed with a header recording the compression used. The data kept
+/// by its blocks follows the blocks.
+pub fn encode_chunk(chunk: &Chunk, compression: Compression) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![compression as u8];
+
+    match compression {
+        Compression::None => {
+            chunk.write(&mut bytes)?;
+            block_entity::write_all(chunk.block_entities(), &mut bytes)?;
+        }
+        Compression::RleDeflate => {
+            let mut encoder = DeflateEncoder::new(bytes, flate2::Compression::default());
+            let blocks = chunk.blocks();
+
+            let mut start = 0;
+            while start < blocks.len() {
+                let block_type = blocks[start];
+                let len = blocks[start..]
+                    .iter()
+                    .take_while(|b| **b == block_type)
+                    .count();
+
+                encoder.write_all(&(len as u32).to_le_bytes())?;
+                encoder.write_all(&(block_type as u32).to_le_bytes())?;
+                start += len;
+            }
+
+            block_entity::write_all(chunk.block_entities(), &mut encoder)?;
+            bytes = encoder.finish()?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Deserializes a chunk written with [`encode_chunk`]. Chunks saved before blocks kept data
+/// end after their blocks, and are read without any.
+pub fn decode_chunk(position: ChunkPos, bytes: &[u8]) -> io::Result<Chunk> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let (&compression, data) = bytes.split_first().ok_or_else(|| invalid("empty chunk"))?;
+    let compression =
+        Compression::from_id(compression).ok_or_else(|| invalid("unknown compression"))?;
+
+    match compression {
+        Compression::None => {
+            let mut reader = data;
+            let mut chunk = Chunk::read(position, &mut reader)?;
+            if !reader.is_empty() {
+                chunk.set_block_entities(block_entity::read_all(&mut reader)?);
+            }
+            Ok(chunk)
+        }
+        Compression::RleDeflate => {
+            let mut decoder = DeflateDecoder::new(data);
+            let mut blocks = PaletteStorage::new(CHUNK_VOLUME, BlockType::Air);
+
+            let mut index = 0;
+            while index < CHUNK_VOLUME {
+                let len = read_u32(&mut decoder)? as usize;
+                let block_type = BlockType::from_id(read_u32(&mut decoder)?)
+                    .ok_or_else(|| invalid("unknown block type"))?;
+                if len == 0 || index + len > CHUNK_VOLUME {
+                    return Err(invalid("invalid block run"));
+                }
+
+                for i in index..index + len {
+                    blocks.set(i, block_type);
+                }
+                index += len;
+            }
+
+            let mut chunk = Chunk::from_storage(position, blocks);
+            let mut rest = Vec::new();
+            decoder.read_to_end(&mut rest)?;
+            if !rest.is_empty() {
+                chunk.set_block_entities(block_entity::read_all(&mut rest.as_slice())?);
+            }
+            Ok(chunk)
+        }
+    }
+}
+
+/// The position of a region, in units of whole regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionPos(pub i32, pub i32);
+
+impl RegionPos {
+    /// Returns the region containing the given chunk.
+    pub fn of(chunk: ChunkPos) -> Self {
+        Self(
+            chunk.0.div_euclid(REGION_SIZE),
+            chunk.2.div_euclid(REGION_SIZE),
+        )
+    }
+}
+
+/// What is saved about a world besides its chunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    /// The seed the terrain of the world was generated with.
+    pub seed: u32,
+    /// The ticks of the world time, see [`crate::time::WorldTime::ticks`].
+    pub ticks: u64,
+    /// Where players start and respawn, the block their feet are in.
+    pub spawn: [i32; 3],
+    pub game_mode: GameMode,
+    /// The generator the chunks of the world are generated with. Missing for worlds saved
+    /// before it was recorded.
+    #[serde(default)]
+    pub generator: Option<GeneratorConfig>,
+}
+
+/// Saves chunks to region files in a world directory, each grouping the chunk columns of a
+/// `REGION_SIZE` by `REGION_SIZE` area. Clones share the same files, so one can write chunks
+/// in the background while another reads them.
+#[derive(Clone)]
+pub struct RegionStorage {
+    directory: PathBuf,
+    /// Held while a region file is read or written, so chunks aren't read halfway through
+    /// being written.
+    lock: Arc<Mutex<()>>,
+}
+
+impl RegionStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Serializes `chunks` with the compression used for newly saved chunks, to be written
+    /// with [`RegionStorage::write_chunks`]. Chunks that fail to serialize are reported and
+    /// left out.
+    pub fn encode_chunks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+    ) -> Vec<(ChunkPos, Vec<u8>)> {
+        chunks
+            .into_iter()
+            .filter_map(|chunk| match encode_chunk(chunk, COMPRESSION) {
+                Ok(data) => Some((chunk.position, data)),
+                Err(err) => {
+                    eprintln!("Failed to save chunk {:?}: {err}", chunk.position);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn region_path(&self, region: RegionPos) -> PathBuf {
+        self.directory
+            .join(format!("r.{}.{}.region", region.0, region.1))
+    }
+
+    /// Opens the file of `region`, creating it if `create` is set. Returns `None` if the region
+    /// was never saved and isn't created. Regions saved in the older format are converted.
+    fn open_region(&self, region: RegionPos, create: bool) -> anyhow::Result<Option<RegionFile>> {
+        let path = self.region_path(region);
+        if !path.exists() {
+            return Ok(if create {
+                Some(RegionFile::create(&path)?)
+            } else {
+                None
+            });
+        }
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        if &header[..4] != REGION_MAGIC {
+            anyhow::bail!("region {:?} has an unsupported format", region);
+        }
+        match u32::from_le_bytes(header[4..].try_into().unwrap()) {
+            REGION_VERSION => Ok(Some(RegionFile::read_table(file)?)),
+            LEGACY_REGION_VERSION => {
+                drop(file);
+                let chunks = read_legacy_region(&fs::read(&path)?)
+                    .map_err(|err| anyhow::anyhow!("region {:?}: {err}", region))?;
+
+                // Convert into a temporary file first so a crash midway can't lose the region.
+                let temp_path = path.with_extension("tmp");
+                let mut converted = RegionFile::create(&temp_path)?;
+                for (position, data) in &chunks {
+                    converted.write_chunk(*position, data)?;
+                }
+                drop(converted);
+                fs::rename(temp_path, &path)?;
+
+                let file = OpenOptions::new().read(true).write(true).open(&path)?;
+                Ok(Some(RegionFile::read_table(file)?))
+            }
+            _ => anyhow::bail!("region {:?} has an unsupported format", region),
+        }
+    }
+
+    /// Loads the chunks at `positions`, each on its own, so a chunk that fails to load doesn't
+    /// keep the others from loading. Chunks that were never saved are `None`. When a whole
+    /// region fails to open, every chunk in it fails.
+    pub fn load_chunks(
+        &self,
+        positions: &[ChunkPos],
+    ) -> Vec<(ChunkPos, anyhow::Result<Option<Chunk>>)> {
+        let mut chunks = Vec::new();
+        for (region, positions) in by_region(positions.iter().copied(), |p| *p) {
+            let _lock = self.lock.lock().unwrap();
+            let mut file = match self.open_region(region, false) {
+                Ok(Some(file)) => file,
+                Ok(None) => {
+                    chunks.extend(positions.into_iter().map(|position| (position, Ok(None))));
+                    continue;
+                }
+                Err(err) => {
+                    chunks.extend(
+                        positions
+                            .into_iter()
+                            .map(|position| (position, Err(anyhow::anyhow!("{err}")))),
+                    );
+                    continue;
+                }
+            };
+            for position in positions {
+                let chunk = file
+                    .read_chunk(position)
+                    .and_then(|data| data.map(|data| decode_chunk(position, &data)).transpose());
+                chunks.push((position, chunk.map_err(anyhow::Error::from)));
+            }
+        }
+
+        chunks
+    }
+
+    /// Returns which of `positions` have been saved, reading only the offset tables of their
+    /// regions.
+    pub fn saved_chunks(&self, positions: &[ChunkPos]) -> anyhow::Result<HashSet<ChunkPos>> {
+        let mut saved = HashSet::new();
+        for (region, positions) in by_region(positions.iter().copied(), |p| *p) {
+            let _lock = self.lock.lock().unwrap();
+            if let Some(file) = self.open_region(region, false)? {
+                saved.extend(positions.into_iter().filter(|p| file.contains(*p)));
+            }
+        }
+
+        Ok(saved)
+    }
+
+    /// Saves the given chunks into the regions they belong to, leaving the other chunks of
+    /// those regions untouched.
+    pub fn save_chunks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+    ) -> anyhow::Result<()> {
+        let encoded = chunks
+            .into_iter()
+            .map(|chunk| Ok((chunk.position, encode_chunk(chunk, COMPRESSION)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.write_chunks(encoded)
+    }
+
+    /// Writes chunks serialized with [`RegionStorage::encode_chunks`] into the regions they belong
+    /// to.
+    pub fn write_chunks(&self, chunks: Vec<(ChunkPos, Vec<u8>)>) -> anyhow::Result<()> {
+        for (region, chunks) in by_region(chunks, |(position, _)| *position) {
+            let _lock = self.lock.lock().unwrap();
+            let Some(mut file) = self.open_region(region, true)? else {
+                continue;
+            };
+            for (position, data) in chunks {
+                file.write_chunk(position, &data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Serialized chunks waiting to be written by the [`SaveWorker`].
+struct SaveJob {
+    id: u64,
+    chunks: Vec<(ChunkPos, Vec<u8>)>,
+}
+
+/// A background thread writing serialized chunks to region files, so writing them doesn't hold
+/// up frames. Jobs are written in the order they're submitted.
+pub struct SaveWorker {
+    jobs: Option<Sender<SaveJob>>,
+    results: Receiver<(u64, anyhow::Result<()>)>,
+    thread: Option<JoinHandle<()>>,
+    /// The chunks waiting to be written, with the last job writing them.
+    pending: HashMap<ChunkPos, u64>,
+    next_id: u64,
+}
+
+impl SaveWorker {
+    pub fn new(storage: RegionStorage) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<SaveJob>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("saver".to_string())
+            .spawn(move || {
+                for SaveJob { id, chunks } in job_receiver {
+                    let result = storage.write_chunks(chunks);
+                    if result_sender.send((id, result)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Failed to spawn the saver thread");
+
+        Self {
+            jobs: Some(job_sender),
+            results: result_receiver,
+            thread: Some(thread),
+            pending: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Queues serialized chunks to be written.
+    pub fn submit(&mut self, chunks: Vec<(ChunkPos, Vec<u8>)>) {
+        if chunks.is_empty() {
+            return;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        for (position, _) in &chunks {
+            self.pending.insert(*position, id);
+        }
+        if let Some(jobs) = &self.jobs {
+            // The thread only stops once the sender is dropped, so this can't fail.
+            let _ = jobs.send(SaveJob { id, chunks });
+        }
+    }
+
+    /// Returns whether `position` is waiting to be written, so what's on disk may be outdated.
+    pub fn is_pending(&self, position: ChunkPos) -> bool {
+        self.pending.contains_key(&position)
+    }
+
+    /// Returns whether any chunks are waiting to be written.
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Forgets the chunks of the jobs that were written, without blocking. Returns the chunks
+    /// of the jobs that failed, which are reported.
+    pub fn poll(&mut self) -> Vec<ChunkPos> {
+        let results: Vec<_> = self.results.try_iter().collect();
+        results
+            .into_iter()
+            .flat_map(|(id, result)| self.finish(id, result))
+            .collect()
+    }
+
+    /// Blocks until every queued chunk is written. Returns the chunks of the jobs that failed,
+    /// which are reported.
+    pub fn flush(&mut self) -> Vec<ChunkPos> {
+        let mut failed = Vec::new();
+        while self.is_busy() {
+            let Ok((id, result)) = self.results.recv() else {
+                break;
+            };
+            failed.extend(self.finish(id, result));
+        }
+        failed
+    }
+
+    /// Forgets the chunks last written by job `id`, returning them if it failed.
+    fn finish(&mut self, id: u64, result: anyhow::Result<()>) -> Vec<ChunkPos> {
+        let finished: Vec<ChunkPos> = self
+            .pending
+            .iter()
+            .filter(|(_, job)| **job == id)
+            .map(|(position, _)| *position)
+            .collect();
+        for position in &finished {
+            self.pending.remove(position);
+        }
+        match result {
+            Ok(()) => Vec::new(),
+            Err(err) => {
+                eprintln!("Failed to save chunks: {err}");
+                finished
+            }
+        }
+    }
+}
+
+impl Drop for SaveWorker {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread once it has written every queued job.
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Groups `items` by the region of the chunk `position` returns for them.
+fn by_region<T>(
+    items: impl IntoIterator<Item = T>,
+    position: impl Fn(&T) -> ChunkPos,
+) -> HashMap<RegionPos, Vec<T>> {
+    let mut regions: HashMap<RegionPos, Vec<T>> = HashMap::new();
+    for item in items {
+        regions
+            .entry(RegionPos::of(position(&item)))
+            .or_default()
+            .push(item);
+    }
+    regions
+}
+
+/// Where a chunk is in its region file, in the offset table.
+#[derive(Debug, Clone, Copy, Default)]
+struct TableEntry {
+    /// The first sector of the chunk.
+    sector: u32,
+    /// How many bytes the chunk takes, or 0 if it was never saved.
+    len: u32,
+}
+
+impl TableEntry {
+    /// Returns the sectors the chunk takes.
+    fn sectors(&self) -> Range<u32> {
+        self.sector..self.sector + (self.len as u64).div_ceil(SECTOR_SIZE) as u32
+    }
+}
+
+/// An open region file. It starts with a header holding an offset table, recording for every
+/// chunk column of the region where its chunk is, followed by the chunks, each starting at a
+/// sector of [`SECTOR_SIZE`] bytes. Reading a chunk takes a single seek, and a chunk is saved
+/// by writing it into free sectors before pointing the table at it, so a crash midway leaves
+/// the chunk as it was.
+struct RegionFile {
+    file: File,
+    table: Vec<TableEntry>,
+}
+
+impl RegionFile {
+    /// Creates an empty region file at `path`, replacing any file there.
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let mut header = vec![0; HEADER_SECTORS as usize * SECTOR_SIZE as usize];
+        header[..4].copy_from_slice(REGION_MAGIC);
+        header[4..8].copy_from_slice(&REGION_VERSION.to_le_bytes());
+        file.write_all(&header)?;
+
+        Ok(Self {
+            file,
+            table: vec![TableEntry::default(); REGION_CHUNKS],
+        })
+    }
+
+    /// Reads the offset table of the region file `file`, whose magic and version were read.
+    /// Fails if any chunk lies in the header, past the end of the file, or is too large to be
+    /// a chunk, since the table must be corrupt then.
+    fn read_table(mut file: File) -> io::Result<Self> {
+        let file_len = file.metadata()?.len();
+        let mut bytes = vec![0; REGION_CHUNKS * 8];
+        file.seek(SeekFrom::Start(8))?;
+        file.read_exact(&mut bytes)?;
+
+        let mut reader = bytes.as_slice();
+        let mut table = Vec::with_capacity(REGION_CHUNKS);
+        for _ in 0..REGION_CHUNKS {
+            let entry = TableEntry {
+                sector: read_u32(&mut reader)?,
+                len: read_u32(&mut reader)?,
+            };
+            let end = entry.sector as u64 * SECTOR_SIZE + entry.len as u64;
+            let valid = entry.sector >= HEADER_SECTORS && entry.len <= MAX_CHUNK_LEN;
+            if entry.len > 0 && !(valid && end <= file_len) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "corrupt offset table",
+                ));
+            }
+            table.push(entry);
+        }
+
+        Ok(Self { file, table })
+    }
+
+    /// Returns the index of the chunk column of `position` in the offset table. A region holds
+    /// a single chunk per column, since the world is a single chunk tall.
+    fn index(position: ChunkPos) -> usize {
+        let x = position.0.rem_euclid(REGION_SIZE);
+        let z = position.2.rem_euclid(REGION_SIZE);
+        (z * REGION_SIZE + x) as usize
+    }
+
+    fn contains(&self, position: ChunkPos) -> bool {
+        self.table[Self::index(position)].len > 0
+    }
+
+    /// Reads the serialized chunk at `position`, or `None` if it was never saved.
+    fn read_chunk(&mut self, position: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+        let entry = self.table[Self::index(position)];
+        if entry.len == 0 {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; entry.len as usize];
+        self.file
+            .seek(SeekFrom::Start(entry.sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Writes the serialized chunk at `position` into the first free sectors it fits in, then
+    /// points the offset table at it, freeing the sectors it took before.
+    fn write_chunk(&mut self, position: ChunkPos, data: &[u8]) -> io::Result<()> {
+        if data.len() > MAX_CHUNK_LEN as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk too large to save",
+            ));
+        }
+        let index = Self::index(position);
+        let needed = (data.len() as u64).div_ceil(SECTOR_SIZE).max(1) as u32;
+
+        let mut used: Vec<Range<u32>> = self
+            .table
+            .iter()
+            .filter(|entry| entry.len > 0)
+            .map(TableEntry::sectors)
+            .collect();
+        used.sort_by_key(|sectors| sectors.start);
+        let mut sector = HEADER_SECTORS;
+        for sectors in used {
+            if sectors.start >= sector + needed {
+                break;
+            }
+            sector = sector.max(sectors.end);
+        }
+
+        let mut bytes = data.to_vec();
+        bytes.resize(needed as usize * SECTOR_SIZE as usize, 0);
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(&bytes)?;
+
+        let entry = TableEntry {
+            sector,
+            len: data.len() as u32,
+        };
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&entry.sector.to_le_bytes());
+        bytes[4..].copy_from_slice(&entry.len.to_le_bytes());
+        self.file.seek(SeekFrom::Start(8 + index as u64 * 8))?;
+        self.file.write_all(&bytes)?;
+        self.table[index] = entry;
+
+        Ok(())
+    }
+}
+
+/// Reads the chunks of a region saved in the older format, which held every chunk one after
+/// another, after the magic and version.
+fn read_legacy_region(bytes: &[u8]) -> anyhow::Result<HashMap<ChunkPos, Vec<u8>>> {
+    let mut reader = &bytes[8..];
+    let count = read_u32(&mut reader)?;
+    let mut chunks = HashMap::new();
+    for _ in 0..count {
+        let position = ChunkPos(
+            read_i32(&mut reader)?,
+            read_i32(&mut reader)?,
+            read_i32(&mut reader)?,
+        );
+        let len = read_u32(&mut reader)? as usize;
+        if len > reader.len() {
+            anyhow::bail!("truncated");
+        }
+
+        let (data, rest) = reader.split_at(len);
+        chunks.insert(position, data.to_vec());
+        reader = rest;
+    }
+
+    Ok(chunks)
+}
+
+impl RegionStorage {
+    /// Saves blocks that were placed into chunks that don't exist yet, replacing those saved
+    /// before.
+    pub fn save_deferred(
+        &self,
+        deferred: &HashMap<ChunkPos, Vec<(BlockPos, BlockType)>>,
+    ) -> anyhow::Result<()> {
+        let path = self.directory.join(DEFERRED_FILE);
+        let count: usize = deferred.values().map(Vec::len).sum();
+        if count == 0 {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+
+        let mut bytes = Vec::new();
+        bytes.write_all(&(count as u32).to_le_bytes())?;
+        for (position, block_type) in deferred.values().flatten() {
+            bytes.write_all(&position.0.to_le_bytes())?;
+            bytes.write_all(&position.1.to_le_bytes())?;
+            bytes.write_all(&position.2.to_le_bytes())?;
+            bytes.write_all(&(*block_type as u32).to_le_bytes())?;
+        }
+
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, bytes)?;
+        fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Saves the metadata of the world, replacing what was saved before.
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> anyhow::Result<()> {
+        let path = self.directory.join(METADATA_FILE);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, toml::to_string(metadata)?)?;
+        fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Loads the metadata saved with [`RegionStorage::save_metadata`], or `None` if the world
+    /// was never saved.
+    pub fn load_metadata(&self) -> anyhow::Result<Option<WorldMetadata>> {
+        let path = self.directory.join(METADATA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(toml::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Loads the blocks saved with [`RegionStorage::save_deferred`], grouped by chunk.
+    pub fn load_deferred(&self) -> anyhow::Result<HashMap<ChunkPos, Vec<(BlockPos, BlockType)>>> {
+        let path = self.directory.join(DEFERRED_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let bytes = fs::read(path)?;
+        let mut reader = bytes.as_slice();
+
+        let count = read_u32(&mut reader)?;
+        let mut deferred: HashMap<ChunkPos, Vec<(BlockPos, BlockType)>> = HashMap::new();
+        for _ in 0..count {
+            let position = BlockPos(
+                read_i32(&mut reader)?,
+                read_i32(&mut reader)?,
+                read_i32(&mut reader)?,
+            );
+            let id = read_u32(&mut reader)?;
+            let Some(block_type) = BlockType::from_id(id) else {
+                anyhow::bail!("deferred block has unknown block type {id}");
+            };
+
+            deferred
+                .entry(position.chunk())
+                .or_default()
+                .push((position, block_type));
+        }
+
+        Ok(deferred)
+    }
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{NoiseGenerator, TerrainGenerator, TerrainSettings};
+
+    fn generated_chunk() -> Chunk {
+        let generator = NoiseGenerator::new(TerrainSettings::default());
+        let mut chunk = Chunk::new(ChunkPos(0, 0, 0));
+        generator.generate(&mut chunk);
+        chunk
+    }
+
+    #[test]
+    fn rle_deflate_compresses_generated_chunks() {
+        let chunk = generated_chunk();
+        let none = encode_chunk(&chunk, Compression::None).unwrap();
+        let compressed = encode_chunk(&chunk, Compression::RleDeflate).unwrap();
+
+        // Terrain is mostly long runs of stone and air, which should shrink at least fourfold.
+        assert!(
+            compressed.len() * 4 <= none.len(),
+            "{} bytes compressed to {}",
+            none.len(),
+            compressed.len()
+        );
+    }
+
+    #[test]
+    fn corrupt_offset_table_fails_to_load() {
+        let directory = std::env::temp_dir().join(format!("craft-region-{}", std::process::id()));
+        let storage = RegionStorage::new(&directory).unwrap();
+
+        // A chunk in the header, one past the end of the file and one of 4 GiB.
+        for (sector, len) in [(0, 100), (1000, 100), (HEADER_SECTORS, u32::MAX)] {
+            let mut bytes = REGION_MAGIC.to_vec();
+            bytes.extend(REGION_VERSION.to_le_bytes());
+            bytes.extend(sector.to_le_bytes());
+            bytes.extend(len.to_le_bytes());
+            bytes.resize(HEADER_SECTORS as usize * SECTOR_SIZE as usize, 0);
+            let path = storage.region_path(RegionPos(0, 0));
+            fs::write(&path, bytes).unwrap();
+            let err = RegionFile::read_table(File::open(&path).unwrap())
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let chunks = storage.load_chunks(&[ChunkPos(0, 0, 0), ChunkPos(5, 0, 5)]);
+            assert_eq!(chunks.len(), 2);
+            assert!(
+                chunks.iter().all(|(_, chunk)| chunk.is_err()),
+                "sector {sector}, len {len}"
+            );
+        }
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn both_compressions_round_trip() {
+        let chunk = generated_chunk();
+        for compression in [Compression::None, Compression::RleDeflate] {
+            let bytes = encode_chunk(&chunk, compression).unwrap();
+            let decoded = decode_chunk(chunk.position, &bytes).unwrap();
+            assert!(decoded.blocks() == chunk.blocks(), "{compression:?}");
+        }
+    }
+}
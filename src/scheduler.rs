@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::position::BlockPos;
+
+/// Block updates waiting for the tick they are due on, like water spreading or sand
+/// falling. A block waits for at most one update at a time.
+#[derive(Debug, Default)]
+pub struct BlockUpdates {
+    /// The number of ticks run so far.
+    tick: u64,
+    /// The blocks waiting to be updated, by the tick they are due on, each in the order they
+    /// were scheduled.
+    due: BTreeMap<u64, Vec<BlockPos>>,
+    /// The blocks in `due`.
+    pending: HashSet<BlockPos>,
+}
+
+impl BlockUpdates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules an update of the block at `position` `delay` ticks from now, at least one.
+    /// Returns `false` if the block is already waiting for an update, which is kept.
+    pub fn schedule(&mut self, position: BlockPos, delay: u64) -> bool {
+        if !self.pending.insert(position) {
+            return false;
+        }
+        self.due
+            .entry(self.tick + delay.max(1))
+            .or_default()
+            .push(position);
+        true
+    }
+
+    /// Advances to the next tick and returns the blocks due to be updated on it, in the order
+    /// they were due, but at most `budget` of them. The rest stay due, and come first on the
+    /// next tick, so a burst of updates is spread over several ticks.
+    pub fn advance(&mut self, budget: usize) -> Vec<BlockPos> {
+        self.tick += 1;
+
+        let mut updates = Vec::new();
+        while updates.len() < budget {
+            let Some(mut entry) = self.due.first_entry() else {
+                break;
+            };
+            if *entry.key() > self.tick {
+                break;
+            }
+
+            let positions = entry.get_mut();
+            let taken = positions.len().min(budget - updates.len());
+            updates.extend(positions.drain(..taken));
+            if positions.is_empty() {
+                entry.remove();
+            }
+        }
+
+        for position in &updates {
+            self.pending.remove(position);
+        }
+        updates
+    }
+}
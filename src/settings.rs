@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::game::{
+    DEFAULT_FOV, LOAD_DISTANCE, MAX_FOV, MAX_RENDER_DISTANCE, MIN_FOV, MIN_RENDER_DISTANCE,
+};
+use crate::input::Action;
+
+/// The file the settings are loaded from and saved to.
+pub const SETTINGS_FILE: &str = "settings.toml";
+/// The file key bindings were loaded from before they moved into the settings file.
+pub const KEY_BINDINGS_FILE: &str = "keybindings.toml";
+/// How fast the camera turns with the mouse unless changed.
+const DEFAULT_SENSITIVITY: f32 = 4.0;
+/// How fast the camera flies unless changed, in world units per second.
+const DEFAULT_SPEED: f32 = 10.0;
+
+/// The options of the game, loaded from `settings.toml` when the game starts and saved to it
+/// when they're changed in the game. Editing the file while the game runs applies it at once.
+/// Missing values keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// How fast the camera turns with the mouse.
+    pub sensitivity: f32,
+    /// How fast the camera flies, in world units per second.
+    pub speed: f32,
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+    /// How many chunks around the camera are loaded and drawn.
+    pub render_distance: usize,
+    /// Whether frames wait for the display to refresh, which stops tearing but limits the
+    /// frame rate to the refresh rate.
+    pub vsync: bool,
+    /// How much dark light levels are raised, from 0 for the plain falloff of light to 1 for
+    /// the brightest caves and nights.
+    pub brightness: f32,
+    /// The keys of the actions to rebind by their winit names, like `jump = ["Space", "KeyJ"]`.
+    /// Actions left out keep their default keys.
+    pub keybinds: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sensitivity: DEFAULT_SENSITIVITY,
+            speed: DEFAULT_SPEED,
+            fov: DEFAULT_FOV.0,
+            render_distance: LOAD_DISTANCE,
+            vsync: true,
+            brightness: 0.0,
+            keybinds: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from the TOML file at `path`, moving the ones with limits into range
+    /// and failing if any other is invalid.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut settings: Settings = toml::from_str(&contents)?;
+        settings.validate()?;
+        settings.clamp();
+        Ok(settings)
+    }
+
+    /// Checks that every setting is a usable number. Settings with limits may still be out of
+    /// range, see [`Settings::clamp`].
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.sensitivity.is_finite() && self.sensitivity > 0.0) {
+            anyhow::bail!("sensitivity must be above 0, got {}", self.sensitivity);
+        }
+        if !(self.speed.is_finite() && self.speed > 0.0) {
+            anyhow::bail!("speed must be above 0, got {}", self.speed);
+        }
+        if !self.fov.is_finite() {
+            anyhow::bail!("fov must be a number, got {}", self.fov);
+        }
+        if !self.brightness.is_finite() {
+            anyhow::bail!("brightness must be a number, got {}", self.brightness);
+        }
+        Ok(())
+    }
+
+    /// Moves the field of view, render distance and brightness into the range the game
+    /// allows, so values out of range are neither applied nor saved back.
+    pub fn clamp(&mut self) {
+        self.fov = self.fov.clamp(MIN_FOV.0, MAX_FOV.0);
+        self.render_distance = self
+            .render_distance
+            .clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+        self.brightness = self.brightness.clamp(0.0, 1.0);
+    }
+
+    /// Reads key bindings from the TOML file at `path` written before they moved into the
+    /// settings, which maps actions to keys the same way as [`Settings::keybinds`].
+    pub fn load_key_bindings(
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<BTreeMap<Action, Vec<KeyCode>>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the settings to the TOML file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Returns how frames are presented, waiting for vsync or not.
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        if self.vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Mailbox
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_settings_load_clamped() {
+        let path = std::env::temp_dir().join(format!("craft-settings-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "fov = 500.0\nrender_distance = 0\nbrightness = -2.0\n",
+        )
+        .unwrap();
+        let settings = Settings::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.fov, MAX_FOV.0);
+        assert_eq!(settings.render_distance, MIN_RENDER_DISTANCE);
+        assert_eq!(settings.brightness, 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+/// How often the averaged statistics are refreshed, in seconds.
+const REPORT_INTERVAL: f32 = 1.0;
+
+/// Frame rate and rendering statistics, averaged over short intervals so they are readable
+/// instead of changing every frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    /// The frames recorded since the last refresh.
+    frames: u32,
+    /// The time covered by the frames recorded since the last refresh, in seconds.
+    elapsed: f32,
+    /// The longest frame since the last refresh, in seconds.
+    longest: f32,
+    fps: f32,
+    frame_time: f32,
+    worst_frame_time: f32,
+    vertices: usize,
+    /// The time each pass took on the GPU, summed over the frames timed since the last
+    /// refresh, in seconds.
+    gpu_sums: Vec<(&'static str, f32)>,
+    /// The frames timed on the GPU since the last refresh.
+    gpu_frames: u32,
+    /// The average time each pass took on the GPU over the last interval, in seconds.
+    gpu_timings: Vec<(&'static str, f32)>,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a frame that took `delta` seconds and drew `vertices` vertices. Returns whether
+    /// the averages were refreshed.
+    pub fn record(&mut self, delta: f32, vertices: usize) -> bool {
+        self.frames += 1;
+        self.elapsed += delta;
+        self.longest = self.longest.max(delta);
+        self.vertices = vertices;
+
+        if self.elapsed < REPORT_INTERVAL {
+            return false;
+        }
+
+        self.fps = self.frames as f32 / self.elapsed;
+        self.frame_time = self.elapsed / self.frames as f32;
+        self.worst_frame_time = self.longest;
+        if self.gpu_frames > 0 {
+            let frames = self.gpu_frames as f32;
+            self.gpu_timings = self
+                .gpu_sums
+                .drain(..)
+                .map(|(pass, sum)| (pass, sum / frames))
+                .collect();
+            self.gpu_frames = 0;
+        }
+        self.frames = 0;
+        self.elapsed = 0.0;
+        self.longest = 0.0;
+        true
+    }
+
+    /// Records the time each pass of a frame took on the GPU, in seconds.
+    pub fn record_gpu_timings(&mut self, timings: &[(&'static str, f32)]) {
+        for &(pass, time) in timings {
+            match self.gpu_sums.iter_mut().find(|(name, _)| *name == pass) {
+                Some((_, sum)) => *sum += time,
+                None => self.gpu_sums.push((pass, time)),
+            }
+        }
+        self.gpu_frames += 1;
+    }
+
+    /// Returns the average time each pass took on the GPU over the last interval, in
+    /// seconds, empty if no frames were timed.
+    pub fn gpu_timings(&self) -> &[(&'static str, f32)] {
+        &self.gpu_timings
+    }
+
+    /// Returns the average frames per second over the last interval.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Returns the average time a frame took over the last interval, in seconds.
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    /// Returns the longest time a frame took over the last interval, in seconds.
+    pub fn worst_frame_time(&self) -> f32 {
+        self.worst_frame_time
+    }
+
+    /// Returns the number of vertices drawn in the last frame.
+    pub fn vertices(&self) -> usize {
+        self.vertices
+    }
+
+    /// Returns the statistics as one line of text, with the total GPU time if frames were
+    /// timed.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{:.0} FPS, {:.2} ms (worst {:.2} ms), {} vertices",
+            self.fps,
+            self.frame_time * 1000.0,
+            self.worst_frame_time * 1000.0,
+            self.vertices
+        );
+        if !self.gpu_timings.is_empty() {
+            let total: f32 = self.gpu_timings.iter().map(|(_, time)| time).sum();
+            summary += &format!(", GPU {:.2} ms", total * 1000.0);
+        }
+        summary
+    }
+
+    /// Returns the average GPU time of each pass as one line of text, or `None` if no frames
+    /// were timed.
+    pub fn gpu_summary(&self) -> Option<String> {
+        if self.gpu_timings.is_empty() {
+            return None;
+        }
+        let passes: Vec<String> = self
+            .gpu_timings
+            .iter()
+            .map(|(pass, time)| format!("{pass} {:.2} ms", time * 1000.0))
+            .collect();
+        Some(passes.join(", "))
+    }
+}
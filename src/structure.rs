@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::biome::Biome;
+use crate::chunk::Chunk;
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+
+/// A structure as written in a file. Layers go from the bottom up, each being a list of rows
+/// along z whose characters go along x. Characters are looked up in the palette, and those
+/// missing from it leave the terrain as it is.
+#[derive(Debug, Deserialize)]
+struct StructureFile {
+    name: String,
+    chance: f32,
+    #[serde(default)]
+    biomes: Vec<String>,
+    #[serde(default)]
+    y_offset: i32,
+    palette: HashMap<char, String>,
+    layers: Vec<Vec<String>>,
+}
+
+/// A prefab arrangement of blocks, like a well or a ruin, stamped into the terrain during
+/// generation.
+#[derive(Debug, Clone)]
+pub struct Structure {
+    pub name: String,
+    /// The chance of the structure appearing in any given chunk.
+    pub chance: f32,
+    /// The biomes the structure appears in, or any biome if empty.
+    pub biomes: Vec<Biome>,
+    /// How far above the terrain surface the bottom layer is placed.
+    pub y_offset: i32,
+    /// The blocks of the structure as offsets from its origin.
+    blocks: Vec<(BlockPos, BlockType)>,
+}
+
+impl Structure {
+    /// Parses a structure from its JSON representation.
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let file: StructureFile = serde_json::from_str(json)?;
+
+        let mut palette = HashMap::new();
+        for (symbol, name) in &file.palette {
+            let Some(block_type) = BlockType::from_name(name) else {
+                anyhow::bail!("unknown block type {name:?}");
+            };
+            palette.insert(*symbol, block_type);
+        }
+
+        let mut biomes = Vec::new();
+        for name in &file.biomes {
+            let Some(biome) = Biome::from_name(name) else {
+                anyhow::bail!("unknown biome {name:?}");
+            };
+            biomes.push(biome);
+        }
+
+        let mut blocks = Vec::new();
+        for (y, layer) in file.layers.iter().enumerate() {
+            for (z, row) in layer.iter().enumerate() {
+                for (x, symbol) in row.chars().enumerate() {
+                    if let Some(block_type) = palette.get(&symbol) {
+                        blocks.push((BlockPos(x as i32, y as i32, z as i32), *block_type));
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            name: file.name,
+            chance: file.chance,
+            biomes,
+            y_offset: file.y_offset,
+            blocks,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Returns whether the structure can appear in `biome`.
+    pub fn allows(&self, biome: Biome) -> bool {
+        self.biomes.is_empty() || self.biomes.contains(&biome)
+    }
+
+    /// Places the structure with its minimum corner at `origin`. Blocks inside `chunk` are set
+    /// right away, and the rest are added to `deferred` to be placed once their chunks exist.
+    pub fn place(
+        &self,
+        chunk: &mut Chunk,
+        origin: BlockPos,
+        deferred: &mut Vec<(BlockPos, BlockType)>,
+    ) {
+        for (offset, block_type) in &self.blocks {
+            let position = origin + *offset;
+            if position.chunk() == chunk.position {
+                let (x, y, z) = position.local();
+                chunk.set_block(x, y, z, *block_type);
+            } else {
+                deferred.push((position, *block_type));
+            }
+        }
+    }
+}
+
+/// Loads every structure in `directory`. Structures that fail to load are skipped.
+pub fn load_structures(directory: impl AsRef<Path>) -> Vec<Structure> {
+    let entries = match fs::read_dir(directory.as_ref()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Failed to read the structures directory: {err}");
+            return Vec::new();
+        }
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    // Sort so structures are tried in the same order on every platform.
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            Structure::load(&path)
+                .map_err(|err| eprintln!("Failed to load structure {}: {err}", path.display()))
+                .ok()
+        })
+        .collect()
+}
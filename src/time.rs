@@ -0,0 +1,218 @@
+use std::f32::consts::TAU;
+
+use cgmath::{InnerSpace, Vector3};
+
+/// How many ticks the world advances per second.
+pub const TICKS_PER_SECOND: f32 = 20.0;
+/// How many ticks a full day takes, which is 20 minutes.
+pub const DAY_LENGTH: u64 = 24_000;
+
+/// The color of the sky at noon.
+const DAY_SKY: [f32; 3] = [0.5, 0.75, 1.0];
+/// The color of the sky at midnight.
+const NIGHT_SKY: [f32; 3] = [0.02, 0.03, 0.08];
+/// The color the sky blends toward while the sun rises and sets.
+const SUNSET_SKY: [f32; 3] = [0.95, 0.55, 0.3];
+/// How bright sky light still is at midnight, so the night isn't pitch black.
+const MOONLIGHT: f32 = 0.2;
+/// How much of the direct sky light shadows block, from 0 to 1.
+const SHADOW_STRENGTH: f32 = 0.45;
+/// How far the path of the sun and moon is tilted away from straight overhead, so shadows
+/// never line up exactly with block edges.
+const SUN_TILT: f32 = 0.3;
+
+/// The time of the world, counted in ticks that advance at a fixed rate regardless of the
+/// frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldTime {
+    /// The ticks since the world was created.
+    ticks: u64,
+    /// Real time passed that doesn't make up a whole tick yet, in seconds.
+    accumulator: f32,
+}
+
+impl WorldTime {
+    /// Creates a world time starting in the morning of the first day.
+    pub fn new() -> Self {
+        let mut this = Self {
+            ticks: 0,
+            accumulator: 0.0,
+        };
+        this.set_time_of_day(0.3);
+        this
+    }
+
+    /// Advances the time by `delta` seconds, returning the number of ticks that passed.
+    pub fn advance(&mut self, delta: f32) -> u64 {
+        self.accumulator += delta;
+        let ticks = (self.accumulator * TICKS_PER_SECOND) as u64;
+        self.accumulator -= ticks as f32 / TICKS_PER_SECOND;
+        self.ticks += ticks;
+        ticks
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn set_ticks(&mut self, ticks: u64) {
+        self.ticks = ticks;
+    }
+
+    /// Returns the number of days that have fully passed.
+    pub fn day(&self) -> u64 {
+        self.ticks / DAY_LENGTH
+    }
+
+    /// Returns how far into the current day it is, from 0 to 1. Midnight is at 0, the sun rises
+    /// at 0.25, is highest at 0.5 and sets at 0.75.
+    pub fn time_of_day(&self) -> f32 {
+        (self.ticks % DAY_LENGTH) as f32 / DAY_LENGTH as f32
+    }
+
+    /// Jumps to `time_of_day` of the current day, see [`WorldTime::time_of_day`].
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        let offset = (time_of_day.rem_euclid(1.0) * DAY_LENGTH as f32) as u64;
+        self.ticks = self.day() * DAY_LENGTH + offset.min(DAY_LENGTH - 1);
+    }
+
+    /// Returns the height of the sun in the sky, from -1 at midnight to 1 at noon.
+    pub fn sun_elevation(&self) -> f32 {
+        ((self.time_of_day() - 0.25) * TAU).sin()
+    }
+
+    /// Returns how far the sky has turned since sunrise, in radians. The sun, moon and stars
+    /// turn with it around the z axis.
+    pub fn sky_rotation(&self) -> f32 {
+        (self.time_of_day() - 0.25) * TAU
+    }
+
+    /// Returns the direction toward the sun, which is below the horizon at night. The sun
+    /// rises toward positive x and sets toward negative x, and the moon is opposite it.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let (sin, cos) = self.sky_rotation().sin_cos();
+        Vector3::new(cos, sin, SUN_TILT).normalize()
+    }
+
+    /// Returns the direction toward the sun, or toward the moon while the sun is down.
+    pub fn light_direction(&self) -> Vector3<f32> {
+        let direction = self.sun_direction();
+        if direction.y < 0.0 {
+            -direction
+        } else {
+            direction
+        }
+    }
+
+    /// Returns how much of the direct light shadows block, fading out as the sun or moon
+    /// reaches the horizon so shadows don't jump when one sets and the other rises.
+    pub fn shadow_strength(&self) -> f32 {
+        SHADOW_STRENGTH * (self.sun_elevation().abs() / 0.2).min(1.0)
+    }
+
+    /// Returns how much of the daylight there is, from 0 at night to 1 during the day, rising
+    /// and falling around sunrise and sunset.
+    pub fn daylight(&self) -> f32 {
+        ((self.sun_elevation() + 0.1) / 0.3).clamp(0.0, 1.0)
+    }
+
+    /// Returns how bright sky light is, from [`MOONLIGHT`] at night to 1 during the day.
+    pub fn sun_intensity(&self) -> f32 {
+        MOONLIGHT + (1.0 - MOONLIGHT) * self.daylight()
+    }
+
+    /// Returns the color of the sky in linear RGB.
+    pub fn sky_color(&self) -> [f32; 3] {
+        let daylight = self.daylight();
+        // The sunset glow is strongest with the sun at the horizon.
+        let glow = (1.0 - self.sun_elevation().abs() / 0.25).clamp(0.0, 1.0) * 0.6;
+
+        std::array::from_fn(|i| {
+            let sky = NIGHT_SKY[i] + (DAY_SKY[i] - NIGHT_SKY[i]) * daylight;
+            sky + (SUNSET_SKY[i] - sky) * glow
+        })
+    }
+}
+
+/// Where the fog starts, as a fraction of the distance it ends at.
+const FOG_START: f32 = 0.6;
+/// The color of the fog under water in daylight, in linear RGB.
+const UNDERWATER_FOG: [f32; 3] = [0.05, 0.2, 0.45];
+/// How far from the camera everything is hidden under water, in world units.
+const UNDERWATER_FOG_END: f32 = 32.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SkyUniform {
+    pub color: [f32; 4],
+    pub sun_intensity: f32,
+    /// The distances from the camera the fog starts and ends at, in world units.
+    pub fog_start: f32,
+    pub fog_end: f32,
+    /// How much dark light levels are raised, from 0 to 1.
+    pub brightness: f32,
+}
+
+impl SkyUniform {
+    /// Creates the uniform for the sky at `time`, with fog hiding everything further than
+    /// `fog_end` from the camera in the color of the sky and dark light levels raised by
+    /// `brightness`.
+    pub fn init(time: &WorldTime, fog_end: f32, brightness: f32) -> Self {
+        let [r, g, b] = time.sky_color();
+        Self {
+            color: [r, g, b, 1.0],
+            sun_intensity: time.sun_intensity(),
+            fog_start: fog_end * FOG_START,
+            fog_end,
+            brightness,
+        }
+    }
+
+    /// Returns the sky as seen with the camera in water, with a short blue fog darkening with
+    /// the time of day in place of the sky.
+    pub fn underwater(self) -> Self {
+        let [r, g, b] = UNDERWATER_FOG.map(|channel| channel * self.sun_intensity);
+        Self {
+            color: [r, g, b, 1.0],
+            fog_start: 0.0,
+            fog_end: UNDERWATER_FOG_END.min(self.fog_end),
+            ..self
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CelestialUniform {
+    /// The direction toward the sun.
+    pub sun_direction: [f32; 3],
+    /// How bright the stars are, from 0 during the day to 1 at night.
+    pub stars: f32,
+    /// How far the sky has turned since sunrise, in radians.
+    pub rotation: f32,
+    /// How much of the sun, moon and stars shows, from 0 to 1.
+    pub opacity: f32,
+    pub _padding: [f32; 2],
+}
+
+impl CelestialUniform {
+    /// Creates the uniform for the sun, moon and stars at `time`.
+    pub fn init(time: &WorldTime) -> Self {
+        Self {
+            sun_direction: time.sun_direction().into(),
+            stars: 1.0 - time.daylight(),
+            rotation: time.sky_rotation(),
+            opacity: 1.0,
+            _padding: [0.0; 2],
+        }
+    }
+
+    /// Returns the sky as seen with the camera in water, where the fog hides the sun, moon
+    /// and stars.
+    pub fn underwater(self) -> Self {
+        Self {
+            opacity: 0.0,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::position::BlockPos;
+use crate::renderer::block::BlockType;
+use crate::world::World;
+
+/// How many simulation steps a torch waits before breaking once the block holding it is gone.
+pub const TORCH_DELAY: u64 = 1;
+
+/// Returns whether `block` at `position` is held up by the block it's attached to, or
+/// whether that block isn't loaded, which doesn't break it. Blocks that aren't attached to
+/// anything are always held up.
+pub fn is_supported(world: &World, position: BlockPos, block: BlockType) -> bool {
+    let Some(side) = block.attachment() else {
+        return true;
+    };
+    let [x, y, z] = side.normal();
+    world
+        .get_block(position.offset(x as i32, y as i32, z as i32))
+        .map_or(true, |support| support.is_solid())
+}
+
+/// Breaks the torch at `position` if the block it stands on or hangs from is gone.
+pub fn update_torch(world: &mut World, position: BlockPos) {
+    let Some(block) = world.get_block(position) else {
+        return;
+    };
+    if !is_supported(world, position, block) {
+        world.set_block(position, BlockType::Air);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::chunk::{block_index, BlockArray, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::position::ChunkPos;
+use crate::renderer::block::Face;
+
+/// Which pairs of faces of a chunk are connected through non-opaque blocks, so that a ray
+/// entering through one face could leave through the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility(u64);
+
+impl ChunkVisibility {
+    /// Every face can see every other face, as for an empty chunk.
+    pub const ALL: ChunkVisibility = ChunkVisibility(u64::MAX);
+    /// No face can see any other face, as for a solid chunk.
+    pub const NONE: ChunkVisibility = ChunkVisibility(0);
+
+    /// Returns whether `from` and `to` are connected.
+    pub fn connected(&self, from: Face, to: Face) -> bool {
+        self.0 & Self::bit(from, to) != 0
+    }
+
+    fn connect(&mut self, from: Face, to: Face) {
+        self.0 |= Self::bit(from, to) | Self::bit(to, from);
+    }
+
+    fn bit(from: Face, to: Face) -> u64 {
+        1 << (from as u32 * 6 + to as u32)
+    }
+
+    /// Flood fills the non-opaque blocks of a chunk and connects every pair of faces touched
+    /// by the same region.
+    pub fn compute(blocks: &BlockArray) -> Self {
+        let mut visibility = Self::NONE;
+        let mut visited = vec![false; blocks.len()];
+        let mut stack = Vec::new();
+
+        for x in 0..CHUNK_WIDTH {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_DEPTH {
+                    let index = block_index(x, y, z);
+                    if visited[index] || blocks[index].is_opaque() {
+                        continue;
+                    }
+
+                    visited[index] = true;
+                    stack.push([x, y, z]);
+                    let mut faces = Vec::with_capacity(6);
+
+                    while let Some(position) = stack.pop() {
+                        for face in Face::ALL {
+                            let Some(neighbor) = step(position, face) else {
+                                // The region reaches the border of the chunk.
+                                if !faces.contains(&face) {
+                                    faces.push(face);
+                                }
+                                continue;
+                            };
+
+                            let [x, y, z] = neighbor;
+                            let index = block_index(x, y, z);
+                            if !visited[index] && !blocks[index].is_opaque() {
+                                visited[index] = true;
+                                stack.push(neighbor);
+                            }
+                        }
+                    }
+
+                    for &from in &faces {
+                        for &to in &faces {
+                            visibility.connect(from, to);
+                        }
+                    }
+                }
+            }
+        }
+
+        visibility
+    }
+}
+
+/// Returns the neighbor of a local block position in the direction of `face`, or `None` if it
+/// lies outside the chunk.
+fn step(position: [usize; 3], face: Face) -> Option<[usize; 3]> {
+    let dimensions = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+    let normal = face.normal();
+
+    let mut neighbor = [0; 3];
+    for axis in 0..3 {
+        let value = position[axis] as isize + normal[axis];
+        if value < 0 || value >= dimensions[axis] as isize {
+            return None;
+        }
+        neighbor[axis] = value as usize;
+    }
+
+    Some(neighbor)
+}
+
+/// Walks outward from the `camera` chunk through connected chunk faces, never stepping back
+/// toward the camera, and returns every chunk that may be visible. `visibility` returns the
+/// connectivity of a chunk, and `in_bounds` limits how far the search goes.
+pub fn visible_chunks(
+    camera: ChunkPos,
+    visibility: impl Fn(ChunkPos) -> ChunkVisibility,
+    in_bounds: impl Fn(ChunkPos) -> bool,
+) -> HashSet<ChunkPos> {
+    let mut visible = HashSet::from([camera]);
+    // The chunk, the face it was entered through and the directions travelled to reach it.
+    let mut queue = VecDeque::from([(camera, None::<Face>, 0u8)]);
+
+    while let Some((position, entered, directions)) = queue.pop_front() {
+        let chunk_visibility = visibility(position);
+
+        for face in Face::ALL {
+            let opposite = face.opposite();
+            if directions & (1 << opposite as u32) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !chunk_visibility.connected(entered, face) {
+                    continue;
+                }
+            }
+
+            let neighbor = position.neighbor(face);
+            if !in_bounds(neighbor) || !visible.insert(neighbor) {
+                continue;
+            }
+
+            queue.push_back((neighbor, Some(opposite), directions | (1 << face as u32)));
+        }
+    }
+
+    visible
+}
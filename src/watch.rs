@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often watched files are checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Notices when a file is changed by checking when it was last modified now and then, so
+/// config files can be edited while the game runs.
+pub struct FileWatcher {
+    path: PathBuf,
+    /// When the file was last modified as of the last check, or `None` if it didn't exist.
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl FileWatcher {
+    /// Watches the file at `path`, which doesn't have to exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            modified: modified(&path),
+            path,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns whether the file was created or modified since the last time it was checked,
+    /// checking at most every [`CHECK_INTERVAL`].
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified(&self.path);
+        let changed = modified.is_some() && modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    /// Takes the current version of the file as seen, like after the game wrote it itself.
+    pub fn mark_seen(&mut self) {
+        self.modified = modified(&self.path);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,457 @@
+use std::path::PathBuf;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::biome::Biome;
+use crate::block_entity::BlockEntity;
+use crate::chunk::{
+    block_index, ChunkManager, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH, DEFAULT_SEED,
+};
+use crate::door::{self, DOOR_DELAY};
+use crate::falling::{self, FALL_DELAY};
+use crate::fluid::{self, WATER_DELAY};
+use crate::generator::TerrainGenerator;
+use crate::growth::{self, RANDOM_TICK_SPEED};
+use crate::leaves::{self, LEAF_DECAY_DELAY};
+use crate::light::{LightChannel, MAX_LIGHT};
+use crate::position::{BlockPos, ChunkPos};
+use crate::renderer::block::BlockType;
+use crate::save::{RegionStorage, WorldMetadata};
+use crate::scheduler::BlockUpdates;
+use crate::time::WorldTime;
+use crate::torch::{self, TORCH_DELAY};
+
+/// The most block updates run in a simulation step. The rest wait for the next steps, so a
+/// flood of updates doesn't stall the game.
+pub const UPDATES_PER_TICK: usize = 512;
+
+/// How the player plays a world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Flying freely, breaking blocks at once and placing as many as wanted.
+    Creative,
+    /// Walking, breaking blocks over time to pick them up and placing only those at hand.
+    Survival,
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Creative => "creative",
+            GameMode::Survival => "survival",
+        }
+    }
+}
+
+pub struct World {
+    /// The loaded chunks of the world.
+    chunks: ChunkManager,
+    /// The blocks waiting to be updated, like liquids flowing and sand falling, with a tick
+    /// for every simulation step.
+    updates: BlockUpdates,
+    /// The time of day and how many days have passed.
+    time: WorldTime,
+    /// Where players start and respawn, the block their feet are in.
+    spawn: BlockPos,
+    /// The seed new chunks are generated from.
+    seed: u32,
+    game_mode: GameMode,
+    /// How many blocks of every loaded chunk are picked at random each simulation step to
+    /// grow or spread.
+    random_tick_speed: u32,
+}
+
+impl World {
+    /// Opens the world saved in `directory`, whose chunks are loaded from there as needed and
+    /// new chunks are filled by `generator` from the seed of the world. A world that was never
+    /// saved gets [`DEFAULT_SEED`] and starts in the morning at the spawn point of the
+    /// generator. If the directory can't be used the world still works, but nothing is saved.
+    ///
+    /// A saved world keeps the generator settings it was created with, like the layers of a
+    /// flat world, and fails to open with another kind of generator, so its terrain doesn't
+    /// change from one chunk to the next.
+    pub fn load(
+        load_distance: usize,
+        directory: impl Into<PathBuf>,
+        mut generator: Box<dyn TerrainGenerator>,
+    ) -> anyhow::Result<Self> {
+        let storage = RegionStorage::new(directory)
+            .map_err(|err| eprintln!("Failed to open the world directory: {err}"))
+            .ok();
+        let metadata = storage.as_ref().and_then(|storage| {
+            storage.load_metadata().unwrap_or_else(|err| {
+                eprintln!("Failed to load the world metadata: {err}");
+                None
+            })
+        });
+
+        if let Some(saved) = metadata
+            .as_ref()
+            .and_then(|metadata| metadata.generator.as_ref())
+        {
+            let picked = generator.config();
+            if saved.name() != picked.name() {
+                anyhow::bail!(
+                    "the world was generated by the {} generator, not the {} one",
+                    saved.name(),
+                    picked.name()
+                );
+            }
+            if *saved != picked {
+                println!(
+                    "Generating the world with the {} settings it was created with",
+                    saved.name()
+                );
+                generator.set_config(saved)?;
+            }
+        }
+
+        let seed = metadata
+            .as_ref()
+            .map_or(DEFAULT_SEED, |metadata| metadata.seed);
+        generator.set_seed(seed);
+
+        let mut world = Self {
+            spawn: generator.spawn_point(),
+            seed,
+            chunks: ChunkManager::new(load_distance, storage, generator),
+            updates: BlockUpdates::new(),
+            time: WorldTime::new(),
+            game_mode: GameMode::Survival,
+            random_tick_speed: RANDOM_TICK_SPEED,
+        };
+        if let Some(metadata) = metadata {
+            let [x, y, z] = metadata.spawn;
+            world.spawn = BlockPos(x, y, z);
+            world.time.set_ticks(metadata.ticks);
+            world.game_mode = metadata.game_mode;
+        }
+        Ok(world)
+    }
+
+    /// Advances the world by one simulation step, updating the blocks due to be updated, up to
+    /// [`UPDATES_PER_TICK`] of them, then letting blocks picked at random grow.
+    pub fn tick(&mut self) {
+        for position in self.updates.advance(UPDATES_PER_TICK) {
+            self.update_block(position);
+        }
+        self.random_ticks(&mut rand::thread_rng());
+    }
+
+    pub fn random_tick_speed(&self) -> u32 {
+        self.random_tick_speed
+    }
+
+    /// Sets how many blocks of every loaded chunk are picked at random each simulation step
+    /// to grow or spread. 0 stops them.
+    pub fn set_random_tick_speed(&mut self, speed: u32) {
+        self.random_tick_speed = speed;
+    }
+
+    /// Picks [`World::random_tick_speed`] blocks of every loaded chunk at random, letting them
+    /// grow or spread.
+    fn random_ticks(&mut self, rng: &mut impl Rng) {
+        let chunks: Vec<ChunkPos> = self.chunks.positions().collect();
+        for chunk in chunks {
+            for _ in 0..self.random_tick_speed {
+                let position = chunk.block(
+                    rng.gen_range(0..CHUNK_WIDTH),
+                    rng.gen_range(0..CHUNK_HEIGHT),
+                    rng.gen_range(0..CHUNK_DEPTH),
+                );
+                self.random_tick_block(position, rng);
+            }
+        }
+    }
+
+    /// Lets the block at `position`, picked at random, spread if it's grass, grow if it's a
+    /// plant or decay if it's leaves without a log nearby.
+    fn random_tick_block(&mut self, position: BlockPos, rng: &mut impl Rng) {
+        match self.get_block(position) {
+            Some(BlockType::Grass) => growth::spread_grass(self, position, rng),
+            Some(BlockType::Sapling) => growth::grow_sapling(self, position, rng),
+            Some(BlockType::Leaves) => leaves::update_leaves(self, position, rng),
+            Some(block) if block.wheat_stage().is_some() => growth::grow_wheat(self, position, rng),
+            _ => {}
+        }
+    }
+
+    /// Schedules an update of the block at `position` `delay` ticks from now, unless it's
+    /// already waiting for one.
+    pub fn schedule_update(&mut self, position: BlockPos, delay: u64) {
+        self.updates.schedule(position, delay);
+    }
+
+    /// Lets the block at `position` flow or fall, or breaks it if it's half of a door missing
+    /// the other half, a torch missing the block holding it or leaves without a log nearby,
+    /// depending on what it is.
+    fn update_block(&mut self, position: BlockPos) {
+        match self.get_block(position) {
+            Some(BlockType::Leaves) => {
+                leaves::update_leaves(self, position, &mut rand::thread_rng())
+            }
+            Some(block) if block.is_liquid() => fluid::update_water(self, position),
+            Some(block) if block.falls() => falling::update_falling(self, position),
+            Some(block) if block.door_state().is_some() => door::update_door(self, position),
+            Some(block) if block.attachment().is_some() => torch::update_torch(self, position),
+            _ => {}
+        }
+    }
+
+    /// Uses the block at `position`, opening or closing doors and trapdoors. Returns whether
+    /// the block did anything.
+    pub fn interact(&mut self, position: BlockPos) -> bool {
+        match self.get_block(position) {
+            Some(block) if block.is_interactive() => door::toggle(self, position),
+            _ => false,
+        }
+    }
+
+    /// Schedules updates for the block at `position` and those next to it, for the ones that
+    /// flow, fall, are doors, hang on other blocks or are leaves that may decay.
+    fn schedule_updates(&mut self, position: BlockPos) {
+        let neighbors = [
+            (0, 0, 0),
+            (-1, 0, 0),
+            (1, 0, 0),
+            (0, -1, 0),
+            (0, 1, 0),
+            (0, 0, -1),
+            (0, 0, 1),
+        ];
+        for (x, y, z) in neighbors {
+            let position = position.offset(x, y, z);
+            match self.get_block(position) {
+                Some(block) if block.is_liquid() => self.schedule_update(position, WATER_DELAY),
+                Some(block) if block.falls() => self.schedule_update(position, FALL_DELAY),
+                Some(block) if block.door_state().is_some() => {
+                    self.schedule_update(position, DOOR_DELAY)
+                }
+                Some(block) if block.attachment().is_some() => {
+                    self.schedule_update(position, TORCH_DELAY)
+                }
+                Some(BlockType::Leaves) => {
+                    let delay = rand::thread_rng().gen_range(LEAF_DECAY_DELAY);
+                    self.schedule_update(position, delay)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Generates and saves every chunk within `radius` chunks of `center`, reporting the
+    /// progress to subscribers of the chunk manager.
+    pub fn pregenerate(&mut self, center: ChunkPos, radius: usize) -> anyhow::Result<()> {
+        self.chunks.pregenerate(center, radius)
+    }
+
+    /// Saves every chunk with unsaved changes and the metadata of the world, like the time and
+    /// the spawn point, so [`World::load`] picks up where it was left.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        self.chunks.save()?;
+        self.save_metadata()
+    }
+
+    /// Saves the metadata of the world, like the time and the spawn point.
+    fn save_metadata(&self) -> anyhow::Result<()> {
+        let Some(storage) = self.chunks.storage() else {
+            return Ok(());
+        };
+        let BlockPos(x, y, z) = self.spawn;
+        storage.save_metadata(&WorldMetadata {
+            seed: self.seed,
+            ticks: self.time.ticks(),
+            spawn: [x, y, z],
+            game_mode: self.game_mode,
+            generator: Some(self.chunks.generator().config()),
+        })
+    }
+
+    /// Returns the seed the chunks of the world are generated from.
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Fills chunks generated from now on with `generator`, deriving the terrain from the seed
+    /// of the world. Chunks that were already generated stay as they are.
+    pub fn set_generator(&mut self, mut generator: Box<dyn TerrainGenerator>) {
+        generator.set_seed(self.seed);
+        self.chunks.set_generator(generator);
+    }
+
+    /// Starts saving the chunks changed since they were last saved, a few at a time with
+    /// [`World::autosave`], unless already doing so.
+    pub fn start_autosave(&mut self) {
+        self.chunks.start_autosave();
+    }
+
+    /// Saves up to `max_chunks` of the chunks left by the autosave under way, writing them in
+    /// the background. Once every chunk is queued the metadata is saved too. Returns whether
+    /// the autosave is done.
+    pub fn autosave(&mut self, max_chunks: usize) -> anyhow::Result<bool> {
+        if !self.chunks.autosave(max_chunks) {
+            return Ok(false);
+        }
+        self.save_metadata()?;
+        Ok(true)
+    }
+
+    pub fn chunks(&self) -> &ChunkManager {
+        &self.chunks
+    }
+
+    pub fn chunks_mut(&mut self) -> &mut ChunkManager {
+        &mut self.chunks
+    }
+
+    /// Returns where players start and respawn, the block their feet are in. It's picked on
+    /// the surface by the generator unless moved.
+    pub fn spawn(&self) -> BlockPos {
+        self.spawn
+    }
+
+    pub fn time(&self) -> &WorldTime {
+        &self.time
+    }
+
+    pub fn time_mut(&mut self) -> &mut WorldTime {
+        &mut self.time
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        self.game_mode
+    }
+
+    pub fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.game_mode = game_mode;
+    }
+
+    /// Returns the height below which generated terrain is filled with water.
+    pub fn sea_level(&self) -> i32 {
+        self.chunks.generator().sea_level()
+    }
+
+    /// Returns the biome of the column containing `position`, whether its chunk is loaded or
+    /// not.
+    pub fn biome_at(&self, position: BlockPos) -> Biome {
+        let (x, _, z) = position.local();
+        match self.chunks.get(position.chunk()) {
+            Some(chunk) => chunk.biome(x, z),
+            None => self.chunks.generator().biome_at(position.0, position.2),
+        }
+    }
+
+    /// Returns the block at `position`, or `None` if the owning chunk is not loaded.
+    pub fn get_block(&self, position: BlockPos) -> Option<BlockType> {
+        let (x, y, z) = position.local();
+        self.chunks
+            .get(position.chunk())
+            .map(|chunk| chunk.block(x, y, z))
+    }
+
+    /// Returns the sky and block light levels at `position` from 0 to 1, like those of the
+    /// vertices of terrain meshes, or `None` if the owning chunk is not loaded.
+    pub fn light_at(&self, position: BlockPos) -> Option<[f32; 2]> {
+        let (x, y, z) = position.local();
+        self.chunks.get(position.chunk()).map(|chunk| {
+            let index = block_index(x, y, z);
+            [LightChannel::Sky, LightChannel::Block]
+                .map(|channel| chunk.light().get(index, channel) as f32 / MAX_LIGHT as f32)
+        })
+    }
+
+    /// Returns the data kept by the block at `position`, or `None` if it keeps none or the
+    /// owning chunk is not loaded.
+    pub fn block_entity(&self, position: BlockPos) -> Option<&BlockEntity> {
+        let (x, y, z) = position.local();
+        self.chunks.get(position.chunk())?.block_entity(x, y, z)
+    }
+
+    /// Returns the data kept by the block at `position` to be changed, marking the owning
+    /// chunk as unsaved.
+    pub fn block_entity_mut(&mut self, position: BlockPos) -> Option<&mut BlockEntity> {
+        let (x, y, z) = position.local();
+        self.chunks
+            .get_mut(position.chunk())?
+            .block_entity_mut(x, y, z)
+    }
+
+    /// Sets the block at `position`, marking the owning chunk and any neighboring chunks
+    /// sharing the changed face or lit differently for remeshing, and letting liquids around
+    /// it flow and blocks above it fall. Returns `false` if the owning chunk is not loaded.
+    pub fn set_block(&mut self, position: BlockPos, block_type: BlockType) -> bool {
+        let chunk_position = position.chunk();
+        let (x, y, z) = position.local();
+
+        let Some(chunk) = self.chunks.get_mut(chunk_position) else {
+            return false;
+        };
+
+        if chunk.block(x, y, z) == block_type {
+            return true;
+        }
+        chunk.set_block(x, y, z, block_type);
+
+        // Blocks on the border of a chunk affect the faces of the neighboring chunk.
+        let ChunkPos(chunk_x, chunk_y, chunk_z) = chunk_position;
+        let mut neighbors = Vec::new();
+        if x == 0 {
+            neighbors.push(ChunkPos(chunk_x - 1, chunk_y, chunk_z));
+        } else if x == CHUNK_WIDTH - 1 {
+            neighbors.push(ChunkPos(chunk_x + 1, chunk_y, chunk_z));
+        }
+        if y == 0 {
+            neighbors.push(ChunkPos(chunk_x, chunk_y - 1, chunk_z));
+        } else if y == CHUNK_HEIGHT - 1 {
+            neighbors.push(ChunkPos(chunk_x, chunk_y + 1, chunk_z));
+        }
+        if z == 0 {
+            neighbors.push(ChunkPos(chunk_x, chunk_y, chunk_z - 1));
+        } else if z == CHUNK_DEPTH - 1 {
+            neighbors.push(ChunkPos(chunk_x, chunk_y, chunk_z + 1));
+        }
+
+        for neighbor in neighbors {
+            if let Some(chunk) = self.chunks.get_mut(neighbor) {
+                chunk.mark_dirty();
+            }
+        }
+
+        self.chunks.relight(position);
+        self.schedule_updates(position);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{FlatGenerator, GeneratorConfig, NoiseGenerator, TerrainSettings};
+
+    #[test]
+    fn saved_world_keeps_its_generator() {
+        let directory = std::env::temp_dir().join(format!("craft-world-{}", std::process::id()));
+        let flat = || {
+            Box::new(FlatGenerator::new(vec![
+                BlockType::Bedrock,
+                BlockType::Sand,
+            ]))
+        };
+        World::load(1, &directory, flat()).unwrap().save().unwrap();
+
+        let noise = Box::new(NoiseGenerator::new(TerrainSettings::default()));
+        assert!(World::load(1, &directory, noise).is_err());
+
+        let world = World::load(1, &directory, Box::new(FlatGenerator::default())).unwrap();
+        assert_eq!(
+            world.chunks().generator().config(),
+            GeneratorConfig::Flat {
+                layers: vec!["bedrock".to_string(), "sand".to_string()],
+            }
+        );
+        assert_eq!(world.spawn(), BlockPos(0, 2, 0));
+        std::fs::remove_dir_all(directory).ok();
+    }
+}